@@ -4,6 +4,7 @@
 
 use std::collections::HashSet;
 use std::fs;
+use std::io::{Seek, Write};
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 use zip::write::SimpleFileOptions;
@@ -16,16 +17,17 @@ use super::extract::copy_with_periodic_cancel;
 use super::jobs::{
     ProgressSink, ARCHIVE_ERROR_DESTINATION_INSIDE_SELECTED_FOLDER, ARCHIVE_JOB_CANCELLED,
 };
+use super::split::write_split_zip;
 
 fn path_to_zip_entry_path(path: &Path) -> String {
     normalize_path(&path.to_string_lossy())
 }
 
-fn zip_file_options() -> SimpleFileOptions {
+pub(super) fn zip_file_options() -> SimpleFileOptions {
     SimpleFileOptions::default().compression_method(CompressionMethod::Deflated)
 }
 
-fn build_zip_entries(sources: &[PathBuf]) -> Result<Vec<(PathBuf, String)>, String> {
+pub(super) fn build_zip_entries(sources: &[PathBuf]) -> Result<Vec<(PathBuf, String)>, String> {
     let mut result: Vec<(PathBuf, String)> = Vec::new();
 
     for source in sources {
@@ -73,11 +75,11 @@ fn build_zip_entries(sources: &[PathBuf]) -> Result<Vec<(PathBuf, String)>, Stri
     Ok(result)
 }
 
-pub fn create_zip_from_sources_with_sink(
+/// Checks that `destination_zip` can take the sources and lists the entries to write.
+fn prepare_zip_entries(
     source_paths: &[PathBuf],
     destination_zip: &Path,
-    sink: Option<&ProgressSink>,
-) -> Result<(), String> {
+) -> Result<Vec<(PathBuf, String)>, String> {
     if source_paths.is_empty() {
         return Err("No sources to compress".to_string());
     }
@@ -117,35 +119,52 @@ pub fn create_zip_from_sources_with_sink(
         }
     }
 
-    let zip_entries = build_zip_entries(&canonical_sources)?;
-    let total_entries = zip_entries.len().max(1) as u32;
+    build_zip_entries(&canonical_sources)
+}
 
-    let zip_result = (|| -> Result<(), String> {
-        let outfile = fs::File::create(&dest_path).map_err(|error| error.to_string())?;
-        let mut zip_writer = ZipWriter::new(outfile);
-        let options = zip_file_options();
+fn write_zip_entries<W: Write + Seek>(
+    zip_writer: &mut ZipWriter<W>,
+    zip_entries: Vec<(PathBuf, String)>,
+    sink: Option<&ProgressSink>,
+) -> Result<(), String> {
+    let total_entries = zip_entries.len().max(1) as u32;
+    let options = zip_file_options();
 
-        for (entry_index, (disk_path, zip_path)) in zip_entries.into_iter().enumerate() {
-            if let Some(progress_sink) = sink {
-                progress_sink.check_cancelled()?;
-                let percent = ((entry_index as u32 + 1) * 100 / total_entries).min(100);
-                progress_sink.report(percent, zip_path.clone());
-            }
+    for (entry_index, (disk_path, zip_path)) in zip_entries.into_iter().enumerate() {
+        if let Some(progress_sink) = sink {
+            progress_sink.check_cancelled()?;
+            let percent = ((entry_index as u32 + 1) * 100 / total_entries).min(100);
+            progress_sink.report(percent, zip_path.clone());
+        }
 
-            if zip_path.ends_with('/') {
-                zip_writer
-                    .add_directory(zip_path.clone(), options)
-                    .map_err(|error| format!("Failed to add directory to zip: {}", error))?;
-            } else {
-                zip_writer
-                    .start_file(zip_path, options)
-                    .map_err(|error| format!("Failed to start zip entry: {}", error))?;
-                let mut source_file =
-                    fs::File::open(&disk_path).map_err(|error| error.to_string())?;
-                copy_with_periodic_cancel(&mut source_file, &mut zip_writer, sink)?;
-            }
+        if zip_path.ends_with('/') {
+            zip_writer
+                .add_directory(zip_path.clone(), options)
+                .map_err(|error| format!("Failed to add directory to zip: {}", error))?;
+        } else {
+            zip_writer
+                .start_file(zip_path, options)
+                .map_err(|error| format!("Failed to start zip entry: {}", error))?;
+            let mut source_file = fs::File::open(&disk_path).map_err(|error| error.to_string())?;
+            copy_with_periodic_cancel(&mut source_file, zip_writer, sink)?;
         }
+    }
+
+    Ok(())
+}
 
+pub fn create_zip_from_sources_with_sink(
+    source_paths: &[PathBuf],
+    destination_zip: &Path,
+    sink: Option<&ProgressSink>,
+) -> Result<(), String> {
+    let zip_entries = prepare_zip_entries(source_paths, destination_zip)?;
+    let dest_path = destination_zip.to_path_buf();
+
+    let zip_result = (|| -> Result<(), String> {
+        let outfile = fs::File::create(&dest_path).map_err(|error| error.to_string())?;
+        let mut zip_writer = ZipWriter::new(outfile);
+        write_zip_entries(&mut zip_writer, zip_entries, sink)?;
         zip_writer
             .finish()
             .map_err(|error| format!("Failed to finalize zip: {}", error))?;
@@ -163,6 +182,20 @@ pub fn create_zip_from_sources_with_sink(
     zip_result
 }
 
+/// Compresses the sources straight into volumes of at most `volume_size` bytes, see
+/// `write_split_zip`.
+pub fn create_split_zip_from_sources_with_sink(
+    source_paths: &[PathBuf],
+    destination_zip: &Path,
+    volume_size: u64,
+    sink: Option<&ProgressSink>,
+) -> Result<(), String> {
+    let zip_entries = prepare_zip_entries(source_paths, destination_zip)?;
+    write_split_zip(destination_zip, volume_size, |zip_writer| {
+        write_zip_entries(zip_writer, zip_entries, sink)
+    })
+}
+
 pub fn unique_zip_destination(base: &Path) -> PathBuf {
     unique_path_with_index(base, 1, "archive", Some("zip"), None)
 }
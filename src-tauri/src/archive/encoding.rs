@@ -5,12 +5,16 @@
 use encoding_rs::Encoding;
 use serde::Serialize;
 use std::fs;
+use std::io::{Read, Seek};
 use std::path::PathBuf;
 use zip::result::ZipError;
 use zip::ZipArchive;
 
 use crate::utils::normalize_path;
 
+use super::split::{find_split_zip_volumes, read_split_central_directory};
+use super::zip_records::ENCRYPTED_FLAG;
+
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ArchiveCheckResult {
@@ -124,9 +128,70 @@ pub fn decode_zip_entry_path_str(raw: &[u8], encoding: &str) -> Result<String, S
     Ok(cleaned.to_string())
 }
 
+pub fn encode_zip_entry_path_str(path: &str, encoding: &str) -> Option<Vec<u8>> {
+    let enc = Encoding::for_label(encoding.as_bytes())?;
+    let (encoded, _encoding, had_unmappable) = enc.encode(path);
+    if had_unmappable || encoded.contains(&0) {
+        return None;
+    }
+
+    let (round_trip, _encoding, had_errors) = enc.decode(&encoded);
+    if had_errors || round_trip != path {
+        return None;
+    }
+
+    Some(encoded.into_owned())
+}
+
+pub fn detect_archive_entry_encoding<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<Option<String>, String> {
+    let mut non_utf8_name_samples: Vec<Vec<u8>> = Vec::new();
+
+    for index in 0..archive.len() {
+        if non_utf8_name_samples.len() >= MAX_ZIP_ENCODING_SAMPLES {
+            break;
+        }
+
+        let entry = archive
+            .by_index_raw(index)
+            .map_err(|error| format!("Failed to read entry: {}", error))?;
+        let raw = entry.name_raw();
+        if std::str::from_utf8(raw).is_err() {
+            non_utf8_name_samples.push(raw.to_vec());
+        }
+    }
+
+    Ok(detect_zip_entry_encoding(&non_utf8_name_samples))
+}
+
+fn check_split_archive(volumes: &[PathBuf]) -> Result<ArchiveCheckResult, String> {
+    let entries = read_split_central_directory(volumes)?;
+    let encrypted = entries
+        .iter()
+        .any(|entry| entry.flags() & ENCRYPTED_FLAG != 0);
+    let non_utf8_name_samples: Vec<Vec<u8>> = entries
+        .iter()
+        .filter(|entry| std::str::from_utf8(&entry.name).is_err())
+        .take(MAX_ZIP_ENCODING_SAMPLES)
+        .map(|entry| entry.name.clone())
+        .collect();
+    let encoding_undetermined = !non_utf8_name_samples.is_empty();
+
+    Ok(ArchiveCheckResult {
+        encrypted,
+        encoding_undetermined,
+        detected_encoding: detect_zip_entry_encoding(&non_utf8_name_samples),
+    })
+}
+
 #[tauri::command]
 pub fn check_archive(archive_path: String) -> Result<ArchiveCheckResult, String> {
     let path = PathBuf::from(normalize_path(&archive_path));
+    if let Some(volumes) = find_split_zip_volumes(&path)? {
+        return check_split_archive(&volumes);
+    }
+
     let file =
         fs::File::open(&path).map_err(|error| format!("Failed to open archive: {}", error))?;
     let mut archive =
@@ -8,11 +8,14 @@ use std::path::{Component, Path, PathBuf};
 use zip::result::ZipError;
 use zip::ZipArchive;
 
+use crate::utils::{path_extension_lowercase, unique_path_with_index};
+
 use super::encoding::decode_zip_entry_path_str;
 use super::jobs::{
     ProgressSink, ARCHIVE_ERROR_OUTPUT_ALREADY_EXISTS, ARCHIVE_ERROR_WRONG_PASSWORD,
     ARCHIVE_JOB_CANCELLED,
 };
+use super::split::with_joined_split_zip;
 
 const IO_COPY_CHUNK_BYTES: usize = 256 * 1024;
 
@@ -119,6 +122,72 @@ pub fn extract_zip_to_directory_with_sink(
     password: Option<&[u8]>,
     encoding: Option<&str>,
     sink: Option<&ProgressSink>,
+) -> Result<(), String> {
+    with_joined_split_zip(zip_path, sink, |readable_zip_path| {
+        let root_dir = zip_single_root_dir(readable_zip_path, encoding)?;
+        extract_zip_entries(
+            readable_zip_path,
+            dest_dir,
+            password,
+            encoding,
+            sink,
+            root_dir.as_deref(),
+        )
+    })
+}
+
+/// The top-level folder that holds every entry of an archive, if there is one.
+/// `entries` are the relative entry paths, each with whether it is a directory.
+pub fn single_root_dir(entries: impl IntoIterator<Item = (PathBuf, bool)>) -> Option<PathBuf> {
+    let mut root_dir: Option<PathBuf> = None;
+
+    for (entry_path, is_dir) in entries {
+        let mut components = entry_path
+            .components()
+            .filter(|component| !matches!(component, Component::CurDir));
+        let Some(first_component) = components.next() else {
+            continue;
+        };
+
+        if components.next().is_none() && !is_dir {
+            return None;
+        }
+
+        let first_component = PathBuf::from(first_component.as_os_str());
+        match &root_dir {
+            Some(root) if root != &first_component => return None,
+            Some(_) => {}
+            None => root_dir = Some(first_component),
+        }
+    }
+
+    root_dir
+}
+
+fn zip_single_root_dir(zip_path: &Path, encoding: Option<&str>) -> Result<Option<PathBuf>, String> {
+    let file =
+        fs::File::open(zip_path).map_err(|error| format!("Failed to open zip file: {}", error))?;
+    let mut archive =
+        ZipArchive::new(file).map_err(|error| format!("Failed to read zip archive: {}", error))?;
+
+    let mut entries = Vec::with_capacity(archive.len());
+    for archive_index in 0..archive.len() {
+        let file = archive
+            .by_index_raw(archive_index)
+            .map_err(|error| format!("Failed to read zip entry: {}", error))?;
+        entries.push((get_entry_path(&file, encoding)?, file.is_dir()));
+    }
+
+    Ok(single_root_dir(entries))
+}
+
+fn extract_zip_entries(
+    zip_path: &Path,
+    dest_dir: &Path,
+    password: Option<&[u8]>,
+    encoding: Option<&str>,
+    sink: Option<&ProgressSink>,
+    root_dir: Option<&Path>,
 ) -> Result<(), String> {
     fs::create_dir_all(dest_dir)
         .map_err(|error| format!("Failed to create destination: {}", error))?;
@@ -132,25 +201,6 @@ pub fn extract_zip_to_directory_with_sink(
         ZipArchive::new(file).map_err(|error| format!("Failed to read zip archive: {}", error))?;
 
     let total_entries = archive.len().max(1) as u32;
-    let mut root_dir: Option<PathBuf> = None;
-
-    for archive_index in 0..archive.len() {
-        if let Some(progress_sink) = sink {
-            progress_sink.check_cancelled()?;
-        }
-
-        let file = read_entry(&mut archive, archive_index, password)?;
-        let enclosed_path = get_entry_path(&file, encoding)?;
-
-        if root_dir.is_none() {
-            let mut components = enclosed_path.components();
-            if let Some(first_component) = components.next() {
-                if components.next().is_some() {
-                    root_dir = Some(PathBuf::from(first_component.as_os_str()));
-                }
-            }
-        }
-    }
 
     for archive_index in 0..archive.len() {
         if let Some(progress_sink) = sink {
@@ -167,7 +217,7 @@ pub fn extract_zip_to_directory_with_sink(
 
         let enclosed_path = get_entry_path(&file, encoding)?;
 
-        let relative_path = if let Some(root) = &root_dir {
+        let relative_path = if let Some(root) = root_dir {
             enclosed_path
                 .strip_prefix(root)
                 .unwrap_or(&enclosed_path)
@@ -257,6 +307,108 @@ pub fn extract_zip_to_directory_with_sink(
     Ok(())
 }
 
+fn collect_nested_zip_paths(dir: &Path) -> Vec<PathBuf> {
+    walkdir::WalkDir::new(dir)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| {
+            path_extension_lowercase(entry.path()).is_some_and(|extension| extension == "zip")
+        })
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Extracts a single-root archive as that folder, and anything else into a folder named
+/// after the archive. Existing folders are never merged into; a free name is picked instead.
+/// With `extract_nested`, the outer archive takes the first half of the progress range and
+/// the archives found inside it share the second half.
+pub fn smart_extract_zip_with_sink(
+    zip_path: &Path,
+    destination_parent: &Path,
+    password: Option<&[u8]>,
+    encoding: Option<&str>,
+    extract_nested: bool,
+    sink: Option<&ProgressSink>,
+) -> Result<PathBuf, String> {
+    let outer_sink = sink.map(|sink| {
+        if extract_nested {
+            sink.sub_range(0, 50)
+        } else {
+            sink.sub_range(0, 100)
+        }
+    });
+    let sink_for_outer = outer_sink.as_ref();
+    let destination = with_joined_split_zip(zip_path, sink_for_outer, |readable_zip_path| {
+        let root_dir = zip_single_root_dir(readable_zip_path, encoding)?;
+        let folder_name = match &root_dir {
+            Some(root) => root.as_os_str().to_os_string(),
+            None => zip_path
+                .file_stem()
+                .ok_or_else(|| "Invalid archive name".to_string())?
+                .to_os_string(),
+        };
+        let destination = unique_path_with_index(
+            &destination_parent.join(folder_name),
+            1,
+            "archive",
+            None,
+            None,
+        );
+
+        extract_zip_entries(
+            readable_zip_path,
+            &destination,
+            password,
+            encoding,
+            sink_for_outer,
+            root_dir.as_deref(),
+        )?;
+        Ok(destination)
+    })?;
+
+    if extract_nested {
+        let nested_zips = collect_nested_zip_paths(&destination);
+        let nested_count = nested_zips.len();
+        for (nested_index, nested_zip) in nested_zips.iter().enumerate() {
+            let nested_sink = sink.map(|sink| {
+                sink.sub_range(
+                    (50 + nested_index * 50 / nested_count) as u32,
+                    (50 + (nested_index + 1) * 50 / nested_count) as u32,
+                )
+            });
+            if let Some(progress_sink) = &nested_sink {
+                progress_sink.check_cancelled()?;
+                progress_sink.report(0, nested_zip.display().to_string());
+            }
+
+            let Some(nested_parent) = nested_zip.parent() else {
+                continue;
+            };
+
+            // Nested archives are best effort: one that needs another password or is
+            // corrupt is left in place instead of failing the outer extraction.
+            match smart_extract_zip_with_sink(
+                nested_zip,
+                nested_parent,
+                password,
+                encoding,
+                false,
+                nested_sink.as_ref(),
+            ) {
+                Ok(_) => {
+                    let _ = fs::remove_file(nested_zip);
+                }
+                Err(message) if message == ARCHIVE_JOB_CANCELLED => return Err(message),
+                Err(_) => {}
+            }
+        }
+    }
+
+    Ok(destination)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -354,4 +506,130 @@ mod tests {
         let result = extract_zip_to_directory(&zip_path, &extract_dir, Some(b"wrong"), None);
         assert_eq!(result.unwrap_err(), ARCHIVE_ERROR_WRONG_PASSWORD);
     }
+
+    #[test]
+    fn single_root_dir_needs_every_entry_inside_one_folder() {
+        let entry = |path: &str, is_dir: bool| (PathBuf::from(path), is_dir);
+
+        assert_eq!(
+            single_root_dir([entry("./project/", true), entry("project/a.txt", false)]),
+            Some(PathBuf::from("project"))
+        );
+        assert_eq!(
+            single_root_dir([entry("project/a.txt", false), entry("readme.txt", false)]),
+            None
+        );
+        assert_eq!(
+            single_root_dir([entry("one/a.jpg", false), entry("two/b.jpg", false)]),
+            None
+        );
+    }
+
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip_writer = ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, content) in files {
+            zip_writer.start_file(*name, zip_file_options()).unwrap();
+            zip_writer.write_all(content).unwrap();
+        }
+        zip_writer.finish().unwrap();
+    }
+
+    #[test]
+    fn smart_extract_keeps_single_root_folder() {
+        let temp = tempfile::tempdir().unwrap();
+        let zip_path = temp.path().join("download.zip");
+        write_zip(
+            &zip_path,
+            &[("project/a.txt", b"a"), ("project/src/b.txt", b"b")],
+        );
+
+        let folder =
+            smart_extract_zip_with_sink(&zip_path, temp.path(), None, None, false, None).unwrap();
+
+        assert_eq!(folder, temp.path().join("project"));
+        assert_eq!(fs::read(folder.join("src/b.txt")).unwrap(), b"b");
+    }
+
+    #[test]
+    fn smart_extract_wraps_loose_entries_in_named_folder() {
+        let temp = tempfile::tempdir().unwrap();
+        let zip_path = temp.path().join("photos.zip");
+        write_zip(&zip_path, &[("one/a.jpg", b"a"), ("two/b.jpg", b"b")]);
+        fs::create_dir_all(temp.path().join("photos")).unwrap();
+
+        let folder =
+            smart_extract_zip_with_sink(&zip_path, temp.path(), None, None, false, None).unwrap();
+
+        assert_eq!(folder, temp.path().join("photos (1)"));
+        assert_eq!(fs::read(folder.join("one/a.jpg")).unwrap(), b"a");
+        assert_eq!(fs::read(folder.join("two/b.jpg")).unwrap(), b"b");
+    }
+
+    #[test]
+    fn smart_extract_unpacks_nested_archives_one_level() {
+        let temp = tempfile::tempdir().unwrap();
+        let inner_zip = temp.path().join("inner.zip");
+        write_zip(&inner_zip, &[("note.txt", b"note")]);
+        let inner_bytes = fs::read(&inner_zip).unwrap();
+
+        let outer_zip = temp.path().join("outer.zip");
+        write_zip(
+            &outer_zip,
+            &[("inner.zip", &inner_bytes), ("readme.txt", b"r")],
+        );
+
+        let output = temp.path().join("output");
+        fs::create_dir_all(&output).unwrap();
+        let folder =
+            smart_extract_zip_with_sink(&outer_zip, &output, None, None, true, None).unwrap();
+
+        assert_eq!(folder, output.join("outer"));
+        assert!(!folder.join("inner.zip").exists());
+        assert_eq!(fs::read(folder.join("inner/note.txt")).unwrap(), b"note");
+    }
+
+    #[test]
+    fn nested_extraction_keeps_progress_moving_forward_and_reuses_options() {
+        let temp = tempfile::tempdir().unwrap();
+        let inner_zip = temp.path().join("inner.zip");
+        let mut zip_writer = ZipWriter::new(fs::File::create(&inner_zip).unwrap());
+        let options = zip_file_options()
+            .with_deprecated_encryption(b"secret")
+            .unwrap();
+        zip_writer.start_file("note.txt", options).unwrap();
+        zip_writer.write_all(b"note").unwrap();
+        zip_writer.finish().unwrap();
+        let inner_bytes = fs::read(&inner_zip).unwrap();
+
+        let outer_zip = temp.path().join("outer.zip");
+        let mut zip_writer = ZipWriter::new(fs::File::create(&outer_zip).unwrap());
+        for (name, content) in [("inner.zip", &inner_bytes[..]), ("readme.txt", b"r")] {
+            zip_writer.start_file(name, options).unwrap();
+            zip_writer.write_all(content).unwrap();
+        }
+        zip_writer.finish().unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let sink = ProgressSink::new(Default::default(), tx);
+        let output = temp.path().join("output");
+        fs::create_dir_all(&output).unwrap();
+        let folder = smart_extract_zip_with_sink(
+            &outer_zip,
+            &output,
+            Some(b"secret"),
+            None,
+            true,
+            Some(&sink),
+        )
+        .unwrap();
+        drop(sink);
+
+        assert_eq!(fs::read(folder.join("inner/note.txt")).unwrap(), b"note");
+        let mut percents = Vec::new();
+        while let Ok((percent, _)) = rx.try_recv() {
+            percents.push(percent);
+        }
+        assert!(percents.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(percents.last(), Some(&100));
+    }
 }
@@ -13,8 +13,13 @@ use tokio::sync::mpsc::UnboundedSender;
 
 use crate::utils::normalize_path;
 
-use super::compress::{create_zip_from_sources_with_sink, unique_zip_destination};
-use super::extract::extract_zip_to_directory_with_sink;
+use super::compress::{
+    create_split_zip_from_sources_with_sink, create_zip_from_sources_with_sink,
+    unique_zip_destination,
+};
+use super::extract::{extract_zip_to_directory_with_sink, smart_extract_zip_with_sink};
+use super::split::unique_split_zip_destination;
+use super::update::{update_zip_archive_with_sink, ArchiveEdit};
 
 pub const ARCHIVE_JOB_CANCELLED: &str = "__ARCHIVE_JOB_CANCELLED__";
 pub const ARCHIVE_ERROR_DESTINATION_INSIDE_SELECTED_FOLDER: &str =
@@ -40,9 +45,41 @@ pub enum ArchiveJobRequest {
         password: Option<String>,
         encoding: Option<String>,
     },
+    SmartExtract {
+        archive_paths: Vec<String>,
+        destination_dir: Option<String>,
+        password: Option<String>,
+        encoding: Option<String>,
+        #[serde(default)]
+        extract_nested: bool,
+    },
     Compress {
         source_paths: Vec<String>,
         destination_zip_path: String,
+        volume_size: Option<u64>,
+    },
+    AddToArchive {
+        archive_path: String,
+        source_paths: Vec<String>,
+        entry_dir: Option<String>,
+        encoding: Option<String>,
+    },
+    ReplaceInArchive {
+        archive_path: String,
+        source_paths: Vec<String>,
+        entry_dir: Option<String>,
+        encoding: Option<String>,
+    },
+    DeleteFromArchive {
+        archive_path: String,
+        entry_paths: Vec<String>,
+        encoding: Option<String>,
+    },
+    RenameInArchive {
+        archive_path: String,
+        entry_path: String,
+        new_name: String,
+        encoding: Option<String>,
     },
 }
 
@@ -68,9 +105,18 @@ pub struct ArchiveJobFinishedPayload {
 pub struct ProgressSink {
     cancel: Arc<AtomicBool>,
     tx: UnboundedSender<(u32, String)>,
+    percent_range: (u32, u32),
 }
 
 impl ProgressSink {
+    pub fn new(cancel: Arc<AtomicBool>, tx: UnboundedSender<(u32, String)>) -> Self {
+        Self {
+            cancel,
+            tx,
+            percent_range: (0, 100),
+        }
+    }
+
     pub fn check_cancelled(&self) -> Result<(), String> {
         if self.cancel.load(Ordering::Relaxed) {
             Err(ARCHIVE_JOB_CANCELLED.to_string())
@@ -80,7 +126,23 @@ impl ProgressSink {
     }
 
     pub fn report(&self, percent: u32, detail: String) {
-        let _ = self.tx.send((percent, detail));
+        let (start, end) = self.percent_range;
+        let scaled = start + percent.min(100) * (end - start) / 100;
+        let _ = self.tx.send((scaled, detail));
+    }
+
+    /// Returns a sink that maps 0-100 reports onto `start..end` of this sink's range.
+    pub fn sub_range(&self, start: u32, end: u32) -> ProgressSink {
+        let (range_start, range_end) = self.percent_range;
+        let span = range_end - range_start;
+        ProgressSink {
+            cancel: self.cancel.clone(),
+            tx: self.tx.clone(),
+            percent_range: (
+                range_start + start.min(100) * span / 100,
+                range_start + end.min(100) * span / 100,
+            ),
+        }
     }
 }
 
@@ -156,22 +218,155 @@ fn run_archive_job_blocking(
             )?;
             Ok(None)
         }
+        ArchiveJobRequest::SmartExtract {
+            archive_paths,
+            destination_dir,
+            password,
+            encoding,
+            extract_nested,
+        } => {
+            if archive_paths.is_empty() {
+                return Err("No archives to extract".to_string());
+            }
+            let password_bytes = password.as_deref().map(str::as_bytes);
+            let encoding_label = encoding.as_deref();
+            let destination_dir = destination_dir.map(|path| PathBuf::from(normalize_path(&path)));
+            let archive_count = archive_paths.len();
+            let mut extracted_folders = Vec::with_capacity(archive_count);
+
+            for (archive_index, archive_path) in archive_paths.iter().enumerate() {
+                let archive = PathBuf::from(normalize_path(archive_path));
+                let destination_parent = match &destination_dir {
+                    Some(path) => path.clone(),
+                    None => archive
+                        .parent()
+                        .map(PathBuf::from)
+                        .ok_or_else(|| "Invalid archive path".to_string())?,
+                };
+                let archive_sink = sink.sub_range(
+                    (archive_index * 100 / archive_count) as u32,
+                    ((archive_index + 1) * 100 / archive_count) as u32,
+                );
+                let folder = smart_extract_zip_with_sink(
+                    &archive,
+                    &destination_parent,
+                    password_bytes,
+                    encoding_label,
+                    extract_nested,
+                    Some(&archive_sink),
+                )?;
+                extracted_folders.push(folder);
+            }
+
+            if extracted_folders.len() == 1 {
+                Ok(Some(normalize_path(
+                    &extracted_folders[0].to_string_lossy(),
+                )))
+            } else {
+                Ok(None)
+            }
+        }
         ArchiveJobRequest::Compress {
             source_paths,
             destination_zip_path,
+            volume_size,
         } => {
-            let sources: Vec<PathBuf> = source_paths
-                .into_iter()
-                .map(|path| PathBuf::from(normalize_path(&path)))
-                .collect();
+            let sources = normalize_source_paths(source_paths);
             let destination = PathBuf::from(normalize_path(&destination_zip_path));
-            let destination = unique_zip_destination(&destination);
-            create_zip_from_sources_with_sink(&sources, &destination, Some(sink))?;
+            let destination = match volume_size {
+                Some(volume_size) => {
+                    let destination = unique_split_zip_destination(&destination);
+                    create_split_zip_from_sources_with_sink(
+                        &sources,
+                        &destination,
+                        volume_size,
+                        Some(sink),
+                    )?;
+                    destination
+                }
+                None => {
+                    let destination = unique_zip_destination(&destination);
+                    create_zip_from_sources_with_sink(&sources, &destination, Some(sink))?;
+                    destination
+                }
+            };
             Ok(Some(normalize_path(&destination.to_string_lossy())))
         }
+        ArchiveJobRequest::AddToArchive {
+            archive_path,
+            source_paths,
+            entry_dir,
+            encoding,
+        } => run_archive_update(
+            &archive_path,
+            ArchiveEdit::Add {
+                source_paths: normalize_source_paths(source_paths),
+                entry_dir,
+                replace_existing: false,
+            },
+            encoding.as_deref(),
+            sink,
+        ),
+        ArchiveJobRequest::ReplaceInArchive {
+            archive_path,
+            source_paths,
+            entry_dir,
+            encoding,
+        } => run_archive_update(
+            &archive_path,
+            ArchiveEdit::Add {
+                source_paths: normalize_source_paths(source_paths),
+                entry_dir,
+                replace_existing: true,
+            },
+            encoding.as_deref(),
+            sink,
+        ),
+        ArchiveJobRequest::DeleteFromArchive {
+            archive_path,
+            entry_paths,
+            encoding,
+        } => run_archive_update(
+            &archive_path,
+            ArchiveEdit::Delete { entry_paths },
+            encoding.as_deref(),
+            sink,
+        ),
+        ArchiveJobRequest::RenameInArchive {
+            archive_path,
+            entry_path,
+            new_name,
+            encoding,
+        } => run_archive_update(
+            &archive_path,
+            ArchiveEdit::Rename {
+                entry_path,
+                new_name,
+            },
+            encoding.as_deref(),
+            sink,
+        ),
     }
 }
 
+fn normalize_source_paths(source_paths: Vec<String>) -> Vec<PathBuf> {
+    source_paths
+        .into_iter()
+        .map(|path| PathBuf::from(normalize_path(&path)))
+        .collect()
+}
+
+fn run_archive_update(
+    archive_path: &str,
+    edit: ArchiveEdit,
+    encoding: Option<&str>,
+    sink: &ProgressSink,
+) -> Result<Option<String>, String> {
+    let archive = PathBuf::from(normalize_path(archive_path));
+    update_zip_archive_with_sink(&archive, &edit, encoding, Some(sink))?;
+    Ok(Some(normalize_path(&archive.to_string_lossy())))
+}
+
 #[tauri::command]
 pub async fn start_archive_job(
     app: AppHandle,
@@ -206,10 +401,7 @@ pub async fn start_archive_job(
     let job_id_done = job_id.clone();
     tokio::spawn(async move {
         let work_result = tokio::task::spawn_blocking(move || {
-            let sink = ProgressSink {
-                cancel: cancel.clone(),
-                tx: progress_tx,
-            };
+            let sink = ProgressSink::new(cancel.clone(), progress_tx);
             run_archive_job_blocking(request, &sink)
         })
         .await;
@@ -6,5 +6,8 @@ pub mod compress;
 pub mod encoding;
 pub mod extract;
 pub mod jobs;
+pub mod split;
+pub mod update;
+mod zip_records;
 
-pub use extract::{extract_zip_to_directory, is_safe_archive_relative_path};
+pub use extract::{extract_zip_to_directory, is_safe_archive_relative_path, single_root_dir};
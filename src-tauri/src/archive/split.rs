@@ -0,0 +1,679 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use zip::ZipWriter;
+
+use crate::utils::path_extension_lowercase;
+
+use super::extract::copy_with_periodic_cancel;
+use super::jobs::{ProgressSink, ARCHIVE_ERROR_OUTPUT_ALREADY_EXISTS};
+use super::update::create_sibling_temp_file;
+use super::zip_records::{
+    read_central_directory, read_u16_at, read_u32_at, CentralDirectoryEntry, EndOfCentralDirectory,
+    ZipVolumes, LOCAL_FILE_HEADER_LEN, LOCAL_FILE_HEADER_SIGNATURE, SPLIT_ARCHIVE_SIGNATURE,
+};
+
+pub const MIN_SPLIT_VOLUME_SIZE: u64 = 64 * 1024;
+
+fn split_io_error(error: io::Error) -> String {
+    format!("Failed to process split archive: {}", error)
+}
+
+/// Returns the path of the `volume_number`-th (1-based) `.zNN` volume of a split set.
+pub fn split_zip_volume_path(final_volume: &Path, volume_number: usize) -> PathBuf {
+    final_volume.with_extension(format!("z{:02}", volume_number))
+}
+
+fn is_split_volume_extension(extension: &str) -> bool {
+    extension.len() >= 3
+        && extension.starts_with('z')
+        && extension[1..]
+            .chars()
+            .all(|character| character.is_ascii_digit())
+}
+
+/// Picks a free path for a new split archive. Neither the `.zip` nor any `.zNN` volume of
+/// the set may exist yet, so volumes left over from an earlier archive are never
+/// overwritten or mixed into the new set.
+pub fn unique_split_zip_destination(base: &Path) -> PathBuf {
+    let parent = base.parent().unwrap_or(Path::new(""));
+    let existing_names: HashSet<String> = fs::read_dir(parent)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.file_name().to_string_lossy().to_lowercase())
+                .collect()
+        })
+        .unwrap_or_default();
+    let is_free = |candidate: &Path| {
+        let stem = candidate
+            .file_stem()
+            .map(|stem| format!("{}.", stem.to_string_lossy().to_lowercase()))
+            .unwrap_or_default();
+        !existing_names.iter().any(|name| {
+            name.strip_prefix(&stem)
+                .is_some_and(|extension| extension == "zip" || is_split_volume_extension(extension))
+        })
+    };
+
+    if is_free(base) {
+        return base.to_path_buf();
+    }
+    let stem = base
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "archive".to_string());
+    (1..)
+        .map(|index| parent.join(format!("{stem} ({index}).zip")))
+        .find(|candidate| is_free(candidate))
+        .unwrap_or_else(|| base.to_path_buf())
+}
+
+/// Finds every volume of the split set `path` belongs to, in disk order. Returns `None` for
+/// regular archives. Any volume of the set can be passed in.
+pub fn find_split_zip_volumes(path: &Path) -> Result<Option<Vec<PathBuf>>, String> {
+    let Some(extension) = path_extension_lowercase(path) else {
+        return Ok(None);
+    };
+    let opened_numbered_volume = is_split_volume_extension(&extension);
+    if extension != "zip" && !opened_numbered_volume {
+        return Ok(None);
+    }
+
+    let final_volume = path.with_extension("zip");
+    let mut volumes = Vec::new();
+    loop {
+        let volume = split_zip_volume_path(&final_volume, volumes.len() + 1);
+        if !volume.is_file() {
+            break;
+        }
+        volumes.push(volume);
+    }
+
+    if volumes.is_empty() {
+        return if opened_numbered_volume {
+            Err("Split archive is incomplete: the first volume is missing".to_string())
+        } else {
+            Ok(None)
+        };
+    }
+    if !final_volume.is_file() {
+        return Err(format!(
+            "Split archive is incomplete: {} is missing",
+            final_volume.display()
+        ));
+    }
+
+    volumes.push(final_volume);
+    Ok(Some(volumes))
+}
+
+/// Reads the central directory of a split set without joining its volumes.
+pub fn read_split_central_directory(
+    volumes: &[PathBuf],
+) -> Result<Vec<CentralDirectoryEntry>, String> {
+    let volume_set = ZipVolumes::new(volumes.to_vec()).map_err(split_io_error)?;
+    let (_, entries) = read_central_directory(&volume_set).map_err(split_io_error)?;
+    Ok(entries)
+}
+
+/// Writes an archive as `.z01`, `.z02`, … volumes, seen by the zip writer as one seekable
+/// stream.
+pub struct SplitVolumeWriter {
+    final_volume: PathBuf,
+    volume_size: u64,
+    volume_paths: Vec<PathBuf>,
+    /// Where each volume starts in the stream.
+    volume_starts: Vec<u64>,
+    file: File,
+    written_in_volume: u64,
+    /// Behind the end of the stream only while the zip writer patches a local header it
+    /// wrote earlier.
+    position: u64,
+}
+
+impl SplitVolumeWriter {
+    fn create(final_volume: &Path, volume_size: u64) -> Result<Self, String> {
+        if final_volume.exists() {
+            return Err(ARCHIVE_ERROR_OUTPUT_ALREADY_EXISTS.to_string());
+        }
+
+        let first_volume = split_zip_volume_path(final_volume, 1);
+        let file = Self::create_volume_file(&first_volume)?;
+        Ok(Self {
+            final_volume: final_volume.to_path_buf(),
+            volume_size,
+            volume_paths: vec![first_volume],
+            volume_starts: vec![0],
+            file,
+            written_in_volume: 0,
+            position: 0,
+        })
+    }
+
+    fn create_volume_file(path: &Path) -> Result<File, String> {
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|error| {
+                if error.kind() == ErrorKind::AlreadyExists {
+                    ARCHIVE_ERROR_OUTPUT_ALREADY_EXISTS.to_string()
+                } else {
+                    format!("Failed to create volume {}: {}", path.display(), error)
+                }
+            })
+    }
+
+    fn disk_number(&self) -> u32 {
+        (self.volume_paths.len() - 1) as u32
+    }
+
+    fn archive_len(&self) -> u64 {
+        self.volume_starts.last().copied().unwrap_or(0) + self.written_in_volume
+    }
+
+    /// Disk number and offset within that volume of a position in the stream.
+    fn locate(&self, position: u64) -> (u32, u64) {
+        let volume_index = self
+            .volume_starts
+            .partition_point(|start| *start <= position)
+            .saturating_sub(1);
+        (
+            volume_index as u32,
+            position - self.volume_starts[volume_index],
+        )
+    }
+
+    fn start_next_volume(&mut self) -> Result<(), String> {
+        self.file.flush().map_err(split_io_error)?;
+        let next_start = self.archive_len();
+        let next_volume = split_zip_volume_path(&self.final_volume, self.volume_paths.len() + 1);
+        self.file = Self::create_volume_file(&next_volume)?;
+        self.volume_paths.push(next_volume);
+        self.volume_starts.push(next_start);
+        self.written_in_volume = 0;
+        Ok(())
+    }
+
+    /// Writes a header that readers expect to find whole on one volume.
+    fn write_record(&mut self, record: &[u8]) -> Result<(u32, u64), String> {
+        let record_len = record.len() as u64;
+        if record_len > self.volume_size {
+            return Err("Volume size is too small for the archive headers".to_string());
+        }
+        if self.written_in_volume + record_len > self.volume_size {
+            self.start_next_volume()?;
+        }
+
+        let position = (self.disk_number(), self.written_in_volume);
+        self.file.write_all(record).map_err(split_io_error)?;
+        self.written_in_volume += record_len;
+        self.position = self.archive_len();
+        Ok(position)
+    }
+
+    /// Overwrites bytes already written, in whichever volume holds them.
+    fn patch(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let (disk_number, offset) = self.locate(self.position);
+        let volume_index = disk_number as usize;
+        let volume_end = self
+            .volume_starts
+            .get(volume_index + 1)
+            .copied()
+            .unwrap_or_else(|| self.archive_len());
+        let len = (volume_end - self.position).min(buffer.len() as u64) as usize;
+
+        if volume_index + 1 == self.volume_paths.len() {
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.file.write_all(&buffer[..len])?;
+            self.file.seek(SeekFrom::Start(self.written_in_volume))?;
+        } else {
+            let mut volume = OpenOptions::new()
+                .write(true)
+                .open(&self.volume_paths[volume_index])?;
+            volume.seek(SeekFrom::Start(offset))?;
+            volume.write_all(&buffer[..len])?;
+        }
+        self.position += len as u64;
+        Ok(len)
+    }
+
+    /// Cuts the stream back to `position`, removing the volumes that start after it.
+    fn truncate(&mut self, position: u64) -> Result<(), String> {
+        let (disk_number, offset) = self.locate(position);
+        let volume_index = disk_number as usize;
+        for volume in self.volume_paths.drain(volume_index + 1..) {
+            let _ = fs::remove_file(volume);
+        }
+        self.volume_starts.truncate(volume_index + 1);
+
+        self.file = OpenOptions::new()
+            .write(true)
+            .open(&self.volume_paths[volume_index])
+            .map_err(split_io_error)?;
+        self.file.set_len(offset).map_err(split_io_error)?;
+        self.file
+            .seek(SeekFrom::Start(offset))
+            .map_err(split_io_error)?;
+        self.written_in_volume = offset;
+        self.position = position;
+        Ok(())
+    }
+
+    /// Replaces the central directory the zip writer left at the end of the stream with
+    /// one that addresses entries by volume and keeps every record whole on one volume.
+    fn rewrite_central_directory(&mut self) -> Result<(), String> {
+        self.file.flush().map_err(split_io_error)?;
+        let stream = ZipVolumes::joined(self.volume_paths.clone()).map_err(split_io_error)?;
+        let (end_record, mut entries) = read_central_directory(&stream).map_err(split_io_error)?;
+
+        for entry in &mut entries {
+            let (disk_number, offset) = self.locate(entry.local_header_offset);
+            entry.disk_number_start = disk_number;
+            entry.local_header_offset = offset;
+        }
+        self.truncate(end_record.central_directory_offset)?;
+        write_split_central_directory(self, end_record, &entries)
+    }
+
+    fn remove_volumes(&self) {
+        for volume in &self.volume_paths {
+            let _ = fs::remove_file(volume);
+        }
+    }
+
+    fn finish(mut self) -> Result<(), String> {
+        self.file.flush().map_err(split_io_error)?;
+        drop(self.file);
+
+        // An archive that fits in one volume is stored as a regular archive instead.
+        if self.volume_paths.len() == 1 {
+            let mut destination = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&self.final_volume)
+                .map_err(|error| format!("Failed to finalize split archive: {}", error))?;
+            join_split_zip_volumes(&self.volume_paths, &mut destination, None)?;
+            let _ = fs::remove_file(&self.volume_paths[0]);
+            return Ok(());
+        }
+
+        let last_volume = self
+            .volume_paths
+            .pop()
+            .ok_or_else(|| "Split archive has no volumes".to_string())?;
+        fs::rename(&last_volume, &self.final_volume)
+            .map_err(|error| format!("Failed to finalize split archive: {}", error))
+    }
+}
+
+impl Write for SplitVolumeWriter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        if self.position < self.archive_len() {
+            return self.patch(buffer);
+        }
+
+        // The zip writer emits each local header's fixed part in one write. Starting a new
+        // volume for it keeps the whole header, name and extra field included, on one
+        // volume. Data that merely looks like a header only ends a volume early.
+        if buffer.len() >= LOCAL_FILE_HEADER_LEN
+            && read_u32_at(buffer, 0) == LOCAL_FILE_HEADER_SIGNATURE
+        {
+            let header_len = LOCAL_FILE_HEADER_LEN as u64
+                + read_u16_at(buffer, 26) as u64
+                + read_u16_at(buffer, 28) as u64;
+            if self.written_in_volume > 0
+                && header_len <= self.volume_size
+                && self.written_in_volume + header_len > self.volume_size
+            {
+                self.start_next_volume().map_err(io::Error::other)?;
+            }
+        }
+
+        if self.written_in_volume >= self.volume_size {
+            self.start_next_volume().map_err(io::Error::other)?;
+        }
+        let room = (self.volume_size - self.written_in_volume).min(buffer.len() as u64) as usize;
+        let written = self.file.write(&buffer[..room])?;
+        self.written_in_volume += written as u64;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for SplitVolumeWriter {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let stream_len = self.archive_len();
+        let target = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => stream_len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        self.position = target
+            .filter(|target| *target <= stream_len)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "Invalid seek position"))?;
+        Ok(self.position)
+    }
+}
+
+fn write_split_central_directory(
+    writer: &mut SplitVolumeWriter,
+    mut end_record: EndOfCentralDirectory,
+    entries: &[CentralDirectoryEntry],
+) -> Result<(), String> {
+    let mut central_directory_start: Option<(u32, u64)> = None;
+    let mut central_directory_size = 0u64;
+    let mut last_entry_disk: Option<u32> = None;
+    let mut last_disk_entry_count = 0u64;
+    for entry in entries {
+        let encoded = entry.encode();
+        let (disk_number, offset) = writer.write_record(&encoded)?;
+        central_directory_start.get_or_insert((disk_number, offset));
+        if last_entry_disk != Some(disk_number) {
+            last_entry_disk = Some(disk_number);
+            last_disk_entry_count = 0;
+        }
+        last_disk_entry_count += 1;
+        central_directory_size += encoded.len() as u64;
+    }
+
+    let (central_directory_disk, central_directory_offset) =
+        central_directory_start.unwrap_or((writer.disk_number(), writer.written_in_volume));
+    end_record.central_directory_disk = central_directory_disk;
+    end_record.central_directory_offset = central_directory_offset;
+    end_record.central_directory_size = central_directory_size;
+    end_record.entry_count = entries.len() as u64;
+
+    let mut end_bytes = encode_split_end_record(&mut end_record, writer, last_disk_entry_count);
+    if writer.written_in_volume + end_bytes.len() as u64 > writer.volume_size {
+        writer.start_next_volume()?;
+        end_bytes = encode_split_end_record(&mut end_record, writer, 0);
+    }
+    writer.write_record(&end_bytes)?;
+
+    Ok(())
+}
+
+fn encode_split_end_record(
+    end_record: &mut EndOfCentralDirectory,
+    writer: &SplitVolumeWriter,
+    disk_entry_count: u64,
+) -> Vec<u8> {
+    end_record.disk_number = writer.disk_number();
+    end_record.disk_entry_count = disk_entry_count;
+    end_record.encode(writer.written_in_volume)
+}
+
+/// Writes a new archive straight into `.z01`, `.z02`, … volumes of at most `volume_size`
+/// bytes, with the final volume at `final_volume`, so the archive never exists as one
+/// file. `write_entries` adds the entries. An archive that fits in one volume is stored
+/// as a regular archive.
+pub fn write_split_zip(
+    final_volume: &Path,
+    volume_size: u64,
+    write_entries: impl FnOnce(&mut ZipWriter<&mut SplitVolumeWriter>) -> Result<(), String>,
+) -> Result<(), String> {
+    if volume_size < MIN_SPLIT_VOLUME_SIZE {
+        return Err(format!(
+            "Volume size must be at least {} bytes",
+            MIN_SPLIT_VOLUME_SIZE
+        ));
+    }
+
+    let mut writer = SplitVolumeWriter::create(final_volume, volume_size)?;
+    let result = writer
+        .write_record(&SPLIT_ARCHIVE_SIGNATURE.to_le_bytes())
+        .and_then(|_| {
+            let mut zip_writer = ZipWriter::new(&mut writer);
+            write_entries(&mut zip_writer)?;
+            zip_writer
+                .finish()
+                .map_err(|error| format!("Failed to finalize zip: {}", error))?;
+            Ok(())
+        })
+        .and_then(|()| writer.rewrite_central_directory());
+
+    match result {
+        Ok(()) => {
+            let volume_paths = writer.volume_paths.clone();
+            let result = writer.finish();
+            if result.is_err() {
+                let _ = fs::remove_file(final_volume);
+                for volume in volume_paths {
+                    let _ = fs::remove_file(volume);
+                }
+            }
+            result
+        }
+        Err(message) => {
+            writer.remove_volumes();
+            Err(message)
+        }
+    }
+}
+
+/// Joins the volumes of a split set into a regular single-file archive.
+pub fn join_split_zip_volumes(
+    volumes: &[PathBuf],
+    destination: &mut File,
+    sink: Option<&ProgressSink>,
+) -> Result<(), String> {
+    let volume_set = ZipVolumes::new(volumes.to_vec()).map_err(split_io_error)?;
+    let (mut end_record, mut entries) =
+        read_central_directory(&volume_set).map_err(split_io_error)?;
+    if end_record.disk_number as usize + 1 != volume_set.len() {
+        return Err("Split archive is incomplete: volumes are missing".to_string());
+    }
+
+    let mut source = volume_set.reader();
+    let mut signature = [0u8; 4];
+    source.read_exact(&mut signature).map_err(split_io_error)?;
+    let prefix_len = if read_u32_at(&signature, 0) == SPLIT_ARCHIVE_SIGNATURE {
+        signature.len() as u64
+    } else {
+        0
+    };
+
+    let central_directory_position = volume_set
+        .absolute_offset(
+            end_record.central_directory_disk,
+            end_record.central_directory_offset,
+        )
+        .map_err(split_io_error)?;
+    if central_directory_position < prefix_len {
+        return Err("Invalid zip central directory".to_string());
+    }
+
+    destination.set_len(0).map_err(split_io_error)?;
+    destination
+        .seek(SeekFrom::Start(0))
+        .map_err(split_io_error)?;
+    source
+        .seek(SeekFrom::Start(prefix_len))
+        .map_err(split_io_error)?;
+    copy_with_periodic_cancel(
+        &mut (&mut source).take(central_directory_position - prefix_len),
+        destination,
+        sink,
+    )?;
+
+    let mut written = central_directory_position - prefix_len;
+    for entry in entries.iter_mut() {
+        let absolute = volume_set
+            .absolute_offset(entry.disk_number_start, entry.local_header_offset)
+            .map_err(split_io_error)?;
+        entry.disk_number_start = 0;
+        entry.local_header_offset = absolute
+            .checked_sub(prefix_len)
+            .ok_or_else(|| "Invalid zip local file header".to_string())?;
+
+        let encoded = entry.encode();
+        destination.write_all(&encoded).map_err(split_io_error)?;
+        written += encoded.len() as u64;
+    }
+
+    end_record.central_directory_size = written - (central_directory_position - prefix_len);
+    end_record.central_directory_offset = central_directory_position - prefix_len;
+    end_record.disk_number = 0;
+    end_record.central_directory_disk = 0;
+    end_record.disk_entry_count = end_record.entry_count;
+    destination
+        .write_all(&end_record.encode(written))
+        .map_err(split_io_error)?;
+    destination.flush().map_err(split_io_error)?;
+
+    Ok(())
+}
+
+/// Runs `operation` against a readable single-file archive. Split sets are joined into a
+/// temporary file next to the archive first, or in the system temp folder if that fails.
+pub fn with_joined_split_zip<T>(
+    zip_path: &Path,
+    sink: Option<&ProgressSink>,
+    operation: impl FnOnce(&Path) -> Result<T, String>,
+) -> Result<T, String> {
+    let Some(volumes) = find_split_zip_volumes(zip_path)? else {
+        return operation(zip_path);
+    };
+
+    let final_volume = volumes.last().cloned().unwrap_or_else(|| zip_path.into());
+    let (mut joined, joined_path) = create_sibling_temp_file(&final_volume).or_else(|_| {
+        let file_name = final_volume
+            .file_name()
+            .ok_or_else(|| "Invalid archive name".to_string())?;
+        create_sibling_temp_file(&std::env::temp_dir().join(file_name))
+    })?;
+
+    let result = join_split_zip_volumes(&volumes, &mut joined, sink).and_then(|()| {
+        drop(joined);
+        operation(&joined_path)
+    });
+    let _ = fs::remove_file(&joined_path);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipArchive};
+
+    fn incompressible_contents(count: u8, len: u32) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|file_index| {
+                (0..len)
+                    .map(|value| (value.wrapping_mul(2_654_435_761) >> 13) as u8 ^ file_index)
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn write_split_files(final_volume: &Path, contents: &[Vec<u8>]) -> Result<(), String> {
+        write_split_zip(final_volume, MIN_SPLIT_VOLUME_SIZE, |zip_writer| {
+            let options =
+                SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+            for (file_index, content) in contents.iter().enumerate() {
+                zip_writer
+                    .start_file(format!("file-{file_index}.bin"), options)
+                    .map_err(|error| error.to_string())?;
+                zip_writer
+                    .write_all(content)
+                    .map_err(|error| error.to_string())?;
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn split_volumes_round_trip_through_join() {
+        let temp = tempfile::tempdir().unwrap();
+        let contents = incompressible_contents(3, 100_000);
+
+        let final_volume = temp.path().join("bundle.zip");
+        write_split_files(&final_volume, &contents).unwrap();
+
+        let volumes = find_split_zip_volumes(&temp.path().join("bundle.z02"))
+            .unwrap()
+            .unwrap();
+        assert!(volumes.len() > 1);
+        assert_eq!(volumes.last().unwrap(), &final_volume);
+        for volume in &volumes {
+            assert!(fs::metadata(volume).unwrap().len() <= MIN_SPLIT_VOLUME_SIZE);
+        }
+
+        let extracted = with_joined_split_zip(&final_volume, None, |joined_path| {
+            let mut archive = ZipArchive::new(File::open(joined_path).unwrap()).unwrap();
+            Ok((0..archive.len())
+                .map(|index| {
+                    let mut entry = archive.by_index(index).unwrap();
+                    let mut content = Vec::new();
+                    entry.read_to_end(&mut content).unwrap();
+                    content
+                })
+                .collect::<Vec<_>>())
+        })
+        .unwrap();
+        assert_eq!(extracted, contents);
+    }
+
+    #[test]
+    fn archives_that_fit_one_volume_are_stored_as_regular_archives() {
+        let temp = tempfile::tempdir().unwrap();
+        let contents = incompressible_contents(2, 1_000);
+
+        let final_volume = temp.path().join("small.zip");
+        write_split_files(&final_volume, &contents).unwrap();
+
+        assert!(!split_zip_volume_path(&final_volume, 1).exists());
+        let mut archive = ZipArchive::new(File::open(&final_volume).unwrap()).unwrap();
+        let mut content = Vec::new();
+        archive
+            .by_index(1)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, contents[1]);
+    }
+
+    #[test]
+    fn split_destinations_avoid_leftover_volumes() {
+        let temp = tempfile::tempdir().unwrap();
+        let base = temp.path().join("bundle.zip");
+        assert_eq!(unique_split_zip_destination(&base), base);
+
+        fs::write(temp.path().join("bundle.z03"), b"").unwrap();
+        assert_eq!(
+            unique_split_zip_destination(&base),
+            temp.path().join("bundle (1).zip")
+        );
+    }
+
+    #[test]
+    fn find_split_zip_volumes_ignores_regular_archives() {
+        let temp = tempfile::tempdir().unwrap();
+        let archive = temp.path().join("plain.zip");
+        fs::write(&archive, b"").unwrap();
+
+        assert!(find_split_zip_volumes(&archive).unwrap().is_none());
+    }
+
+    #[test]
+    fn find_split_zip_volumes_reports_missing_final_volume() {
+        let temp = tempfile::tempdir().unwrap();
+        fs::write(temp.path().join("bundle.z01"), b"").unwrap();
+
+        let result = find_split_zip_volumes(&temp.path().join("bundle.z01"));
+        assert!(result.unwrap_err().contains("bundle.zip"));
+    }
+}
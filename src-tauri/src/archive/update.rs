@@ -0,0 +1,719 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use super::compress::{build_zip_entries, zip_file_options};
+use super::encoding::{
+    decode_zip_entry_path_str, detect_archive_entry_encoding, encode_zip_entry_path_str,
+};
+use super::extract::copy_with_periodic_cancel;
+use super::jobs::{ProgressSink, ARCHIVE_ERROR_OUTPUT_ALREADY_EXISTS};
+use super::zip_records::{
+    read_central_directory, read_local_file_header, read_u16_at, write_u16_at, ZipVolumes,
+    LOCAL_FILE_HEADER_LEN, UTF8_NAME_FLAG,
+};
+
+static ARCHIVE_UPDATE_TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+pub enum ArchiveEdit {
+    Add {
+        source_paths: Vec<PathBuf>,
+        entry_dir: Option<String>,
+        replace_existing: bool,
+    },
+    Delete {
+        entry_paths: Vec<String>,
+    },
+    Rename {
+        entry_path: String,
+        new_name: String,
+    },
+}
+
+enum PlannedSource {
+    Existing(usize),
+    Disk(PathBuf),
+}
+
+struct PlannedEntry {
+    source: PlannedSource,
+    name: String,
+    raw_name: Option<Vec<u8>>,
+}
+
+impl PlannedEntry {
+    fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+
+    fn trimmed_name(&self) -> &str {
+        self.name.trim_end_matches('/')
+    }
+}
+
+fn normalize_entry_path(path: &str) -> String {
+    path.replace('\\', "/")
+        .trim_start_matches("./")
+        .trim_matches('/')
+        .to_string()
+}
+
+fn is_same_or_descendant_entry(name: &str, entry_path: &str) -> bool {
+    let trimmed = name.trim_end_matches('/');
+    trimmed == entry_path
+        || trimmed
+            .strip_prefix(entry_path)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn validate_new_entry_name(new_name: &str) -> Result<(), String> {
+    if new_name.is_empty()
+        || new_name == "."
+        || new_name == ".."
+        || new_name.contains(['/', '\\', '\0'])
+    {
+        return Err(format!("Invalid entry name: {}", new_name));
+    }
+    Ok(())
+}
+
+fn encoded_raw_name(name: &str, encoding: Option<&str>) -> Option<Vec<u8>> {
+    if name.is_ascii() {
+        return None;
+    }
+    encoding.and_then(|label| encode_zip_entry_path_str(name, label))
+}
+
+fn read_existing_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    encoding: Option<&str>,
+) -> Result<Vec<PlannedEntry>, String> {
+    let mut entries = Vec::with_capacity(archive.len());
+
+    for index in 0..archive.len() {
+        let entry = archive
+            .by_index_raw(index)
+            .map_err(|error| format!("Failed to read zip entry: {}", error))?;
+        if entry.encrypted() {
+            return Err("Updating password-protected archives is not supported".to_string());
+        }
+
+        let raw = entry.name_raw();
+        let name = match (std::str::from_utf8(raw), encoding) {
+            (Ok(name), _) => name.to_string(),
+            (Err(_), Some(label)) => decode_zip_entry_path_str(raw, label)?,
+            (Err(_), None) => entry.name().to_string(),
+        };
+        let raw_name = (raw != name.as_bytes()).then(|| raw.to_vec());
+
+        entries.push(PlannedEntry {
+            source: PlannedSource::Existing(index),
+            name,
+            raw_name,
+        });
+    }
+
+    Ok(entries)
+}
+
+fn apply_add(
+    entries: &mut Vec<PlannedEntry>,
+    archive_path: &Path,
+    source_paths: &[PathBuf],
+    entry_dir: Option<&str>,
+    replace_existing: bool,
+    encoding: Option<&str>,
+) -> Result<(), String> {
+    if source_paths.is_empty() {
+        return Err("No sources to add".to_string());
+    }
+
+    let canonical_archive = archive_path
+        .canonicalize()
+        .map_err(|error| format!("Failed to resolve archive path: {}", error))?;
+    let mut canonical_sources = Vec::with_capacity(source_paths.len());
+    for path in source_paths {
+        let canonical = path
+            .canonicalize()
+            .map_err(|error| format!("Failed to resolve path: {}", error))?;
+        if canonical == canonical_archive {
+            return Err("Cannot add the archive to itself".to_string());
+        }
+        canonical_sources.push(canonical);
+    }
+
+    let prefix = entry_dir
+        .map(normalize_entry_path)
+        .filter(|value| !value.is_empty())
+        .map(|value| format!("{}/", value))
+        .unwrap_or_default();
+
+    for (disk_path, zip_path) in build_zip_entries(&canonical_sources)? {
+        let name = format!("{}{}", prefix, zip_path);
+        let new_is_dir = name.ends_with('/');
+        let trimmed = name.trim_end_matches('/');
+
+        if let Some(position) = entries
+            .iter()
+            .position(|entry| entry.trimmed_name() == trimmed)
+        {
+            let existing_is_dir = entries[position].is_dir();
+            if existing_is_dir != new_is_dir {
+                return Err(ARCHIVE_ERROR_OUTPUT_ALREADY_EXISTS.to_string());
+            }
+            if new_is_dir {
+                continue;
+            }
+            if !replace_existing {
+                return Err(ARCHIVE_ERROR_OUTPUT_ALREADY_EXISTS.to_string());
+            }
+            entries.remove(position);
+        }
+
+        entries.push(PlannedEntry {
+            source: PlannedSource::Disk(disk_path),
+            raw_name: encoded_raw_name(&name, encoding),
+            name,
+        });
+    }
+
+    Ok(())
+}
+
+fn apply_delete(entries: &mut Vec<PlannedEntry>, entry_paths: &[String]) -> Result<(), String> {
+    for entry_path in entry_paths {
+        let target = normalize_entry_path(entry_path);
+        if target.is_empty() {
+            return Err(format!("Invalid entry path: {}", entry_path));
+        }
+
+        let count_before = entries.len();
+        entries.retain(|entry| !is_same_or_descendant_entry(&entry.name, &target));
+        if entries.len() == count_before {
+            return Err(format!("Entry not found in archive: {}", entry_path));
+        }
+    }
+
+    Ok(())
+}
+
+fn apply_rename(
+    entries: &mut [PlannedEntry],
+    entry_path: &str,
+    new_name: &str,
+    encoding: Option<&str>,
+) -> Result<(), String> {
+    validate_new_entry_name(new_name)?;
+
+    let target = normalize_entry_path(entry_path);
+    if target.is_empty() {
+        return Err(format!("Invalid entry path: {}", entry_path));
+    }
+
+    let renamed_target = match target.rsplit_once('/') {
+        Some((parent, _)) => format!("{}/{}", parent, new_name),
+        None => new_name.to_string(),
+    };
+    if renamed_target == target {
+        return Ok(());
+    }
+
+    if entries
+        .iter()
+        .any(|entry| is_same_or_descendant_entry(&entry.name, &renamed_target))
+    {
+        return Err(ARCHIVE_ERROR_OUTPUT_ALREADY_EXISTS.to_string());
+    }
+
+    let mut renamed_count = 0usize;
+    for entry in entries.iter_mut() {
+        if !is_same_or_descendant_entry(&entry.name, &target) {
+            continue;
+        }
+
+        let name = format!("{}{}", renamed_target, &entry.name[target.len()..]);
+        entry.raw_name = encoded_raw_name(&name, encoding);
+        entry.name = name;
+        renamed_count += 1;
+    }
+
+    if renamed_count == 0 {
+        return Err(format!("Entry not found in archive: {}", entry_path));
+    }
+
+    Ok(())
+}
+
+pub(super) fn create_sibling_temp_file(archive_path: &Path) -> Result<(File, PathBuf), String> {
+    let parent = archive_path
+        .parent()
+        .ok_or_else(|| "Invalid archive path".to_string())?;
+    let file_name = archive_path
+        .file_name()
+        .map(|value| value.to_string_lossy().to_string())
+        .ok_or_else(|| "Invalid archive name".to_string())?;
+
+    loop {
+        let sequence = ARCHIVE_UPDATE_TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let temporary_path = parent.join(format!(
+            ".{file_name}.{}.{}.tmp",
+            std::process::id(),
+            sequence
+        ));
+
+        match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&temporary_path)
+        {
+            Ok(file) => return Ok((file, temporary_path)),
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => {}
+            Err(error) => {
+                return Err(format!(
+                    "Failed to create temporary file \"{}\": {error}",
+                    temporary_path.display()
+                ));
+            }
+        }
+    }
+}
+
+fn directory_entry_options<R: Read>(entry: &zip::read::ZipFile<'_, R>) -> SimpleFileOptions {
+    let mut options = zip_file_options();
+    if let Some(modified) = entry.last_modified() {
+        options = options.last_modified_time(modified);
+    }
+    if let Some(mode) = entry.unix_mode() {
+        options = options.unix_permissions(mode & 0o7777);
+    }
+    options
+}
+
+fn write_planned_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    entries: &[PlannedEntry],
+    output: File,
+    sink: Option<&ProgressSink>,
+) -> Result<(), String> {
+    let mut zip_writer = ZipWriter::new(output);
+    let total_entries = entries.len().max(1) as u32;
+
+    if !archive.comment().is_empty() {
+        zip_writer
+            .set_raw_comment(archive.comment().into())
+            .map_err(|error| format!("Failed to copy archive comment: {}", error))?;
+    }
+
+    for (entry_index, planned) in entries.iter().enumerate() {
+        if let Some(progress_sink) = sink {
+            progress_sink.check_cancelled()?;
+            let percent = ((entry_index as u32 + 1) * 100 / total_entries).min(100);
+            progress_sink.report(percent, planned.name.clone());
+        }
+
+        match &planned.source {
+            PlannedSource::Existing(index) => {
+                let entry = archive
+                    .by_index_raw(*index)
+                    .map_err(|error| format!("Failed to read zip entry: {}", error))?;
+                if planned.is_dir() {
+                    let options = directory_entry_options(&entry);
+                    drop(entry);
+                    zip_writer
+                        .add_directory(planned.name.clone(), options)
+                        .map_err(|error| format!("Failed to add directory to zip: {}", error))?;
+                } else {
+                    zip_writer
+                        .raw_copy_file_rename(entry, planned.name.clone())
+                        .map_err(|error| format!("Failed to copy zip entry: {}", error))?;
+                }
+            }
+            PlannedSource::Disk(disk_path) => {
+                let options = zip_file_options();
+                if planned.is_dir() {
+                    zip_writer
+                        .add_directory(planned.name.clone(), options)
+                        .map_err(|error| format!("Failed to add directory to zip: {}", error))?;
+                } else {
+                    zip_writer
+                        .start_file(planned.name.clone(), options)
+                        .map_err(|error| format!("Failed to start zip entry: {}", error))?;
+                    let mut source_file =
+                        File::open(disk_path).map_err(|error| error.to_string())?;
+                    copy_with_periodic_cancel(&mut source_file, &mut zip_writer, sink)?;
+                }
+            }
+        }
+    }
+
+    zip_writer
+        .finish()
+        .map_err(|error| format!("Failed to finalize zip: {}", error))?;
+
+    Ok(())
+}
+
+fn flags_for_raw_name(flags: u16, raw_name: &[u8]) -> u16 {
+    if !raw_name.is_ascii() && std::str::from_utf8(raw_name).is_ok() {
+        flags | UTF8_NAME_FLAG
+    } else {
+        flags & !UTF8_NAME_FLAG
+    }
+}
+
+/// Copies a zip written by `ZipWriter` while swapping entry names for the given raw bytes.
+/// `ZipWriter` always stores names as UTF-8, so this is how legacy-encoded names survive an
+/// update.
+pub(super) fn copy_zip_with_raw_entry_names(
+    source_path: &Path,
+    destination: &mut File,
+    raw_names: &HashMap<String, Vec<u8>>,
+) -> Result<(), String> {
+    let io_error = |error: io::Error| format!("Failed to rewrite entry names: {}", error);
+    let volumes = ZipVolumes::new(vec![source_path.to_path_buf()]).map_err(io_error)?;
+    let (mut end_record, mut entries) = read_central_directory(&volumes).map_err(io_error)?;
+    let mut source = volumes.reader();
+
+    let mut local_order: Vec<usize> = (0..entries.len()).collect();
+    local_order.sort_by_key(|index| entries[*index].local_header_offset);
+
+    destination.set_len(0).map_err(io_error)?;
+    destination.seek(SeekFrom::Start(0)).map_err(io_error)?;
+    let mut written = 0u64;
+
+    for (order_index, entry_index) in local_order.iter().copied().enumerate() {
+        let data_end = local_order
+            .get(order_index + 1)
+            .map(|next| entries[*next].local_header_offset)
+            .unwrap_or(end_record.central_directory_offset);
+        let entry = &mut entries[entry_index];
+
+        let (mut local_header, _) =
+            read_local_file_header(&mut source, entry.local_header_offset).map_err(io_error)?;
+        let old_name_len = read_u16_at(&local_header, 26) as u64;
+        let data_start = entry.local_header_offset + LOCAL_FILE_HEADER_LEN as u64 + old_name_len;
+        if data_start > data_end {
+            return Err("Invalid zip local file header".to_string());
+        }
+
+        if let Some(raw_name) = String::from_utf8(entry.name.clone())
+            .ok()
+            .and_then(|name| raw_names.get(&name))
+            .filter(|raw_name| raw_name.len() <= u16::MAX as usize)
+        {
+            entry.name = raw_name.clone();
+            entry.set_flags(flags_for_raw_name(entry.flags(), raw_name));
+        }
+        write_u16_at(&mut local_header, 6, entry.flags());
+        write_u16_at(&mut local_header, 26, entry.name.len() as u16);
+
+        entry.local_header_offset = written;
+        destination.write_all(&local_header).map_err(io_error)?;
+        destination.write_all(&entry.name).map_err(io_error)?;
+        source.seek(SeekFrom::Start(data_start)).map_err(io_error)?;
+        let copied = io::copy(
+            &mut (&mut source).take(data_end - data_start),
+            &mut *destination,
+        )
+        .map_err(io_error)?;
+        written += (LOCAL_FILE_HEADER_LEN + entry.name.len()) as u64 + copied;
+    }
+
+    let central_directory_offset = written;
+    for entry in &entries {
+        let encoded = entry.encode();
+        destination.write_all(&encoded).map_err(io_error)?;
+        written += encoded.len() as u64;
+    }
+
+    end_record.central_directory_size = written - central_directory_offset;
+    end_record.central_directory_offset = central_directory_offset;
+    destination
+        .write_all(&end_record.encode(written))
+        .map_err(io_error)?;
+    destination.flush().map_err(io_error)?;
+
+    Ok(())
+}
+
+fn swap_in_updated_archive(temporary_path: &Path, archive_path: &Path) -> Result<(), String> {
+    if let Ok(metadata) = fs::metadata(archive_path) {
+        let _ = fs::set_permissions(temporary_path, metadata.permissions());
+    }
+    fs::rename(temporary_path, archive_path)
+        .map_err(|error| format!("Failed to replace archive: {}", error))
+}
+
+pub fn update_zip_archive_with_sink(
+    archive_path: &Path,
+    edit: &ArchiveEdit,
+    encoding: Option<&str>,
+    sink: Option<&ProgressSink>,
+) -> Result<(), String> {
+    let file =
+        File::open(archive_path).map_err(|error| format!("Failed to open zip file: {}", error))?;
+    let mut archive =
+        ZipArchive::new(file).map_err(|error| format!("Failed to read zip archive: {}", error))?;
+
+    let detected_encoding = match encoding {
+        Some(label) => Some(label.to_string()),
+        None => detect_archive_entry_encoding(&mut archive)?,
+    };
+    let encoding_label = detected_encoding.as_deref();
+
+    let mut entries = read_existing_entries(&mut archive, encoding_label)?;
+    let legacy_encoding = encoding_label.filter(|_| {
+        entries.iter().any(|entry| {
+            entry
+                .raw_name
+                .as_ref()
+                .is_some_and(|raw| std::str::from_utf8(raw).is_err())
+        })
+    });
+
+    match edit {
+        ArchiveEdit::Add {
+            source_paths,
+            entry_dir,
+            replace_existing,
+        } => apply_add(
+            &mut entries,
+            archive_path,
+            source_paths,
+            entry_dir.as_deref(),
+            *replace_existing,
+            legacy_encoding,
+        )?,
+        ArchiveEdit::Delete { entry_paths } => apply_delete(&mut entries, entry_paths)?,
+        ArchiveEdit::Rename {
+            entry_path,
+            new_name,
+        } => apply_rename(&mut entries, entry_path, new_name, legacy_encoding)?,
+    }
+
+    let mut seen = HashSet::new();
+    for entry in &entries {
+        if !seen.insert(entry.name.as_str()) {
+            return Err(format!("Duplicate entry name in archive: {}", entry.name));
+        }
+    }
+
+    let raw_names: HashMap<String, Vec<u8>> = entries
+        .iter()
+        .filter_map(|entry| {
+            entry
+                .raw_name
+                .as_ref()
+                .map(|raw| (entry.name.clone(), raw.clone()))
+        })
+        .collect();
+
+    let (output, written_path) = create_sibling_temp_file(archive_path)?;
+    let mut temporary_paths = vec![written_path.clone()];
+
+    let update_result = (|| -> Result<(), String> {
+        write_planned_entries(&mut archive, &entries, output, sink)?;
+        drop(archive);
+
+        let mut final_path = written_path.clone();
+        if !raw_names.is_empty() {
+            if let Some(progress_sink) = sink {
+                progress_sink.check_cancelled()?;
+            }
+            let (mut renamed_output, renamed_path) = create_sibling_temp_file(archive_path)?;
+            temporary_paths.push(renamed_path.clone());
+            copy_zip_with_raw_entry_names(&written_path, &mut renamed_output, &raw_names)?;
+            final_path = renamed_path;
+        }
+
+        swap_in_updated_archive(&final_path, archive_path)
+    })();
+
+    for temporary_path in temporary_paths {
+        if temporary_path.exists() {
+            let _ = fs::remove_file(&temporary_path);
+        }
+    }
+
+    update_result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip_writer = ZipWriter::new(File::create(path).unwrap());
+        for (name, content) in files {
+            if name.ends_with('/') {
+                zip_writer
+                    .add_directory(*name, SimpleFileOptions::default())
+                    .unwrap();
+            } else {
+                zip_writer
+                    .start_file(*name, SimpleFileOptions::default())
+                    .unwrap();
+                zip_writer.write_all(content).unwrap();
+            }
+        }
+        zip_writer.finish().unwrap();
+    }
+
+    fn entry_names(path: &Path) -> Vec<String> {
+        let mut archive = ZipArchive::new(File::open(path).unwrap()).unwrap();
+        (0..archive.len())
+            .map(|index| archive.by_index(index).unwrap().name().to_string())
+            .collect()
+    }
+
+    fn entry_content(path: &Path, name: &str) -> Vec<u8> {
+        let mut archive = ZipArchive::new(File::open(path).unwrap()).unwrap();
+        let mut entry = archive.by_name(name).unwrap();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn add_appends_files_under_entry_dir() {
+        let temp = tempfile::tempdir().unwrap();
+        let zip_path = temp.path().join("archive.zip");
+        write_zip(&zip_path, &[("docs/", b""), ("docs/a.txt", b"a")]);
+        let source = temp.path().join("b.txt");
+        fs::write(&source, b"b").unwrap();
+
+        let edit = ArchiveEdit::Add {
+            source_paths: vec![source],
+            entry_dir: Some("docs".to_string()),
+            replace_existing: false,
+        };
+        update_zip_archive_with_sink(&zip_path, &edit, None, None).unwrap();
+
+        assert_eq!(
+            entry_names(&zip_path),
+            ["docs/", "docs/a.txt", "docs/b.txt"]
+        );
+        assert_eq!(entry_content(&zip_path, "docs/a.txt"), b"a");
+        assert_eq!(entry_content(&zip_path, "docs/b.txt"), b"b");
+    }
+
+    #[test]
+    fn add_rejects_existing_entry_unless_replacing() {
+        let temp = tempfile::tempdir().unwrap();
+        let zip_path = temp.path().join("archive.zip");
+        write_zip(&zip_path, &[("a.txt", b"old")]);
+        let source = temp.path().join("a.txt");
+        fs::write(&source, b"new").unwrap();
+
+        let add = ArchiveEdit::Add {
+            source_paths: vec![source.clone()],
+            entry_dir: None,
+            replace_existing: false,
+        };
+        let result = update_zip_archive_with_sink(&zip_path, &add, None, None);
+        assert_eq!(result.unwrap_err(), ARCHIVE_ERROR_OUTPUT_ALREADY_EXISTS);
+        assert_eq!(entry_content(&zip_path, "a.txt"), b"old");
+
+        let replace = ArchiveEdit::Add {
+            source_paths: vec![source],
+            entry_dir: None,
+            replace_existing: true,
+        };
+        update_zip_archive_with_sink(&zip_path, &replace, None, None).unwrap();
+        assert_eq!(entry_names(&zip_path), ["a.txt"]);
+        assert_eq!(entry_content(&zip_path, "a.txt"), b"new");
+    }
+
+    #[test]
+    fn delete_removes_directory_with_descendants() {
+        let temp = tempfile::tempdir().unwrap();
+        let zip_path = temp.path().join("archive.zip");
+        write_zip(
+            &zip_path,
+            &[
+                ("keep.txt", b"k"),
+                ("dir/", b""),
+                ("dir/x.txt", b"x"),
+                ("dirx.txt", b"d"),
+            ],
+        );
+
+        let edit = ArchiveEdit::Delete {
+            entry_paths: vec!["dir".to_string()],
+        };
+        update_zip_archive_with_sink(&zip_path, &edit, None, None).unwrap();
+
+        assert_eq!(entry_names(&zip_path), ["keep.txt", "dirx.txt"]);
+    }
+
+    #[test]
+    fn rename_moves_directory_children() {
+        let temp = tempfile::tempdir().unwrap();
+        let zip_path = temp.path().join("archive.zip");
+        write_zip(&zip_path, &[("old/", b""), ("old/a.txt", b"a")]);
+
+        let edit = ArchiveEdit::Rename {
+            entry_path: "old/".to_string(),
+            new_name: "new".to_string(),
+        };
+        update_zip_archive_with_sink(&zip_path, &edit, None, None).unwrap();
+
+        assert_eq!(entry_names(&zip_path), ["new/", "new/a.txt"]);
+        assert_eq!(entry_content(&zip_path, "new/a.txt"), b"a");
+    }
+
+    #[test]
+    fn update_preserves_legacy_encoded_entry_names() {
+        let temp = tempfile::tempdir().unwrap();
+        let utf8_path = temp.path().join("utf8.zip");
+        write_zip(&utf8_path, &[("あ.txt", b"a"), ("い.txt", b"i")]);
+
+        let zip_path = temp.path().join("shift_jis.zip");
+        let raw_names = HashMap::from([
+            (
+                "あ.txt".to_string(),
+                encode_zip_entry_path_str("あ.txt", "shift_jis").unwrap(),
+            ),
+            (
+                "い.txt".to_string(),
+                encode_zip_entry_path_str("い.txt", "shift_jis").unwrap(),
+            ),
+        ]);
+        let mut output = File::create(&zip_path).unwrap();
+        copy_zip_with_raw_entry_names(&utf8_path, &mut output, &raw_names).unwrap();
+        drop(output);
+
+        let edit = ArchiveEdit::Rename {
+            entry_path: "い.txt".to_string(),
+            new_name: "う.txt".to_string(),
+        };
+        update_zip_archive_with_sink(&zip_path, &edit, None, None).unwrap();
+
+        let mut archive = ZipArchive::new(File::open(&zip_path).unwrap()).unwrap();
+        let raw_names: Vec<Vec<u8>> = (0..archive.len())
+            .map(|index| archive.by_index(index).unwrap().name_raw().to_vec())
+            .collect();
+        assert_eq!(
+            raw_names,
+            [
+                encode_zip_entry_path_str("あ.txt", "shift_jis").unwrap(),
+                encode_zip_entry_path_str("う.txt", "shift_jis").unwrap(),
+            ]
+        );
+
+        let mut renamed = archive.by_index(1).unwrap();
+        let mut content = Vec::new();
+        renamed.read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"i");
+    }
+}
@@ -0,0 +1,543 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+pub const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+pub const SPLIT_ARCHIVE_SIGNATURE: u32 = 0x0807_4b50;
+pub const LOCAL_FILE_HEADER_LEN: usize = 30;
+pub const UTF8_NAME_FLAG: u16 = 1 << 11;
+pub const ENCRYPTED_FLAG: u16 = 1;
+
+const CENTRAL_DIRECTORY_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0606_4b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const ZIP64_EXTRA_FIELD_ID: u16 = 0x0001;
+const ZIP64_VERSION_NEEDED: u16 = 45;
+const CENTRAL_DIRECTORY_HEADER_LEN: usize = 46;
+const END_OF_CENTRAL_DIRECTORY_LEN: usize = 22;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_LEN: usize = 56;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_LEN: usize = 20;
+const MAX_ZIP_COMMENT_LEN: usize = u16::MAX as usize;
+
+pub fn read_u16_at(buffer: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buffer[offset], buffer[offset + 1]])
+}
+
+pub fn read_u32_at(buffer: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        buffer[offset],
+        buffer[offset + 1],
+        buffer[offset + 2],
+        buffer[offset + 3],
+    ])
+}
+
+fn read_u64_at(buffer: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buffer[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+pub fn write_u16_at(buffer: &mut [u8], offset: usize, value: u16) {
+    buffer[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+pub fn write_u32_at(buffer: &mut [u8], offset: usize, value: u32) {
+    buffer[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn invalid_zip(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Reads the 30 byte local file header at `offset` and returns it with the length of the
+/// name and extra field that follow it.
+pub fn read_local_file_header<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+) -> io::Result<([u8; LOCAL_FILE_HEADER_LEN], u64)> {
+    let mut header = [0u8; LOCAL_FILE_HEADER_LEN];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut header)?;
+    if read_u32_at(&header, 0) != LOCAL_FILE_HEADER_SIGNATURE {
+        return Err(invalid_zip("Invalid zip local file header"));
+    }
+    let variable_len = read_u16_at(&header, 26) as u64 + read_u16_at(&header, 28) as u64;
+    Ok((header, variable_len))
+}
+
+/// The volumes of a zip archive laid end to end. A regular archive is a single volume.
+pub struct ZipVolumes {
+    paths: Vec<PathBuf>,
+    starts: Vec<u64>,
+    total_len: u64,
+    /// The files hold one archive cut at arbitrary points, addressed as disk 0, rather
+    /// than a split set whose last volume holds the end record.
+    joined: bool,
+}
+
+impl ZipVolumes {
+    /// Files that together hold one regular archive, such as one being written.
+    pub fn joined(paths: Vec<PathBuf>) -> io::Result<Self> {
+        Ok(Self {
+            joined: true,
+            ..Self::new(paths)?
+        })
+    }
+
+    pub fn new(paths: Vec<PathBuf>) -> io::Result<Self> {
+        let mut starts = Vec::with_capacity(paths.len());
+        let mut total_len = 0u64;
+        for path in &paths {
+            starts.push(total_len);
+            total_len += std::fs::metadata(path)?.len();
+        }
+        Ok(Self {
+            paths,
+            starts,
+            total_len,
+            joined: false,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn absolute_offset(&self, disk: u32, offset: u64) -> io::Result<u64> {
+        self.starts
+            .get(disk as usize)
+            .map(|start| start + offset)
+            .ok_or_else(|| invalid_zip("Zip archive references a missing volume"))
+    }
+
+    pub fn reader(&self) -> ZipVolumesReader<'_> {
+        ZipVolumesReader {
+            volumes: self,
+            open_volume: None,
+            position: 0,
+        }
+    }
+}
+
+pub struct ZipVolumesReader<'a> {
+    volumes: &'a ZipVolumes,
+    open_volume: Option<(usize, File)>,
+    position: u64,
+}
+
+impl Read for ZipVolumesReader<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if buffer.is_empty() || self.position >= self.volumes.total_len {
+            return Ok(0);
+        }
+
+        let volume_index = self
+            .volumes
+            .starts
+            .partition_point(|start| *start <= self.position)
+            - 1;
+        let volume_start = self.volumes.starts[volume_index];
+        let volume_end = self
+            .volumes
+            .starts
+            .get(volume_index + 1)
+            .copied()
+            .unwrap_or(self.volumes.total_len);
+
+        let file = match &mut self.open_volume {
+            Some((open_index, file)) if *open_index == volume_index => file,
+            _ => {
+                let file = File::open(&self.volumes.paths[volume_index])?;
+                &mut self.open_volume.insert((volume_index, file)).1
+            }
+        };
+        file.seek(SeekFrom::Start(self.position - volume_start))?;
+
+        let available = (volume_end - self.position).min(buffer.len() as u64) as usize;
+        let read_count = file.read(&mut buffer[..available])?;
+        self.position += read_count as u64;
+        Ok(read_count)
+    }
+}
+
+impl Seek for ZipVolumesReader<'_> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let target = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.volumes.total_len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        self.position = target.ok_or_else(|| invalid_zip("Invalid seek position"))?;
+        Ok(self.position)
+    }
+}
+
+pub struct EndOfCentralDirectory {
+    pub disk_number: u32,
+    pub central_directory_disk: u32,
+    pub disk_entry_count: u64,
+    pub entry_count: u64,
+    pub central_directory_size: u64,
+    pub central_directory_offset: u64,
+    pub comment: Vec<u8>,
+}
+
+impl EndOfCentralDirectory {
+    fn needs_zip64(&self) -> bool {
+        self.disk_number >= u16::MAX as u32
+            || self.central_directory_disk >= u16::MAX as u32
+            || self.disk_entry_count >= u16::MAX as u64
+            || self.entry_count >= u16::MAX as u64
+            || self.central_directory_size >= u32::MAX as u64
+            || self.central_directory_offset >= u32::MAX as u64
+    }
+
+    /// Encodes the end records. `record_offset` is where the returned bytes start on the
+    /// current volume and is only needed for the ZIP64 locator.
+    pub fn encode(&self, record_offset: u64) -> Vec<u8> {
+        let mut output = Vec::with_capacity(
+            ZIP64_END_OF_CENTRAL_DIRECTORY_LEN
+                + ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_LEN
+                + END_OF_CENTRAL_DIRECTORY_LEN
+                + self.comment.len(),
+        );
+
+        if self.needs_zip64() {
+            output.extend_from_slice(&ZIP64_END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+            output.extend_from_slice(
+                &((ZIP64_END_OF_CENTRAL_DIRECTORY_LEN - 12) as u64).to_le_bytes(),
+            );
+            output.extend_from_slice(&ZIP64_VERSION_NEEDED.to_le_bytes());
+            output.extend_from_slice(&ZIP64_VERSION_NEEDED.to_le_bytes());
+            output.extend_from_slice(&self.disk_number.to_le_bytes());
+            output.extend_from_slice(&self.central_directory_disk.to_le_bytes());
+            output.extend_from_slice(&self.disk_entry_count.to_le_bytes());
+            output.extend_from_slice(&self.entry_count.to_le_bytes());
+            output.extend_from_slice(&self.central_directory_size.to_le_bytes());
+            output.extend_from_slice(&self.central_directory_offset.to_le_bytes());
+
+            output
+                .extend_from_slice(&ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIGNATURE.to_le_bytes());
+            output.extend_from_slice(&self.disk_number.to_le_bytes());
+            output.extend_from_slice(&record_offset.to_le_bytes());
+            output.extend_from_slice(&(self.disk_number + 1).to_le_bytes());
+        }
+
+        output.extend_from_slice(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        output.extend_from_slice(&(self.disk_number.min(u16::MAX as u32) as u16).to_le_bytes());
+        output.extend_from_slice(
+            &(self.central_directory_disk.min(u16::MAX as u32) as u16).to_le_bytes(),
+        );
+        output
+            .extend_from_slice(&(self.disk_entry_count.min(u16::MAX as u64) as u16).to_le_bytes());
+        output.extend_from_slice(&(self.entry_count.min(u16::MAX as u64) as u16).to_le_bytes());
+        output.extend_from_slice(
+            &(self.central_directory_size.min(u32::MAX as u64) as u32).to_le_bytes(),
+        );
+        output.extend_from_slice(
+            &(self.central_directory_offset.min(u32::MAX as u64) as u32).to_le_bytes(),
+        );
+        output.extend_from_slice(&(self.comment.len() as u16).to_le_bytes());
+        output.extend_from_slice(&self.comment);
+        output
+    }
+}
+
+pub struct CentralDirectoryEntry {
+    header: Vec<u8>,
+    pub name: Vec<u8>,
+    extra: Vec<u8>,
+    comment: Vec<u8>,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    pub disk_number_start: u32,
+    pub local_header_offset: u64,
+}
+
+impl CentralDirectoryEntry {
+    pub fn flags(&self) -> u16 {
+        read_u16_at(&self.header, 8)
+    }
+
+    pub fn set_flags(&mut self, flags: u16) {
+        write_u16_at(&mut self.header, 8, flags);
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut header = self.header.clone();
+        let mut zip64_field = Vec::new();
+
+        let mut encode_u32_field = |offset: usize, value: u64, zip64_field: &mut Vec<u8>| {
+            if value >= u32::MAX as u64 {
+                write_u32_at(&mut header, offset, u32::MAX);
+                zip64_field.extend_from_slice(&value.to_le_bytes());
+            } else {
+                write_u32_at(&mut header, offset, value as u32);
+            }
+        };
+        encode_u32_field(24, self.uncompressed_size, &mut zip64_field);
+        encode_u32_field(20, self.compressed_size, &mut zip64_field);
+        encode_u32_field(42, self.local_header_offset, &mut zip64_field);
+
+        if self.disk_number_start >= u16::MAX as u32 {
+            write_u16_at(&mut header, 34, u16::MAX);
+            zip64_field.extend_from_slice(&self.disk_number_start.to_le_bytes());
+        } else {
+            write_u16_at(&mut header, 34, self.disk_number_start as u16);
+        }
+
+        let mut extra = Vec::with_capacity(self.extra.len() + zip64_field.len() + 4);
+        if !zip64_field.is_empty() {
+            extra.extend_from_slice(&ZIP64_EXTRA_FIELD_ID.to_le_bytes());
+            extra.extend_from_slice(&(zip64_field.len() as u16).to_le_bytes());
+            extra.extend_from_slice(&zip64_field);
+            let version_needed = read_u16_at(&header, 6).max(ZIP64_VERSION_NEEDED);
+            write_u16_at(&mut header, 6, version_needed);
+        }
+        extra.extend_from_slice(&self.extra);
+
+        write_u16_at(&mut header, 28, self.name.len() as u16);
+        write_u16_at(&mut header, 30, extra.len() as u16);
+        write_u16_at(&mut header, 32, self.comment.len() as u16);
+
+        let mut output =
+            Vec::with_capacity(header.len() + self.name.len() + extra.len() + self.comment.len());
+        output.extend_from_slice(&header);
+        output.extend_from_slice(&self.name);
+        output.extend_from_slice(&extra);
+        output.extend_from_slice(&self.comment);
+        output
+    }
+}
+
+fn parse_central_directory_entry(
+    buffer: &[u8],
+    cursor: &mut usize,
+) -> io::Result<CentralDirectoryEntry> {
+    let start = *cursor;
+    if start + CENTRAL_DIRECTORY_HEADER_LEN > buffer.len()
+        || read_u32_at(buffer, start) != CENTRAL_DIRECTORY_HEADER_SIGNATURE
+    {
+        return Err(invalid_zip("Invalid zip central directory"));
+    }
+
+    let header = buffer[start..start + CENTRAL_DIRECTORY_HEADER_LEN].to_vec();
+    let name_start = start + CENTRAL_DIRECTORY_HEADER_LEN;
+    let extra_start = name_start + read_u16_at(&header, 28) as usize;
+    let comment_start = extra_start + read_u16_at(&header, 30) as usize;
+    let entry_end = comment_start + read_u16_at(&header, 32) as usize;
+    if entry_end > buffer.len() {
+        return Err(invalid_zip("Invalid zip central directory"));
+    }
+
+    let mut uncompressed_size = read_u32_at(&header, 24) as u64;
+    let mut compressed_size = read_u32_at(&header, 20) as u64;
+    let mut local_header_offset = read_u32_at(&header, 42) as u64;
+    let mut disk_number_start = read_u16_at(&header, 34) as u32;
+
+    let raw_extra = &buffer[extra_start..comment_start];
+    let mut extra = Vec::with_capacity(raw_extra.len());
+    let mut extra_cursor = 0usize;
+    while extra_cursor + 4 <= raw_extra.len() {
+        let field_id = read_u16_at(raw_extra, extra_cursor);
+        let field_len = read_u16_at(raw_extra, extra_cursor + 2) as usize;
+        let field_end = (extra_cursor + 4 + field_len).min(raw_extra.len());
+
+        if field_id == ZIP64_EXTRA_FIELD_ID {
+            let field = &raw_extra[extra_cursor + 4..field_end];
+            let mut field_cursor = 0usize;
+            let mut take_u64 = |value: &mut u64| {
+                if *value == u32::MAX as u64 && field_cursor + 8 <= field.len() {
+                    *value = read_u64_at(field, field_cursor);
+                    field_cursor += 8;
+                }
+            };
+            take_u64(&mut uncompressed_size);
+            take_u64(&mut compressed_size);
+            take_u64(&mut local_header_offset);
+            if disk_number_start == u16::MAX as u32 && field_cursor + 4 <= field.len() {
+                disk_number_start = read_u32_at(field, field_cursor);
+            }
+        } else {
+            extra.extend_from_slice(&raw_extra[extra_cursor..field_end]);
+        }
+        extra_cursor = field_end;
+    }
+
+    *cursor = entry_end;
+    Ok(CentralDirectoryEntry {
+        name: buffer[name_start..extra_start].to_vec(),
+        comment: buffer[comment_start..entry_end].to_vec(),
+        header,
+        extra,
+        compressed_size,
+        uncompressed_size,
+        disk_number_start,
+        local_header_offset,
+    })
+}
+
+fn read_end_of_central_directory(
+    volumes: &ZipVolumes,
+    reader: &mut ZipVolumesReader<'_>,
+) -> io::Result<EndOfCentralDirectory> {
+    let last_volume_start = if volumes.joined {
+        0
+    } else {
+        volumes.starts.last().copied().unwrap_or(0)
+    };
+    let last_volume_len = volumes.total_len - last_volume_start;
+    let tail_len = last_volume_len.min((END_OF_CENTRAL_DIRECTORY_LEN + MAX_ZIP_COMMENT_LEN) as u64);
+    let tail_start = volumes.total_len - tail_len;
+    let mut tail = vec![0u8; tail_len as usize];
+    reader.seek(SeekFrom::Start(tail_start))?;
+    reader.read_exact(&mut tail)?;
+
+    let record_offset = (0..=tail.len().saturating_sub(END_OF_CENTRAL_DIRECTORY_LEN))
+        .rev()
+        .find(|offset| {
+            read_u32_at(&tail, *offset) == END_OF_CENTRAL_DIRECTORY_SIGNATURE
+                && offset + END_OF_CENTRAL_DIRECTORY_LEN + read_u16_at(&tail, offset + 20) as usize
+                    == tail.len()
+        })
+        .ok_or_else(|| invalid_zip("Failed to locate zip central directory"))?;
+    let record = &tail[record_offset..];
+
+    let mut end_record = EndOfCentralDirectory {
+        disk_number: read_u16_at(record, 4) as u32,
+        central_directory_disk: read_u16_at(record, 6) as u32,
+        disk_entry_count: read_u16_at(record, 8) as u64,
+        entry_count: read_u16_at(record, 10) as u64,
+        central_directory_size: read_u32_at(record, 12) as u64,
+        central_directory_offset: read_u32_at(record, 16) as u64,
+        comment: record[END_OF_CENTRAL_DIRECTORY_LEN..].to_vec(),
+    };
+
+    let record_position = tail_start + record_offset as u64;
+    let locator_len = ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_LEN as u64;
+    if record_position < last_volume_start + locator_len {
+        return Ok(end_record);
+    }
+
+    let mut locator = [0u8; ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_LEN];
+    reader.seek(SeekFrom::Start(record_position - locator_len))?;
+    reader.read_exact(&mut locator)?;
+    if read_u32_at(&locator, 0) != ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIGNATURE {
+        return Ok(end_record);
+    }
+
+    let zip64_position =
+        volumes.absolute_offset(read_u32_at(&locator, 4), read_u64_at(&locator, 8))?;
+    let mut zip64_record = [0u8; ZIP64_END_OF_CENTRAL_DIRECTORY_LEN];
+    reader.seek(SeekFrom::Start(zip64_position))?;
+    reader.read_exact(&mut zip64_record)?;
+    if read_u32_at(&zip64_record, 0) != ZIP64_END_OF_CENTRAL_DIRECTORY_SIGNATURE {
+        return Err(invalid_zip("Invalid zip64 end of central directory"));
+    }
+
+    end_record.disk_number = read_u32_at(&zip64_record, 16);
+    end_record.central_directory_disk = read_u32_at(&zip64_record, 20);
+    end_record.disk_entry_count = read_u64_at(&zip64_record, 24);
+    end_record.entry_count = read_u64_at(&zip64_record, 32);
+    end_record.central_directory_size = read_u64_at(&zip64_record, 40);
+    end_record.central_directory_offset = read_u64_at(&zip64_record, 48);
+    Ok(end_record)
+}
+
+/// Reads the end record and every central directory entry of the archive.
+pub fn read_central_directory(
+    volumes: &ZipVolumes,
+) -> io::Result<(EndOfCentralDirectory, Vec<CentralDirectoryEntry>)> {
+    let mut reader = volumes.reader();
+    let end_record = read_end_of_central_directory(volumes, &mut reader)?;
+
+    let central_directory_position = volumes.absolute_offset(
+        end_record.central_directory_disk,
+        end_record.central_directory_offset,
+    )?;
+    if central_directory_position + end_record.central_directory_size > volumes.total_len {
+        return Err(invalid_zip("Invalid zip central directory"));
+    }
+
+    let mut central_directory = vec![0u8; end_record.central_directory_size as usize];
+    reader.seek(SeekFrom::Start(central_directory_position))?;
+    reader.read_exact(&mut central_directory)?;
+
+    let mut entries = Vec::with_capacity(end_record.entry_count.min(u16::MAX as u64) as usize);
+    let mut cursor = 0usize;
+    for _ in 0..end_record.entry_count {
+        entries.push(parse_central_directory_entry(
+            &central_directory,
+            &mut cursor,
+        )?);
+    }
+
+    Ok((end_record, entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    fn central_directory_round_trips_through_encode() {
+        let temp = tempfile::tempdir().unwrap();
+        let zip_path = temp.path().join("archive.zip");
+        let mut zip_writer = ZipWriter::new(File::create(&zip_path).unwrap());
+        zip_writer
+            .start_file("a.txt", SimpleFileOptions::default())
+            .unwrap();
+        zip_writer.write_all(b"a").unwrap();
+        zip_writer.set_comment("comment").unwrap();
+        zip_writer.finish().unwrap();
+
+        let volumes = ZipVolumes::new(vec![zip_path.clone()]).unwrap();
+        let (end_record, entries) = read_central_directory(&volumes).unwrap();
+        assert_eq!(end_record.entry_count, 1);
+        assert_eq!(end_record.comment, b"comment");
+        assert_eq!(entries[0].name, b"a.txt");
+
+        let bytes = std::fs::read(&zip_path).unwrap();
+        let central_directory_start = end_record.central_directory_offset as usize;
+        let encoded = entries[0].encode();
+        assert_eq!(
+            &bytes[central_directory_start..central_directory_start + encoded.len()],
+            encoded.as_slice()
+        );
+    }
+
+    #[test]
+    fn end_record_switches_to_zip64_for_large_offsets() {
+        let end_record = EndOfCentralDirectory {
+            disk_number: 0,
+            central_directory_disk: 0,
+            disk_entry_count: 1,
+            entry_count: 1,
+            central_directory_size: 46,
+            central_directory_offset: u32::MAX as u64 + 10,
+            comment: Vec::new(),
+        };
+
+        let encoded = end_record.encode(u32::MAX as u64 + 56);
+        assert_eq!(
+            encoded.len(),
+            ZIP64_END_OF_CENTRAL_DIRECTORY_LEN
+                + ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_LEN
+                + END_OF_CENTRAL_DIRECTORY_LEN
+        );
+        assert_eq!(
+            read_u32_at(&encoded, 0),
+            ZIP64_END_OF_CENTRAL_DIRECTORY_SIGNATURE
+        );
+        assert_eq!(read_u64_at(&encoded, 48), u32::MAX as u64 + 10);
+    }
+}
@@ -91,6 +91,10 @@ pub async fn start_copy_move_job(
     let destination_path = request.destination_path;
     let conflict_resolution = request.conflict_resolution;
     let per_path_resolutions = request.per_path_resolutions;
+    let mut changed_paths = vec![destination_path.clone()];
+    if kind == "move" {
+        changed_paths.extend(source_paths.iter().cloned());
+    }
 
     tokio::spawn(async move {
         let work_result = tokio::task::spawn_blocking(move || {
@@ -133,6 +137,7 @@ pub async fn start_copy_move_job(
         .await;
 
         let _ = emit_progress.await;
+        crate::dir_size::invalidate_paths(&changed_paths, true);
 
         let finished = match work_result {
             Ok((result, cancelled)) => CopyMoveJobFinishedPayload {
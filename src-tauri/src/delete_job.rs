@@ -172,6 +172,7 @@ pub async fn start_delete_job(
     let app_done = app.clone();
     let job_id_done = job_id.clone();
     let use_trash_done = use_trash;
+    let changed_paths = paths.clone();
     tokio::spawn(async move {
         let work_result = tokio::task::spawn_blocking(move || {
             run_delete_blocking(paths, use_trash, cancel, progress_tx)
@@ -179,6 +180,7 @@ pub async fn start_delete_job(
         .await;
 
         let _ = emit_progress.await;
+        crate::dir_size::invalidate_paths(&changed_paths, true);
 
         let finished = match work_result {
             Ok(outcome) => {
@@ -6,13 +6,17 @@ mod blocking_timeout;
 mod commands;
 mod drives;
 mod drives_platform;
+mod flatten;
 mod mount;
 mod mountable;
 mod network_shares;
 mod path_helpers;
 mod path_volume;
+mod properties;
 mod read;
+mod stream;
 mod types;
+mod view;
 
 #[allow(unused_imports)]
 pub use types::{
@@ -20,6 +24,7 @@ pub use types::{
 };
 
 pub use commands::*;
+pub use read::{read_entries, ReadDirOptions};
 
 #[cfg(all(test, windows))]
 mod tests {
@@ -3,24 +3,80 @@
 // Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
 
 use super::drives;
+use super::flatten::{self, FlattenDirOptions};
 use super::mount;
 use super::mountable;
 use super::network_shares;
 use super::path_helpers;
+use super::properties;
 use super::read::{self, DirItemCountOptions, ReadDirOptions};
+use super::stream;
 use super::types::{
-    DirContents, DirEntryItemCount, DirEntryLinkMetadata, DriveInfo, MountableDevice,
-    NetworkShareParams,
+    DirContents, DirEntryItemCount, DirEntryLinkMetadata, DriveInfo, ItemProperties,
+    MountableDevice, NetworkShareParams,
 };
 
 #[tauri::command]
 pub async fn read_dir(
+    app: tauri::AppHandle,
     path: String,
     options: Option<ReadDirOptions>,
 ) -> Result<DirContents, String> {
-    tauri::async_runtime::spawn_blocking(move || read::read_dir(path, options))
-        .await
-        .map_err(|join_error| format!("Failed to read directory: {join_error}"))?
+    tauri::async_runtime::spawn_blocking(move || {
+        let records_visit = options.as_ref().is_some_and(ReadDirOptions::records_visit);
+        let contents = read::read_dir(path.clone(), options)?;
+        if records_visit {
+            crate::frecency::record_visit(&app, &path, true);
+        }
+        Ok(contents)
+    })
+    .await
+    .map_err(|join_error| format!("Failed to read directory: {join_error}"))?
+}
+
+/// Lists a directory through "read-dir-stream-*" events instead of one
+/// response, for directories with too many entries to return at once.
+#[tauri::command]
+pub fn start_read_dir_stream(
+    app: tauri::AppHandle,
+    path: String,
+    stream_id: String,
+    options: Option<ReadDirOptions>,
+) -> Result<(), String> {
+    stream::start_read_dir_stream(app, path, stream_id, options)
+}
+
+/// Every file under `path` as one listing, for gathering files from nested
+/// folders outside indexed search roots.
+#[tauri::command]
+pub async fn read_dir_flat(
+    path: String,
+    flatten_options: Option<FlattenDirOptions>,
+    options: Option<ReadDirOptions>,
+) -> Result<DirContents, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        flatten::read_dir_flat(path, flatten_options, options)
+    })
+    .await
+    .map_err(|join_error| format!("Failed to read directory tree: {join_error}"))?
+}
+
+/// Streams a flattened listing through the "read-dir-stream-*" events, for
+/// trees too large to return at once. Cancelled with `cancel_read_dir_stream`.
+#[tauri::command]
+pub fn start_read_dir_flat_stream(
+    app: tauri::AppHandle,
+    path: String,
+    stream_id: String,
+    flatten_options: Option<FlattenDirOptions>,
+    options: Option<ReadDirOptions>,
+) -> Result<(), String> {
+    stream::start_read_dir_flat_stream(app, path, stream_id, flatten_options, options)
+}
+
+#[tauri::command]
+pub fn cancel_read_dir_stream(stream_id: String) -> bool {
+    stream::cancel_read_dir_stream(&stream_id)
 }
 
 #[tauri::command]
@@ -40,6 +96,15 @@ pub async fn get_dir_entry_with_timeout(
     read::get_dir_entry_with_timeout(path, timeout_ms.unwrap_or(2500)).await
 }
 
+/// Full metadata of one path for the properties dialog, with the filesystem
+/// type and mount point it lives on.
+#[tauri::command]
+pub async fn get_item_properties(path: String) -> Result<ItemProperties, String> {
+    tauri::async_runtime::spawn_blocking(move || properties::get_item_properties(path))
+        .await
+        .map_err(|join_error| format!("Failed to read item properties: {join_error}"))?
+}
+
 #[tauri::command]
 pub async fn get_link_metadata_batch(
     paths: Vec<String>,
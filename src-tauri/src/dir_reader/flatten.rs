@@ -0,0 +1,299 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Flattened listings: every file under a directory tree as one list, for
+//! views that gather files from nested folders without an indexed search.
+
+use super::read::{
+    opened_directory_times, read_entry, should_skip_path, ReadDirOptions, ReadEntryOptions,
+};
+use super::types::{DirContents, DirEntry, DirEntryName};
+use super::view::{natural_cmp, sort_entries, DirEntrySort};
+use crate::utils::{is_hidden_from_metadata, normalize_path};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlattenDirOptions {
+    /// Folder levels to descend below the listed directory; 0 lists only
+    /// its own files. Unlimited when unset.
+    pub max_depth: Option<usize>,
+    /// Files must match one of these globs when any are given. Globs
+    /// without a `/` match the file name, others the relative path, e.g.
+    /// `DCIM/**/*.jpg`. Case is ignored.
+    #[serde(default)]
+    pub include_globs: Vec<String>,
+    /// Files and folders to leave out, matched like `include_globs`.
+    /// Excluded folders are not entered.
+    #[serde(default)]
+    pub exclude_globs: Vec<String>,
+    /// Lists hidden files and enters hidden folders.
+    #[serde(default)]
+    pub include_hidden: bool,
+}
+
+pub(super) struct CompiledFlattenOptions {
+    max_depth: Option<usize>,
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    include_hidden: bool,
+}
+
+fn compile_globs(patterns: &[String]) -> Result<Option<GlobSet>, String> {
+    let mut builder = GlobSetBuilder::new();
+    let mut is_empty = true;
+    for pattern in patterns.iter().map(|pattern| pattern.trim()) {
+        if pattern.is_empty() {
+            continue;
+        }
+        let relative_pattern = match pattern.strip_prefix('/') {
+            Some(relative_pattern) => relative_pattern.to_string(),
+            None if pattern.contains('/') => pattern.to_string(),
+            None => format!("**/{pattern}"),
+        };
+        let glob = GlobBuilder::new(&relative_pattern)
+            .case_insensitive(true)
+            .literal_separator(true)
+            .build()
+            .map_err(|error| format!("Invalid filter pattern {pattern}: {error}"))?;
+        builder.add(glob);
+        is_empty = false;
+    }
+
+    if is_empty {
+        return Ok(None);
+    }
+    builder
+        .build()
+        .map(Some)
+        .map_err(|error| format!("Invalid filter patterns: {error}"))
+}
+
+impl FlattenDirOptions {
+    pub(super) fn compile(&self) -> Result<CompiledFlattenOptions, String> {
+        Ok(CompiledFlattenOptions {
+            max_depth: self.max_depth,
+            include: compile_globs(&self.include_globs)?,
+            exclude: compile_globs(&self.exclude_globs)?,
+            include_hidden: self.include_hidden,
+        })
+    }
+}
+
+fn is_hidden(entry: &walkdir::DirEntry) -> bool {
+    entry.file_name().to_string_lossy().starts_with('.')
+        || (cfg!(windows)
+            && entry
+                .metadata()
+                .is_ok_and(|metadata| is_hidden_from_metadata(entry.path(), &metadata)))
+}
+
+impl CompiledFlattenOptions {
+    /// Whether the walk yields `entry`, or for folders, enters it.
+    fn admits(&self, entry: &walkdir::DirEntry, relative_path: &str) -> bool {
+        if should_skip_path(entry.path()) || (!self.include_hidden && is_hidden(entry)) {
+            return false;
+        }
+        if self
+            .exclude
+            .as_ref()
+            .is_some_and(|exclude| exclude.is_match(relative_path))
+        {
+            return false;
+        }
+        entry.file_type().is_dir()
+            || self
+                .include
+                .as_ref()
+                .is_none_or(|include| include.is_match(relative_path))
+    }
+}
+
+fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    Some(normalize_path(path.strip_prefix(root).ok()?.to_str()?))
+}
+
+/// Names of the files under `root`, in walk order. Symlinked folders are
+/// listed but not entered, and folders that cannot be read are skipped.
+pub(super) fn flattened_names(
+    root: &Path,
+    options: CompiledFlattenOptions,
+) -> impl Iterator<Item = (DirEntryName, PathBuf)> {
+    let walk_root = root.to_path_buf();
+    let name_root = root.to_path_buf();
+
+    WalkDir::new(root)
+        .min_depth(1)
+        .max_depth(options.max_depth.map_or(usize::MAX, |depth| depth + 1))
+        .into_iter()
+        .filter_entry(move |entry| {
+            relative_path(&walk_root, entry.path())
+                .is_some_and(|relative_path| options.admits(entry, &relative_path))
+        })
+        .flatten()
+        .filter(|entry| !entry.file_type().is_dir())
+        .filter_map(move |entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            let path = normalize_path(entry.path().to_str()?);
+            let relative_path = relative_path(&name_root, entry.path())?;
+            let file_type = entry.file_type();
+
+            Some((
+                DirEntryName {
+                    name,
+                    path,
+                    is_file: file_type.is_file(),
+                    is_dir: false,
+                    is_symlink: file_type.is_symlink(),
+                    relative_path: Some(relative_path),
+                },
+                entry.into_path(),
+            ))
+        })
+}
+
+/// Every file under `path` as one listing. The filter, sort and paging of
+/// `options` apply as in `read_dir`; without a sort, files are ordered by
+/// relative path.
+pub fn read_dir_flat(
+    path: String,
+    flatten_options: Option<FlattenDirOptions>,
+    options: Option<ReadDirOptions>,
+) -> Result<DirContents, String> {
+    let flatten_options = flatten_options.unwrap_or_default().compile()?;
+    let options = options.unwrap_or_default();
+    let read_entry_options = ReadEntryOptions::from(Some(&options));
+    let filter = options.compile_filter()?;
+    let opened_directory_times = opened_directory_times(&path)?;
+
+    let names: Vec<(DirEntryName, PathBuf)> =
+        flattened_names(Path::new(&path), flatten_options).collect();
+    let mut entries: Vec<DirEntry> = names
+        .into_par_iter()
+        .filter_map(|(name, entry_path)| {
+            let mut entry = read_entry(&entry_path, read_entry_options)?;
+            entry.relative_path = name.relative_path;
+            Some(entry)
+        })
+        .collect();
+
+    if let Some(filter) = &filter {
+        entries.retain(|entry| filter.matches(entry));
+    }
+    sort_flattened_entries(&mut entries, options.sort());
+
+    let dir_count = entries.iter().filter(|entry| entry.is_dir).count();
+    let file_count = entries.iter().filter(|entry| entry.is_file).count();
+    options.keep_page(&mut entries);
+
+    Ok(DirContents {
+        path: normalize_path(&path),
+        entries,
+        total_count: dir_count + file_count,
+        dir_count,
+        file_count,
+        opened_directory_times,
+        git: None,
+    })
+}
+
+/// Orders a flattened listing by `sort`, or by relative path without one.
+pub(super) fn sort_flattened_entries(entries: &mut [DirEntry], sort: Option<DirEntrySort>) {
+    match sort {
+        Some(sort) => sort_entries(entries, sort),
+        None => entries.sort_by(|left, right| {
+            natural_cmp(
+                left.relative_path.as_deref().unwrap_or_default(),
+                right.relative_path.as_deref().unwrap_or_default(),
+            )
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn relative_paths(contents: &DirContents) -> Vec<&str> {
+        contents
+            .entries
+            .iter()
+            .filter_map(|entry| entry.relative_path.as_deref())
+            .collect()
+    }
+
+    #[test]
+    fn nested_files_are_listed_with_depth_glob_and_hidden_rules() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let root = temp_dir.path();
+        for dir in ["DCIM/100CANON", "DCIM/.thumbnails", "node_modules/pkg"] {
+            fs::create_dir_all(root.join(dir)).expect("create folder");
+        }
+        for file in [
+            "top.log",
+            ".hidden.log",
+            "DCIM/100CANON/IMG_2.JPG",
+            "DCIM/100CANON/IMG_10.jpg",
+            "DCIM/100CANON/notes.txt",
+            "DCIM/.thumbnails/IMG_2.jpg",
+            "node_modules/pkg/index.log",
+        ] {
+            fs::write(root.join(file), b"x").expect("write file");
+        }
+        let read = |flatten_options: serde_json::Value| {
+            read_dir_flat(
+                root.to_string_lossy().into_owned(),
+                Some(serde_json::from_value(flatten_options).expect("parse options")),
+                None,
+            )
+            .expect("read flattened directory")
+        };
+
+        let contents = read(serde_json::json!({}));
+        assert_eq!(
+            relative_paths(&contents),
+            vec![
+                "DCIM/100CANON/IMG_2.JPG",
+                "DCIM/100CANON/IMG_10.jpg",
+                "DCIM/100CANON/notes.txt",
+                "node_modules/pkg/index.log",
+                "top.log",
+            ]
+        );
+        assert_eq!((contents.file_count, contents.dir_count), (5, 0));
+        assert_eq!(contents.entries[0].name, "IMG_2.JPG");
+
+        let photos = read(serde_json::json!({ "includeGlobs": ["DCIM/**/*.jpg"] }));
+        assert_eq!(
+            relative_paths(&photos),
+            vec!["DCIM/100CANON/IMG_2.JPG", "DCIM/100CANON/IMG_10.jpg"]
+        );
+
+        let logs = read(serde_json::json!({
+            "includeGlobs": ["*.log"],
+            "excludeGlobs": ["node_modules"],
+            "includeHidden": true,
+        }));
+        assert_eq!(relative_paths(&logs), vec![".hidden.log", "top.log"]);
+
+        let shallow = read(serde_json::json!({ "maxDepth": 0, "includeHidden": true }));
+        assert_eq!(relative_paths(&shallow), vec![".hidden.log", "top.log"]);
+
+        let error = read_dir_flat(
+            root.to_string_lossy().into_owned(),
+            Some(FlattenDirOptions {
+                include_globs: vec!["[".to_string()],
+                ..Default::default()
+            }),
+            None,
+        )
+        .expect_err("invalid glob");
+        assert!(error.starts_with("Invalid filter pattern ["));
+    }
+}
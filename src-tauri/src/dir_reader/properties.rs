@@ -0,0 +1,334 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::read::get_dir_entry;
+use super::types::{DirEntryPosixMetadata, ItemProperties};
+use std::fs;
+use std::path::Path;
+
+#[cfg(unix)]
+use std::collections::HashMap;
+#[cfg(unix)]
+use std::sync::{LazyLock, Mutex};
+
+#[cfg(unix)]
+static USER_NAMES: LazyLock<Mutex<HashMap<u32, Option<String>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+#[cfg(unix)]
+static GROUP_NAMES: LazyLock<Mutex<HashMap<u32, Option<String>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Formats `mode` like the first column of `ls -l`.
+fn permissions_string(mode: u32) -> String {
+    let file_type = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o020000 => 'c',
+        0o060000 => 'b',
+        0o010000 => 'p',
+        0o140000 => 's',
+        _ => '-',
+    };
+    let special = |bit: u32, executable: bool, set: char| match (mode & bit != 0, executable) {
+        (true, true) => set,
+        (true, false) => set.to_ascii_uppercase(),
+        (false, true) => 'x',
+        (false, false) => '-',
+    };
+    let flag = |bit: u32, set: char| if mode & bit != 0 { set } else { '-' };
+
+    [
+        file_type,
+        flag(0o400, 'r'),
+        flag(0o200, 'w'),
+        special(0o4000, mode & 0o100 != 0, 's'),
+        flag(0o040, 'r'),
+        flag(0o020, 'w'),
+        special(0o2000, mode & 0o010 != 0, 's'),
+        flag(0o004, 'r'),
+        flag(0o002, 'w'),
+        special(0o1000, mode & 0o001 != 0, 't'),
+    ]
+    .into_iter()
+    .collect()
+}
+
+/// Looks `id` up once and remembers the answer, since a listing usually has
+/// few distinct owners.
+#[cfg(unix)]
+fn cached_name(
+    cache: &Mutex<HashMap<u32, Option<String>>>,
+    id: u32,
+    lookup: fn(u32) -> Option<String>,
+) -> Option<String> {
+    if let Some(name) = cache.lock().ok()?.get(&id) {
+        return name.clone();
+    }
+    let name = lookup(id);
+    if let Ok(mut cache) = cache.lock() {
+        cache.insert(id, name.clone());
+    }
+    name
+}
+
+/// Calls a reentrant `getpwuid_r`/`getgrgid_r` style lookup, growing the
+/// string buffer until the record fits.
+#[cfg(unix)]
+fn lookup_name<T>(
+    id: u32,
+    lookup: unsafe extern "C" fn(
+        u32,
+        *mut T,
+        *mut libc::c_char,
+        libc::size_t,
+        *mut *mut T,
+    ) -> libc::c_int,
+    name_of: fn(&T) -> *const libc::c_char,
+) -> Option<String> {
+    let mut buffer = vec![0 as libc::c_char; 1024];
+
+    loop {
+        let mut record = std::mem::MaybeUninit::<T>::uninit();
+        let mut result = std::ptr::null_mut();
+        let status = unsafe {
+            lookup(
+                id,
+                record.as_mut_ptr(),
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+        if status == libc::ERANGE && buffer.len() < 1 << 20 {
+            buffer.resize(buffer.len() * 2, 0);
+            continue;
+        }
+        if status != 0 || result.is_null() {
+            return None;
+        }
+
+        let name = unsafe { std::ffi::CStr::from_ptr(name_of(&*result)) };
+        return Some(name.to_string_lossy().into_owned());
+    }
+}
+
+#[cfg(unix)]
+fn user_name(uid: u32) -> Option<String> {
+    cached_name(&USER_NAMES, uid, |uid| {
+        lookup_name(uid, libc::getpwuid_r, |passwd: &libc::passwd| {
+            passwd.pw_name
+        })
+    })
+}
+
+#[cfg(unix)]
+fn group_name(gid: u32) -> Option<String> {
+    cached_name(&GROUP_NAMES, gid, |gid| {
+        lookup_name(gid, libc::getgrgid_r, |group: &libc::group| group.gr_name)
+    })
+}
+
+/// Reads the Unix-only fields of `metadata`. Birth time comes from `statx`
+/// on Linux, through `Metadata::created`.
+#[cfg(unix)]
+pub(super) fn posix_metadata(metadata: &fs::Metadata) -> Option<DirEntryPosixMetadata> {
+    use std::os::unix::fs::MetadataExt;
+
+    let birth_time = metadata
+        .created()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis() as u64);
+
+    Some(DirEntryPosixMetadata {
+        mode: metadata.mode(),
+        permissions: permissions_string(metadata.mode()),
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        owner: user_name(metadata.uid()),
+        group: group_name(metadata.gid()),
+        inode: metadata.ino(),
+        device: metadata.dev(),
+        blocks: metadata.blocks(),
+        block_size: metadata.blksize(),
+        birth_time,
+    })
+}
+
+#[cfg(not(unix))]
+pub(super) fn posix_metadata(metadata: &fs::Metadata) -> Option<DirEntryPosixMetadata> {
+    let _ = metadata;
+    None
+}
+
+/// Picks the deepest mount point containing `path`, the last one mounted when
+/// several share it.
+fn mount_for_path<'a>(path: &Path, mounts: &'a [(String, String)]) -> Option<&'a (String, String)> {
+    mounts
+        .iter()
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| Path::new(mount_point).components().count())
+}
+
+/// Decodes the octal escapes (`\040` for a space) the kernel uses in mount
+/// points.
+#[cfg(target_os = "linux")]
+fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped_byte = (bytes[index] == b'\\')
+            .then(|| bytes.get(index + 1..index + 4))
+            .flatten()
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match escaped_byte {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 4;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Mount points and filesystem types from `/proc/self/mountinfo`, in mount
+/// order. Unlike the disk list, this includes tmpfs, overlay, FUSE and
+/// network mounts.
+#[cfg(target_os = "linux")]
+fn parse_mountinfo(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let (mount_fields, filesystem_fields) = line.split_once(" - ")?;
+            let mount_point = mount_fields.split(' ').nth(4)?;
+            let file_system = filesystem_fields.split(' ').next()?;
+            Some((unescape_mount_field(mount_point), file_system.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn list_mounts() -> Vec<(String, String)> {
+    match fs::read_to_string("/proc/self/mountinfo") {
+        Ok(text) => parse_mountinfo(&text),
+        Err(_) => list_disk_mounts(),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn list_mounts() -> Vec<(String, String)> {
+    list_disk_mounts()
+}
+
+fn list_disk_mounts() -> Vec<(String, String)> {
+    sysinfo::Disks::new_with_refreshed_list()
+        .iter()
+        .map(|disk| {
+            (
+                disk.mount_point().to_string_lossy().into_owned(),
+                disk.file_system().to_string_lossy().into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// Everything known about a single path, including the filesystem it is on.
+pub fn get_item_properties(path: String) -> Result<ItemProperties, String> {
+    let entry = get_dir_entry(path.clone())?;
+    let resolved_path = dunce::canonicalize(&path).unwrap_or_else(|_| Path::new(&path).into());
+
+    let mounts = list_mounts();
+    let (mount_point, file_system) = mount_for_path(&resolved_path, &mounts)
+        .map(|(mount_point, file_system)| (Some(mount_point.clone()), Some(file_system.clone())))
+        .unwrap_or_default();
+
+    Ok(ItemProperties {
+        entry,
+        file_system,
+        mount_point,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permissions_match_ls_and_mounts_prefer_the_deepest() {
+        assert_eq!(permissions_string(0o040755), "drwxr-xr-x");
+        assert_eq!(permissions_string(0o100644), "-rw-r--r--");
+        assert_eq!(permissions_string(0o104755), "-rwsr-xr-x");
+        assert_eq!(permissions_string(0o041777), "drwxrwxrwt");
+        assert_eq!(permissions_string(0o102640), "-rw-r-S---");
+        assert_eq!(permissions_string(0o120777), "lrwxrwxrwx");
+
+        let mounts = vec![
+            ("/".to_string(), "ext4".to_string()),
+            ("/home".to_string(), "btrfs".to_string()),
+            ("/home/user/mnt".to_string(), "nfs".to_string()),
+        ];
+        assert_eq!(
+            mount_for_path(Path::new("/home/user/file.txt"), &mounts),
+            Some(&mounts[1])
+        );
+        assert_eq!(
+            mount_for_path(Path::new("/homework"), &mounts),
+            Some(&mounts[0])
+        );
+        assert_eq!(
+            mount_for_path(Path::new("/home/user/mnt"), &mounts),
+            Some(&mounts[2])
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn mountinfo_lists_virtual_and_network_mounts() {
+        let text = "\
+22 1 8:2 / / rw,relatime shared:1 - ext4 /dev/sda2 rw
+35 22 0:32 / /tmp rw,nosuid shared:15 - tmpfs tmpfs rw
+41 22 0:40 / /mnt/my\\040share rw,relatime - cifs //nas/share rw
+52 22 0:45 / /home/user/gdrive rw,nosuid shared:30 master:2 - fuse.rclone gdrive: rw
+";
+        let mounts = parse_mountinfo(text);
+
+        assert_eq!(
+            mounts,
+            vec![
+                ("/".to_string(), "ext4".to_string()),
+                ("/tmp".to_string(), "tmpfs".to_string()),
+                ("/mnt/my share".to_string(), "cifs".to_string()),
+                ("/home/user/gdrive".to_string(), "fuse.rclone".to_string()),
+            ]
+        );
+        assert_eq!(
+            mount_for_path(Path::new("/mnt/my share/docs"), &mounts),
+            Some(&mounts[2])
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn posix_metadata_reads_owner_and_inode() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let file_path = temp_dir.path().join("file.txt");
+        fs::write(&file_path, b"hello").expect("write file");
+        let metadata = fs::metadata(&file_path).expect("stat file");
+
+        let posix = posix_metadata(&metadata).expect("posix metadata on unix");
+        assert_eq!(posix.inode, metadata.ino());
+        assert_eq!(posix.uid, metadata.uid());
+        assert!(posix.permissions.starts_with('-'));
+        if metadata.uid() == 0 {
+            assert_eq!(posix.owner.as_deref(), Some("root"));
+        }
+    }
+}
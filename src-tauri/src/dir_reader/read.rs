@@ -3,6 +3,8 @@
 // Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
 
 use super::blocking_timeout::{with_blocking_timeout, BlockingTimeoutError};
+use crate::git_status::{read_dir_git_status, GitRepositoryStatus};
+use crate::mime_detect::detect_mime_type;
 use crate::utils::{
     is_hidden_from_metadata, metadata_times_unix_ms, normalize_path, path_extension_lowercase,
 };
@@ -12,17 +14,29 @@ use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use super::properties::posix_metadata;
 use super::types::{
     DirContents, DirEntry, DirEntryItemCount, DirEntryLinkMetadata, DirEntryLinkStatus,
     DirEntryLinkType, OpenedDirectoryTimes,
 };
+use super::view::{sort_entries, CompiledDirEntryFilter, DirEntryFilter, DirEntrySort};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum MimeDetection {
+    Skip,
+    /// Reads the content only when the name gives no single type.
+    ByName,
+    ByContent,
+}
 
 #[derive(Debug, Clone, Copy)]
-struct ReadEntryOptions {
+pub(super) struct ReadEntryOptions {
     include_shortcut_targets: bool,
     include_hard_link_counts: bool,
     include_item_counts: bool,
     include_hidden: bool,
+    include_posix_metadata: bool,
+    mime_detection: MimeDetection,
 }
 
 impl Default for ReadEntryOptions {
@@ -32,6 +46,8 @@ impl Default for ReadEntryOptions {
             include_hard_link_counts: false,
             include_item_counts: false,
             include_hidden: true,
+            include_posix_metadata: false,
+            mime_detection: MimeDetection::ByName,
         }
     }
 }
@@ -43,6 +59,8 @@ impl ReadEntryOptions {
             include_hard_link_counts: true,
             include_item_counts: true,
             include_hidden: true,
+            include_posix_metadata: true,
+            mime_detection: MimeDetection::ByContent,
         }
     }
 }
@@ -54,85 +72,91 @@ pub struct DirItemCountOptions {
     include_hidden: bool,
 }
 
-#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ReadDirOptions {
     include_shortcut_targets: bool,
     include_hard_link_counts: bool,
     include_item_counts: Option<bool>,
     include_hidden_item_counts: Option<bool>,
+    record_visit: Option<bool>,
+    /// Mode bits, owner, inode and the other fields of `DirEntry::posix`.
+    /// Off by default, since owner names cost a lookup per entry.
+    include_posix_metadata: Option<bool>,
+    /// Checks the content of every file, not only of files whose name gives
+    /// no type, so files with a wrong extension get the right one.
+    detect_mime_from_content: Option<bool>,
+    /// Git status of each returned entry, plus the branch and its
+    /// ahead/behind counts, when the directory is inside a git work tree.
+    include_git_status: Option<bool>,
+    /// Folders first by name when unset.
+    sort: Option<DirEntrySort>,
+    filter: Option<DirEntryFilter>,
+    /// Page of the sorted entries to return. Counts still cover every entry
+    /// that passed the filter.
+    offset: Option<usize>,
+    limit: Option<usize>,
 }
 
-impl From<Option<ReadDirOptions>> for ReadEntryOptions {
-    fn from(options: Option<ReadDirOptions>) -> Self {
-        let options = options.unwrap_or_default();
+impl ReadDirOptions {
+    /// Whether the read is a user navigation that should feed the frecency store.
+    pub fn records_visit(&self) -> bool {
+        self.record_visit.unwrap_or(false)
+    }
+
+    pub(super) fn compile_filter(&self) -> Result<Option<CompiledDirEntryFilter>, String> {
+        self.filter
+            .as_ref()
+            .map(DirEntryFilter::compile)
+            .transpose()
+    }
+
+    pub(super) fn sort(&self) -> Option<DirEntrySort> {
+        self.sort
+    }
+
+    pub(super) fn includes_git_status(&self) -> bool {
+        self.include_git_status.unwrap_or(false)
+    }
+
+    /// Whether a stream has to end with the whole listing, since an order,
+    /// a page or git status only applies once every entry has been read.
+    pub(super) fn needs_full_listing(&self) -> bool {
+        self.sort.is_some()
+            || self.offset.is_some()
+            || self.limit.is_some()
+            || self.includes_git_status()
+    }
+
+    /// Drops the entries outside the requested page.
+    pub(super) fn keep_page(&self, entries: &mut Vec<DirEntry>) {
+        let offset = self.offset.unwrap_or(0).min(entries.len());
+        entries.drain(..offset);
+        if let Some(limit) = self.limit {
+            entries.truncate(limit);
+        }
+    }
+}
+
+impl From<Option<&ReadDirOptions>> for ReadEntryOptions {
+    fn from(options: Option<&ReadDirOptions>) -> Self {
+        let options = options.cloned().unwrap_or_default();
 
         Self {
             include_shortcut_targets: options.include_shortcut_targets,
             include_hard_link_counts: options.include_hard_link_counts,
             include_item_counts: options.include_item_counts.unwrap_or(false),
             include_hidden: options.include_hidden_item_counts.unwrap_or(true),
+            include_posix_metadata: options.include_posix_metadata.unwrap_or(false),
+            mime_detection: if options.detect_mime_from_content.unwrap_or(false) {
+                MimeDetection::ByContent
+            } else {
+                MimeDetection::ByName
+            },
         }
     }
 }
 
-fn get_mime_type(extension: &Option<String>) -> Option<String> {
-    extension.as_ref().map(|ext| {
-        match ext.as_str() {
-            "txt" | "text" => "text/plain",
-            "html" | "htm" => "text/html",
-            "css" => "text/css",
-            "js" | "mjs" => "text/javascript",
-            "json" => "application/json",
-            "xml" => "application/xml",
-            "pdf" => "application/pdf",
-            "zip" => "application/zip",
-            "tar" => "application/x-tar",
-            "gz" | "gzip" => "application/gzip",
-            "rar" => "application/vnd.rar",
-            "7z" => "application/x-7z-compressed",
-            "png" => "image/png",
-            "jpg" | "jpeg" => "image/jpeg",
-            "gif" => "image/gif",
-            "webp" => "image/webp",
-            "svg" => "image/svg+xml",
-            "ico" => "image/x-icon",
-            "mp3" => "audio/mpeg",
-            "wav" => "audio/wav",
-            "ogg" => "audio/ogg",
-            "flac" => "audio/flac",
-            "mp4" => "video/mp4",
-            "webm" => "video/webm",
-            "avi" => "video/x-msvideo",
-            "mkv" => "video/x-matroska",
-            "mov" => "video/quicktime",
-            "doc" => "application/msword",
-            "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
-            "xls" => "application/vnd.ms-excel",
-            "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
-            "ppt" => "application/vnd.ms-powerpoint",
-            "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
-            "rs" => "text/x-rust",
-            "ts" | "tsx" => "text/typescript",
-            "vue" => "text/x-vue",
-            "py" => "text/x-python",
-            "rb" => "text/x-ruby",
-            "go" => "text/x-go",
-            "java" => "text/x-java",
-            "c" | "h" => "text/x-c",
-            "cpp" | "hpp" | "cc" => "text/x-c++",
-            "md" | "markdown" => "text/markdown",
-            "yaml" | "yml" => "text/yaml",
-            "toml" => "text/x-toml",
-            "exe" => "application/x-msdownload",
-            "dll" => "application/x-msdownload",
-            "so" => "application/x-sharedlib",
-            _ => "application/octet-stream",
-        }
-        .to_string()
-    })
-}
-
 #[cfg(all(test, windows))]
 pub(super) fn windows_system_drive_root() -> String {
     let system_drive = std::env::var("SystemDrive").unwrap_or_else(|_| "C:".to_string());
@@ -183,7 +207,7 @@ pub(super) fn is_blacklisted_windows_system_path(path: &Path) -> bool {
     )
 }
 
-fn should_skip_path(path: &Path) -> bool {
+pub(super) fn should_skip_path(path: &Path) -> bool {
     #[cfg(windows)]
     {
         is_blacklisted_windows_system_path(path)
@@ -563,7 +587,7 @@ fn needs_followed_metadata(symlink_metadata: &fs::Metadata) -> bool {
     }
 }
 
-fn read_entry(path: &Path, options: ReadEntryOptions) -> Option<DirEntry> {
+pub(super) fn read_entry(path: &Path, options: ReadEntryOptions) -> Option<DirEntry> {
     if should_skip_path(path) {
         return None;
     }
@@ -600,8 +624,8 @@ fn read_entry(path: &Path, options: ReadEntryOptions) -> Option<DirEntry> {
         None
     };
 
-    let mime = if is_file {
-        get_mime_type(&extension)
+    let mime = if is_file && options.mime_detection != MimeDetection::Skip {
+        detect_mime_type(path, options.mime_detection == MimeDetection::ByContent)
     } else {
         None
     };
@@ -612,6 +636,11 @@ fn read_entry(path: &Path, options: ReadEntryOptions) -> Option<DirEntry> {
         options,
         reparse_tag,
     );
+    let posix = if options.include_posix_metadata {
+        posix_metadata(metadata_for_type)
+    } else {
+        None
+    };
 
     Some(DirEntry {
         name,
@@ -631,10 +660,13 @@ fn read_entry(path: &Path, options: ReadEntryOptions) -> Option<DirEntry> {
         link_target,
         link_status,
         hard_link_count,
+        posix,
+        git_status: None,
+        relative_path: None,
     })
 }
 
-fn is_entry_hidden(entry: &DirEntry) -> bool {
+pub(super) fn is_entry_hidden(entry: &DirEntry) -> bool {
     entry.is_hidden || entry.name.starts_with('.')
 }
 
@@ -645,6 +677,8 @@ fn count_listable_dir_entries(path: &Path, options: ReadEntryOptions) -> Option<
         include_shortcut_targets: false,
         include_hard_link_counts: false,
         include_hidden: options.include_hidden,
+        include_posix_metadata: false,
+        mime_detection: MimeDetection::Skip,
     };
 
     let count = directory_entries
@@ -841,7 +875,7 @@ pub fn get_link_metadata_batch(
     paths: Vec<String>,
     options: Option<ReadDirOptions>,
 ) -> Vec<DirEntryLinkMetadata> {
-    let read_entry_options = ReadEntryOptions::from(options);
+    let read_entry_options = ReadEntryOptions::from(options.as_ref());
     let mut seen_paths = HashSet::new();
     let mut metadata_items = Vec::new();
 
@@ -895,11 +929,9 @@ pub async fn get_dir_entry_with_timeout(path: String, timeout_ms: u64) -> Result
     }
 }
 
-pub fn read_dir(path: String, options: Option<ReadDirOptions>) -> Result<DirContents, String> {
-    let directory = Path::new(&path);
-    let read_entry_options = ReadEntryOptions::from(options);
-
-    let self_metadata = match fs::metadata(directory) {
+/// Times of the directory at `path`, or why it cannot be listed.
+pub(super) fn opened_directory_times(path: &str) -> Result<OpenedDirectoryTimes, String> {
+    let self_metadata = match fs::metadata(path) {
         Ok(metadata) => metadata,
         Err(io_error) if io_error.kind() == std::io::ErrorKind::NotFound => {
             return Err(format!("Path does not exist: {}", path));
@@ -913,7 +945,20 @@ pub fn read_dir(path: String, options: Option<ReadDirOptions>) -> Result<DirCont
         return Err(format!("Path is not a directory: {}", path));
     }
 
-    let (self_modified, self_accessed, self_created) = metadata_times_unix_ms(&self_metadata);
+    let (modified_time, accessed_time, created_time) = metadata_times_unix_ms(&self_metadata);
+    Ok(OpenedDirectoryTimes {
+        modified_time,
+        accessed_time,
+        created_time,
+    })
+}
+
+pub fn read_dir(path: String, options: Option<ReadDirOptions>) -> Result<DirContents, String> {
+    let directory = Path::new(&path);
+    let options = options.unwrap_or_default();
+    let read_entry_options = ReadEntryOptions::from(Some(&options));
+    let filter = options.compile_filter()?;
+    let opened_directory_times = opened_directory_times(&path)?;
 
     let read_result = fs::read_dir(directory).map_err(|error| error.to_string())?;
     let entry_paths: Vec<PathBuf> = read_result.flatten().map(|entry| entry.path()).collect();
@@ -931,25 +976,80 @@ pub fn read_dir(path: String, options: Option<ReadDirOptions>) -> Result<DirCont
             .collect()
     };
 
-    entries.sort_by_cached_key(|entry| (!entry.is_dir, entry.name.to_lowercase()));
+    arrange_entries(&mut entries, filter.as_ref(), options.sort);
 
     let dir_count = entries.iter().filter(|entry| entry.is_dir).count();
     let file_count = entries.iter().filter(|entry| entry.is_file).count();
 
+    options.keep_page(&mut entries);
+
+    let git = if options.includes_git_status() {
+        apply_git_status(directory, &mut entries)
+    } else {
+        None
+    };
+
     Ok(DirContents {
         path: normalize_path(&path),
         entries,
         total_count: dir_count + file_count,
         dir_count,
         file_count,
-        opened_directory_times: OpenedDirectoryTimes {
-            modified_time: self_modified,
-            accessed_time: self_accessed,
-            created_time: self_created,
-        },
+        opened_directory_times,
+        git,
+    })
+}
+
+/// Sets the git status of `entries` of `directory`, and returns the status
+/// of the repository the directory belongs to.
+pub(super) fn apply_git_status(
+    directory: &Path,
+    entries: &mut [DirEntry],
+) -> Option<GitRepositoryStatus> {
+    let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+    read_dir_git_status(directory, &names).map(|status| {
+        for entry in entries.iter_mut() {
+            entry.git_status = status.entries.get(&entry.name).copied();
+        }
+        status.repository
     })
 }
 
+fn sort_dir_entries(entries: &mut [DirEntry]) {
+    entries.sort_by_cached_key(|entry| (!entry.is_dir, entry.name.to_lowercase()));
+}
+
+pub(super) fn arrange_entries(
+    entries: &mut Vec<DirEntry>,
+    filter: Option<&CompiledDirEntryFilter>,
+    sort: Option<DirEntrySort>,
+) {
+    if let Some(filter) = filter {
+        entries.retain(|entry| filter.matches(entry));
+    }
+    match sort {
+        Some(sort) => sort_entries(entries, sort),
+        None => sort_dir_entries(entries),
+    }
+}
+
+/// Reads the entries of arbitrary paths, filtered and ordered the way
+/// `read_dir` lists a directory. Paths that cannot be read are left out.
+pub fn read_entries(
+    paths: &[String],
+    options: Option<ReadDirOptions>,
+) -> Result<Vec<DirEntry>, String> {
+    let options = options.unwrap_or_default();
+    let read_entry_options = ReadEntryOptions::from(Some(&options));
+    let filter = options.compile_filter()?;
+    let mut entries: Vec<DirEntry> = paths
+        .par_iter()
+        .filter_map(|path| read_entry(Path::new(path), read_entry_options))
+        .collect();
+    arrange_entries(&mut entries, filter.as_ref(), options.sort);
+    Ok(entries)
+}
+
 pub async fn read_dir_with_timeout(
     path: String,
     timeout_ms: u64,
@@ -970,6 +1070,7 @@ pub async fn read_dir_with_timeout(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::git_status::GitFileStatus;
 
     #[test]
     fn read_entry_marks_hard_linked_files() {
@@ -1025,6 +1126,8 @@ mod tests {
                 include_hard_link_counts: true,
                 include_item_counts: None,
                 include_hidden_item_counts: None,
+                record_visit: None,
+                ..Default::default()
             }),
         );
 
@@ -1034,6 +1137,60 @@ mod tests {
         assert!(results[0].hard_link_count.unwrap_or(0) >= 2);
     }
 
+    #[test]
+    fn read_dir_reads_posix_metadata_only_when_asked() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        fs::write(temp_dir.path().join("file.txt"), b"contents").expect("write file");
+        let path = temp_dir.path().to_string_lossy().to_string();
+
+        let contents = read_dir(path.clone(), None).expect("read dir");
+        assert!(contents.entries[0].posix.is_none());
+
+        let options: ReadDirOptions = serde_json::from_value(serde_json::json!({
+            "includeShortcutTargets": false,
+            "includeHardLinkCounts": false,
+            "includePosixMetadata": true,
+        }))
+        .expect("parse options");
+        let contents = read_dir(path, Some(options)).expect("read dir");
+        assert_eq!(contents.entries[0].posix.is_some(), cfg!(unix));
+    }
+
+    #[test]
+    fn read_dir_reads_git_status_only_when_asked() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let src_dir = temp_dir.path().join("src");
+        fs::create_dir_all(temp_dir.path().join(".git")).expect("create git dir");
+        fs::create_dir_all(&src_dir).expect("create src dir");
+        fs::write(temp_dir.path().join(".git/HEAD"), "ref: refs/heads/main\n").expect("write HEAD");
+        fs::write(src_dir.join("new.rs"), b"fn main() {}").expect("write file");
+        let path = temp_dir.path().to_string_lossy().to_string();
+
+        let contents = read_dir(path.clone(), None).expect("read dir");
+        assert!(contents.git.is_none());
+        assert!(contents
+            .entries
+            .iter()
+            .all(|entry| entry.git_status.is_none()));
+
+        let options: ReadDirOptions = serde_json::from_value(serde_json::json!({
+            "includeShortcutTargets": false,
+            "includeHardLinkCounts": false,
+            "includeGitStatus": true,
+        }))
+        .expect("parse options");
+        let contents = read_dir(path, Some(options)).expect("read dir");
+        let git = contents.git.expect("git status");
+        assert_eq!(git.branch.as_deref(), Some("main"));
+        assert_eq!(git.head, None);
+        let src = contents
+            .entries
+            .iter()
+            .find(|entry| entry.name == "src")
+            .expect("src entry");
+        assert_eq!(src.git_status, Some(GitFileStatus::Untracked));
+    }
+
     #[test]
     fn read_dir_can_skip_item_counts() {
         let temp_dir = tempfile::tempdir().expect("create temp dir");
@@ -1048,6 +1205,8 @@ mod tests {
                 include_hard_link_counts: false,
                 include_item_counts: Some(false),
                 include_hidden_item_counts: None,
+                record_visit: None,
+                ..Default::default()
             }),
         )
         .expect("read dir");
@@ -1060,6 +1219,37 @@ mod tests {
         assert_eq!(child_entry.item_count, None);
     }
 
+    #[test]
+    fn read_dir_filters_sorts_and_pages_entries() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        fs::create_dir(temp_dir.path().join("folder")).expect("create folder");
+        for (name, size) in [("img10.png", 30), ("img9.png", 10), ("img2.png", 20)] {
+            fs::write(temp_dir.path().join(name), vec![0u8; size]).expect("write image");
+        }
+        fs::write(temp_dir.path().join("notes.txt"), b"notes").expect("write notes");
+
+        let options: ReadDirOptions = serde_json::from_value(serde_json::json!({
+            "includeShortcutTargets": false,
+            "includeHardLinkCounts": false,
+            "sort": { "by": "name" },
+            "filter": { "glob": "*.png" },
+            "offset": 1,
+            "limit": 1,
+        }))
+        .expect("parse options");
+        let contents = read_dir(temp_dir.path().to_string_lossy().to_string(), Some(options))
+            .expect("read dir");
+
+        assert_eq!(contents.total_count, 3);
+        assert_eq!(contents.file_count, 3);
+        let names: Vec<&str> = contents
+            .entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["img9.png"]);
+    }
+
     #[test]
     fn read_dir_defaults_to_skipping_item_counts() {
         let temp_dir = tempfile::tempdir().expect("create temp dir");
@@ -1093,6 +1283,8 @@ mod tests {
                 include_hard_link_counts: false,
                 include_item_counts: Some(true),
                 include_hidden_item_counts: Some(false),
+                record_visit: None,
+                ..Default::default()
             }),
         )
         .expect("read dir");
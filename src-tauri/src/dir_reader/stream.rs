@@ -0,0 +1,508 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Streaming directory reads for directories too large for one `read_dir`
+//! response.
+//!
+//! Names and entry types, which the directory listing provides without a
+//! `stat` per entry, arrive first in "read-dir-stream-names" batches. Full
+//! entries follow in "read-dir-stream-entries" batches, and
+//! "read-dir-stream-finished" carries the counts. When the options ask for
+//! an order, a page or git status, the finished event also carries the whole
+//! listing with them applied. Flattened listings of a whole tree stream
+//! through the same events.
+
+use super::flatten::{flattened_names, sort_flattened_entries, FlattenDirOptions};
+use super::read::{
+    apply_git_status, arrange_entries, opened_directory_times, read_entry, should_skip_path,
+    ReadEntryOptions,
+};
+use super::types::{DirEntry, DirEntryName, OpenedDirectoryTimes};
+use super::ReadDirOptions;
+use crate::git_status::GitRepositoryStatus;
+use crate::utils::normalize_path;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use tauri::{AppHandle, Emitter};
+
+const NAME_BATCH_SIZE: usize = 5000;
+const ENTRY_BATCH_SIZE: usize = 1000;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReadDirStreamBatchPayload<'a, T> {
+    stream_id: &'a str,
+    entries: &'a [T],
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReadDirStreamFinishedPayload {
+    stream_id: String,
+    path: String,
+    success: bool,
+    cancelled: bool,
+    error: Option<String>,
+    total_count: usize,
+    dir_count: usize,
+    file_count: usize,
+    opened_directory_times: Option<OpenedDirectoryTimes>,
+    /// Sorted and paged listing, when the options need one.
+    entries: Option<Vec<DirEntry>>,
+    git: Option<GitRepositoryStatus>,
+}
+
+pub(super) enum ReadDirStreamBatch {
+    Names(Vec<DirEntryName>),
+    Entries(Vec<DirEntry>),
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct ReadDirStreamCounts {
+    pub total_count: usize,
+    pub dir_count: usize,
+    pub file_count: usize,
+}
+
+/// How a stream that was not cancelled ended.
+#[derive(Debug, Default)]
+pub(super) struct ReadDirStreamEnd {
+    pub counts: ReadDirStreamCounts,
+    /// Every entry that passed the filter, sorted and paged, when the
+    /// options need the whole listing. The batches stay in directory order.
+    pub entries: Option<Vec<DirEntry>>,
+    pub git: Option<GitRepositoryStatus>,
+}
+
+static READ_DIR_STREAMS: LazyLock<Mutex<HashMap<String, Arc<AtomicBool>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn entry_name(entry: &fs::DirEntry) -> Option<(DirEntryName, PathBuf)> {
+    let entry_path = entry.path();
+    if should_skip_path(&entry_path) {
+        return None;
+    }
+
+    let name = entry.file_name().to_str()?.to_string();
+    let path = normalize_path(entry_path.to_str()?);
+    let file_type = entry.file_type().ok();
+
+    Some((
+        DirEntryName {
+            name,
+            path,
+            is_file: file_type.is_some_and(|file_type| file_type.is_file()),
+            is_dir: file_type.is_some_and(|file_type| file_type.is_dir()),
+            is_symlink: file_type.is_some_and(|file_type| file_type.is_symlink()),
+            relative_path: None,
+        },
+        entry_path,
+    ))
+}
+
+/// Lists `path` in batches: every name first, then the full entries in
+/// directory order. The filter of `options` applies to the batches; its
+/// sort, paging and git status apply to the listing the stream ends with,
+/// as in `read_dir`. Returns `None` when cancelled.
+pub(super) fn stream_dir(
+    path: &str,
+    options: Option<&ReadDirOptions>,
+    cancel: &AtomicBool,
+    emit: impl FnMut(ReadDirStreamBatch),
+) -> Result<Option<ReadDirStreamEnd>, String> {
+    let read_result = fs::read_dir(path).map_err(|error| error.to_string())?;
+    let names = read_result.flatten().filter_map(|entry| entry_name(&entry));
+    let Some(mut end) = stream_names(names, options, cancel, emit)? else {
+        return Ok(None);
+    };
+
+    if let (Some(options), Some(entries)) = (options, end.entries.as_mut()) {
+        arrange_entries(entries, None, options.sort());
+        options.keep_page(entries);
+        if options.includes_git_status() {
+            end.git = apply_git_status(Path::new(path), entries);
+        }
+    }
+    Ok(Some(end))
+}
+
+/// Emits `names` in batches as they are found, then reads and emits their
+/// full entries, keeping them when the options need the whole listing.
+fn stream_names(
+    found_names: impl Iterator<Item = (DirEntryName, PathBuf)>,
+    options: Option<&ReadDirOptions>,
+    cancel: &AtomicBool,
+    mut emit: impl FnMut(ReadDirStreamBatch),
+) -> Result<Option<ReadDirStreamEnd>, String> {
+    let read_entry_options = ReadEntryOptions::from(options);
+    let keeps_entries = options.is_some_and(ReadDirOptions::needs_full_listing);
+    let filter = options
+        .map(ReadDirOptions::compile_filter)
+        .transpose()?
+        .flatten();
+    let mut entry_paths = Vec::new();
+    let mut names = Vec::with_capacity(NAME_BATCH_SIZE);
+
+    for (name, entry_path) in found_names {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        if filter
+            .as_ref()
+            .is_some_and(|filter| !filter.matches_name(&name.name))
+        {
+            continue;
+        }
+
+        entry_paths.push((entry_path, name.relative_path.clone()));
+        names.push(name);
+        if names.len() == NAME_BATCH_SIZE {
+            emit(ReadDirStreamBatch::Names(std::mem::take(&mut names)));
+        }
+    }
+    if !names.is_empty() {
+        emit(ReadDirStreamBatch::Names(names));
+    }
+
+    let mut counts = ReadDirStreamCounts::default();
+    let mut kept_entries = Vec::new();
+    for chunk in entry_paths.chunks(ENTRY_BATCH_SIZE) {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+
+        let entries: Vec<DirEntry> = chunk
+            .par_iter()
+            .filter_map(|(entry_path, relative_path)| {
+                let mut entry = read_entry(entry_path, read_entry_options)?;
+                entry.relative_path = relative_path.clone();
+                Some(entry)
+            })
+            .filter(|entry| filter.as_ref().is_none_or(|filter| filter.matches(entry)))
+            .collect();
+        counts.dir_count += entries.iter().filter(|entry| entry.is_dir).count();
+        counts.file_count += entries.iter().filter(|entry| entry.is_file).count();
+        if keeps_entries {
+            kept_entries.extend(entries.iter().cloned());
+        }
+        emit(ReadDirStreamBatch::Entries(entries));
+    }
+    counts.total_count = counts.dir_count + counts.file_count;
+
+    Ok(Some(ReadDirStreamEnd {
+        counts,
+        entries: keeps_entries.then_some(kept_entries),
+        git: None,
+    }))
+}
+
+/// Lists every file under `path` in batches, like `stream_dir` lists one
+/// directory. The listing it ends with is ordered like `read_dir_flat`'s.
+pub(super) fn stream_dir_flat(
+    path: &str,
+    flatten_options: &FlattenDirOptions,
+    options: Option<&ReadDirOptions>,
+    cancel: &AtomicBool,
+    emit: impl FnMut(ReadDirStreamBatch),
+) -> Result<Option<ReadDirStreamEnd>, String> {
+    let names = flattened_names(Path::new(path), flatten_options.compile()?);
+    let Some(mut end) = stream_names(names, options, cancel, emit)? else {
+        return Ok(None);
+    };
+
+    if let (Some(options), Some(entries)) = (options, end.entries.as_mut()) {
+        sort_flattened_entries(entries, options.sort());
+        options.keep_page(entries);
+    }
+    Ok(Some(end))
+}
+
+type StreamFn = dyn FnOnce(
+        &str,
+        Option<&ReadDirOptions>,
+        &AtomicBool,
+        &mut dyn FnMut(ReadDirStreamBatch),
+    ) -> Result<Option<ReadDirStreamEnd>, String>
+    + Send;
+
+/// Checks that `path` can be listed, then streams it on a blocking thread.
+pub(super) fn start_read_dir_stream(
+    app: AppHandle,
+    path: String,
+    stream_id: String,
+    options: Option<ReadDirOptions>,
+) -> Result<(), String> {
+    start_stream(
+        app,
+        path,
+        stream_id,
+        options,
+        Box::new(|path, options, cancel, emit| stream_dir(path, options, cancel, emit)),
+    )
+}
+
+pub(super) fn start_read_dir_flat_stream(
+    app: AppHandle,
+    path: String,
+    stream_id: String,
+    flatten_options: Option<FlattenDirOptions>,
+    options: Option<ReadDirOptions>,
+) -> Result<(), String> {
+    let flatten_options = flatten_options.unwrap_or_default();
+    flatten_options.compile()?;
+    if options
+        .as_ref()
+        .is_some_and(ReadDirOptions::includes_git_status)
+    {
+        return Err("Git status is not available for flattened listings".to_string());
+    }
+    start_stream(
+        app,
+        path,
+        stream_id,
+        options,
+        Box::new(move |path, options, cancel, emit| {
+            stream_dir_flat(path, &flatten_options, options, cancel, emit)
+        }),
+    )
+}
+
+fn start_stream(
+    app: AppHandle,
+    path: String,
+    stream_id: String,
+    options: Option<ReadDirOptions>,
+    stream: Box<StreamFn>,
+) -> Result<(), String> {
+    let opened_directory_times = opened_directory_times(&path)?;
+    if let Some(options) = &options {
+        options.compile_filter()?;
+    }
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    {
+        let mut guard = READ_DIR_STREAMS
+            .lock()
+            .map_err(|_| "Read dir stream registry lock failed".to_string())?;
+        guard.insert(stream_id.clone(), cancel.clone());
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        if options.as_ref().is_some_and(ReadDirOptions::records_visit) {
+            crate::frecency::record_visit(&app, &path, true);
+        }
+
+        let result = stream(&path, options.as_ref(), &cancel, &mut |batch| {
+            let _ = match &batch {
+                ReadDirStreamBatch::Names(names) => app.emit(
+                    "read-dir-stream-names",
+                    &ReadDirStreamBatchPayload {
+                        stream_id: &stream_id,
+                        entries: names,
+                    },
+                ),
+                ReadDirStreamBatch::Entries(entries) => app.emit(
+                    "read-dir-stream-entries",
+                    &ReadDirStreamBatchPayload {
+                        stream_id: &stream_id,
+                        entries,
+                    },
+                ),
+            };
+        });
+
+        let mut finished = ReadDirStreamFinishedPayload {
+            stream_id: stream_id.clone(),
+            path: normalize_path(&path),
+            success: false,
+            cancelled: false,
+            error: None,
+            total_count: 0,
+            dir_count: 0,
+            file_count: 0,
+            opened_directory_times: None,
+            entries: None,
+            git: None,
+        };
+        match result {
+            Ok(Some(end)) => {
+                finished.success = true;
+                finished.total_count = end.counts.total_count;
+                finished.dir_count = end.counts.dir_count;
+                finished.file_count = end.counts.file_count;
+                finished.opened_directory_times = Some(opened_directory_times);
+                finished.entries = end.entries;
+                finished.git = end.git;
+            }
+            Ok(None) => finished.cancelled = true,
+            Err(error) => finished.error = Some(error),
+        }
+
+        let _ = app.emit("read-dir-stream-finished", &finished);
+
+        if let Ok(mut guard) = READ_DIR_STREAMS.lock() {
+            guard.remove(&stream_id);
+        }
+    });
+
+    Ok(())
+}
+
+pub(super) fn cancel_read_dir_stream(stream_id: &str) -> bool {
+    let Ok(guard) = READ_DIR_STREAMS.lock() else {
+        return false;
+    };
+    if let Some(flag) = guard.get(stream_id) {
+        flag.store(true, Ordering::Relaxed);
+        true
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_arrive_before_entries_and_counts_match() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        fs::create_dir(temp_dir.path().join("folder")).expect("create folder");
+        for index in 0..(ENTRY_BATCH_SIZE + 5) {
+            fs::write(temp_dir.path().join(format!("file-{index}.txt")), b"x").expect("write file");
+        }
+
+        let mut batches = Vec::new();
+        let end = stream_dir(
+            temp_dir.path().to_str().unwrap(),
+            None,
+            &AtomicBool::new(false),
+            |batch| batches.push(batch),
+        )
+        .expect("stream directory")
+        .expect("stream was not cancelled");
+
+        assert!(end.entries.is_none());
+        assert_eq!(
+            end.counts,
+            ReadDirStreamCounts {
+                total_count: ENTRY_BATCH_SIZE + 6,
+                dir_count: 1,
+                file_count: ENTRY_BATCH_SIZE + 5,
+            }
+        );
+        let Some(ReadDirStreamBatch::Names(names)) = batches.first() else {
+            panic!("names should come first");
+        };
+        assert_eq!(names.len(), ENTRY_BATCH_SIZE + 6);
+        assert_eq!(names.iter().filter(|name| name.is_dir).count(), 1);
+        let entry_batch_sizes: Vec<usize> = batches[1..]
+            .iter()
+            .map(|batch| match batch {
+                ReadDirStreamBatch::Entries(entries) => entries.len(),
+                ReadDirStreamBatch::Names(_) => panic!("names should not follow entries"),
+            })
+            .collect();
+        assert_eq!(entry_batch_sizes, vec![ENTRY_BATCH_SIZE, 6]);
+    }
+
+    #[test]
+    fn cancelled_streams_stop_without_counts() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        fs::write(temp_dir.path().join("file.txt"), b"x").expect("write file");
+
+        let mut batch_count = 0;
+        let result = stream_dir(
+            temp_dir.path().to_str().unwrap(),
+            None,
+            &AtomicBool::new(true),
+            |_| batch_count += 1,
+        )
+        .expect("stream directory");
+
+        assert!(result.is_none());
+        assert_eq!(batch_count, 0);
+    }
+
+    #[test]
+    fn flattened_streams_carry_relative_paths() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        fs::create_dir_all(temp_dir.path().join("a/b")).expect("create folders");
+        fs::write(temp_dir.path().join("a/b/deep.txt"), b"x").expect("write file");
+        fs::write(temp_dir.path().join("top.txt"), b"x").expect("write file");
+
+        let mut batches = Vec::new();
+        let end = stream_dir_flat(
+            temp_dir.path().to_str().unwrap(),
+            &FlattenDirOptions::default(),
+            None,
+            &AtomicBool::new(false),
+            |batch| batches.push(batch),
+        )
+        .expect("stream directory")
+        .expect("stream was not cancelled");
+
+        assert_eq!(end.counts.file_count, 2);
+        assert_eq!(end.counts.dir_count, 0);
+        let Some(ReadDirStreamBatch::Entries(entries)) = batches.last() else {
+            panic!("entries should come last");
+        };
+        let mut relative_paths: Vec<&str> = entries
+            .iter()
+            .filter_map(|entry| entry.relative_path.as_deref())
+            .collect();
+        relative_paths.sort();
+        assert_eq!(relative_paths, vec!["a/b/deep.txt", "top.txt"]);
+    }
+
+    #[test]
+    fn streams_end_with_the_sorted_page_and_git_status() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let work_tree = temp_dir.path();
+        fs::create_dir(work_tree.join(".git")).expect("create git dir");
+        fs::write(work_tree.join(".git/HEAD"), "ref: refs/heads/main\n").expect("write HEAD");
+        fs::create_dir(work_tree.join("folder")).expect("create folder");
+        for name in ["b.txt", "a.txt", "c.txt", "file10.txt", "file9.txt"] {
+            fs::write(work_tree.join(name), b"x").expect("write file");
+        }
+        let options: ReadDirOptions = serde_json::from_value(serde_json::json!({
+            "includeShortcutTargets": false,
+            "includeHardLinkCounts": false,
+            "includeGitStatus": true,
+            "sort": { "by": "name", "descending": true },
+            "offset": 2,
+            "limit": 3,
+        }))
+        .expect("parse options");
+
+        let end = stream_dir(
+            work_tree.to_str().unwrap(),
+            Some(&options),
+            &AtomicBool::new(false),
+            |_| {},
+        )
+        .expect("stream directory")
+        .expect("stream was not cancelled");
+
+        assert_eq!(end.counts.total_count, 7);
+        let entries = end.entries.expect("the options need the whole listing");
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!["file10.txt", "file9.txt", "c.txt"]);
+        assert!(entries
+            .iter()
+            .all(|entry| entry.git_status == Some(crate::git_status::GitFileStatus::Untracked)));
+        assert_eq!(
+            end.git
+                .expect("the directory is a work tree")
+                .branch
+                .as_deref(),
+            Some("main")
+        );
+    }
+}
@@ -2,6 +2,7 @@
 // License: GNU GPLv3 or later. See the license file in the project root for more information.
 // Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
 
+use crate::git_status::{GitFileStatus, GitRepositoryStatus};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -22,7 +23,7 @@ pub enum DirEntryLinkStatus {
     Unsupported,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DirEntry {
     pub name: String,
     pub ext: Option<String>,
@@ -41,6 +42,47 @@ pub struct DirEntry {
     pub link_target: Option<String>,
     pub link_status: Option<DirEntryLinkStatus>,
     pub hard_link_count: Option<u64>,
+    /// Only read when requested, and only on Unix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub posix: Option<DirEntryPosixMetadata>,
+    /// Only read when requested, for entries inside a git work tree.
+    /// Directories show the status of their contents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_status: Option<GitFileStatus>,
+    /// Path from the listed directory, with `/` separators, in flattened
+    /// listings of a whole tree.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relative_path: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirEntryPosixMetadata {
+    pub mode: u32,
+    /// `ls -l` style, e.g. "drwxr-xr-x".
+    pub permissions: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    pub inode: u64,
+    pub device: u64,
+    /// Allocated 512-byte blocks.
+    pub blocks: u64,
+    pub block_size: u64,
+    /// Unix milliseconds, when the filesystem records it.
+    pub birth_time: Option<u64>,
+}
+
+/// The fields a directory listing provides without a `stat` per entry.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DirEntryName {
+    pub name: String,
+    pub path: String,
+    pub is_file: bool,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relative_path: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -52,13 +94,20 @@ pub struct DirEntryLinkMetadata {
     pub hard_link_count: Option<u64>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ItemProperties {
+    pub entry: DirEntry,
+    pub file_system: Option<String>,
+    pub mount_point: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DirEntryItemCount {
     pub path: String,
     pub item_count: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenedDirectoryTimes {
     pub modified_time: u64,
     pub accessed_time: u64,
@@ -73,6 +122,10 @@ pub struct DirContents {
     pub dir_count: usize,
     pub file_count: usize,
     pub opened_directory_times: OpenedDirectoryTimes,
+    /// Branch and upstream of the repository the directory belongs to,
+    /// when git status was requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitRepositoryStatus>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
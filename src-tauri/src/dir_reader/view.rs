@@ -0,0 +1,321 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::read::is_entry_hidden;
+use super::types::DirEntry;
+use globset::{GlobBuilder, GlobMatcher};
+use icu_collator::{Collator, CollatorOptions, Numeric};
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DirEntrySortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+    Accessed,
+    Created,
+    Extension,
+    /// MIME type, which groups files of the same kind.
+    Type,
+}
+
+/// Order of a listing. Directories always come first; `descending` only
+/// reverses the order within directories and within files.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirEntrySort {
+    #[serde(default)]
+    pub by: DirEntrySortKey,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirEntryFilter {
+    /// Glob matched against entry names, ignoring case.
+    pub glob: Option<String>,
+    /// Size range in bytes. Only files are checked, so directories stay
+    /// navigable.
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Modification time range in Unix milliseconds.
+    pub modified_after: Option<u64>,
+    pub modified_before: Option<u64>,
+    pub include_hidden: Option<bool>,
+}
+
+pub(super) struct CompiledDirEntryFilter {
+    glob: Option<GlobMatcher>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    modified_after: Option<u64>,
+    modified_before: Option<u64>,
+    include_hidden: bool,
+}
+
+impl DirEntryFilter {
+    pub(super) fn compile(&self) -> Result<CompiledDirEntryFilter, String> {
+        let glob = self
+            .glob
+            .as_deref()
+            .map(str::trim)
+            .filter(|glob| !glob.is_empty())
+            .map(|glob| {
+                GlobBuilder::new(glob)
+                    .case_insensitive(true)
+                    .build()
+                    .map(|glob| glob.compile_matcher())
+                    .map_err(|error| format!("Invalid filter pattern {glob}: {error}"))
+            })
+            .transpose()?;
+
+        Ok(CompiledDirEntryFilter {
+            glob,
+            min_size: self.min_size,
+            max_size: self.max_size,
+            modified_after: self.modified_after,
+            modified_before: self.modified_before,
+            include_hidden: self.include_hidden.unwrap_or(true),
+        })
+    }
+}
+
+impl CompiledDirEntryFilter {
+    /// Checks what can be told from the name alone, before metadata is read.
+    pub(super) fn matches_name(&self, name: &str) -> bool {
+        if !self.include_hidden && name.starts_with('.') {
+            return false;
+        }
+        self.glob.as_ref().is_none_or(|glob| glob.is_match(name))
+    }
+
+    pub(super) fn matches(&self, entry: &DirEntry) -> bool {
+        if !self.matches_name(&entry.name) || (!self.include_hidden && is_entry_hidden(entry)) {
+            return false;
+        }
+        if entry.is_file
+            && (self.min_size.is_some_and(|min_size| entry.size < min_size)
+                || self.max_size.is_some_and(|max_size| entry.size > max_size))
+        {
+            return false;
+        }
+
+        self.modified_after
+            .is_none_or(|modified_after| entry.modified_time >= modified_after)
+            && self
+                .modified_before
+                .is_none_or(|modified_before| entry.modified_time <= modified_before)
+    }
+}
+
+thread_local! {
+    /// Root locale collation with numeric ordering, the way the file
+    /// browser's `Intl.Collator` with `numeric: true` sorts. One per thread,
+    /// as the collator isn't `Sync`.
+    static NAME_COLLATOR: Collator = {
+        let mut options = CollatorOptions::new();
+        options.numeric = Some(Numeric::On);
+        Collator::try_new(&Default::default(), options)
+            .expect("the collation data for the root locale is compiled in")
+    };
+}
+
+/// Natural order: runs of digits compare by value, accents and case only
+/// break ties, and letters of any script collate by their Unicode rules.
+/// Names that differ only in case put lowercase first.
+pub(super) fn natural_cmp(left: &str, right: &str) -> Ordering {
+    NAME_COLLATOR
+        .with(|collator| collator.compare(left, right))
+        .then_with(|| left.cmp(right))
+}
+
+fn compare_by_key(left: &DirEntry, right: &DirEntry, key: DirEntrySortKey) -> Ordering {
+    match key {
+        DirEntrySortKey::Name => Ordering::Equal,
+        DirEntrySortKey::Size => left.size.cmp(&right.size),
+        DirEntrySortKey::Modified => left.modified_time.cmp(&right.modified_time),
+        DirEntrySortKey::Accessed => left.accessed_time.cmp(&right.accessed_time),
+        DirEntrySortKey::Created => left.created_time.cmp(&right.created_time),
+        DirEntrySortKey::Extension => natural_cmp(
+            left.ext.as_deref().unwrap_or_default(),
+            right.ext.as_deref().unwrap_or_default(),
+        ),
+        DirEntrySortKey::Type => natural_cmp(
+            left.mime.as_deref().unwrap_or_default(),
+            right.mime.as_deref().unwrap_or_default(),
+        ),
+    }
+}
+
+/// Sorts directories first, then by `sort.by`, with ties broken by name.
+pub(super) fn sort_entries(entries: &mut [DirEntry], sort: DirEntrySort) {
+    entries.par_sort_by(|left, right| {
+        right.is_dir.cmp(&left.is_dir).then_with(|| {
+            let ordering = compare_by_key(left, right, sort.by)
+                .then_with(|| natural_cmp(&left.name, &right.name));
+            if sort.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        })
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, is_dir: bool, size: u64, modified_time: u64) -> DirEntry {
+        DirEntry {
+            name: name.to_string(),
+            ext: name.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()),
+            path: format!("/dir/{name}"),
+            size,
+            item_count: None,
+            modified_time,
+            accessed_time: 0,
+            created_time: 0,
+            mime: None,
+            is_file: !is_dir,
+            is_dir,
+            is_symlink: false,
+            is_hidden: false,
+            link_type: None,
+            link_target: None,
+            link_status: None,
+            hard_link_count: None,
+            posix: None,
+            git_status: None,
+            relative_path: None,
+        }
+    }
+
+    fn names(entries: &[DirEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    #[test]
+    fn natural_order_compares_numbers_by_value_and_ignores_case() {
+        let mut names = vec![
+            "file10.txt",
+            "File2.txt",
+            "file1.txt",
+            "file02b",
+            "a",
+            "A",
+            "b",
+        ];
+        names.sort_by(|left, right| natural_cmp(left, right));
+
+        assert_eq!(
+            names,
+            vec![
+                "a",
+                "A",
+                "b",
+                "file1.txt",
+                "File2.txt",
+                "file02b",
+                "file10.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn natural_order_collates_accented_and_non_latin_names() {
+        let mut names = vec![
+            "zebra",
+            "Éclair",
+            "eclair",
+            "Ärger",
+            "apple",
+            "Ωmega",
+            "Альфа",
+            "ёлка",
+            "ель",
+            "日本",
+        ];
+        names.sort_by(|left, right| natural_cmp(left, right));
+
+        assert_eq!(
+            names,
+            vec![
+                "apple",
+                "Ärger",
+                "eclair",
+                "Éclair",
+                "zebra",
+                "Ωmega",
+                "Альфа",
+                "ёлка",
+                "ель",
+                "日本",
+            ]
+        );
+    }
+
+    #[test]
+    fn sorting_keeps_directories_first_and_filters_combine() {
+        let mut entries = vec![
+            entry("photo-10.jpg", false, 300, 30),
+            entry("photo-9.jpg", false, 100, 10),
+            entry("Albums", true, 0, 5),
+            entry("notes.md", false, 200, 20),
+            entry("archive", true, 0, 50),
+            entry(".hidden.jpg", false, 500, 40),
+        ];
+
+        sort_entries(
+            &mut entries,
+            DirEntrySort {
+                by: DirEntrySortKey::Size,
+                descending: true,
+            },
+        );
+        assert_eq!(
+            names(&entries),
+            vec![
+                "archive",
+                "Albums",
+                ".hidden.jpg",
+                "photo-10.jpg",
+                "notes.md",
+                "photo-9.jpg"
+            ]
+        );
+
+        let filter = DirEntryFilter {
+            glob: Some("*.JPG".to_string()),
+            min_size: Some(150),
+            include_hidden: Some(false),
+            ..Default::default()
+        }
+        .compile()
+        .unwrap();
+        entries.retain(|entry| filter.matches(entry));
+        assert_eq!(names(&entries), vec!["photo-10.jpg"]);
+
+        let filter = DirEntryFilter {
+            modified_after: Some(10),
+            modified_before: Some(30),
+            ..Default::default()
+        }
+        .compile()
+        .unwrap();
+        assert!(!filter.matches(&entry("archive", true, 0, 50)));
+        assert!(filter.matches(&entry("notes.md", false, 200, 20)));
+        assert!(DirEntryFilter {
+            glob: Some("[".to_string()),
+            ..Default::default()
+        }
+        .compile()
+        .is_err());
+    }
+}
@@ -2,21 +2,29 @@
 // License: GNU GPLv3 or later. See the license file in the project root for more information.
 // Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
 
-use crate::utils::normalize_path;
+use crate::utils::{normalize_path, path_is_descendant_of};
 use lru::LruCache;
 use once_cell::sync::Lazy;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, Metadata};
 use std::num::NonZeroUsize;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::Manager;
 use walkdir::WalkDir;
 
 const CACHE_SIZE: usize = 2000;
+// Watchers only see a directory's own entries, and a file rewritten in place
+// changes no directory mtime. Sizes older than this, or from an earlier
+// session, are checked against the mtimes of every directory in their
+// subtree and returned flagged stale, for the caller to recompute.
 const CACHE_TTL_SECONDS: u64 = 300;
+const CACHE_FILE_NAME: &str = "dir-size-cache.json";
+const CACHE_SCHEMA_VERSION: u32 = 3;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum SizeStatus {
@@ -30,26 +38,49 @@ pub enum SizeStatus {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DirSizeResult {
     pub path: String,
+    /// Apparent size, as reported by `du --apparent-size`.
     pub size: u64,
+    /// Space allocated on disk, as reported by `du`.
+    pub allocated_size: u64,
     pub status: SizeStatus,
     pub file_count: u64,
     pub dir_count: u64,
     pub error: Option<String>,
+    /// Taken from a cache entry no directory mtime can vouch for, so the
+    /// size may be out of date and should be recomputed.
+    #[serde(default)]
+    pub is_stale: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct CacheEntry {
     size: u64,
+    allocated_size: u64,
     file_count: u64,
     dir_count: u64,
     status: SizeStatus,
     calculated_at: u64,
     dir_mtime: u64,
+    /// Latest mtime of any directory in the subtree, including the root.
+    subtree_mtime: u64,
+    /// Not set on entries loaded from the cache file.
+    #[serde(skip)]
+    calculated_this_session: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedCache {
+    version: u32,
+    /// Least recently used first, so loading them in order keeps the LRU order.
+    entries: Vec<(String, CacheEntry)>,
 }
 
 static SIZE_CACHE: Lazy<Mutex<LruCache<String, CacheEntry>>> =
     Lazy::new(|| Mutex::new(LruCache::new(NonZeroUsize::new(CACHE_SIZE).unwrap())));
 
+// Set once the cache has been loaded from disk; sizes are only saved after that
+static CACHE_FILE: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+
 // Map of path -> cancellation token for active calculations
 static ACTIVE_CALCULATIONS: Lazy<Mutex<HashMap<String, Arc<AtomicBool>>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
@@ -58,21 +89,131 @@ static ACTIVE_CALCULATIONS: Lazy<Mutex<HashMap<String, Arc<AtomicBool>>>> =
 #[derive(Debug, Clone)]
 struct CalculationProgress {
     size: Arc<AtomicU64>,
+    allocated_size: Arc<AtomicU64>,
     file_count: Arc<AtomicU64>,
     dir_count: Arc<AtomicU64>,
+    subtree_mtime: Arc<AtomicU64>,
 }
 
 static CALCULATION_PROGRESS: Lazy<Mutex<HashMap<String, CalculationProgress>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
+#[cfg(unix)]
+fn allocated_size(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    // st_blocks is in 512-byte units whatever the filesystem block size is
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated_size(metadata: &Metadata) -> u64 {
+    metadata.len()
+}
+
+/// Device and inode of an entry that has more than one hard link.
+#[cfg(unix)]
+fn hard_link_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    (!metadata.is_dir() && metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn hard_link_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+impl CalculationProgress {
+    fn new() -> Self {
+        Self {
+            size: Arc::new(AtomicU64::new(0)),
+            allocated_size: Arc::new(AtomicU64::new(0)),
+            file_count: Arc::new(AtomicU64::new(0)),
+            dir_count: Arc::new(AtomicU64::new(0)),
+            subtree_mtime: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn add_sizes(&self, metadata: &Metadata) {
+        self.size.fetch_add(metadata.len(), Ordering::Relaxed);
+        self.allocated_size
+            .fetch_add(allocated_size(metadata), Ordering::Relaxed);
+        if metadata.is_dir() {
+            self.subtree_mtime
+                .fetch_max(mtime_secs(metadata), Ordering::Relaxed);
+        }
+    }
+
+    /// Counts an entry below the root the way `du` does: each inode once,
+    /// however many hard links point at it, and directories and symlinks by
+    /// their own size.
+    fn add_entry(&self, metadata: &Metadata, seen_hard_links: &Mutex<HashSet<(u64, u64)>>) {
+        if let Some(id) = hard_link_id(metadata) {
+            let is_first_link = seen_hard_links
+                .lock()
+                .map(|mut seen| seen.insert(id))
+                .unwrap_or(true);
+            if !is_first_link {
+                return;
+            }
+        }
+
+        self.add_sizes(metadata);
+        if metadata.is_file() {
+            self.file_count.fetch_add(1, Ordering::Relaxed);
+        } else if metadata.is_dir() {
+            self.dir_count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn to_result(&self, path: String, status: SizeStatus) -> DirSizeResult {
+        DirSizeResult {
+            path,
+            size: self.size.load(Ordering::SeqCst),
+            allocated_size: self.allocated_size.load(Ordering::SeqCst),
+            status,
+            file_count: self.file_count.load(Ordering::SeqCst),
+            dir_count: self.dir_count.load(Ordering::SeqCst),
+            error: None,
+            is_stale: false,
+        }
+    }
+}
+
+impl DirSizeResult {
+    fn error(path: String, error: &str) -> Self {
+        Self {
+            path,
+            size: 0,
+            allocated_size: 0,
+            status: SizeStatus::Error,
+            file_count: 0,
+            dir_count: 0,
+            error: Some(error.to_string()),
+            is_stale: false,
+        }
+    }
+
+    fn to_cache_entry(&self, dir_mtime: u64, subtree_mtime: u64) -> CacheEntry {
+        CacheEntry {
+            size: self.size,
+            allocated_size: self.allocated_size,
+            file_count: self.file_count,
+            dir_count: self.dir_count,
+            status: self.status.clone(),
+            calculated_at: get_current_timestamp(),
+            dir_mtime,
+            subtree_mtime,
+            calculated_this_session: true,
+        }
+    }
+}
+
 fn register_calculation(path: &str) -> (Arc<AtomicBool>, CalculationProgress) {
     let normalized = normalize_path(path);
     let cancel_token = Arc::new(AtomicBool::new(false));
-    let progress = CalculationProgress {
-        size: Arc::new(AtomicU64::new(0)),
-        file_count: Arc::new(AtomicU64::new(0)),
-        dir_count: Arc::new(AtomicU64::new(0)),
-    };
+    let progress = CalculationProgress::new();
 
     if let Ok(mut active) = ACTIVE_CALCULATIONS.lock() {
         active.insert(normalized.clone(), cancel_token.clone());
@@ -94,15 +235,36 @@ fn unregister_calculation(path: &str) {
     }
 }
 
-fn get_dir_mtime(path: &Path) -> u64 {
-    path.metadata()
-        .and_then(|meta| meta.modified())
+fn mtime_secs(metadata: &Metadata) -> u64 {
+    metadata
+        .modified()
         .ok()
         .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
         .map(|duration| duration.as_secs())
         .unwrap_or(0)
 }
 
+fn get_dir_mtime(path: &Path) -> u64 {
+    path.metadata()
+        .map(|metadata| mtime_secs(&metadata))
+        .unwrap_or(0)
+}
+
+/// Whether any directory in the subtree of `path` was modified after
+/// `subtree_mtime`. Only directories are stat'ed, which is enough to notice
+/// entries being added, removed or renamed anywhere below `path`.
+fn is_subtree_modified_since(path: &Path, subtree_mtime: u64) -> bool {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_entry(|entry| entry.file_type().is_dir())
+        .any(|entry| match entry {
+            Ok(entry) => entry
+                .metadata()
+                .map_or(true, |metadata| mtime_secs(&metadata) > subtree_mtime),
+            Err(_) => true,
+        })
+}
+
 fn get_current_timestamp() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -110,22 +272,39 @@ fn get_current_timestamp() -> u64 {
         .unwrap_or(0)
 }
 
-fn get_cached_size(path: &str) -> Option<CacheEntry> {
+/// The cached size of `path` and whether it is stale. Entries whose
+/// directories changed are not returned at all.
+fn get_cached_size(path: &str) -> Option<(CacheEntry, bool)> {
     let normalized = normalize_path(path);
-    let mut cache = SIZE_CACHE.lock().ok()?;
-    let entry = cache.get(&normalized)?;
+    let entry = SIZE_CACHE.lock().ok()?.get(&normalized)?.clone();
 
-    let now = get_current_timestamp();
-    if now - entry.calculated_at > CACHE_TTL_SECONDS {
+    let current_mtime = get_dir_mtime(Path::new(path));
+    if current_mtime > entry.dir_mtime {
         return None;
     }
 
-    let current_mtime = get_dir_mtime(Path::new(path));
-    if current_mtime > entry.dir_mtime {
+    let is_expired =
+        get_current_timestamp().saturating_sub(entry.calculated_at) > CACHE_TTL_SECONDS;
+    let is_stale = is_expired || !entry.calculated_this_session;
+    if is_stale && is_subtree_modified_since(Path::new(path), entry.subtree_mtime) {
         return None;
     }
 
-    Some(entry.clone())
+    Some((entry, is_stale))
+}
+
+fn cached_result(path: &str) -> Option<DirSizeResult> {
+    let (entry, is_stale) = get_cached_size(path)?;
+    Some(DirSizeResult {
+        path: normalize_path(path),
+        size: entry.size,
+        allocated_size: entry.allocated_size,
+        status: entry.status,
+        file_count: entry.file_count,
+        dir_count: entry.dir_count,
+        error: None,
+        is_stale,
+    })
 }
 
 fn set_cached_size(path: &str, entry: CacheEntry) {
@@ -135,36 +314,142 @@ fn set_cached_size(path: &str, entry: CacheEntry) {
     }
 }
 
-fn calculate_dir_size_with_timeout(path: &Path, timeout: Duration) -> DirSizeResult {
-    let path_str = normalize_path(&path.to_string_lossy());
+fn read_cache_file(file_path: &Path) -> Vec<(String, CacheEntry)> {
+    fs::read_to_string(file_path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<PersistedCache>(&text).ok())
+        .filter(|persisted| persisted.version == CACHE_SCHEMA_VERSION)
+        .map(|persisted| persisted.entries)
+        .unwrap_or_default()
+}
 
-    if !path.exists() {
-        return DirSizeResult {
-            path: path_str,
-            size: 0,
-            status: SizeStatus::Error,
-            file_count: 0,
-            dir_count: 0,
-            error: Some("Path does not exist".to_string()),
-        };
+fn write_cache_file(file_path: &Path, entries: Vec<(String, CacheEntry)>) -> Result<(), String> {
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent).map_err(|error| error.to_string())?;
     }
+    let persisted = PersistedCache {
+        version: CACHE_SCHEMA_VERSION,
+        entries,
+    };
+    let json = serde_json::to_string(&persisted).map_err(|error| error.to_string())?;
+    let tmp_path = file_path.with_extension("json.tmp");
+    fs::write(&tmp_path, json).map_err(|error| error.to_string())?;
+    fs::rename(&tmp_path, file_path).or_else(|rename_error| {
+        let _ = fs::remove_file(file_path);
+        fs::rename(&tmp_path, file_path).map_err(|replace_error| {
+            format!(
+                "Failed to replace directory size cache: {}; {}",
+                rename_error, replace_error
+            )
+        })
+    })
+}
 
-    if !path.is_dir() {
-        return DirSizeResult {
-            path: path_str,
-            size: 0,
-            status: SizeStatus::Error,
-            file_count: 0,
-            dir_count: 0,
-            error: Some("Path is not a directory".to_string()),
-        };
+/// Loads the sizes computed in earlier sessions and keeps saving new ones to
+/// the app data directory.
+pub fn load_persistent_cache(app: &tauri::AppHandle) {
+    let Ok(app_data_dir) = app.path().app_data_dir() else {
+        return;
+    };
+    let file_path = app_data_dir.join(CACHE_FILE_NAME);
+    let entries = read_cache_file(&file_path);
+
+    if let Ok(mut cache) = SIZE_CACHE.lock() {
+        for (path, entry) in entries {
+            if !cache.contains(&path) {
+                cache.put(path, entry);
+            }
+        }
+    }
+    if let Ok(mut cache_file) = CACHE_FILE.lock() {
+        *cache_file = Some(file_path);
+    }
+}
+
+fn persist_cache() {
+    // Held while writing so concurrent saves don't interleave
+    let Ok(cache_file) = CACHE_FILE.lock() else {
+        return;
+    };
+    let Some(file_path) = cache_file.as_ref() else {
+        return;
+    };
+    let entries: Vec<(String, CacheEntry)> = match SIZE_CACHE.lock() {
+        Ok(cache) => cache
+            .iter()
+            .rev()
+            .map(|(path, entry)| (path.clone(), entry.clone()))
+            .collect(),
+        Err(_) => return,
+    };
+
+    if let Err(error) = write_cache_file(file_path, entries) {
+        log::warn!("Failed to save directory size cache: {}", error);
+    }
+}
+
+/// Drops the cached sizes of `path` and everything below it. With
+/// `include_ancestors`, also drops every directory above it, since their
+/// totals include it. Returns whether anything was dropped.
+fn invalidate_cached(
+    cache: &mut LruCache<String, CacheEntry>,
+    path: &str,
+    include_ancestors: bool,
+) -> bool {
+    let normalized = normalize_path(path);
+    let keys_to_remove: Vec<String> = cache
+        .iter()
+        .map(|(key, _)| key)
+        .filter(|key| {
+            **key == normalized
+                || path_is_descendant_of(key, &normalized)
+                || (include_ancestors && path_is_descendant_of(&normalized, key))
+        })
+        .cloned()
+        .collect();
+
+    for key in &keys_to_remove {
+        cache.pop(key);
+    }
+    !keys_to_remove.is_empty()
+}
+
+/// Invalidates the cached sizes affected by changes to `paths`. Used by the
+/// directory watcher and by jobs that modify the file system.
+pub(crate) fn invalidate_paths<S: AsRef<str>>(paths: &[S], include_ancestors: bool) {
+    let mut removed = false;
+    if let Ok(mut cache) = SIZE_CACHE.lock() {
+        for path in paths {
+            removed |= invalidate_cached(&mut cache, path.as_ref(), include_ancestors);
+        }
+    }
+
+    if removed {
+        persist_cache();
+    }
+}
+
+/// Checks that `path` is a directory and counts the directory itself, which
+/// `du` includes in its totals.
+fn start_calculation(path: &Path, progress: &CalculationProgress) -> Result<(), &'static str> {
+    let metadata = fs::metadata(path).map_err(|_| "Path does not exist")?;
+    if !metadata.is_dir() {
+        return Err("Path is not a directory");
+    }
+    progress.add_sizes(&metadata);
+    Ok(())
+}
+
+fn calculate_dir_size_with_timeout(path: &Path, timeout: Duration) -> DirSizeResult {
+    let path_str = normalize_path(&path.to_string_lossy());
+    let progress = CalculationProgress::new();
+    if let Err(error) = start_calculation(path, &progress) {
+        return DirSizeResult::error(path_str, error);
     }
 
     let start_time = Instant::now();
     let cancelled = Arc::new(AtomicBool::new(false));
-    let total_size = Arc::new(AtomicU64::new(0));
-    let file_count = Arc::new(AtomicU64::new(0));
-    let dir_count = Arc::new(AtomicU64::new(0));
+    let seen_hard_links = Mutex::new(HashSet::new());
 
     let entries: Vec<_> = WalkDir::new(path)
         .min_depth(1)
@@ -184,49 +469,27 @@ fn calculate_dir_size_with_timeout(path: &Path, timeout: Duration) -> DirSizeRes
 
     entries.par_iter().for_each(|entry| {
         if let Ok(metadata) = entry.metadata() {
-            if metadata.is_file() {
-                total_size.fetch_add(metadata.len(), Ordering::Relaxed);
-                file_count.fetch_add(1, Ordering::Relaxed);
-            } else if metadata.is_dir() {
-                dir_count.fetch_add(1, Ordering::Relaxed);
-            }
+            progress.add_entry(&metadata, &seen_hard_links);
         }
     });
 
-    let final_size = total_size.load(Ordering::SeqCst);
-    let final_file_count = file_count.load(Ordering::SeqCst);
-    let final_dir_count = dir_count.load(Ordering::SeqCst);
-
     let status = if was_cancelled {
         SizeStatus::Partial
     } else {
         SizeStatus::Complete
     };
+    let result = progress.to_result(path_str, status);
 
     // Only cache complete results - partial sizes are not stored
     if !was_cancelled {
-        let dir_mtime = get_dir_mtime(path);
+        let subtree_mtime = progress.subtree_mtime.load(Ordering::SeqCst);
         set_cached_size(
-            &path_str,
-            CacheEntry {
-                size: final_size,
-                file_count: final_file_count,
-                dir_count: final_dir_count,
-                status: status.clone(),
-                calculated_at: get_current_timestamp(),
-                dir_mtime,
-            },
+            &result.path,
+            result.to_cache_entry(get_dir_mtime(path), subtree_mtime),
         );
     }
 
-    DirSizeResult {
-        path: path_str,
-        size: final_size,
-        status,
-        file_count: final_file_count,
-        dir_count: final_dir_count,
-        error: None,
-    }
+    result
 }
 
 fn calculate_dir_size_no_timeout(
@@ -235,41 +498,11 @@ fn calculate_dir_size_no_timeout(
     progress: CalculationProgress,
 ) -> DirSizeResult {
     let path_str = normalize_path(&path.to_string_lossy());
-
-    if !path.exists() {
-        return DirSizeResult {
-            path: path_str,
-            size: 0,
-            status: SizeStatus::Error,
-            file_count: 0,
-            dir_count: 0,
-            error: Some("Path does not exist".to_string()),
-        };
-    }
-
-    if !path.is_dir() {
-        return DirSizeResult {
-            path: path_str,
-            size: 0,
-            status: SizeStatus::Error,
-            file_count: 0,
-            dir_count: 0,
-            error: Some("Path is not a directory".to_string()),
-        };
+    if let Err(error) = start_calculation(path, &progress) {
+        return DirSizeResult::error(path_str, error);
     }
 
-    let was_cancelled = Arc::new(AtomicBool::new(false));
-    let was_cancelled_clone = was_cancelled.clone();
-    let cancel_token_clone = cancel_token.clone();
-
-    // Use the shared progress counters
-    let total_size = progress.size.clone();
-    let file_count = progress.file_count.clone();
-    let dir_count = progress.dir_count.clone();
-
-    let total_size_clone = total_size.clone();
-    let file_count_clone = file_count.clone();
-    let dir_count_clone = dir_count.clone();
+    let seen_hard_links = Mutex::new(HashSet::new());
 
     // Process entries one by one, updating progress as we go
     for entry in WalkDir::new(path)
@@ -277,59 +510,22 @@ fn calculate_dir_size_no_timeout(
         .into_iter()
         .filter_map(|entry| entry.ok())
     {
-        // Check cancellation
-        if cancel_token_clone.load(Ordering::SeqCst) {
-            was_cancelled_clone.store(true, Ordering::SeqCst);
-            break;
+        if cancel_token.load(Ordering::SeqCst) {
+            return progress.to_result(path_str, SizeStatus::Cancelled);
         }
 
         if let Ok(metadata) = entry.metadata() {
-            if metadata.is_file() {
-                total_size_clone.fetch_add(metadata.len(), Ordering::Relaxed);
-                file_count_clone.fetch_add(1, Ordering::Relaxed);
-            } else if metadata.is_dir() {
-                dir_count_clone.fetch_add(1, Ordering::Relaxed);
-            }
+            progress.add_entry(&metadata, &seen_hard_links);
         }
     }
 
-    // Check if cancelled
-    if cancel_token.load(Ordering::SeqCst) || was_cancelled.load(Ordering::SeqCst) {
-        return DirSizeResult {
-            path: path_str,
-            size: total_size.load(Ordering::SeqCst),
-            status: SizeStatus::Cancelled,
-            file_count: file_count.load(Ordering::SeqCst),
-            dir_count: dir_count.load(Ordering::SeqCst),
-            error: None,
-        };
-    }
-
-    let final_size = total_size.load(Ordering::SeqCst);
-    let final_file_count = file_count.load(Ordering::SeqCst);
-    let final_dir_count = dir_count.load(Ordering::SeqCst);
-
-    let dir_mtime = get_dir_mtime(path);
+    let result = progress.to_result(path_str, SizeStatus::Complete);
+    let subtree_mtime = progress.subtree_mtime.load(Ordering::SeqCst);
     set_cached_size(
-        &path_str,
-        CacheEntry {
-            size: final_size,
-            file_count: final_file_count,
-            dir_count: final_dir_count,
-            status: SizeStatus::Complete,
-            calculated_at: get_current_timestamp(),
-            dir_mtime,
-        },
+        &result.path,
+        result.to_cache_entry(get_dir_mtime(path), subtree_mtime),
     );
-
-    DirSizeResult {
-        path: path_str,
-        size: final_size,
-        status: SizeStatus::Complete,
-        file_count: final_file_count,
-        dir_count: final_dir_count,
-        error: None,
-    }
+    result
 }
 
 #[tauri::command]
@@ -340,20 +536,17 @@ pub async fn get_dir_size(path: String, timeout_ms: Option<u64>) -> DirSizeResul
     let result = tokio::task::spawn_blocking(move || {
         let dir_path = Path::new(&path_clone);
 
-        match timeout_ms {
+        let result = match timeout_ms {
             Some(ms) => calculate_dir_size_with_timeout(dir_path, Duration::from_millis(ms)),
             None => calculate_dir_size_no_timeout(dir_path, cancel_token, progress),
+        };
+        if result.status == SizeStatus::Complete {
+            persist_cache();
         }
+        result
     })
     .await
-    .unwrap_or_else(|_| DirSizeResult {
-        path: normalize_path(&path),
-        size: 0,
-        status: SizeStatus::Error,
-        file_count: 0,
-        dir_count: 0,
-        error: Some("Task failed".to_string()),
-    });
+    .unwrap_or_else(|_| DirSizeResult::error(normalize_path(&path), "Task failed"));
 
     unregister_calculation(&path);
     result
@@ -366,14 +559,8 @@ pub fn get_dir_size_progress(path: String) -> Option<DirSizeResult> {
 
     if let Ok(prog) = CALCULATION_PROGRESS.lock() {
         if let Some(progress) = prog.get(&normalized) {
-            return Some(DirSizeResult {
-                path: normalized,
-                size: progress.size.load(Ordering::SeqCst),
-                status: SizeStatus::Partial, // In progress, so partial
-                file_count: progress.file_count.load(Ordering::SeqCst),
-                dir_count: progress.dir_count.load(Ordering::SeqCst),
-                error: None,
-            });
+            // In progress, so partial
+            return Some(progress.to_result(normalized, SizeStatus::Partial));
         }
     }
 
@@ -387,14 +574,7 @@ pub fn get_active_calculations() -> Vec<DirSizeResult> {
 
     if let Ok(prog) = CALCULATION_PROGRESS.lock() {
         for (path, progress) in prog.iter() {
-            results.push(DirSizeResult {
-                path: path.clone(),
-                size: progress.size.load(Ordering::SeqCst),
-                status: SizeStatus::Partial,
-                file_count: progress.file_count.load(Ordering::SeqCst),
-                dir_count: progress.dir_count.load(Ordering::SeqCst),
-                error: None,
-            });
+            results.push(progress.to_result(path.clone(), SizeStatus::Partial));
         }
     }
 
@@ -426,59 +606,217 @@ pub async fn get_dir_sizes_batch(
         };
         let should_use_cache = use_cache.unwrap_or(true);
 
-        paths
+        let results: Vec<(DirSizeResult, bool)> = paths
             .par_iter()
             .map(|path| {
                 if should_use_cache {
-                    if let Some(cached) = get_cached_size(path) {
-                        return DirSizeResult {
-                            path: normalize_path(path),
-                            size: cached.size,
-                            status: cached.status,
-                            file_count: cached.file_count,
-                            dir_count: cached.dir_count,
-                            error: None,
-                        };
+                    if let Some(result) = cached_result(path) {
+                        return (result, false);
                     }
                 }
 
-                calculate_dir_size_with_timeout(Path::new(path), timeout)
+                let result = calculate_dir_size_with_timeout(Path::new(path), timeout);
+                let is_new_complete_size = result.status == SizeStatus::Complete;
+                (result, is_new_complete_size)
             })
-            .collect()
+            .collect();
+
+        if results
+            .iter()
+            .any(|(_, is_new_complete_size)| *is_new_complete_size)
+        {
+            persist_cache();
+        }
+        results.into_iter().map(|(result, _)| result).collect()
     })
     .await
     .unwrap_or_default()
 }
 
+/// Drops the cached sizes of `paths` and everything below them. With
+/// `include_ancestors`, also drops the sizes of every directory containing
+/// them.
+#[tauri::command]
+pub fn invalidate_dir_size_cache(paths: Vec<String>, include_ancestors: Option<bool>) {
+    invalidate_paths(&paths, include_ancestors.unwrap_or(false));
+}
+
 #[tauri::command]
-pub fn invalidate_dir_size_cache(paths: Vec<String>) {
+pub fn clear_dir_size_cache() {
     if let Ok(mut cache) = SIZE_CACHE.lock() {
+        cache.clear();
+    }
+    persist_cache();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn cache_entry(size: u64) -> CacheEntry {
+        CacheEntry {
+            size,
+            allocated_size: size,
+            file_count: 1,
+            dir_count: 0,
+            status: SizeStatus::Complete,
+            calculated_at: 1,
+            dir_mtime: 1,
+            subtree_mtime: 1,
+            calculated_this_session: true,
+        }
+    }
+
+    fn cache_with(paths: &[&str]) -> LruCache<String, CacheEntry> {
+        let mut cache = LruCache::new(NonZeroUsize::new(16).unwrap());
         for path in paths {
-            let normalized = normalize_path(&path);
-            cache.pop(&normalized);
+            cache.put(path.to_string(), cache_entry(1));
+        }
+        cache
+    }
 
-            let path_with_slash = if normalized.ends_with('/') {
-                normalized.clone()
-            } else {
-                format!("{}/", normalized)
-            };
+    fn cached_paths(cache: &LruCache<String, CacheEntry>) -> Vec<&str> {
+        let mut paths: Vec<&str> = cache.iter().map(|(path, _)| path.as_str()).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn invalidation_drops_descendants_and_optionally_ancestors() {
+        let paths = [
+            "/",
+            "/home",
+            "/home/me",
+            "/home/me/projects",
+            "/home/me/projects/app",
+            "/home/me/music",
+            "/home/mead",
+        ];
+
+        let mut cache = cache_with(&paths);
+        assert!(invalidate_cached(&mut cache, "/home/me/projects", false));
+        assert_eq!(
+            cached_paths(&cache),
+            vec!["/", "/home", "/home/me", "/home/me/music", "/home/mead"]
+        );
 
-            let keys_to_remove: Vec<String> = cache
-                .iter()
-                .filter(|(key, _)| key.starts_with(&path_with_slash))
-                .map(|(key, _)| key.clone())
-                .collect();
+        let mut cache = cache_with(&paths);
+        assert!(invalidate_cached(
+            &mut cache,
+            "/home/me/projects/app/src/main.rs",
+            true
+        ));
+        assert_eq!(cached_paths(&cache), vec!["/home/me/music", "/home/mead"]);
+        assert!(!invalidate_cached(&mut cache, "/var/log", true));
+
+        let mut cache = cache_with(&["C:/", "C:/Users", "C:/Users/me", "D:/Data"]);
+        assert!(invalidate_cached(
+            &mut cache,
+            "C:\\Users\\me\\file.txt",
+            true
+        ));
+        assert_eq!(cached_paths(&cache), vec!["D:/Data"]);
+    }
 
-            for key in keys_to_remove {
-                cache.pop(&key);
-            }
+    #[cfg(unix)]
+    #[test]
+    fn sizes_count_hard_links_once_and_report_allocated_blocks() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir(root.join("nested")).unwrap();
+        fs::write(root.join("data.bin"), vec![1u8; 10_000]).unwrap();
+        fs::hard_link(root.join("data.bin"), root.join("nested/data-link.bin")).unwrap();
+        let sparse = fs::File::create(root.join("sparse.img")).unwrap();
+        sparse.set_len(1 << 20).unwrap();
+        drop(sparse);
+
+        let result = calculate_dir_size_with_timeout(root, Duration::from_secs(60));
+
+        let counted = ["", "nested", "data.bin", "sparse.img"]
+            .map(|name| fs::symlink_metadata(root.join(name)).unwrap());
+        let expected_size: u64 = counted.iter().map(|metadata| metadata.len()).sum();
+        let expected_allocated_size: u64 = counted.iter().map(allocated_size).sum();
+        assert_eq!(result.status, SizeStatus::Complete);
+        assert_eq!(result.size, expected_size);
+        assert_eq!(result.allocated_size, expected_allocated_size);
+        assert_eq!(result.file_count, 2);
+        assert_eq!(result.dir_count, 1);
+    }
+
+    #[test]
+    fn subtree_check_notices_changes_below_the_root() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        fs::write(root.join("a/b/c/file.txt"), b"data").unwrap();
+
+        let result = calculate_dir_size_with_timeout(root, Duration::from_secs(60));
+        let (entry, is_stale) = get_cached_size(&result.path).unwrap();
+        assert!(!is_stale);
+        assert!(!is_subtree_modified_since(root, entry.subtree_mtime));
+
+        let deep_dir = root.join("a/b/c");
+        let later = SystemTime::now() + Duration::from_secs(120);
+        fs::File::open(&deep_dir)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(is_subtree_modified_since(root, entry.subtree_mtime));
+    }
+
+    #[test]
+    fn sizes_from_an_earlier_session_are_stale() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir(root.join("nested")).unwrap();
+        fs::write(root.join("nested/data.bin"), vec![1u8; 100]).unwrap();
+        let path = calculate_dir_size_with_timeout(root, Duration::from_secs(60)).path;
+        assert!(!cached_result(&path).unwrap().is_stale);
+
+        // Save and load the cache as across a restart, with the file
+        // rewritten in place while the app was closed.
+        let cache_dir = TempDir::new().unwrap();
+        let file_path = cache_dir.path().join(CACHE_FILE_NAME);
+        let (entry, _) = get_cached_size(&path).unwrap();
+        write_cache_file(&file_path, vec![(path.clone(), entry)]).unwrap();
+        for (path, entry) in read_cache_file(&file_path) {
+            set_cached_size(&path, entry);
         }
+        let nested_mtime = fs::metadata(root.join("nested"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        fs::write(root.join("nested/data.bin"), vec![1u8; 5000]).unwrap();
+        fs::File::open(root.join("nested"))
+            .unwrap()
+            .set_modified(nested_mtime)
+            .unwrap();
+
+        let cached = cached_result(&path).unwrap();
+        assert!(cached.is_stale);
+        let recalculated = calculate_dir_size_with_timeout(root, Duration::from_secs(60));
+        assert_eq!(recalculated.size, cached.size + 4900);
+        assert!(!cached_result(&path).unwrap().is_stale);
     }
-}
 
-#[tauri::command]
-pub fn clear_dir_size_cache() {
-    if let Ok(mut cache) = SIZE_CACHE.lock() {
-        cache.clear();
+    #[test]
+    fn cache_file_round_trips_entries_in_lru_order() {
+        let temp = TempDir::new().unwrap();
+        let file_path = temp.path().join("cache").join(CACHE_FILE_NAME);
+        let entries = vec![
+            ("/old".to_string(), cache_entry(10)),
+            ("/new".to_string(), cache_entry(20)),
+        ];
+
+        write_cache_file(&file_path, entries).unwrap();
+        let loaded = read_cache_file(&file_path);
+
+        let loaded: Vec<(&str, u64)> = loaded
+            .iter()
+            .map(|(path, entry)| (path.as_str(), entry.size))
+            .collect();
+        assert_eq!(loaded, vec![("/old", 10), ("/new", 20)]);
+        assert!(read_cache_file(&temp.path().join("missing.json")).is_empty());
     }
 }
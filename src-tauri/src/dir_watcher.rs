@@ -5,7 +5,7 @@
 use crate::utils::normalize_path;
 use notify::{event::ModifyKind, Config, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::thread;
@@ -50,6 +50,16 @@ fn event_kind_to_string(kind: &EventKind) -> &'static str {
     }
 }
 
+/// Drops the cached sizes of the paths changed since the last debounce tick,
+/// saving the size cache once for the whole batch.
+fn flush_size_invalidations(changed_paths: &mut HashSet<String>) {
+    if changed_paths.is_empty() {
+        return;
+    }
+    let changed_paths: Vec<String> = changed_paths.drain().collect();
+    crate::dir_size::invalidate_paths(&changed_paths, true);
+}
+
 fn dir_watcher_diag_enabled() -> bool {
     std::env::var("SFM_DIR_WATCHER_DIAG")
         .ok()
@@ -183,6 +193,7 @@ pub async fn watch_directory(app: AppHandle, path: String) -> Result<(), String>
         let debounce_duration = Duration::from_millis(300);
         let mut last_emit_time: Option<Instant> = None;
         let mut pending_emit = false;
+        let mut pending_size_invalidations: HashSet<String> = HashSet::new();
 
         loop {
             {
@@ -204,6 +215,13 @@ pub async fn watch_directory(app: AppHandle, path: String) -> Result<(), String>
                         continue;
                     }
 
+                    pending_size_invalidations.extend(
+                        event
+                            .paths
+                            .iter()
+                            .map(|path| normalize_path(&path.to_string_lossy())),
+                    );
+
                     let now = Instant::now();
                     let should_emit = match last_emit_time {
                         Some(last_time) => now.duration_since(last_time) >= debounce_duration,
@@ -211,6 +229,7 @@ pub async fn watch_directory(app: AppHandle, path: String) -> Result<(), String>
                     };
 
                     if should_emit {
+                        flush_size_invalidations(&mut pending_size_invalidations);
                         let kind = event_kind_to_string(&event.kind);
                         let changed_path = event
                             .paths
@@ -238,6 +257,7 @@ pub async fn watch_directory(app: AppHandle, path: String) -> Result<(), String>
                     if pending_emit {
                         if let Some(last_time) = last_emit_time {
                             if Instant::now().duration_since(last_time) >= debounce_duration {
+                                flush_size_invalidations(&mut pending_size_invalidations);
                                 let payload = serde_json::json!({
                                     "watchedPath": path_for_thread.clone(),
                                     "changedPath": "",
@@ -264,6 +284,8 @@ pub async fn watch_directory(app: AppHandle, path: String) -> Result<(), String>
             }
         }
 
+        flush_size_invalidations(&mut pending_size_invalidations);
+
         if let Ok(mut watchers) = ACTIVE_WATCHERS.lock() {
             watchers.remove(&path_for_thread);
         }
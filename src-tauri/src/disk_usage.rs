@@ -0,0 +1,681 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Disk usage analysis.
+//!
+//! A job walks a root once and builds a size tree: every directory gets its
+//! totals and keeps its largest children, with the rest folded into an
+//! "other" bucket. The finished tree is saved per root so the UI can drill
+//! down and draw treemaps without walking the disk again.
+
+use crate::utils::{normalize_path, path_is_descendant_of};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+
+const DISK_USAGE_DIR_NAME: &str = "disk-usage";
+const DISK_USAGE_SCHEMA_VERSION: u32 = 1;
+const DEFAULT_TOP_CHILDREN: usize = 32;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskUsageOptions {
+    /// Skips directories on other filesystems than the root, like `du -x`.
+    #[serde(default)]
+    pub one_file_system: bool,
+    /// Paths to leave out. Patterns without a `/` match entry names.
+    #[serde(default)]
+    pub exclude_globs: Vec<String>,
+    /// Children kept per directory; smaller ones are summed into `other_size`.
+    #[serde(default)]
+    pub top_children: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskUsageNode {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Files at any depth below a directory; 1 for a file.
+    pub file_count: u64,
+    /// Directories at any depth below a directory, not counting itself.
+    pub dir_count: u64,
+    /// Entries below a directory that could not be read.
+    pub error_count: u64,
+    /// Largest children, biggest first.
+    pub children: Vec<DiskUsageNode>,
+    /// Combined size of the children left out of `children`.
+    pub other_size: u64,
+    pub other_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskUsageReport {
+    pub version: u32,
+    pub root: String,
+    pub options: DiskUsageOptions,
+    pub started_time: u64,
+    pub finished_time: u64,
+    pub tree: DiskUsageNode,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskUsageJobProgressPayload {
+    pub job_id: String,
+    pub size: u64,
+    pub file_count: u64,
+    pub dir_count: u64,
+    pub current_path: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskUsageJobFinishedPayload {
+    pub job_id: String,
+    pub root: String,
+    pub success: bool,
+    pub cancelled: bool,
+    pub error: Option<String>,
+    pub size: u64,
+    pub file_count: u64,
+    pub dir_count: u64,
+}
+
+static DISK_USAGE_JOBS: LazyLock<Mutex<HashMap<String, Arc<AtomicBool>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Default)]
+struct DiskUsageProgress {
+    size: AtomicU64,
+    file_count: AtomicU64,
+    dir_count: AtomicU64,
+    current_path: Mutex<String>,
+}
+
+struct DiskUsageWalk<'a> {
+    root_device: Option<u64>,
+    one_file_system: bool,
+    exclude_set: Option<GlobSet>,
+    top_children: usize,
+    cancel: &'a AtomicBool,
+    progress: &'a DiskUsageProgress,
+    /// Files with more than one hard link seen so far, so each is counted once.
+    seen_links: Mutex<HashSet<(u64, u64)>>,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(unix)]
+fn device_id(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.dev())
+}
+
+// Other volumes are only reachable through mount point reparse points on
+// Windows, and the walk never follows those.
+#[cfg(not(unix))]
+fn device_id(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+/// Identity of a file that has other hard links, `None` when it has only one.
+#[cfg(unix)]
+fn hard_link_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn hard_link_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+fn build_exclude_set(exclude_globs: &[String]) -> Result<Option<GlobSet>, String> {
+    let mut builder = GlobSetBuilder::new();
+    let mut has_patterns = false;
+
+    for pattern in exclude_globs {
+        let pattern = normalize_path(pattern.trim());
+        if pattern.is_empty() {
+            continue;
+        }
+
+        let glob = if pattern.contains('/') {
+            pattern.clone()
+        } else {
+            format!("**/{pattern}")
+        };
+        let glob = GlobBuilder::new(&glob)
+            .literal_separator(true)
+            .case_insensitive(cfg!(windows))
+            .build()
+            .map_err(|error| format!("Invalid exclude pattern {pattern}: {error}"))?;
+        builder.add(glob);
+        has_patterns = true;
+    }
+
+    if !has_patterns {
+        return Ok(None);
+    }
+    builder.build().map(Some).map_err(|error| error.to_string())
+}
+
+impl DiskUsageNode {
+    fn new(name: String, path: String, is_dir: bool, size: u64) -> Self {
+        Self {
+            name,
+            path,
+            is_dir,
+            size,
+            file_count: u64::from(!is_dir),
+            dir_count: 0,
+            error_count: 0,
+            children: Vec::new(),
+            other_size: 0,
+            other_count: 0,
+        }
+    }
+
+    /// Adds `children` to the totals and keeps the `top_children` largest.
+    fn set_children(&mut self, mut children: Vec<DiskUsageNode>, top_children: usize) {
+        for child in &children {
+            self.size += child.size;
+            self.file_count += child.file_count;
+            self.dir_count += child.dir_count + u64::from(child.is_dir);
+            self.error_count += child.error_count;
+        }
+
+        children.sort_by(|child_a, child_b| {
+            child_b
+                .size
+                .cmp(&child_a.size)
+                .then_with(|| child_a.name.cmp(&child_b.name))
+        });
+        for child in children.drain(top_children.min(children.len())..) {
+            self.other_size += child.size;
+            self.other_count += 1;
+        }
+        self.children = children;
+    }
+
+    /// The node for `path` in this tree, if the tree kept it.
+    fn find(&self, path: &str) -> Option<&DiskUsageNode> {
+        if self.path == path {
+            return Some(self);
+        }
+
+        self.children
+            .iter()
+            .find(|child| child.path == path || path_is_descendant_of(path, &child.path))
+            .and_then(|child| child.find(path))
+    }
+
+    /// Copy of this node with children kept `depth` levels deep.
+    fn limited_to_depth(&self, depth: usize) -> DiskUsageNode {
+        DiskUsageNode {
+            children: if depth == 0 {
+                Vec::new()
+            } else {
+                self.children
+                    .iter()
+                    .map(|child| child.limited_to_depth(depth - 1))
+                    .collect()
+            },
+            ..self.clone_without_children()
+        }
+    }
+
+    fn clone_without_children(&self) -> DiskUsageNode {
+        DiskUsageNode {
+            name: self.name.clone(),
+            path: self.path.clone(),
+            is_dir: self.is_dir,
+            size: self.size,
+            file_count: self.file_count,
+            dir_count: self.dir_count,
+            error_count: self.error_count,
+            children: Vec::new(),
+            other_size: self.other_size,
+            other_count: self.other_count,
+        }
+    }
+}
+
+impl DiskUsageWalk<'_> {
+    fn is_excluded(&self, path_string: &str) -> bool {
+        self.exclude_set
+            .as_ref()
+            .is_some_and(|exclude_set| exclude_set.is_match(path_string))
+    }
+
+    fn measure_dir(&self, path: &Path, path_string: String, name: String) -> DiskUsageNode {
+        let mut node = DiskUsageNode::new(name, path_string, true, 0);
+        if self.cancel.load(Ordering::Relaxed) {
+            return node;
+        }
+        let Ok(read_dir) = fs::read_dir(path) else {
+            node.error_count = 1;
+            return node;
+        };
+
+        self.progress.dir_count.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut current_path) = self.progress.current_path.try_lock() {
+            current_path.clone_from(&node.path);
+        }
+
+        let mut children = Vec::new();
+        let mut subdirs = Vec::new();
+        for entry in read_dir.flatten() {
+            // Links are not followed, so nothing is counted twice.
+            let Ok(file_type) = entry.file_type() else {
+                node.error_count += 1;
+                continue;
+            };
+            if file_type.is_symlink() {
+                continue;
+            }
+
+            let child_path = entry.path();
+            let Some(child_string) = child_path.to_str().map(normalize_path) else {
+                continue;
+            };
+            if self.is_excluded(&child_string) {
+                continue;
+            }
+
+            let Ok(metadata) = entry.metadata() else {
+                node.error_count += 1;
+                continue;
+            };
+            let child_name = entry.file_name().to_string_lossy().into_owned();
+
+            if metadata.is_dir() {
+                if self.one_file_system && device_id(&metadata) != self.root_device {
+                    continue;
+                }
+                subdirs.push((child_path, child_string, child_name));
+            } else {
+                if let Some(link_id) = hard_link_id(&metadata) {
+                    let is_first_link = self
+                        .seen_links
+                        .lock()
+                        .map(|mut seen_links| seen_links.insert(link_id))
+                        .unwrap_or(true);
+                    if !is_first_link {
+                        continue;
+                    }
+                }
+                let size = metadata.len();
+                self.progress.size.fetch_add(size, Ordering::Relaxed);
+                self.progress.file_count.fetch_add(1, Ordering::Relaxed);
+                children.push(DiskUsageNode::new(child_name, child_string, false, size));
+            }
+        }
+
+        children.par_extend(subdirs.into_par_iter().map(
+            |(child_path, child_string, child_name)| {
+                self.measure_dir(&child_path, child_string, child_name)
+            },
+        ));
+        node.set_children(children, self.top_children);
+        node
+    }
+}
+
+fn disk_usage_dir(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(DISK_USAGE_DIR_NAME)
+}
+
+fn report_file(app_data_dir: &Path, root: &str) -> PathBuf {
+    let digest = Sha256::digest(root.as_bytes());
+    let file_stem: String = digest[..16]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    disk_usage_dir(app_data_dir).join(format!("{file_stem}.json"))
+}
+
+fn app_data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|error| format!("Failed to resolve app data directory: {error}"))
+}
+
+fn save_report(app_data_dir: &Path, report: &DiskUsageReport) -> Result<(), String> {
+    let file_path = report_file(app_data_dir, &report.root);
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+    let json = serde_json::to_string(report).map_err(|error| error.to_string())?;
+    let tmp_path = file_path.with_extension("json.tmp");
+    fs::write(&tmp_path, json).map_err(|error| error.to_string())?;
+    fs::rename(&tmp_path, &file_path).or_else(|rename_error| {
+        let _ = fs::remove_file(&file_path);
+        fs::rename(&tmp_path, &file_path).map_err(|replace_error| {
+            format!(
+                "Failed to replace disk usage report: {}; {}",
+                rename_error, replace_error
+            )
+        })
+    })
+}
+
+fn load_report(app_data_dir: &Path, root: &str) -> Option<DiskUsageReport> {
+    fs::read_to_string(report_file(app_data_dir, root))
+        .ok()
+        .and_then(|text| serde_json::from_str::<DiskUsageReport>(&text).ok())
+        .filter(|report| report.version == DISK_USAGE_SCHEMA_VERSION && report.root == root)
+}
+
+fn root_name(root_path: &Path, root: &str) -> String {
+    root_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| root.to_string())
+}
+
+/// Walks `root` and returns its tree, or `None` when the job was cancelled.
+fn measure_root_blocking(
+    app: &AppHandle,
+    job_id: &str,
+    root: &str,
+    options: &DiskUsageOptions,
+    exclude_set: Option<GlobSet>,
+    cancel: &AtomicBool,
+) -> Result<Option<DiskUsageNode>, String> {
+    let root_path = Path::new(root);
+    let root_metadata = fs::metadata(root_path)
+        .map_err(|error| format!("Failed to access path: {root}: {error}"))?;
+    let progress = DiskUsageProgress::default();
+    let walk = DiskUsageWalk {
+        root_device: device_id(&root_metadata),
+        one_file_system: options.one_file_system,
+        exclude_set,
+        top_children: options.top_children.unwrap_or(DEFAULT_TOP_CHILDREN).max(1),
+        cancel,
+        progress: &progress,
+        seen_links: Mutex::default(),
+    };
+
+    let tree = std::thread::scope(|scope| {
+        let walker = scope
+            .spawn(|| walk.measure_dir(root_path, root.to_string(), root_name(root_path, root)));
+
+        while !walker.is_finished() {
+            std::thread::sleep(PROGRESS_INTERVAL);
+            let current_path = progress
+                .current_path
+                .lock()
+                .map(|current_path| current_path.clone())
+                .unwrap_or_default();
+            let _ = app.emit(
+                "disk-usage-job-progress",
+                &DiskUsageJobProgressPayload {
+                    job_id: job_id.to_string(),
+                    size: progress.size.load(Ordering::Relaxed),
+                    file_count: progress.file_count.load(Ordering::Relaxed),
+                    dir_count: progress.dir_count.load(Ordering::Relaxed),
+                    current_path,
+                },
+            );
+        }
+
+        walker
+            .join()
+            .map_err(|_| "Disk usage walk panicked".to_string())
+    })?;
+
+    Ok((!cancel.load(Ordering::Relaxed)).then_some(tree))
+}
+
+#[tauri::command]
+pub async fn start_disk_usage_job(
+    app: AppHandle,
+    job_id: String,
+    path: String,
+    options: Option<DiskUsageOptions>,
+) -> Result<(), String> {
+    let root = normalize_path(&path);
+    if !Path::new(&root).is_dir() {
+        return Err(format!("Path is not a directory: {path}"));
+    }
+    let options = options.unwrap_or_default();
+    let exclude_set = build_exclude_set(&options.exclude_globs)?;
+    let app_data_dir = app_data_dir(&app)?;
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    {
+        let mut guard = DISK_USAGE_JOBS
+            .lock()
+            .map_err(|_| "Disk usage job registry lock failed".to_string())?;
+        guard.insert(job_id.clone(), cancel.clone());
+    }
+
+    tokio::spawn(async move {
+        let started_time = now_millis();
+        let app_walk = app.clone();
+        let job_id_walk = job_id.clone();
+        let root_walk = root.clone();
+        let work_result = tokio::task::spawn_blocking(move || {
+            let tree = measure_root_blocking(
+                &app_walk,
+                &job_id_walk,
+                &root_walk,
+                &options,
+                exclude_set,
+                &cancel,
+            )?;
+            let Some(tree) = tree else {
+                return Ok(None);
+            };
+
+            let report = DiskUsageReport {
+                version: DISK_USAGE_SCHEMA_VERSION,
+                root: root_walk,
+                options,
+                started_time,
+                finished_time: now_millis(),
+                tree,
+            };
+            save_report(&app_data_dir, &report)?;
+            Ok(Some(report))
+        })
+        .await
+        .map_err(|join_error| format!("Disk usage task failed: {join_error}"))
+        .and_then(|result| result);
+
+        let mut finished = DiskUsageJobFinishedPayload {
+            job_id: job_id.clone(),
+            root,
+            success: false,
+            cancelled: false,
+            error: None,
+            size: 0,
+            file_count: 0,
+            dir_count: 0,
+        };
+        match work_result {
+            Ok(Some(report)) => {
+                finished.success = true;
+                finished.size = report.tree.size;
+                finished.file_count = report.tree.file_count;
+                finished.dir_count = report.tree.dir_count;
+            }
+            Ok(None) => finished.cancelled = true,
+            Err(error) => finished.error = Some(error),
+        }
+
+        let _ = app.emit("disk-usage-job-finished", &finished);
+
+        if let Ok(mut guard) = DISK_USAGE_JOBS.lock() {
+            guard.remove(&job_id);
+        }
+    });
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn cancel_disk_usage_job(job_id: String) -> bool {
+    let Ok(guard) = DISK_USAGE_JOBS.lock() else {
+        return false;
+    };
+    if let Some(flag) = guard.get(&job_id) {
+        flag.store(true, Ordering::Relaxed);
+        true
+    } else {
+        false
+    }
+}
+
+/// Returns the saved report for `root`, with its tree rooted at `path` when
+/// given and cut off `depth` levels down. `None` when `root` was never
+/// analyzed or `path` was folded into an "other" bucket.
+#[tauri::command]
+pub async fn get_disk_usage_report(
+    app: AppHandle,
+    root: String,
+    path: Option<String>,
+    depth: Option<usize>,
+) -> Result<Option<DiskUsageReport>, String> {
+    let app_data_dir = app_data_dir(&app)?;
+
+    tokio::task::spawn_blocking(move || {
+        let root = normalize_path(&root);
+        let report = load_report(&app_data_dir, &root)?;
+        let path = path
+            .map(|path| normalize_path(&path))
+            .unwrap_or_else(|| root.clone());
+        let node = report.tree.find(&path)?;
+        let tree = match depth {
+            Some(depth) => node.limited_to_depth(depth),
+            None => node.clone(),
+        };
+
+        Some(DiskUsageReport { tree, ..report })
+    })
+    .await
+    .map_err(|join_error| format!("Failed to read disk usage report: {join_error}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn measure(root: &Path, exclude_globs: &[&str], top_children: usize) -> DiskUsageNode {
+        let root_string = normalize_path(&root.to_string_lossy());
+        let cancel = AtomicBool::new(false);
+        let progress = DiskUsageProgress::default();
+        let exclude_globs: Vec<String> =
+            exclude_globs.iter().map(|glob| glob.to_string()).collect();
+        let walk = DiskUsageWalk {
+            root_device: None,
+            one_file_system: false,
+            exclude_set: build_exclude_set(&exclude_globs).unwrap(),
+            top_children,
+            cancel: &cancel,
+            progress: &progress,
+            seen_links: Mutex::default(),
+        };
+        walk.measure_dir(root, root_string, "root".to_string())
+    }
+
+    #[test]
+    fn directories_total_their_subtrees_and_keep_the_largest_children() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("videos/2024")).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("videos/2024/trip.mp4"), vec![0u8; 5000]).unwrap();
+        fs::write(root.join("videos/clip.mp4"), vec![0u8; 1000]).unwrap();
+        fs::write(root.join("docs/a.txt"), vec![0u8; 30]).unwrap();
+        fs::write(root.join("docs/b.txt"), vec![0u8; 20]).unwrap();
+        fs::write(root.join("docs/c.txt"), vec![0u8; 10]).unwrap();
+        fs::write(root.join("notes.txt"), vec![0u8; 100]).unwrap();
+
+        let tree = measure(root, &[], 2);
+
+        assert_eq!(tree.size, 6160);
+        assert_eq!(tree.file_count, 6);
+        assert_eq!(tree.dir_count, 3);
+        let names: Vec<&str> = tree
+            .children
+            .iter()
+            .map(|child| child.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["videos", "notes.txt"]);
+        assert_eq!((tree.other_size, tree.other_count), (60, 1));
+
+        let docs_path = format!("{}/docs", tree.path);
+        assert!(tree.find(&docs_path).is_none());
+        let year_path = format!("{}/videos/2024", tree.path);
+        let year = tree.find(&year_path).unwrap();
+        assert_eq!(year.size, 5000);
+        assert_eq!(year.children[0].name, "trip.mp4");
+
+        let shallow = tree.limited_to_depth(1);
+        assert_eq!(shallow.children.len(), 2);
+        assert!(shallow.children[0].children.is_empty());
+    }
+
+    #[test]
+    fn exclude_globs_skip_names_and_paths() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("project/node_modules/pkg")).unwrap();
+        fs::create_dir_all(root.join("cache")).unwrap();
+        fs::write(
+            root.join("project/node_modules/pkg/index.js"),
+            vec![0u8; 500],
+        )
+        .unwrap();
+        fs::write(root.join("project/main.rs"), vec![0u8; 40]).unwrap();
+        fs::write(root.join("cache/blob"), vec![0u8; 900]).unwrap();
+        let cache_glob = format!("{}/cache", normalize_path(&root.to_string_lossy()));
+
+        let tree = measure(root, &["node_modules", &cache_glob], DEFAULT_TOP_CHILDREN);
+
+        assert_eq!(tree.size, 40);
+        assert_eq!(tree.file_count, 1);
+        assert!(build_exclude_set(&["[".to_string()]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_linked_files_are_counted_once() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join("b")).unwrap();
+        fs::write(root.join("a/data.bin"), vec![0u8; 700]).unwrap();
+        fs::hard_link(root.join("a/data.bin"), root.join("b/data.bin")).unwrap();
+        fs::write(root.join("b/own.bin"), vec![0u8; 50]).unwrap();
+
+        let tree = measure(root, &[], DEFAULT_TOP_CHILDREN);
+
+        assert_eq!(tree.size, 750);
+        assert_eq!(tree.file_count, 2);
+    }
+}
@@ -0,0 +1,644 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Persistent frecency store for visited directories and opened files.
+//!
+//! Ranking follows zoxide: every visit adds 1 to a path's rank, the rank is
+//! weighted by how long ago the path was last visited, and once the ranks add
+//! up to `MAX_TOTAL_RANK` they are all scaled down and the ones that fall
+//! below 1 are forgotten.
+
+use crate::utils::normalize_path;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+
+const FRECENCY_FILE_NAME: &str = "frecency.json";
+const FRECENCY_SCHEMA_VERSION: u32 = 1;
+const MAX_TOTAL_RANK: f64 = 10_000.0;
+const REVISIT_COOLDOWN_SECONDS: u64 = 30;
+const HOUR_SECONDS: u64 = 60 * 60;
+const DAY_SECONDS: u64 = 24 * HOUR_SECONDS;
+const WEEK_SECONDS: u64 = 7 * DAY_SECONDS;
+/// Frecency at which the normalized score reaches 0.5.
+const NORMALIZED_HALF_SCORE: f64 = 10.0;
+const DEFAULT_JUMP_LIMIT: usize = 1;
+const ZOXIDE_DATABASE_VERSION: u32 = 3;
+/// Visits within this window after a rank change are written together.
+const SAVE_DEBOUNCE: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FrecencyEntry {
+    rank: f64,
+    last_accessed: u64,
+    is_dir: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FrecencyDatabase {
+    version: u32,
+    entries: HashMap<String, FrecencyEntry>,
+}
+
+struct FrecencyStore {
+    file_path: PathBuf,
+    database: FrecencyDatabase,
+    is_dirty: bool,
+}
+
+static FRECENCY_STORE: Lazy<Mutex<Option<FrecencyStore>>> = Lazy::new(|| Mutex::new(None));
+static IS_SAVE_SCHEDULED: AtomicBool = AtomicBool::new(false);
+/// Held from taking a snapshot until it is written, so an older snapshot
+/// never overwrites a newer one.
+static SAVE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FrecencyJumpMatch {
+    pub path: String,
+    pub score: f64,
+}
+
+fn now_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn frecency_key(path: &str) -> String {
+    let normalized = normalize_path(path);
+    if normalized.len() > 1 && !normalized.ends_with(":/") {
+        normalized.trim_end_matches('/').to_string()
+    } else {
+        normalized
+    }
+}
+
+fn frecency_file(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(FRECENCY_FILE_NAME)
+}
+
+fn app_data_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|error| format!("Failed to resolve app data directory: {error}"))
+}
+
+impl FrecencyEntry {
+    fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_accessed);
+        let factor = if age < HOUR_SECONDS {
+            4.0
+        } else if age < DAY_SECONDS {
+            2.0
+        } else if age < WEEK_SECONDS {
+            0.5
+        } else {
+            0.25
+        };
+        self.rank * factor
+    }
+}
+
+impl FrecencyDatabase {
+    /// Records a visit. Returns whether the rank changed; repeat visits within
+    /// the cooldown (refreshes, re-reads) only update the access time.
+    fn record(&mut self, key: String, is_dir: bool, now: u64) -> bool {
+        match self.entries.get_mut(&key) {
+            Some(entry) if now.saturating_sub(entry.last_accessed) < REVISIT_COOLDOWN_SECONDS => {
+                entry.last_accessed = now;
+                false
+            }
+            Some(entry) => {
+                entry.rank += 1.0;
+                entry.last_accessed = now;
+                entry.is_dir = is_dir;
+                self.age();
+                true
+            }
+            None => {
+                self.entries.insert(
+                    key,
+                    FrecencyEntry {
+                        rank: 1.0,
+                        last_accessed: now,
+                        is_dir,
+                    },
+                );
+                self.age();
+                true
+            }
+        }
+    }
+
+    fn age(&mut self) {
+        let total_rank: f64 = self.entries.values().map(|entry| entry.rank).sum();
+        if total_rank <= MAX_TOTAL_RANK {
+            return;
+        }
+
+        let factor = 0.9 * MAX_TOTAL_RANK / total_rank;
+        self.entries.retain(|_, entry| {
+            entry.rank *= factor;
+            entry.rank >= 1.0
+        });
+    }
+
+    fn normalized_score(&self, key: &str, now: u64) -> f64 {
+        self.entries.get(key).map_or(0.0, |entry| {
+            let frecency = entry.frecency(now);
+            frecency / (frecency + NORMALIZED_HALF_SCORE)
+        })
+    }
+
+    /// Directories matching `keywords`, best first. Like zoxide, the keywords
+    /// must appear in the path in order and the last one must appear in the
+    /// last path component.
+    fn jump_candidates(&self, keywords: &[String], now: u64) -> Vec<FrecencyJumpMatch> {
+        let mut candidates: Vec<FrecencyJumpMatch> = self
+            .entries
+            .iter()
+            .filter(|(path, entry)| entry.is_dir && matches_keywords(path, keywords))
+            .map(|(path, entry)| FrecencyJumpMatch {
+                path: path.clone(),
+                score: entry.frecency(now),
+            })
+            .collect();
+
+        candidates.sort_by(|candidate_a, candidate_b| {
+            candidate_b
+                .score
+                .partial_cmp(&candidate_a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        candidates
+    }
+
+    fn merge_imported(&mut self, key: String, rank: f64, last_accessed: u64) {
+        let entry = self.entries.entry(key).or_insert(FrecencyEntry {
+            rank: 0.0,
+            last_accessed,
+            is_dir: true,
+        });
+        entry.rank += rank;
+        entry.last_accessed = entry.last_accessed.max(last_accessed);
+    }
+}
+
+fn matches_keywords(path: &str, keywords: &[String]) -> bool {
+    let path_lower = path.to_lowercase();
+    let mut remaining = path_lower.as_str();
+
+    for keyword in keywords {
+        match remaining.find(keyword.as_str()) {
+            Some(position) => remaining = &remaining[position + keyword.len()..],
+            None => return false,
+        }
+    }
+
+    match keywords.last() {
+        Some(last_keyword) => {
+            let last_component = path_lower
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or("");
+            last_component.contains(last_keyword.as_str())
+        }
+        None => true,
+    }
+}
+
+fn split_keywords(query: &str) -> Vec<String> {
+    query
+        .split_whitespace()
+        .map(|keyword| normalize_path(&keyword.to_lowercase()))
+        .collect()
+}
+
+fn load_database(file_path: &Path) -> FrecencyDatabase {
+    fs::read_to_string(file_path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<FrecencyDatabase>(&text).ok())
+        .filter(|database| database.version == FRECENCY_SCHEMA_VERSION)
+        .unwrap_or_else(|| FrecencyDatabase {
+            version: FRECENCY_SCHEMA_VERSION,
+            entries: HashMap::new(),
+        })
+}
+
+fn save_database(file_path: &Path, database: &FrecencyDatabase) -> Result<(), String> {
+    let json = serde_json::to_string(database).map_err(|error| error.to_string())?;
+    write_store_file(file_path, &json)
+}
+
+fn write_store_file(file_path: &Path, json: &str) -> Result<(), String> {
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+    let tmp_path = file_path.with_extension("json.tmp");
+    fs::write(&tmp_path, json).map_err(|error| error.to_string())?;
+    fs::rename(&tmp_path, file_path).or_else(|rename_error| {
+        let _ = fs::remove_file(file_path);
+        fs::rename(&tmp_path, file_path).map_err(|replace_error| {
+            format!(
+                "Failed to replace frecency store: {}; {}",
+                rename_error, replace_error
+            )
+        })
+    })
+}
+
+/// Runs `action` on the store for `app_data_dir`, loading it on first use.
+fn with_store<T>(
+    app_data_dir: &Path,
+    action: impl FnOnce(&mut FrecencyStore) -> T,
+) -> Result<T, String> {
+    let file_path = frecency_file(app_data_dir);
+    let mut store = FRECENCY_STORE.lock().map_err(|error| error.to_string())?;
+
+    if store
+        .as_ref()
+        .is_none_or(|store| store.file_path != file_path)
+    {
+        // Unsaved visits of the previous store are written before it is swapped out.
+        if let Some(previous_store) = store.as_ref().filter(|store| store.is_dirty) {
+            if let Err(error) = save_database(&previous_store.file_path, &previous_store.database) {
+                log::warn!("Failed to save frecency store: {error}");
+            }
+        }
+        *store = Some(FrecencyStore {
+            database: load_database(&file_path),
+            file_path: file_path.clone(),
+            is_dirty: false,
+        });
+    }
+
+    let store = store.as_mut().expect("frecency store was just loaded");
+    Ok(action(store))
+}
+
+fn with_database<T>(
+    app_data_dir: &Path,
+    action: impl FnOnce(&mut FrecencyDatabase) -> T,
+) -> Result<T, String> {
+    with_store(app_data_dir, |store| action(&mut store.database))
+}
+
+/// Writes the store when it has unsaved changes. The JSON is built under the
+/// lock and written after releasing it, so visits are never blocked on disk.
+pub fn flush() {
+    let Ok(_save_guard) = SAVE_LOCK.lock() else {
+        return;
+    };
+    let pending_write = FRECENCY_STORE.lock().ok().and_then(|mut store| {
+        let store = store.as_mut().filter(|store| store.is_dirty)?;
+        store.is_dirty = false;
+        let json = serde_json::to_string(&store.database).ok()?;
+        Some((store.file_path.clone(), json))
+    });
+
+    if let Some((file_path, json)) = pending_write {
+        if let Err(error) = write_store_file(&file_path, &json) {
+            log::warn!("Failed to save frecency store: {error}");
+        }
+    }
+}
+
+fn has_unsaved_changes() -> bool {
+    FRECENCY_STORE
+        .lock()
+        .is_ok_and(|store| store.as_ref().is_some_and(|store| store.is_dirty))
+}
+
+/// Saves the store on a background thread once visits settle for
+/// `SAVE_DEBOUNCE`. At most one save thread runs at a time.
+fn schedule_save() {
+    if IS_SAVE_SCHEDULED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    std::thread::spawn(|| loop {
+        std::thread::sleep(SAVE_DEBOUNCE);
+        flush();
+        IS_SAVE_SCHEDULED.store(false, Ordering::SeqCst);
+
+        // A visit recorded while writing saw the flag still set and did not
+        // schedule its own save.
+        if !has_unsaved_changes() || IS_SAVE_SCHEDULED.swap(true, Ordering::SeqCst) {
+            break;
+        }
+    });
+}
+
+/// Records a visit to `path`. Rank changes are saved in the background.
+pub fn record_visit(app: &tauri::AppHandle, path: &str, is_dir: bool) {
+    let Ok(app_data_dir) = app_data_dir(app) else {
+        return;
+    };
+
+    let result = with_store(&app_data_dir, |store| {
+        if store
+            .database
+            .record(frecency_key(path), is_dir, now_seconds())
+        {
+            store.is_dirty = true;
+        }
+        store.is_dirty
+    });
+
+    match result {
+        Ok(true) => schedule_save(),
+        Ok(false) => {}
+        Err(error) => log::warn!("Failed to record frecency visit for {path}: {error}"),
+    }
+}
+
+/// Normalized frecency in `[0, 1)` for each path; unvisited paths score 0.
+pub fn normalized_scores(app_data_dir: &Path, paths: &[&str]) -> Vec<f64> {
+    let now = now_seconds();
+    with_database(app_data_dir, |database| {
+        paths
+            .iter()
+            .map(|path| database.normalized_score(&frecency_key(path), now))
+            .collect()
+    })
+    .unwrap_or_else(|_| vec![0.0; paths.len()])
+}
+
+/// Returns the best-matching visited directories for partial input, best
+/// first. Directories that no longer exist are skipped.
+#[tauri::command]
+pub async fn jump_query(
+    app: tauri::AppHandle,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<FrecencyJumpMatch>, String> {
+    let app_data_dir = app_data_dir(&app)?;
+    let limit = limit.unwrap_or(DEFAULT_JUMP_LIMIT);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let keywords = split_keywords(&query);
+        let candidates = with_database(&app_data_dir, |database| {
+            database.jump_candidates(&keywords, now_seconds())
+        })?;
+
+        Ok(candidates
+            .into_iter()
+            .filter(|candidate| Path::new(&candidate.path).is_dir())
+            .take(limit)
+            .collect())
+    })
+    .await
+    .map_err(|join_error| format!("Jump query task failed: {join_error}"))?
+}
+
+fn default_zoxide_database_path() -> Option<PathBuf> {
+    let data_dir = match std::env::var_os("_ZO_DATA_DIR") {
+        Some(data_dir) => PathBuf::from(data_dir),
+        None => dirs::data_local_dir()?.join("zoxide"),
+    };
+    Some(data_dir.join("db.zo"))
+}
+
+struct ZoxideReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl ZoxideReader<'_> {
+    fn take(&mut self, length: usize) -> Result<&[u8], String> {
+        let end = self
+            .offset
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| "Unexpected end of zoxide database".to_string())?;
+        let slice = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(slice)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, String> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_bits(self.read_u64()?))
+    }
+
+    fn read_string(&mut self) -> Result<String, String> {
+        let length = usize::try_from(self.read_u64()?)
+            .map_err(|_| "Invalid path length in zoxide database".to_string())?;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| "Invalid path encoding in zoxide database".to_string())
+    }
+}
+
+/// Parses a zoxide `db.zo` file: a bincode-encoded version number followed by
+/// a list of `(path, rank, last_accessed)` records.
+fn parse_zoxide_database(bytes: &[u8]) -> Result<Vec<(String, f64, u64)>, String> {
+    let mut reader = ZoxideReader { bytes, offset: 0 };
+
+    let version = reader.read_u32()?;
+    if version != ZOXIDE_DATABASE_VERSION {
+        return Err(format!("Unsupported zoxide database version: {version}"));
+    }
+
+    let count = reader.read_u64()?;
+    let mut records = Vec::new();
+    for _ in 0..count {
+        let path = reader.read_string()?;
+        let rank = reader.read_f64()?;
+        let last_accessed = reader.read_u64()?;
+        records.push((path, rank, last_accessed));
+    }
+
+    Ok(records)
+}
+
+/// Merges a zoxide database into the frecency store. Uses the default zoxide
+/// location when `database_path` is not given. Returns the number of imported
+/// directories.
+#[tauri::command]
+pub async fn frecency_import_zoxide(
+    app: tauri::AppHandle,
+    database_path: Option<String>,
+) -> Result<u64, String> {
+    let app_data_dir = app_data_dir(&app)?;
+    let database_path = match database_path {
+        Some(database_path) => PathBuf::from(database_path),
+        None => default_zoxide_database_path()
+            .ok_or_else(|| "Failed to resolve the zoxide data directory".to_string())?,
+    };
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let bytes = fs::read(&database_path).map_err(|error| {
+            format!(
+                "Failed to read zoxide database {}: {error}",
+                database_path.display()
+            )
+        })?;
+        let records = parse_zoxide_database(&bytes)?;
+
+        let imported_count = with_store(&app_data_dir, |store| {
+            let mut imported_count = 0;
+            for (path, rank, last_accessed) in records {
+                if !rank.is_finite() || rank <= 0.0 {
+                    continue;
+                }
+                store
+                    .database
+                    .merge_imported(frecency_key(&path), rank, last_accessed);
+                imported_count += 1;
+            }
+            store.database.age();
+            store.is_dirty = true;
+            imported_count
+        })?;
+        flush();
+        Ok(imported_count)
+    })
+    .await
+    .map_err(|join_error| format!("Zoxide import task failed: {join_error}"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keywords(query: &str) -> Vec<String> {
+        split_keywords(query)
+    }
+
+    #[test]
+    fn repeat_visits_within_cooldown_do_not_raise_rank() {
+        let mut database = FrecencyDatabase::default();
+        assert!(database.record("/home/user/projects".into(), true, 1_000));
+        assert!(!database.record("/home/user/projects".into(), true, 1_010));
+        assert!(database.record("/home/user/projects".into(), true, 1_100));
+
+        let entry = &database.entries["/home/user/projects"];
+        assert_eq!(entry.rank, 2.0);
+        assert_eq!(entry.last_accessed, 1_100);
+    }
+
+    #[test]
+    fn recent_visits_weigh_more_and_old_ranks_age_out() {
+        let mut database = FrecencyDatabase::default();
+        database.merge_imported("/old".into(), 20.0, 0);
+        database.merge_imported("/recent".into(), 10.0, WEEK_SECONDS * 2);
+        let now = WEEK_SECONDS * 2 + 60;
+        assert!(database.normalized_score("/recent", now) > database.normalized_score("/old", now));
+        assert_eq!(database.normalized_score("/unknown", now), 0.0);
+
+        database.merge_imported("/huge".into(), MAX_TOTAL_RANK, now);
+        database.merge_imported("/tiny".into(), 1.0, now);
+        database.age();
+        let total_rank: f64 = database.entries.values().map(|entry| entry.rank).sum();
+        assert!(total_rank <= MAX_TOTAL_RANK);
+        assert!(!database.entries.contains_key("/tiny"));
+    }
+
+    #[test]
+    fn jump_keywords_match_in_order_and_end_in_last_component() {
+        assert!(matches_keywords(
+            "/home/user/projects/sigma",
+            &keywords("pro sig")
+        ));
+        assert!(matches_keywords("/home/user/Projects", &keywords("PROJ")));
+        assert!(!matches_keywords(
+            "/home/user/projects/sigma",
+            &keywords("sig pro")
+        ));
+        assert!(!matches_keywords(
+            "/home/user/projects/sigma",
+            &keywords("user")
+        ));
+
+        let mut database = FrecencyDatabase::default();
+        database.merge_imported("/work/sigma".into(), 2.0, 0);
+        database.merge_imported("/home/sigma".into(), 8.0, 0);
+        database.entries.insert(
+            "/home/sigma.txt".into(),
+            FrecencyEntry {
+                rank: 50.0,
+                last_accessed: 0,
+                is_dir: false,
+            },
+        );
+        let candidates = database.jump_candidates(&keywords("sigma"), 0);
+        let paths: Vec<&str> = candidates
+            .iter()
+            .map(|candidate| candidate.path.as_str())
+            .collect();
+        assert_eq!(paths, vec!["/home/sigma", "/work/sigma"]);
+    }
+
+    #[test]
+    fn flush_writes_only_unsaved_changes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = frecency_file(temp_dir.path());
+
+        with_store(temp_dir.path(), |store| {
+            store.database.record("/home/user".into(), true, 1_000);
+        })
+        .unwrap();
+        flush();
+        assert!(!file_path.exists());
+
+        with_store(temp_dir.path(), |store| {
+            store
+                .database
+                .record("/home/user/projects".into(), true, 1_000);
+            store.is_dirty = true;
+        })
+        .unwrap();
+        flush();
+        assert!(!has_unsaved_changes());
+        let saved = load_database(&file_path);
+        assert_eq!(saved.entries.len(), 2);
+        assert_eq!(saved.entries["/home/user/projects"].rank, 1.0);
+    }
+
+    #[test]
+    fn parses_zoxide_database() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&2u64.to_le_bytes());
+        for (path, rank, last_accessed) in
+            [("/home/user", 12.5, 1_700_000_000u64), ("/tmp", 1.0, 5)]
+        {
+            bytes.extend_from_slice(&(path.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(path.as_bytes());
+            bytes.extend_from_slice(&f64::to_bits(rank).to_le_bytes());
+            bytes.extend_from_slice(&last_accessed.to_le_bytes());
+        }
+
+        let records = parse_zoxide_database(&bytes).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                ("/home/user".to_string(), 12.5, 1_700_000_000),
+                ("/tmp".to_string(), 1.0, 5)
+            ]
+        );
+
+        assert!(parse_zoxide_database(&bytes[..bytes.len() - 1]).is_err());
+        bytes[0] = 2;
+        assert!(parse_zoxide_database(&bytes).is_err());
+    }
+}
@@ -0,0 +1,925 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Git status of directory entries, read from the index and the object
+//! store without running git.
+//!
+//! Like `git status`, the index is compared with HEAD for staged changes
+//! and the work tree with the index for unstaged ones. A work tree file is
+//! only hashed when its size or modification time differs from the index,
+//! and the outcome is kept until the file, the index or HEAD changes.
+//! CRLF line endings are normalized the way `core.autocrlf` and the `text`
+//! and `eol` attributes ask for. Other content filters, such as Git LFS, are
+//! not run, so a touched file that uses one gets no status rather than a
+//! possibly wrong one.
+
+mod attributes;
+mod history;
+mod ignore;
+mod index;
+mod objects;
+mod repository;
+
+use crate::utils::normalize_path;
+use attributes::{Attributes, TextAttribute};
+use ignore::ExcludeStack;
+use index::{read_index, IndexEntry};
+use lru::LruCache;
+use objects::{object_id_to_hex, ObjectId, ObjectStore, TREE_MODE};
+use rayon::prelude::*;
+use repository::Repository;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::SystemTime;
+
+const FILE_TYPE_MASK: u32 = 0o170000;
+const REGULAR_FILE_MODE: u32 = 0o100000;
+const SYMLINK_MODE: u32 = 0o120000;
+const GITLINK_MODE: u32 = 0o160000;
+/// Repositories whose index and hashed files are kept between listings.
+const REPOSITORY_CACHE_SIZE: usize = 16;
+
+/// Ordered by precedence: a directory shows the highest status among its
+/// contents, and a file both staged and changed again shows as modified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GitFileStatus {
+    Ignored,
+    Untracked,
+    Staged,
+    Modified,
+    Conflicted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitRepositoryStatus {
+    /// The work tree root.
+    pub root: String,
+    /// `None` when HEAD is detached.
+    pub branch: Option<String>,
+    /// Commit id of HEAD, `None` before the first commit.
+    pub head: Option<String>,
+    /// Short name of the tracked branch, e.g. `origin/main`.
+    pub upstream: Option<String>,
+    /// Commits on the branch that are not on its upstream.
+    pub ahead: Option<u32>,
+    /// Commits on the upstream that are not on the branch.
+    pub behind: Option<u32>,
+}
+
+pub struct DirGitStatus {
+    pub repository: GitRepositoryStatus,
+    /// By entry name. Clean entries are left out.
+    pub entries: HashMap<String, GitFileStatus>,
+}
+
+fn child_path(relative_dir: &str, name: &str) -> String {
+    if relative_dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{relative_dir}/{name}")
+    }
+}
+
+/// The name of the listed entry that `path` is or lies under, given the
+/// listed directory's `prefix`, which is `""` or ends with `/`.
+fn listed_name<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    path.strip_prefix(prefix)?.split('/').next()
+}
+
+fn mark(
+    statuses: &mut HashMap<String, GitFileStatus>,
+    prefix: &str,
+    path: &str,
+    status: GitFileStatus,
+) {
+    if let Some(name) = listed_name(path, prefix) {
+        let current = statuses.entry(name.to_string()).or_insert(status);
+        *current = (*current).max(status);
+    }
+}
+
+fn flatten_tree(
+    store: &mut ObjectStore,
+    tree: &ObjectId,
+    prefix: &str,
+    names: Option<&HashSet<&str>>,
+    entries: &mut HashMap<String, (u32, ObjectId)>,
+) {
+    for entry in store.read_tree(tree).unwrap_or_default() {
+        if names.is_some_and(|names| !names.contains(entry.name.as_str())) {
+            continue;
+        }
+        let path = format!("{prefix}{}", entry.name);
+        if entry.mode == TREE_MODE {
+            flatten_tree(store, &entry.id, &format!("{path}/"), None, entries);
+        } else {
+            entries.insert(path, (entry.mode, entry.id));
+        }
+    }
+}
+
+/// Files of the HEAD commit under the listed entries of `relative_dir`.
+fn head_tree_entries(
+    store: &mut ObjectStore,
+    head: &ObjectId,
+    relative_dir: &str,
+    names: &HashSet<&str>,
+) -> HashMap<String, (u32, ObjectId)> {
+    let mut entries = HashMap::new();
+    let Some(mut tree) = store.read_commit(head).map(|commit| commit.tree) else {
+        return entries;
+    };
+    for component in relative_dir
+        .split('/')
+        .filter(|component| !component.is_empty())
+    {
+        let subtree = store
+            .read_tree(&tree)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|entry| entry.mode == TREE_MODE && entry.name == component);
+        match subtree {
+            Some(subtree) => tree = subtree.id,
+            None => return entries,
+        }
+    }
+
+    let prefix = child_path(relative_dir, "");
+    flatten_tree(store, &tree, &prefix, Some(names), &mut entries);
+    entries
+}
+
+/// Line endings seen while hashing a file.
+#[derive(Default)]
+struct LineEndingStats {
+    crlf_count: u64,
+    has_lone_cr: bool,
+    has_nul: bool,
+}
+
+impl LineEndingStats {
+    fn update(&mut self, bytes: &[u8], follows_cr: bool) {
+        let mut previous_is_cr = follows_cr;
+        for byte in bytes {
+            if previous_is_cr {
+                if *byte == b'\n' {
+                    self.crlf_count += 1;
+                } else {
+                    self.has_lone_cr = true;
+                }
+            }
+            self.has_nul |= *byte == 0;
+            previous_is_cr = *byte == b'\r';
+        }
+    }
+
+    /// Whether git's text detection would take the file for binary.
+    fn looks_binary(&self) -> bool {
+        self.has_nul || self.has_lone_cr
+    }
+}
+
+fn read_chunks(path: &Path, mut on_chunk: impl FnMut(&[u8])) -> Option<()> {
+    let mut file = fs::File::open(path).ok()?;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).ok()?;
+        if read == 0 {
+            return Some(());
+        }
+        on_chunk(&buffer[..read]);
+    }
+}
+
+/// The id git would give the work tree copy of a file as is, and the line
+/// endings it has.
+fn blob_id(path: &Path, metadata: &fs::Metadata) -> Option<(ObjectId, LineEndingStats)> {
+    let mut hasher = Sha1::new();
+    let mut stats = LineEndingStats::default();
+    if metadata.is_symlink() {
+        let target = fs::read_link(path).ok()?;
+        let target = normalize_path(&target.to_string_lossy());
+        hasher.update(format!("blob {}\0", target.len()).as_bytes());
+        hasher.update(target.as_bytes());
+    } else {
+        hasher.update(format!("blob {}\0", metadata.len()).as_bytes());
+        let mut ends_with_cr = false;
+        read_chunks(path, |chunk| {
+            hasher.update(chunk);
+            stats.update(chunk, ends_with_cr);
+            ends_with_cr = chunk.last() == Some(&b'\r');
+        })?;
+        stats.has_lone_cr |= ends_with_cr;
+    }
+    let id = hasher.finalize().as_slice().try_into().ok()?;
+    Some((id, stats))
+}
+
+/// The id of a file with its CRLF line endings turned into LF.
+fn normalized_blob_id(path: &Path, metadata: &fs::Metadata, crlf_count: u64) -> Option<ObjectId> {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", metadata.len() - crlf_count).as_bytes());
+    let mut has_pending_cr = false;
+    let mut normalized = Vec::new();
+    read_chunks(path, |chunk| {
+        normalized.clear();
+        for byte in chunk {
+            if has_pending_cr && *byte != b'\n' {
+                normalized.push(b'\r');
+            }
+            has_pending_cr = *byte == b'\r';
+            if !has_pending_cr {
+                normalized.push(*byte);
+            }
+        }
+        hasher.update(&normalized);
+    })?;
+    if has_pending_cr {
+        hasher.update(b"\r");
+    }
+    hasher.finalize().as_slice().try_into().ok()
+}
+
+/// Outcome of hashing a work tree file whose stat differs from the index.
+#[derive(Clone, Copy)]
+struct HashedFile {
+    size: u64,
+    modified: Option<SystemTime>,
+    is_modified: Option<bool>,
+}
+
+type HashedFiles = Arc<Mutex<HashMap<String, HashedFile>>>;
+
+/// What is kept of a repository between listings, valid while its index
+/// and HEAD stay the same.
+struct RepositoryCache {
+    index_stamp: (u64, Option<SystemTime>),
+    head: Option<ObjectId>,
+    index: Arc<Vec<IndexEntry>>,
+    /// By path, so a file is only hashed again once it changes.
+    hashed_files: HashedFiles,
+}
+
+/// By git directory.
+static REPOSITORY_CACHE: LazyLock<Mutex<LruCache<PathBuf, RepositoryCache>>> =
+    LazyLock::new(|| {
+        Mutex::new(LruCache::new(
+            NonZeroUsize::new(REPOSITORY_CACHE_SIZE).unwrap(),
+        ))
+    });
+
+/// The index of `repository` and the files hashed against it, read again
+/// only when the index or HEAD changed since the last listing.
+fn cached_index(
+    repository: &Repository,
+    head: Option<ObjectId>,
+) -> Result<(Arc<Vec<IndexEntry>>, HashedFiles), String> {
+    let index_path = repository.git_dir.join("index");
+    let index_stamp = fs::metadata(&index_path)
+        .map(|metadata| (metadata.len(), metadata.modified().ok()))
+        .unwrap_or_default();
+
+    if let Ok(mut cache) = REPOSITORY_CACHE.lock() {
+        if let Some(cached) = cache.get(&repository.git_dir) {
+            if cached.index_stamp == index_stamp && cached.head == head {
+                return Ok((cached.index.clone(), cached.hashed_files.clone()));
+            }
+        }
+    }
+
+    let index = Arc::new(read_index(&index_path)?);
+    let hashed_files = HashedFiles::default();
+    if let Ok(mut cache) = REPOSITORY_CACHE.lock() {
+        cache.put(
+            repository.git_dir.clone(),
+            RepositoryCache {
+                index_stamp,
+                head,
+                index: index.clone(),
+                hashed_files: hashed_files.clone(),
+            },
+        );
+    }
+    Ok((index, hashed_files))
+}
+
+/// What the work tree is compared with the index by.
+struct WorkTreeComparison<'a> {
+    work_tree: &'a Path,
+    trusts_file_mode: bool,
+    /// `core.autocrlf` is `true` or `input`, which makes text files without
+    /// a `text` attribute get LF line endings when staged.
+    converts_line_endings: bool,
+    attributes: Attributes,
+    hashed_files: HashedFiles,
+}
+
+impl WorkTreeComparison<'_> {
+    /// Whether git turns CRLF into LF when staging `relative_path`, given
+    /// its line endings.
+    fn normalizes_crlf(&self, relative_path: &str, stats: &LineEndingStats) -> bool {
+        let content_attributes = self.attributes.content_attributes(relative_path);
+        match content_attributes.text {
+            Some(TextAttribute::Unset) => false,
+            Some(TextAttribute::Set) => true,
+            _ if content_attributes.has_eol => true,
+            Some(TextAttribute::Auto) => !stats.looks_binary(),
+            None => self.converts_line_endings && !stats.looks_binary(),
+        }
+    }
+
+    /// Whether the work tree copy of `entry` differs from the index, or
+    /// `None` when that can't be told without running a content filter.
+    fn is_modified(&self, entry: &IndexEntry) -> Option<bool> {
+        let path = self.work_tree.join(&entry.path);
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            return Some(true);
+        };
+        let file_type = entry.mode & FILE_TYPE_MASK;
+        let type_matches = match file_type {
+            SYMLINK_MODE => metadata.is_symlink(),
+            REGULAR_FILE_MODE => metadata.is_file(),
+            _ => true,
+        };
+        if !type_matches {
+            return Some(true);
+        }
+
+        #[cfg(unix)]
+        if self.trusts_file_mode && file_type == REGULAR_FILE_MODE {
+            use std::os::unix::fs::PermissionsExt;
+            let is_executable = metadata.permissions().mode() & 0o100 != 0;
+            if is_executable != (entry.mode & 0o100 != 0) {
+                return Some(true);
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = self.trusts_file_mode;
+
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok());
+        if let Some(modified) = modified {
+            let seconds_match = modified.as_secs() as u32 == entry.mtime_seconds;
+            let nanoseconds_match =
+                entry.mtime_nanoseconds == 0 || modified.subsec_nanos() == entry.mtime_nanoseconds;
+            if metadata.len() as u32 == entry.size && seconds_match && nanoseconds_match {
+                return Some(false);
+            }
+        }
+
+        let size = metadata.len();
+        let modified = metadata.modified().ok();
+        let hashed_file = self
+            .hashed_files
+            .lock()
+            .ok()
+            .and_then(|hashed_files| hashed_files.get(&entry.path).copied())
+            .filter(|hashed_file| hashed_file.size == size && hashed_file.modified == modified);
+        if let Some(hashed_file) = hashed_file {
+            return hashed_file.is_modified;
+        }
+
+        let is_modified = self.compare_content(&path, &metadata, entry);
+        if let Ok(mut hashed_files) = self.hashed_files.lock() {
+            hashed_files.insert(
+                entry.path.clone(),
+                HashedFile {
+                    size,
+                    modified,
+                    is_modified,
+                },
+            );
+        }
+        is_modified
+    }
+
+    fn compare_content(
+        &self,
+        path: &Path,
+        metadata: &fs::Metadata,
+        entry: &IndexEntry,
+    ) -> Option<bool> {
+        let Some((id, stats)) = blob_id(path, metadata) else {
+            return Some(true);
+        };
+        if id == entry.id {
+            return Some(false);
+        }
+        if metadata.is_file()
+            && stats.crlf_count > 0
+            && self.normalizes_crlf(&entry.path, &stats)
+            && normalized_blob_id(path, metadata, stats.crlf_count) == Some(entry.id)
+        {
+            return Some(false);
+        }
+        if metadata.is_file() && self.attributes.content_attributes(&entry.path).is_filtered {
+            return None;
+        }
+        Some(true)
+    }
+}
+
+/// Index paths under the listed entries, sorted, for prefix lookups.
+struct TrackedPaths<'a> {
+    paths: Vec<&'a str>,
+}
+
+impl TrackedPaths<'_> {
+    fn contains(&self, path: &str) -> bool {
+        self.paths.binary_search(&path).is_ok()
+    }
+
+    fn contains_under(&self, dir: &str) -> bool {
+        let prefix = format!("{dir}/");
+        let start = self.paths.partition_point(|path| *path < prefix.as_str());
+        self.paths
+            .get(start)
+            .is_some_and(|path| path.starts_with(&prefix))
+    }
+}
+
+/// Whether `dir` holds a file that is neither tracked nor excluded. Empty
+/// directories do not count, as git does not list them.
+fn contains_untracked(
+    dir: &Path,
+    relative_dir: &str,
+    excludes: &ExcludeStack,
+    tracked: &TrackedPaths,
+) -> bool {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return false;
+    };
+    for entry in read_dir.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name == ".git" {
+            continue;
+        }
+        let relative_path = child_path(relative_dir, &name);
+        let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+        if tracked.contains(&relative_path) || excludes.is_excluded(&relative_path, is_dir) {
+            continue;
+        }
+        if !is_dir {
+            return true;
+        }
+
+        let path = entry.path();
+        let is_nested_repository =
+            !tracked.contains_under(&relative_path) && path.join(".git").exists();
+        if is_nested_repository
+            || contains_untracked(
+                &path,
+                &relative_path,
+                &excludes.child(&path, &relative_path),
+                tracked,
+            )
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/// Untracked or ignored status of a listed entry that has no tracked
+/// changes of higher precedence.
+fn untracked_status(
+    path: &Path,
+    relative_path: &str,
+    excludes: &ExcludeStack,
+    tracked: &TrackedPaths,
+) -> Option<GitFileStatus> {
+    let is_dir = fs::symlink_metadata(path).ok()?.is_dir();
+    if tracked.contains(relative_path) {
+        return None;
+    }
+    let has_tracked_contents = is_dir && tracked.contains_under(relative_path);
+    if excludes.is_excluded(relative_path, is_dir) {
+        // Files tracked before being excluded keep their own status.
+        return (!has_tracked_contents).then_some(GitFileStatus::Ignored);
+    }
+    if !is_dir || (!has_tracked_contents && path.join(".git").exists()) {
+        return Some(GitFileStatus::Untracked);
+    }
+
+    contains_untracked(
+        path,
+        relative_path,
+        &excludes.child(path, relative_path),
+        tracked,
+    )
+    .then_some(GitFileStatus::Untracked)
+}
+
+fn repository_status(
+    repository: &Repository,
+    store: &mut ObjectStore,
+    head_id: Option<ObjectId>,
+    branch: Option<String>,
+) -> GitRepositoryStatus {
+    let upstream = branch
+        .as_deref()
+        .and_then(|branch| repository.upstream(branch));
+    let counts = head_id
+        .zip(
+            upstream
+                .as_ref()
+                .and_then(|(upstream_ref, _)| repository.resolve_ref(upstream_ref)),
+        )
+        .and_then(|(local, upstream)| history::ahead_behind(store, local, upstream));
+
+    GitRepositoryStatus {
+        root: normalize_path(&repository.work_tree.to_string_lossy()),
+        branch,
+        head: head_id.as_ref().map(object_id_to_hex),
+        upstream: upstream.map(|(_, short_name)| short_name),
+        ahead: counts.map(|(ahead, _)| ahead),
+        behind: counts.map(|(_, behind)| behind),
+    }
+}
+
+/// Git status of the entries `names` of `dir`, or `None` when `dir` is not
+/// inside a git work tree.
+pub fn read_dir_git_status(dir: &Path, names: &[&str]) -> Option<DirGitStatus> {
+    let repository = Repository::discover(dir)?;
+    if !repository.uses_sha1() {
+        return None;
+    }
+    let relative_dir = dir
+        .strip_prefix(&repository.work_tree)
+        .ok()?
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let prefix = child_path(&relative_dir, "");
+    let names: HashSet<&str> = names
+        .iter()
+        .copied()
+        .filter(|name| *name != ".git")
+        .collect();
+
+    let head = repository.head();
+    let (index, hashed_files) = cached_index(&repository, head.id)
+        .map_err(|error| log::warn!("{error}"))
+        .ok()?;
+    let listed_index: Vec<&IndexEntry> = index
+        .iter()
+        .filter(|entry| listed_name(&entry.path, &prefix).is_some_and(|name| names.contains(name)))
+        .collect();
+    let mut store = ObjectStore::open(&repository.common_dir.join("objects"));
+    let head_entries = head
+        .id
+        .map(|head_id| head_tree_entries(&mut store, &head_id, &relative_dir, &names))
+        .unwrap_or_default();
+
+    let mut statuses = HashMap::new();
+
+    let mut deleted_from_index: HashSet<&str> = head_entries.keys().map(String::as_str).collect();
+    for entry in &listed_index {
+        deleted_from_index.remove(entry.path.as_str());
+        if entry.stage > 0 {
+            mark(
+                &mut statuses,
+                &prefix,
+                &entry.path,
+                GitFileStatus::Conflicted,
+            );
+        } else if head_entries.get(&entry.path) != Some(&(entry.mode, entry.id)) {
+            mark(&mut statuses, &prefix, &entry.path, GitFileStatus::Staged);
+        }
+    }
+    for path in deleted_from_index {
+        mark(&mut statuses, &prefix, path, GitFileStatus::Staged);
+    }
+
+    let comparison = WorkTreeComparison {
+        work_tree: &repository.work_tree,
+        trusts_file_mode: repository
+            .config
+            .get_bool("core", "filemode")
+            .unwrap_or(true),
+        converts_line_endings: repository.config.get_bool("core", "autocrlf") == Some(true)
+            || repository
+                .config
+                .get("core", None, "autocrlf")
+                .is_some_and(|autocrlf| autocrlf.eq_ignore_ascii_case("input")),
+        attributes: Attributes::for_repository(&repository),
+        hashed_files,
+    };
+    // A listed entry is modified once any file under it is, so the rest of
+    // its files are not compared, and entries already conflicted are skipped.
+    let mut comparable_by_name: HashMap<&str, Vec<&IndexEntry>> = HashMap::new();
+    for entry in &listed_index {
+        let Some(name) = listed_name(&entry.path, &prefix) else {
+            continue;
+        };
+        if entry.stage == 0
+            && !entry.skips_worktree
+            && entry.mode & FILE_TYPE_MASK != GITLINK_MODE
+            && statuses
+                .get(name)
+                .is_none_or(|status| *status < GitFileStatus::Modified)
+        {
+            comparable_by_name.entry(name).or_default().push(entry);
+        }
+    }
+    let modified: Vec<&str> = comparable_by_name
+        .into_par_iter()
+        .filter(|(_, entries)| {
+            entries
+                .par_iter()
+                .any(|entry| comparison.is_modified(entry) == Some(true))
+        })
+        .map(|(name, _)| name)
+        .collect();
+    for name in modified {
+        mark(&mut statuses, "", name, GitFileStatus::Modified);
+    }
+
+    let mut tracked = TrackedPaths {
+        paths: listed_index
+            .iter()
+            .map(|entry| entry.path.as_str())
+            .collect(),
+    };
+    tracked.paths.sort_unstable();
+    tracked.paths.dedup();
+    let mut excludes = ExcludeStack::for_repository(&repository).child(&repository.work_tree, "");
+    let mut walked_dir = repository.work_tree.clone();
+    let mut walked_relative_dir = String::new();
+    for component in relative_dir
+        .split('/')
+        .filter(|component| !component.is_empty())
+    {
+        walked_dir.push(component);
+        walked_relative_dir = child_path(&walked_relative_dir, component);
+        excludes = excludes.child(&walked_dir, &walked_relative_dir);
+    }
+
+    let untracked: Vec<(&str, GitFileStatus)> = names
+        .par_iter()
+        .filter(|name| {
+            statuses
+                .get(**name)
+                .is_none_or(|status| *status < GitFileStatus::Staged)
+        })
+        .filter_map(|name| {
+            let status = untracked_status(
+                &dir.join(name),
+                &child_path(&relative_dir, name),
+                &excludes,
+                &tracked,
+            )?;
+            Some((*name, status))
+        })
+        .collect();
+    for (name, status) in untracked {
+        mark(&mut statuses, "", name, status);
+    }
+
+    Some(DirGitStatus {
+        repository: repository_status(&repository, &mut store, head.id, head.branch),
+        entries: statuses,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn write_object(git_dir: &Path, kind: &str, content: &[u8]) -> ObjectId {
+        let mut data = format!("{kind} {}\0", content.len()).into_bytes();
+        data.extend_from_slice(content);
+        let id: ObjectId = Sha1::digest(&data).as_slice().try_into().unwrap();
+        let hex = object_id_to_hex(&id);
+        let dir = git_dir.join("objects").join(&hex[..2]);
+        fs::create_dir_all(&dir).unwrap();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data).unwrap();
+        fs::write(dir.join(&hex[2..]), encoder.finish().unwrap()).unwrap();
+        id
+    }
+
+    /// Entries must be sorted by name, as git sorts them.
+    fn write_tree(git_dir: &Path, entries: &[(&str, &str, ObjectId)]) -> ObjectId {
+        let mut content = Vec::new();
+        for (mode, name, id) in entries {
+            content.extend_from_slice(format!("{mode} {name}\0").as_bytes());
+            content.extend_from_slice(id);
+        }
+        write_object(git_dir, "tree", &content)
+    }
+
+    fn write_commit(git_dir: &Path, tree: ObjectId, parents: &[ObjectId], time: i64) -> ObjectId {
+        let mut content = format!("tree {}\n", object_id_to_hex(&tree));
+        for parent in parents {
+            content.push_str(&format!("parent {}\n", object_id_to_hex(parent)));
+        }
+        content.push_str(&format!(
+            "author A <a@example.com> {time} +0000\ncommitter A <a@example.com> {time} +0000\n\nCommit\n"
+        ));
+        write_object(git_dir, "commit", content.as_bytes())
+    }
+
+    /// Entries must be sorted by path. Sizes come from `contents`, and zero
+    /// modification times make every file get hashed.
+    fn write_index(git_dir: &Path, entries: &[(&str, &[u8], u16)]) {
+        let mut data = b"DIRC\0\0\0\x02".to_vec();
+        data.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        for (path, content, stage) in entries {
+            let id = write_object(git_dir, "blob", content);
+            let mut entry = vec![0; 62];
+            entry[24..28].copy_from_slice(&0o100644u32.to_be_bytes());
+            entry[36..40].copy_from_slice(&(content.len() as u32).to_be_bytes());
+            entry[40..60].copy_from_slice(&id);
+            entry[60..62].copy_from_slice(&((stage << 12) | path.len() as u16).to_be_bytes());
+            entry.extend_from_slice(path.as_bytes());
+            entry.resize((entry.len() + 8) & !7, 0);
+            data.extend(entry);
+        }
+        fs::write(git_dir.join("index"), data).unwrap();
+    }
+
+    #[test]
+    fn entries_get_staged_modified_untracked_ignored_and_conflicted_status() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let root = temp_dir.path();
+        let git_dir = root.join(".git");
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        fs::create_dir_all(git_dir.join("refs/remotes/origin")).unwrap();
+
+        let blob = |content: &[u8]| write_object(&git_dir, "blob", content);
+        let docs = write_tree(&git_dir, &[("100644", "guide.md", blob(b"guide"))]);
+        let src = write_tree(
+            &git_dir,
+            &[
+                ("100644", "lib.rs", blob(b"lib")),
+                ("100644", "old.rs", blob(b"old")),
+            ],
+        );
+        let tree = write_tree(
+            &git_dir,
+            &[
+                ("100644", "README.md", blob(b"readme")),
+                ("40000", "docs", docs),
+                ("40000", "src", src),
+            ],
+        );
+        let base = write_commit(&git_dir, tree, &[], 1);
+        let local = write_commit(&git_dir, tree, &[base], 2);
+        let upstream = write_commit(&git_dir, tree, &[base], 3);
+        let upstream = write_commit(&git_dir, tree, &[upstream], 4);
+
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(
+            git_dir.join("refs/heads/main"),
+            object_id_to_hex(&local) + "\n",
+        )
+        .unwrap();
+        fs::write(
+            git_dir.join("refs/remotes/origin/main"),
+            object_id_to_hex(&upstream) + "\n",
+        )
+        .unwrap();
+        fs::write(
+            git_dir.join("config"),
+            "[branch \"main\"]\n\tremote = origin\n\tmerge = refs/heads/main\n",
+        )
+        .unwrap();
+        write_index(
+            &git_dir,
+            &[
+                ("README.md", b"readme", 0),
+                ("conflict.txt", b"ours", 2),
+                ("conflict.txt", b"theirs", 3),
+                ("docs/guide.md", b"guide", 0),
+                ("src/lib.rs", b"lib v2", 0),
+            ],
+        );
+
+        for (path, content) in [
+            ("README.md", "readme"),
+            ("conflict.txt", "<<<<<<<"),
+            ("docs/guide.md", "guide, edited"),
+            ("src/lib.rs", "lib v2"),
+            ("notes.txt", "notes"),
+            ("debug.log", "log"),
+            ("build/out.bin", "out"),
+            ("extra/new.txt", "new"),
+            ("cache/data.log", "log"),
+            (".gitignore", "*.log\nbuild/\n"),
+        ] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        fs::create_dir_all(root.join("empty")).unwrap();
+
+        let names: Vec<String> = fs::read_dir(root)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let status = read_dir_git_status(root, &names).expect("read status");
+
+        let expected = HashMap::from([
+            ("src", GitFileStatus::Staged),
+            ("docs", GitFileStatus::Modified),
+            ("conflict.txt", GitFileStatus::Conflicted),
+            ("notes.txt", GitFileStatus::Untracked),
+            ("extra", GitFileStatus::Untracked),
+            (".gitignore", GitFileStatus::Untracked),
+            ("debug.log", GitFileStatus::Ignored),
+            ("build", GitFileStatus::Ignored),
+        ]);
+        let expected: HashMap<String, GitFileStatus> = expected
+            .into_iter()
+            .map(|(name, status)| (name.to_string(), status))
+            .collect();
+        assert_eq!(status.entries, expected);
+
+        assert_eq!(status.repository.branch.as_deref(), Some("main"));
+        assert_eq!(status.repository.head, Some(object_id_to_hex(&local)));
+        assert_eq!(status.repository.upstream.as_deref(), Some("origin/main"));
+        assert_eq!(
+            (status.repository.ahead, status.repository.behind),
+            (Some(1), Some(2))
+        );
+
+        let src_status = read_dir_git_status(&root.join("src"), &["lib.rs"]).expect("read src");
+        assert_eq!(
+            src_status.entries.get("lib.rs"),
+            Some(&GitFileStatus::Staged)
+        );
+        assert!(read_dir_git_status(&git_dir, &["HEAD"]).is_none());
+    }
+
+    #[test]
+    fn converted_line_endings_are_clean_and_filtered_files_get_no_status() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let root = temp_dir.path();
+        let git_dir = root.join(".git");
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        fs::write(git_dir.join("config"), "[core]\n\tautocrlf = true\n").unwrap();
+
+        let attributes = "*.psd filter=lfs -text\n*.bin -text\n";
+        let files: [(&str, &[u8], &str); 5] = [
+            (".gitattributes", attributes.as_bytes(), attributes),
+            ("data.bin", b"a\nb\n", "a\r\nb\r\n"),
+            ("edited.txt", b"one\n", "two\r\n"),
+            (
+                "image.psd",
+                b"version https://git-lfs.github.com/spec/v1\n",
+                "image",
+            ),
+            ("notes.txt", b"first\nsecond\n", "first\r\nsecond\r\n"),
+        ];
+        let tree_entries: Vec<(&str, &str, ObjectId)> = files
+            .iter()
+            .map(|(path, staged, _)| ("100644", *path, write_object(&git_dir, "blob", staged)))
+            .collect();
+        let commit = write_commit(&git_dir, write_tree(&git_dir, &tree_entries), &[], 1);
+        fs::write(git_dir.join("HEAD"), object_id_to_hex(&commit) + "\n").unwrap();
+        let index_entries: Vec<(&str, &[u8], u16)> = files
+            .iter()
+            .map(|(path, staged, _)| (*path, *staged, 0))
+            .collect();
+        write_index(&git_dir, &index_entries);
+        for (path, _, content) in files {
+            fs::write(root.join(path), content).unwrap();
+        }
+
+        let names: Vec<&str> = files.iter().map(|(path, _, _)| *path).collect();
+        let status = read_dir_git_status(root, &names).expect("read status");
+
+        let expected = HashMap::from([
+            ("data.bin".to_string(), GitFileStatus::Modified),
+            ("edited.txt".to_string(), GitFileStatus::Modified),
+        ]);
+        assert_eq!(status.entries, expected);
+
+        // Hashed files are cached by size and modification time.
+        let edited = fs::File::create(root.join("edited.txt")).unwrap();
+        edited.set_len(0).unwrap();
+        (&edited).write_all(b"one\r\n").unwrap();
+        edited
+            .set_modified(SystemTime::now() + std::time::Duration::from_secs(60))
+            .unwrap();
+        drop(edited);
+        let status = read_dir_git_status(root, &names).expect("read status again");
+        assert_eq!(status.entries.get("edited.txt"), None);
+    }
+}
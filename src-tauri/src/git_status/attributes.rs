@@ -0,0 +1,275 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! The `.gitattributes` settings that change how git turns a work tree file
+//! into a blob: line ending conversion and content filters. Read from the
+//! `.gitattributes` files of the work tree and `info/attributes`.
+
+use super::ignore::compile_pattern;
+use super::repository::Repository;
+use globset::GlobMatcher;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AttributeValue {
+    Set,
+    Unset,
+    Value(String),
+    /// Reset with `!`, as if no rule had mentioned it.
+    Unspecified,
+}
+
+struct AttributeRule {
+    matcher: GlobMatcher,
+    assignments: Vec<(String, AttributeValue)>,
+}
+
+/// Rules of one attributes file, matched against paths relative to `base`,
+/// which is `""` or ends with `/`.
+struct AttributeFile {
+    base: String,
+    rules: Vec<AttributeRule>,
+}
+
+impl AttributeFile {
+    fn load(path: PathBuf, base: String) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+        let rules = parse_attribute_rules(&content);
+        (!rules.is_empty()).then_some(Self { base, rules })
+    }
+
+    /// The value the last matching rule gives `name`.
+    fn value(&self, relative_path: &str, name: &str) -> Option<&AttributeValue> {
+        let relative_path = relative_path.strip_prefix(self.base.as_str())?;
+        self.rules
+            .iter()
+            .rev()
+            .filter(|rule| rule.matcher.is_match(relative_path))
+            .find_map(|rule| {
+                rule.assignments
+                    .iter()
+                    .rev()
+                    .find(|(assigned, _)| assigned == name)
+                    .map(|(_, value)| value)
+            })
+    }
+}
+
+fn parse_assignment(token: &str, assignments: &mut Vec<(String, AttributeValue)>) {
+    let (name, value) = if let Some(name) = token.strip_prefix('-') {
+        (name, AttributeValue::Unset)
+    } else if let Some(name) = token.strip_prefix('!') {
+        (name, AttributeValue::Unspecified)
+    } else if let Some((name, value)) = token.split_once('=') {
+        (name, AttributeValue::Value(value.to_string()))
+    } else {
+        (token, AttributeValue::Set)
+    };
+
+    // `binary` is a macro, and `crlf` the older spelling of `text`.
+    match (name, &value) {
+        ("binary", AttributeValue::Set) => {
+            assignments.push(("text".to_string(), AttributeValue::Unset));
+        }
+        ("crlf", AttributeValue::Value(value)) if value == "input" => {
+            assignments.push(("eol".to_string(), AttributeValue::Value("lf".to_string())));
+        }
+        ("crlf", _) => assignments.push(("text".to_string(), value)),
+        _ => assignments.push((name.to_string(), value)),
+    }
+}
+
+fn parse_attribute_rules(content: &str) -> Vec<AttributeRule> {
+    let mut rules = Vec::new();
+    for line in content.lines() {
+        let mut tokens = line.split_whitespace();
+        let Some(pattern) = tokens.next() else {
+            continue;
+        };
+        // Negative patterns are forbidden, and patterns for directories
+        // never apply to files.
+        if pattern.starts_with('#') || pattern.starts_with('!') || pattern.ends_with('/') {
+            continue;
+        }
+        let Some(matcher) = compile_pattern(pattern) else {
+            continue;
+        };
+
+        let mut assignments = Vec::new();
+        for token in tokens {
+            parse_assignment(token, &mut assignments);
+        }
+        if !assignments.is_empty() {
+            rules.push(AttributeRule {
+                matcher,
+                assignments,
+            });
+        }
+    }
+    rules
+}
+
+/// How the `text` attribute is set for a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum TextAttribute {
+    Set,
+    Unset,
+    Auto,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct ContentAttributes {
+    /// `None` when unspecified, which leaves it to `core.autocrlf`.
+    pub text: Option<TextAttribute>,
+    /// An `eol` is set, which makes the path text.
+    pub has_eol: bool,
+    /// A `filter`, `ident` or `working-tree-encoding` is set, which changes
+    /// the content in ways not reproduced here.
+    pub is_filtered: bool,
+}
+
+/// Attributes files of a repository, loaded as paths under them are asked
+/// about.
+pub(super) struct Attributes {
+    work_tree: PathBuf,
+    info_file: Option<Arc<AttributeFile>>,
+    dir_files: Mutex<HashMap<String, Option<Arc<AttributeFile>>>>,
+}
+
+impl Attributes {
+    pub(super) fn for_repository(repository: &Repository) -> Self {
+        let info_path = repository.common_dir.join("info").join("attributes");
+        Self {
+            work_tree: repository.work_tree.clone(),
+            info_file: AttributeFile::load(info_path, String::new()).map(Arc::new),
+            dir_files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn dir_file(&self, relative_dir: &str) -> Option<Arc<AttributeFile>> {
+        let mut dir_files = self.dir_files.lock().ok()?;
+        dir_files
+            .entry(relative_dir.to_string())
+            .or_insert_with(|| {
+                let (dir, base) = if relative_dir.is_empty() {
+                    (self.work_tree.clone(), String::new())
+                } else {
+                    (
+                        self.work_tree.join(relative_dir),
+                        format!("{relative_dir}/"),
+                    )
+                };
+                AttributeFile::load(dir.join(".gitattributes"), base).map(Arc::new)
+            })
+            .clone()
+    }
+
+    /// Files that apply to `relative_path`, lowest precedence first: the
+    /// `.gitattributes` files from the root down, then `info/attributes`.
+    fn files_for(&self, relative_path: &str) -> Vec<Arc<AttributeFile>> {
+        let mut files = Vec::new();
+        let mut relative_dir = String::new();
+        files.extend(self.dir_file(""));
+        let parent_components = relative_path
+            .rsplit_once('/')
+            .map(|(parent, _)| parent)
+            .unwrap_or_default();
+        for component in parent_components
+            .split('/')
+            .filter(|component| !component.is_empty())
+        {
+            if !relative_dir.is_empty() {
+                relative_dir.push('/');
+            }
+            relative_dir.push_str(component);
+            files.extend(self.dir_file(&relative_dir));
+        }
+        files.extend(self.info_file.clone());
+        files
+    }
+
+    pub(super) fn content_attributes(&self, relative_path: &str) -> ContentAttributes {
+        let files = self.files_for(relative_path);
+        let value = |name: &str| {
+            files
+                .iter()
+                .rev()
+                .find_map(|file| file.value(relative_path, name))
+                .filter(|value| **value != AttributeValue::Unspecified)
+                .cloned()
+        };
+
+        let text = match value("text") {
+            Some(AttributeValue::Set) => Some(TextAttribute::Set),
+            Some(AttributeValue::Unset) => Some(TextAttribute::Unset),
+            Some(AttributeValue::Value(value)) if value == "auto" => Some(TextAttribute::Auto),
+            _ => None,
+        };
+        let is_set = |value: Option<AttributeValue>| {
+            matches!(value, Some(AttributeValue::Set | AttributeValue::Value(_)))
+        };
+
+        ContentAttributes {
+            text,
+            has_eol: matches!(value("eol"), Some(AttributeValue::Value(_))),
+            is_filtered: is_set(value("filter"))
+                || is_set(value("ident"))
+                || is_set(value("working-tree-encoding")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn deeper_files_and_info_attributes_take_precedence() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let work_tree = temp_dir.path();
+        let git_dir = work_tree.join(".git");
+        fs::create_dir_all(git_dir.join("info")).unwrap();
+        fs::create_dir_all(work_tree.join("assets/raw")).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(
+            work_tree.join(".gitattributes"),
+            "* text=auto\n*.psd filter=lfs diff=lfs merge=lfs -text\n*.bat eol=crlf\n",
+        )
+        .unwrap();
+        fs::write(
+            work_tree.join("assets/.gitattributes"),
+            "*.txt binary\nraw/*.psd !filter\n",
+        )
+        .unwrap();
+        fs::write(git_dir.join("info/attributes"), "notes.txt -text\n").unwrap();
+        let repository = Repository::discover(work_tree).expect("discover");
+        let attributes = Attributes::for_repository(&repository);
+
+        let readme = attributes.content_attributes("README.md");
+        assert_eq!(readme.text, Some(TextAttribute::Auto));
+        assert!(!readme.is_filtered);
+
+        let image = attributes.content_attributes("art/cover.psd");
+        assert_eq!(image.text, Some(TextAttribute::Unset));
+        assert!(image.is_filtered);
+        assert!(
+            !attributes
+                .content_attributes("assets/raw/cover.psd")
+                .is_filtered
+        );
+
+        assert!(attributes.content_attributes("tools/run.bat").has_eol);
+        assert_eq!(
+            attributes.content_attributes("assets/data.txt").text,
+            Some(TextAttribute::Unset)
+        );
+        assert_eq!(
+            attributes.content_attributes("notes.txt").text,
+            Some(TextAttribute::Unset)
+        );
+    }
+}
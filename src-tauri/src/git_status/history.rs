@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::objects::{ObjectId, ObjectStore};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Commits read before the counts are given up on.
+const MAX_WALKED_COMMITS: usize = 100_000;
+
+const LOCAL: u8 = 0b01;
+const UPSTREAM: u8 = 0b10;
+const BOTH: u8 = LOCAL | UPSTREAM;
+
+/// Commits reachable from `local` but not `upstream`, and the reverse.
+///
+/// Walks both histories newest first, like `git rev-list --count
+/// --left-right`, and stops once every commit left to visit is reachable
+/// from both sides.
+pub(super) fn ahead_behind(
+    store: &mut ObjectStore,
+    local: ObjectId,
+    upstream: ObjectId,
+) -> Option<(u32, u32)> {
+    if local == upstream {
+        return Some((0, 0));
+    }
+
+    let mut flags: HashMap<ObjectId, u8> = HashMap::new();
+    let mut times: HashMap<ObjectId, i64> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    // Each commit is queued at most once at a time, and the walk can stop
+    // once none of the queued ones is reachable from only one side.
+    let mut queued: HashSet<ObjectId> = HashSet::new();
+    let mut queued_one_sided = 0usize;
+
+    for (id, side) in [(local, LOCAL), (upstream, UPSTREAM)] {
+        let time = store.read_commit(&id)?.time;
+        times.insert(id, time);
+        flags.insert(id, side);
+        queue.push((time, id));
+        queued.insert(id);
+        queued_one_sided += 1;
+    }
+
+    let mut walked = 0;
+    while queued_one_sided > 0 {
+        let Some((_, id)) = queue.pop() else {
+            break;
+        };
+        queued.remove(&id);
+        let side = flags[&id];
+        if side != BOTH {
+            queued_one_sided -= 1;
+        }
+        walked += 1;
+        if walked > MAX_WALKED_COMMITS {
+            return None;
+        }
+
+        for parent in store.read_commit(&id)?.parents {
+            let parent_flags = flags.entry(parent).or_insert(0);
+            if *parent_flags | side == *parent_flags {
+                continue;
+            }
+            let was_one_sided = *parent_flags != BOTH;
+            *parent_flags |= side;
+            let is_one_sided = *parent_flags != BOTH;
+
+            if queued.contains(&parent) {
+                if was_one_sided && !is_one_sided {
+                    queued_one_sided -= 1;
+                }
+                continue;
+            }
+
+            let time = match times.get(&parent) {
+                Some(time) => *time,
+                None => {
+                    let time = store.read_commit(&parent)?.time;
+                    times.insert(parent, time);
+                    time
+                }
+            };
+            queue.push((time, parent));
+            queued.insert(parent);
+            if is_one_sided {
+                queued_one_sided += 1;
+            }
+        }
+    }
+
+    let count = |side| flags.values().filter(|flags| **flags == side).count() as u32;
+    Some((count(LOCAL), count(UPSTREAM)))
+}
@@ -0,0 +1,234 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Git's exclude rules: `.gitignore` files, `info/exclude` and the global
+//! excludes file. Unlike global search's ignore files, only the ones git
+//! reads count, and their precedence is git's.
+
+use super::repository::Repository;
+use globset::{GlobBuilder, GlobMatcher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+struct ExcludeRule {
+    matcher: GlobMatcher,
+    is_negated: bool,
+    is_dir_only: bool,
+}
+
+/// Rules of one exclude file, matched against paths relative to `base`,
+/// which is `""` or ends with `/`.
+struct ExcludeFile {
+    base: String,
+    rules: Vec<ExcludeRule>,
+}
+
+impl ExcludeFile {
+    fn load(path: &Path, base: String) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+        let rules = parse_exclude_rules(&content);
+        (!rules.is_empty()).then_some(Self { base, rules })
+    }
+
+    /// `Some(true)` when the last matching rule excludes the path,
+    /// `Some(false)` when it re-includes it with `!`.
+    fn matched(&self, relative_path: &str, is_dir: bool) -> Option<bool> {
+        let relative_path = relative_path.strip_prefix(self.base.as_str())?;
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.is_dir_only) && rule.matcher.is_match(relative_path))
+            .map(|rule| !rule.is_negated)
+    }
+}
+
+fn parse_exclude_rules(content: &str) -> Vec<ExcludeRule> {
+    let mut rules = Vec::new();
+    for line in content.lines() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (is_negated, pattern) = match line.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (is_dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let Some(matcher) = compile_pattern(pattern) else {
+            continue;
+        };
+
+        rules.push(ExcludeRule {
+            matcher,
+            is_negated,
+            is_dir_only,
+        });
+    }
+    rules
+}
+
+/// Compiles a gitignore-style pattern, matched against paths relative to the
+/// directory of the file it is in. Also used for `.gitattributes` patterns.
+pub(super) fn compile_pattern(pattern: &str) -> Option<GlobMatcher> {
+    if pattern.is_empty() {
+        return None;
+    }
+
+    // A pattern with a slash is anchored to the file's directory; a bare
+    // name matches at any depth below it.
+    let glob = match pattern.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None if pattern.contains('/') => pattern.to_string(),
+        None => format!("**/{pattern}"),
+    };
+    GlobBuilder::new(&glob)
+        .literal_separator(true)
+        .case_insensitive(cfg!(windows))
+        .build()
+        .ok()
+        .map(|glob| glob.compile_matcher())
+}
+
+/// `core.excludesFile`, or git's default of `$XDG_CONFIG_HOME/git/ignore`.
+fn global_excludes_file(repository: &Repository) -> Option<PathBuf> {
+    if let Some(path) = repository.config.get("core", None, "excludesfile") {
+        return match path.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir().map(|home| home.join(rest)),
+            None => Some(PathBuf::from(path)),
+        };
+    }
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|config_home| !config_home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+        .map(|config_home| config_home.join("git").join("ignore"))
+}
+
+/// Exclude rules in effect for the entries of one directory. Deeper files
+/// take precedence over shallower ones, and `.gitignore` files over
+/// `info/exclude` and the global file.
+#[derive(Clone)]
+pub(super) struct ExcludeStack {
+    files: Vec<Arc<ExcludeFile>>,
+    /// Set once a directory on the way down is excluded, which excludes
+    /// everything below it.
+    is_excluded: bool,
+}
+
+impl ExcludeStack {
+    /// Rules for the work tree root, before any `.gitignore` is read.
+    pub(super) fn for_repository(repository: &Repository) -> Self {
+        let files = [
+            global_excludes_file(repository),
+            Some(repository.common_dir.join("info").join("exclude")),
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(|path| ExcludeFile::load(&path, String::new()))
+        .map(Arc::new)
+        .collect();
+
+        Self {
+            files,
+            is_excluded: false,
+        }
+    }
+
+    /// Rules for the entries of `dir`, a child of the directory this stack
+    /// is for, at `relative_dir` in the work tree (`""` for the root).
+    pub(super) fn child(&self, dir: &Path, relative_dir: &str) -> Self {
+        let mut stack = self.clone();
+        if relative_dir.is_empty() || !self.is_excluded(relative_dir, true) {
+            let base = if relative_dir.is_empty() {
+                String::new()
+            } else {
+                format!("{relative_dir}/")
+            };
+            if let Some(file) = ExcludeFile::load(&dir.join(".gitignore"), base) {
+                stack.files.push(Arc::new(file));
+            }
+        } else {
+            stack.is_excluded = true;
+        }
+        stack
+    }
+
+    pub(super) fn is_excluded(&self, relative_path: &str, is_dir: bool) -> bool {
+        self.is_excluded
+            || self
+                .files
+                .iter()
+                .rev()
+                .find_map(|file| file.matched(relative_path, is_dir))
+                .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deeper_gitignore_files_override_shallower_ones() {
+        let root = ExcludeFile {
+            base: String::new(),
+            rules: parse_exclude_rules("*.log\nbuild/\n/dist\n"),
+        };
+        let nested = ExcludeFile {
+            base: "app/".to_string(),
+            rules: parse_exclude_rules("!keep.log\n"),
+        };
+        let stack = ExcludeStack {
+            files: vec![Arc::new(root), Arc::new(nested)],
+            is_excluded: false,
+        };
+
+        assert!(stack.is_excluded("debug.log", false));
+        assert!(stack.is_excluded("app/debug.log", false));
+        assert!(!stack.is_excluded("app/keep.log", false));
+        assert!(stack.is_excluded("app/build", true));
+        assert!(!stack.is_excluded("app/build", false));
+        assert!(stack.is_excluded("dist", true));
+        assert!(!stack.is_excluded("app/dist", true));
+    }
+
+    #[test]
+    fn nested_gitignore_files_override_info_exclude_but_not_excluded_parents() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let work_tree = temp_dir.path();
+        let git_dir = work_tree.join(".git");
+        std::fs::create_dir_all(git_dir.join("info")).unwrap();
+        std::fs::create_dir_all(work_tree.join("app/logs")).unwrap();
+        std::fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        let global_excludes = work_tree.join("global-ignore");
+        std::fs::write(
+            git_dir.join("config"),
+            format!("[core]\n\texcludesFile = {}\n", global_excludes.display()),
+        )
+        .unwrap();
+        std::fs::write(&global_excludes, "*.bak\n*.tmp\n").unwrap();
+        std::fs::write(git_dir.join("info/exclude"), "!keep.bak\n").unwrap();
+        std::fs::write(work_tree.join(".gitignore"), "!*.tmp\n").unwrap();
+        std::fs::write(work_tree.join("app/.gitignore"), "*.tmp\nlogs/\n").unwrap();
+        std::fs::write(work_tree.join("app/logs/.gitignore"), "!*\n").unwrap();
+        let repository = Repository::discover(work_tree).expect("discover");
+
+        let root = ExcludeStack::for_repository(&repository).child(work_tree, "");
+        assert!(root.is_excluded("old.bak", false));
+        assert!(!root.is_excluded("keep.bak", false));
+        assert!(!root.is_excluded("draft.tmp", false));
+
+        let app_dir = work_tree.join("app");
+        let app = root.child(&app_dir, "app");
+        assert!(app.is_excluded("app/draft.tmp", false));
+        assert!(app.is_excluded("app/logs", true));
+
+        let logs = app.child(&app_dir.join("logs"), "app/logs");
+        assert!(logs.is_excluded("app/logs/today.log", false));
+    }
+}
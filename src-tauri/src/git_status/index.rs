@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Reads the entries of the index (the staging area), versions 2 to 4.
+
+use super::objects::{read_offset_varint, ObjectId};
+use std::fs;
+use std::path::Path;
+
+const ASSUME_VALID_FLAG: u16 = 0x8000;
+const EXTENDED_FLAG: u16 = 0x4000;
+const SKIP_WORKTREE_FLAG: u16 = 0x4000;
+/// Fixed-size fields of an entry, from ctime to flags.
+const ENTRY_FIXED_LEN: usize = 62;
+
+#[derive(Debug, Clone)]
+pub(super) struct IndexEntry {
+    /// Relative to the work tree, with `/` separators.
+    pub path: String,
+    pub id: ObjectId,
+    pub mode: u32,
+    /// 0 for normal entries, 1 to 3 for the sides of a merge conflict.
+    pub stage: u8,
+    /// Low 32 bits of the file size when it was staged.
+    pub size: u32,
+    pub mtime_seconds: u32,
+    pub mtime_nanoseconds: u32,
+    /// Marked assume-unchanged or outside a sparse checkout, so the work
+    /// tree copy is not compared.
+    pub skips_worktree: bool,
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn be_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn parse_index(data: &[u8]) -> Result<Vec<IndexEntry>, String> {
+    let invalid = || "Git index is corrupt".to_string();
+    if data.get(..4) != Some(b"DIRC") {
+        return Err(invalid());
+    }
+    let version = be_u32(data, 4).ok_or_else(invalid)?;
+    if !(2..=4).contains(&version) {
+        return Err(format!("Unsupported git index version {version}"));
+    }
+    let count = be_u32(data, 8).ok_or_else(invalid)? as usize;
+
+    let mut entries = Vec::with_capacity(count.min(data.len() / ENTRY_FIXED_LEN));
+    let mut offset = 12;
+    let mut previous_path: Vec<u8> = Vec::new();
+
+    for _ in 0..count {
+        let fixed = data
+            .get(offset..offset + ENTRY_FIXED_LEN)
+            .ok_or_else(invalid)?;
+        let flags = be_u16(fixed, 60).ok_or_else(invalid)?;
+        let mut cursor = offset + ENTRY_FIXED_LEN;
+        let mut skips_worktree = flags & ASSUME_VALID_FLAG != 0;
+        if version >= 3 && flags & EXTENDED_FLAG != 0 {
+            let extended_flags = be_u16(data, cursor).ok_or_else(invalid)?;
+            skips_worktree |= extended_flags & SKIP_WORKTREE_FLAG != 0;
+            cursor += 2;
+        }
+
+        let path = if version == 4 {
+            // The path is stored as how much of the previous path to drop
+            // and the suffix to append.
+            let mut reader = data.get(cursor..).ok_or_else(invalid)?;
+            let before = reader.len();
+            let strip = read_offset_varint(&mut reader).ok_or_else(invalid)? as usize;
+            cursor += before - reader.len();
+            let suffix_len = reader
+                .iter()
+                .position(|byte| *byte == 0)
+                .ok_or_else(invalid)?;
+            let keep = previous_path.len().checked_sub(strip).ok_or_else(invalid)?;
+            previous_path.truncate(keep);
+            previous_path.extend_from_slice(&reader[..suffix_len]);
+            offset = cursor + suffix_len + 1;
+            previous_path.clone()
+        } else {
+            let path_len = data[cursor..]
+                .iter()
+                .position(|byte| *byte == 0)
+                .ok_or_else(invalid)?;
+            let path = data[cursor..cursor + path_len].to_vec();
+            // Entries are NUL-padded to a multiple of eight bytes.
+            let entry_len = cursor - offset + path_len;
+            offset += (entry_len + 8) & !7;
+            path
+        };
+
+        entries.push(IndexEntry {
+            path: String::from_utf8_lossy(&path).into_owned(),
+            id: fixed[40..60].try_into().map_err(|_| invalid())?,
+            mode: be_u32(fixed, 24).ok_or_else(invalid)?,
+            stage: ((flags >> 12) & 0x3) as u8,
+            size: be_u32(fixed, 36).ok_or_else(invalid)?,
+            mtime_seconds: be_u32(fixed, 8).ok_or_else(invalid)?,
+            mtime_nanoseconds: be_u32(fixed, 12).ok_or_else(invalid)?,
+            skips_worktree,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Entries of the index at `path`, sorted by path. A repository without
+/// an index, such as a fresh one, has no entries.
+pub(super) fn read_index(path: &Path) -> Result<Vec<IndexEntry>, String> {
+    match fs::read(path) {
+        Ok(data) => parse_index(&data),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(format!("Failed to read git index: {error}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry_fields(mode: u32, size: u32, flags: u16) -> Vec<u8> {
+        let mut fields = vec![0; ENTRY_FIXED_LEN];
+        fields[8..12].copy_from_slice(&1_700_000_000u32.to_be_bytes());
+        fields[24..28].copy_from_slice(&mode.to_be_bytes());
+        fields[36..40].copy_from_slice(&size.to_be_bytes());
+        fields[40..60].copy_from_slice(&[0xab; 20]);
+        fields[60..62].copy_from_slice(&flags.to_be_bytes());
+        fields
+    }
+
+    #[test]
+    fn version_2_and_4_paths_are_read() {
+        let mut version_2 = b"DIRC\0\0\0\x02\0\0\0\x02".to_vec();
+        for (path, stage) in [("src/lib.rs", 0u16), ("src/main.rs", 2)] {
+            let mut entry = entry_fields(0o100644, 42, (stage << 12) | path.len() as u16);
+            entry.extend_from_slice(path.as_bytes());
+            entry.resize((entry.len() + 8) & !7, 0);
+            version_2.extend(entry);
+        }
+        let entries = parse_index(&version_2).expect("parse version 2");
+        assert_eq!(entries[0].path, "src/lib.rs");
+        assert_eq!((entries[0].size, entries[0].stage), (42, 0));
+        assert_eq!(entries[0].mtime_seconds, 1_700_000_000);
+        assert_eq!(
+            (entries[1].path.as_str(), entries[1].stage),
+            ("src/main.rs", 2)
+        );
+
+        let mut version_4 = b"DIRC\0\0\0\x04\0\0\0\x02".to_vec();
+        for (strip, suffix) in [(0u8, "src/lib.rs"), (6, "main.rs")] {
+            version_4.extend(entry_fields(0o100755, 7, 0));
+            version_4.push(strip);
+            version_4.extend_from_slice(suffix.as_bytes());
+            version_4.push(0);
+        }
+        let entries = parse_index(&version_4).expect("parse version 4");
+        assert_eq!(entries[1].path, "src/main.rs");
+        assert_eq!(entries[1].mode, 0o100755);
+    }
+
+    #[test]
+    fn version_4_prefixes_chain_and_extended_flags_skip_the_work_tree() {
+        let paths = [
+            (0u8, "docs/guide.md", false),
+            (8, "readme.md", true),
+            (14, "src/a/very/long/path/lib.rs", false),
+            (6, "main.rs", false),
+        ];
+        let mut version_4 = b"DIRC\0\0\0\x04\0\0\0\x04".to_vec();
+        for (strip, suffix, skips_worktree) in paths {
+            let flags = if skips_worktree { EXTENDED_FLAG } else { 0 };
+            version_4.extend(entry_fields(0o100644, 1, flags));
+            if skips_worktree {
+                version_4.extend_from_slice(&SKIP_WORKTREE_FLAG.to_be_bytes());
+            }
+            version_4.push(strip);
+            version_4.extend_from_slice(suffix.as_bytes());
+            version_4.push(0);
+        }
+
+        let entries = parse_index(&version_4).expect("parse version 4");
+        let paths: Vec<(&str, bool)> = entries
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry.skips_worktree))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("docs/guide.md", false),
+                ("docs/readme.md", true),
+                ("src/a/very/long/path/lib.rs", false),
+                ("src/a/very/long/path/main.rs", false),
+            ]
+        );
+
+        // Stripping more than the previous path is corrupt.
+        let mut corrupt = b"DIRC\0\0\0\x04\0\0\0\x01".to_vec();
+        corrupt.extend(entry_fields(0o100644, 1, 0));
+        corrupt.extend_from_slice(b"\x05a\0");
+        assert!(parse_index(&corrupt).is_err());
+    }
+}
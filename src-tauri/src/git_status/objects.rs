@@ -0,0 +1,569 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Reads commits and trees from loose objects and pack files.
+
+use flate2::read::ZlibDecoder;
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+pub(super) type ObjectId = [u8; 20];
+
+/// Longest chain of deltas followed before an object counts as unreadable.
+const MAX_DELTA_DEPTH: usize = 128;
+/// Upper bound on the memory reserved up front for an object, whatever
+/// size its header claims.
+const MAX_PREALLOCATION: usize = 1024 * 1024;
+
+pub(super) fn parse_object_id(hex: &str) -> Option<ObjectId> {
+    let hex = hex.trim();
+    if hex.len() != 40 {
+        return None;
+    }
+    let mut id = [0; 20];
+    for (index, byte) in id.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(index * 2..index * 2 + 2)?, 16).ok()?;
+    }
+    Some(id)
+}
+
+pub(super) fn object_id_to_hex(id: &ObjectId) -> String {
+    id.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ObjectKind {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+}
+
+impl ObjectKind {
+    fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"commit" => Some(Self::Commit),
+            b"tree" => Some(Self::Tree),
+            b"blob" => Some(Self::Blob),
+            b"tag" => Some(Self::Tag),
+            _ => None,
+        }
+    }
+
+    fn from_pack_type(pack_type: u8) -> Option<Self> {
+        match pack_type {
+            1 => Some(Self::Commit),
+            2 => Some(Self::Tree),
+            3 => Some(Self::Blob),
+            4 => Some(Self::Tag),
+            _ => None,
+        }
+    }
+}
+
+pub(super) struct Commit {
+    pub tree: ObjectId,
+    pub parents: Vec<ObjectId>,
+    /// Committer time, Unix seconds.
+    pub time: i64,
+}
+
+pub(super) struct TreeEntry {
+    pub mode: u32,
+    pub name: String,
+    pub id: ObjectId,
+}
+
+pub(super) const TREE_MODE: u32 = 0o040000;
+
+fn parse_commit(data: &[u8]) -> Option<Commit> {
+    let text = String::from_utf8_lossy(data);
+    let mut tree = None;
+    let mut parents = Vec::new();
+    let mut time = 0;
+
+    for line in text.lines().take_while(|line| !line.is_empty()) {
+        if let Some(id) = line.strip_prefix("tree ") {
+            tree = parse_object_id(id);
+        } else if let Some(id) = line.strip_prefix("parent ") {
+            parents.extend(parse_object_id(id));
+        } else if let Some(committer) = line.strip_prefix("committer ") {
+            // "Name <email> 1700000000 +0100"
+            time = committer
+                .rsplit(' ')
+                .nth(1)
+                .and_then(|seconds| seconds.parse().ok())
+                .unwrap_or(0);
+        }
+    }
+
+    Some(Commit {
+        tree: tree?,
+        parents,
+        time,
+    })
+}
+
+fn parse_tree(data: &[u8]) -> Option<Vec<TreeEntry>> {
+    let mut entries = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let space = rest.iter().position(|byte| *byte == b' ')?;
+        let nul = space + rest[space..].iter().position(|byte| *byte == 0)?;
+        let mode = u32::from_str_radix(std::str::from_utf8(&rest[..space]).ok()?, 8).ok()?;
+        let name = String::from_utf8_lossy(&rest[space + 1..nul]).into_owned();
+        let id = rest.get(nul + 1..nul + 21)?.try_into().ok()?;
+        entries.push(TreeEntry { mode, name, id });
+        rest = &rest[nul + 21..];
+    }
+    Some(entries)
+}
+
+fn inflate<R: Read>(reader: R, size: usize) -> Option<Vec<u8>> {
+    let mut data = Vec::with_capacity(size.min(MAX_PREALLOCATION));
+    ZlibDecoder::new(reader)
+        .take(size as u64)
+        .read_to_end(&mut data)
+        .ok()?;
+    (data.len() == size).then_some(data)
+}
+
+/// Reads git's offset encoding, used by pack offset deltas and index v4.
+pub(super) fn read_offset_varint<R: Read>(reader: &mut R) -> Option<u64> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte).ok()?;
+    let mut value = u64::from(byte[0] & 0x7f);
+    while byte[0] & 0x80 != 0 {
+        reader.read_exact(&mut byte).ok()?;
+        value = value.checked_add(1)?.checked_shl(7)? | u64::from(byte[0] & 0x7f);
+    }
+    Some(value)
+}
+
+fn read_size_varint(data: &mut &[u8]) -> Option<usize> {
+    let mut value = 0usize;
+    let mut shift = 0;
+    loop {
+        let (byte, rest) = data.split_first()?;
+        *data = rest;
+        value |= usize::from(byte & 0x7f).checked_shl(shift)?;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+fn apply_delta(base: &[u8], mut delta: &[u8]) -> Option<Vec<u8>> {
+    if read_size_varint(&mut delta)? != base.len() {
+        return None;
+    }
+    let result_size = read_size_varint(&mut delta)?;
+    let mut result = Vec::with_capacity(result_size.min(MAX_PREALLOCATION));
+
+    while let Some((&instruction, rest)) = delta.split_first() {
+        delta = rest;
+        if instruction & 0x80 != 0 {
+            let mut read_parameter = |bits: std::ops::Range<u8>| {
+                let mut value = 0usize;
+                for (index, bit) in bits.enumerate() {
+                    if instruction & (1 << bit) != 0 {
+                        let (byte, rest) = delta.split_first()?;
+                        delta = rest;
+                        value |= usize::from(*byte) << (8 * index);
+                    }
+                }
+                Some(value)
+            };
+            let offset = read_parameter(0..4)?;
+            let size = match read_parameter(4..7)? {
+                0 => 0x10000,
+                size => size,
+            };
+            result.extend_from_slice(base.get(offset..offset.checked_add(size)?)?);
+        } else if instruction != 0 {
+            let (data, rest) = delta.split_at_checked(usize::from(instruction))?;
+            result.extend_from_slice(data);
+            delta = rest;
+        } else {
+            return None;
+        }
+    }
+
+    (result.len() == result_size).then_some(result)
+}
+
+/// A pack file and its version 2 index, read on demand.
+struct Pack {
+    index: File,
+    data: File,
+    /// Cumulative object counts by first id byte.
+    fanout: [u32; 256],
+}
+
+enum PackEntry {
+    Object(ObjectKind, Vec<u8>),
+    OffsetDelta(u64, Vec<u8>),
+    RefDelta(ObjectId, Vec<u8>),
+}
+
+impl Pack {
+    const INDEX_HEADER_LEN: u64 = 8 + 256 * 4;
+
+    fn open(index_path: &Path) -> Option<Self> {
+        let mut index = File::open(index_path).ok()?;
+        let mut header = [0; Self::INDEX_HEADER_LEN as usize];
+        index.read_exact(&mut header).ok()?;
+        if header[..8] != *b"\xfftOc\0\0\0\x02" {
+            return None;
+        }
+
+        let mut fanout = [0; 256];
+        for (slot, bytes) in fanout.iter_mut().zip(header[8..].chunks_exact(4)) {
+            *slot = u32::from_be_bytes(bytes.try_into().ok()?);
+        }
+
+        Some(Self {
+            index,
+            data: File::open(index_path.with_extension("pack")).ok()?,
+            fanout,
+        })
+    }
+
+    fn read_index_at(&mut self, position: u64, buffer: &mut [u8]) -> Option<()> {
+        self.index.seek(SeekFrom::Start(position)).ok()?;
+        self.index.read_exact(buffer).ok()
+    }
+
+    /// Offset of `id` in the pack, found by binary search of the index.
+    fn find(&mut self, id: &ObjectId) -> Option<u64> {
+        let count = u64::from(self.fanout[255]);
+        let first_byte = usize::from(id[0]);
+        let mut low = if first_byte == 0 {
+            0
+        } else {
+            u64::from(self.fanout[first_byte - 1])
+        };
+        let mut high = u64::from(self.fanout[first_byte]);
+
+        let mut candidate = [0; 20];
+        let position = loop {
+            if low >= high {
+                return None;
+            }
+            let middle = low + (high - low) / 2;
+            self.read_index_at(Self::INDEX_HEADER_LEN + middle * 20, &mut candidate)?;
+            match candidate.cmp(id) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => break middle,
+            }
+        };
+
+        let offsets_start = Self::INDEX_HEADER_LEN + count * 24;
+        let mut offset = [0; 4];
+        self.read_index_at(offsets_start + position * 4, &mut offset)?;
+        let offset = u32::from_be_bytes(offset);
+        if offset & 0x8000_0000 == 0 {
+            return Some(u64::from(offset));
+        }
+
+        let large_offsets_start = offsets_start + count * 4;
+        let mut large_offset = [0; 8];
+        self.read_index_at(
+            large_offsets_start + u64::from(offset & 0x7fff_ffff) * 8,
+            &mut large_offset,
+        )?;
+        Some(u64::from_be_bytes(large_offset))
+    }
+
+    fn read_entry(&mut self, offset: u64) -> Option<PackEntry> {
+        self.data.seek(SeekFrom::Start(offset)).ok()?;
+        let mut reader = BufReader::new(&mut self.data);
+
+        let mut byte = [0];
+        reader.read_exact(&mut byte).ok()?;
+        let pack_type = (byte[0] >> 4) & 0x7;
+        let mut size = usize::from(byte[0] & 0x0f);
+        let mut shift = 4;
+        while byte[0] & 0x80 != 0 {
+            reader.read_exact(&mut byte).ok()?;
+            size |= usize::from(byte[0] & 0x7f).checked_shl(shift)?;
+            shift += 7;
+        }
+
+        match pack_type {
+            6 => {
+                let base_distance = read_offset_varint(&mut reader)?;
+                let base_offset = offset.checked_sub(base_distance)?;
+                Some(PackEntry::OffsetDelta(base_offset, inflate(reader, size)?))
+            }
+            7 => {
+                let mut base_id = [0; 20];
+                reader.read_exact(&mut base_id).ok()?;
+                Some(PackEntry::RefDelta(base_id, inflate(reader, size)?))
+            }
+            pack_type => Some(PackEntry::Object(
+                ObjectKind::from_pack_type(pack_type)?,
+                inflate(reader, size)?,
+            )),
+        }
+    }
+}
+
+/// The object database of a repository. Lookups seek in the pack indexes
+/// rather than loading them, since those grow to hundreds of megabytes.
+pub(super) struct ObjectStore {
+    objects_dir: PathBuf,
+    packs: Vec<Pack>,
+}
+
+impl ObjectStore {
+    pub(super) fn open(objects_dir: &Path) -> Self {
+        let packs = fs::read_dir(objects_dir.join("pack"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "idx"))
+            .filter_map(|path| Pack::open(&path))
+            .collect();
+
+        Self {
+            objects_dir: objects_dir.to_path_buf(),
+            packs,
+        }
+    }
+
+    fn read_loose(&self, id: &ObjectId) -> Option<(ObjectKind, Vec<u8>)> {
+        let hex = object_id_to_hex(id);
+        let file = File::open(self.objects_dir.join(&hex[..2]).join(&hex[2..])).ok()?;
+        let mut data = Vec::new();
+        ZlibDecoder::new(file).read_to_end(&mut data).ok()?;
+
+        let header_end = data.iter().position(|byte| *byte == 0)?;
+        let (kind, size) = std::str::from_utf8(&data[..header_end])
+            .ok()?
+            .split_once(' ')?;
+        let kind = ObjectKind::from_name(kind.as_bytes())?;
+        let size: usize = size.parse().ok()?;
+        let content = data.split_off(header_end + 1);
+        (content.len() == size).then_some((kind, content))
+    }
+
+    fn read_packed_at(
+        &mut self,
+        pack_index: usize,
+        offset: u64,
+        depth: usize,
+    ) -> Option<(ObjectKind, Vec<u8>)> {
+        if depth > MAX_DELTA_DEPTH {
+            return None;
+        }
+        match self.packs[pack_index].read_entry(offset)? {
+            PackEntry::Object(kind, data) => Some((kind, data)),
+            PackEntry::OffsetDelta(base_offset, delta) => {
+                let (kind, base) = self.read_packed_at(pack_index, base_offset, depth + 1)?;
+                Some((kind, apply_delta(&base, &delta)?))
+            }
+            PackEntry::RefDelta(base_id, delta) => {
+                let (kind, base) = self.read_object(&base_id, depth + 1)?;
+                Some((kind, apply_delta(&base, &delta)?))
+            }
+        }
+    }
+
+    fn read_object(&mut self, id: &ObjectId, depth: usize) -> Option<(ObjectKind, Vec<u8>)> {
+        for pack_index in 0..self.packs.len() {
+            if let Some(offset) = self.packs[pack_index].find(id) {
+                return self.read_packed_at(pack_index, offset, depth);
+            }
+        }
+        self.read_loose(id)
+    }
+
+    pub(super) fn read(&mut self, id: &ObjectId) -> Option<(ObjectKind, Vec<u8>)> {
+        self.read_object(id, 0)
+    }
+
+    pub(super) fn read_commit(&mut self, id: &ObjectId) -> Option<Commit> {
+        match self.read(id)? {
+            (ObjectKind::Commit, data) => parse_commit(&data),
+            _ => None,
+        }
+    }
+
+    pub(super) fn read_tree(&mut self, id: &ObjectId) -> Option<Vec<TreeEntry>> {
+        match self.read(id)? {
+            (ObjectKind::Tree, data) => parse_tree(&data),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use sha1::{Digest, Sha1};
+    use std::io::Write;
+
+    fn blob_id(content: &[u8]) -> ObjectId {
+        let mut hasher = Sha1::new();
+        hasher.update(format!("blob {}\0", content.len()).as_bytes());
+        hasher.update(content);
+        hasher.finalize().as_slice().try_into().unwrap()
+    }
+
+    /// A pack entry: the type and size header, `base` (a delta's base
+    /// reference) and the compressed `data`.
+    fn pack_entry(pack_type: u8, base: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut size = data.len();
+        let mut entry = vec![(pack_type << 4) | (size & 0x0f) as u8];
+        size >>= 4;
+        while size != 0 {
+            *entry.last_mut().unwrap() |= 0x80;
+            entry.push((size & 0x7f) as u8);
+            size >>= 7;
+        }
+        entry.extend_from_slice(base);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        entry.extend(encoder.finish().unwrap());
+        entry
+    }
+
+    /// The inverse of `read_offset_varint`.
+    fn offset_varint(mut value: u64) -> Vec<u8> {
+        let mut bytes = vec![(value & 0x7f) as u8];
+        value >>= 7;
+        while value != 0 {
+            value -= 1;
+            bytes.push(0x80 | (value & 0x7f) as u8);
+            value >>= 7;
+        }
+        bytes.reverse();
+        bytes
+    }
+
+    /// Writes `pack-test.pack` and its version 2 index for `entries` of ids
+    /// and encoded pack entries.
+    fn write_pack(pack_dir: &Path, entries: &[(ObjectId, Vec<u8>)]) {
+        let mut pack = b"PACK\0\0\0\x02".to_vec();
+        pack.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        let mut offsets = Vec::new();
+        for (id, entry) in entries {
+            offsets.push((*id, pack.len() as u32));
+            pack.extend_from_slice(entry);
+        }
+        pack.extend_from_slice(&[0; 20]);
+        offsets.sort();
+
+        let mut index = b"\xfftOc\0\0\0\x02".to_vec();
+        for first_byte in 0..=255u8 {
+            let count = offsets.iter().filter(|(id, _)| id[0] <= first_byte).count();
+            index.extend_from_slice(&(count as u32).to_be_bytes());
+        }
+        for (id, _) in &offsets {
+            index.extend_from_slice(id);
+        }
+        index.extend(std::iter::repeat_n(0, offsets.len() * 4));
+        for (_, offset) in &offsets {
+            index.extend_from_slice(&offset.to_be_bytes());
+        }
+        index.extend_from_slice(&[0; 40]);
+
+        fs::create_dir_all(pack_dir).unwrap();
+        fs::write(pack_dir.join("pack-test.pack"), pack).unwrap();
+        fs::write(pack_dir.join("pack-test.idx"), index).unwrap();
+    }
+
+    #[test]
+    fn packed_objects_resolve_offset_and_ref_deltas() {
+        let base = b"hello, world".to_vec();
+        let offset_delta_result = b"hello!!!rld".to_vec();
+        let ref_delta_result = b"hello!!!rld???".to_vec();
+        let (base_id, offset_delta_id, ref_delta_id) = (
+            blob_id(&base),
+            blob_id(&offset_delta_result),
+            blob_id(&ref_delta_result),
+        );
+
+        let base_entry = pack_entry(3, &[], &base);
+        let offset_delta = [12, 11, 0x90, 5, 3, b'!', b'!', b'!', 0x91, 9, 3];
+        let offset_delta_entry =
+            pack_entry(6, &offset_varint(base_entry.len() as u64), &offset_delta);
+        // Based on the offset delta's result, so both chains are followed.
+        let ref_delta = [11, 14, 0x90, 11, 3, b'?', b'?', b'?'];
+        let ref_delta_entry = pack_entry(7, &offset_delta_id, &ref_delta);
+
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let objects_dir = temp_dir.path().join("objects");
+        write_pack(
+            &objects_dir.join("pack"),
+            &[
+                (base_id, base_entry),
+                (offset_delta_id, offset_delta_entry),
+                (ref_delta_id, ref_delta_entry),
+            ],
+        );
+
+        let mut store = ObjectStore::open(&objects_dir);
+        assert_eq!(store.read(&base_id), Some((ObjectKind::Blob, base)));
+        assert_eq!(
+            store.read(&offset_delta_id),
+            Some((ObjectKind::Blob, offset_delta_result))
+        );
+        assert_eq!(
+            store.read(&ref_delta_id),
+            Some((ObjectKind::Blob, ref_delta_result))
+        );
+        assert_eq!(store.read(&[0x55; 20]), None);
+        assert_eq!(
+            read_offset_varint(&mut offset_varint(300_000).as_slice()),
+            Some(300_000)
+        );
+    }
+
+    #[test]
+    fn deltas_copy_from_the_base_and_insert_new_data() {
+        let base = b"hello, world";
+        // Sizes 12 -> 11, copy 5 bytes from offset 0, insert "!!!",
+        // copy 3 bytes from offset 9.
+        let delta = [12, 11, 0x90, 5, 3, b'!', b'!', b'!', 0x91, 9, 3];
+        assert_eq!(
+            apply_delta(base, &delta).as_deref(),
+            Some(&b"hello!!!rld"[..])
+        );
+        assert_eq!(apply_delta(b"short", &delta), None);
+    }
+
+    #[test]
+    fn commits_and_trees_are_parsed() {
+        let tree_id = [0x11; 20];
+        let commit = format!(
+            "tree {}\nparent {}\nauthor A <a@example.com> 1 +0000\ncommitter C <c@example.com> 1700000000 +0100\n\nMessage\n",
+            object_id_to_hex(&tree_id),
+            object_id_to_hex(&[0x22; 20]),
+        );
+        let commit = parse_commit(commit.as_bytes()).expect("parse commit");
+        assert_eq!(commit.tree, tree_id);
+        assert_eq!(commit.parents, vec![[0x22; 20]]);
+        assert_eq!(commit.time, 1_700_000_000);
+
+        let mut tree = b"100644 README.md\0".to_vec();
+        tree.extend_from_slice(&[0x33; 20]);
+        tree.extend_from_slice(b"40000 src\0");
+        tree.extend_from_slice(&[0x44; 20]);
+        let entries = parse_tree(&tree).expect("parse tree");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            (entries[0].mode, entries[0].name.as_str()),
+            (0o100644, "README.md")
+        );
+        assert_eq!((entries[1].mode, entries[1].id), (TREE_MODE, [0x44; 20]));
+    }
+}
@@ -0,0 +1,259 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Finds the repository of a work tree directory and reads its config and
+//! refs.
+
+use super::objects::{parse_object_id, ObjectId};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Symbolic refs followed before giving up, in case of a cycle.
+const MAX_SYMBOLIC_REF_DEPTH: usize = 8;
+
+/// `section.subsection.key = value` entries, in file order.
+#[derive(Default)]
+pub(super) struct Config {
+    entries: Vec<(String, Option<String>, String, String)>,
+}
+
+impl Config {
+    pub(super) fn parse(text: &str) -> Self {
+        let mut entries = Vec::new();
+        let mut section = String::new();
+        let mut subsection = None;
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(header) = line
+                .strip_prefix('[')
+                .and_then(|line| line.split(']').next())
+            {
+                // `[branch "main"]`, or the legacy `[branch.main]`.
+                let (name, sub) = match header.split_once(' ') {
+                    Some((name, sub)) => (name, Some(sub.trim().trim_matches('"').to_string())),
+                    None => match header.split_once('.') {
+                        Some((name, sub)) => (name, Some(sub.to_string())),
+                        None => (header, None),
+                    },
+                };
+                section = name.to_lowercase();
+                subsection = sub;
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').unwrap_or((line, "true"));
+            let value = value.split([';', '#']).next().unwrap_or_default().trim();
+            entries.push((
+                section.clone(),
+                subsection.clone(),
+                key.trim().to_lowercase(),
+                value.trim_matches('"').to_string(),
+            ));
+        }
+
+        Self { entries }
+    }
+
+    pub(super) fn get(&self, section: &str, subsection: Option<&str>, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(entry_section, entry_subsection, entry_key, _)| {
+                entry_section == section
+                    && entry_subsection.as_deref() == subsection
+                    && entry_key == key
+            })
+            .map(|(_, _, _, value)| value.as_str())
+    }
+
+    pub(super) fn get_bool(&self, section: &str, key: &str) -> Option<bool> {
+        match self.get(section, None, key)?.to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Some(true),
+            "false" | "no" | "off" | "0" | "" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+pub(super) struct Repository {
+    pub work_tree: PathBuf,
+    /// The `.git` directory, or the per-worktree directory of a linked
+    /// worktree.
+    pub git_dir: PathBuf,
+    /// Where objects, branches and config live, shared by linked worktrees.
+    pub common_dir: PathBuf,
+    pub config: Config,
+}
+
+pub(super) struct Head {
+    /// `None` when HEAD is detached.
+    pub branch: Option<String>,
+    /// `None` on a branch without commits yet.
+    pub id: Option<ObjectId>,
+}
+
+fn git_dir_of(work_tree: &Path) -> Option<PathBuf> {
+    let dot_git = work_tree.join(".git");
+    let metadata = fs::metadata(&dot_git).ok()?;
+    if metadata.is_dir() {
+        return dot_git.join("HEAD").is_file().then_some(dot_git);
+    }
+
+    // Linked worktrees and submodules have a `.git` file pointing elsewhere.
+    let content = fs::read_to_string(&dot_git).ok()?;
+    let git_dir = content.trim().strip_prefix("gitdir:")?.trim();
+    Some(work_tree.join(git_dir))
+}
+
+impl Repository {
+    /// The repository whose work tree contains `dir`. Directories inside a
+    /// `.git` directory belong to none.
+    pub(super) fn discover(dir: &Path) -> Option<Self> {
+        for ancestor in dir.ancestors() {
+            if ancestor.file_name().is_some_and(|name| name == ".git") {
+                return None;
+            }
+            let Some(git_dir) = git_dir_of(ancestor) else {
+                continue;
+            };
+
+            let common_dir = fs::read_to_string(git_dir.join("commondir"))
+                .map(|common_dir| git_dir.join(common_dir.trim()))
+                .unwrap_or_else(|_| git_dir.clone());
+            let config = fs::read_to_string(common_dir.join("config"))
+                .map(|text| Config::parse(&text))
+                .unwrap_or_default();
+
+            return Some(Self {
+                work_tree: ancestor.to_path_buf(),
+                git_dir,
+                common_dir,
+                config,
+            });
+        }
+        None
+    }
+
+    fn packed_ref(&self, name: &str) -> Option<ObjectId> {
+        let packed_refs = fs::read_to_string(self.common_dir.join("packed-refs")).ok()?;
+        packed_refs
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+            .find_map(|line| {
+                let (id, ref_name) = line.split_once(' ')?;
+                (ref_name.trim() == name)
+                    .then(|| parse_object_id(id))
+                    .flatten()
+            })
+    }
+
+    fn read_ref(&self, name: &str, depth: usize) -> Option<ObjectId> {
+        if depth > MAX_SYMBOLIC_REF_DEPTH {
+            return None;
+        }
+        let loose = [&self.git_dir, &self.common_dir]
+            .into_iter()
+            .find_map(|dir| fs::read_to_string(dir.join(name)).ok());
+
+        match loose {
+            Some(content) => match content.trim().strip_prefix("ref:") {
+                Some(target) => self.read_ref(target.trim(), depth + 1),
+                None => parse_object_id(&content),
+            },
+            None => self.packed_ref(name),
+        }
+    }
+
+    pub(super) fn resolve_ref(&self, name: &str) -> Option<ObjectId> {
+        self.read_ref(name, 0)
+    }
+
+    pub(super) fn head(&self) -> Head {
+        let content = fs::read_to_string(self.git_dir.join("HEAD")).unwrap_or_default();
+        let branch = content
+            .trim()
+            .strip_prefix("ref:")
+            .map(str::trim)
+            .and_then(|target| target.strip_prefix("refs/heads/"))
+            .map(str::to_string);
+
+        Head {
+            branch,
+            id: self.resolve_ref("HEAD"),
+        }
+    }
+
+    /// The ref `branch` tracks and its short name, e.g. `origin/main`.
+    pub(super) fn upstream(&self, branch: &str) -> Option<(String, String)> {
+        let remote = self.config.get("branch", Some(branch), "remote")?;
+        let merge = self.config.get("branch", Some(branch), "merge")?;
+        if remote == "." {
+            let short_name = merge.strip_prefix("refs/heads/").unwrap_or(merge);
+            return Some((merge.to_string(), short_name.to_string()));
+        }
+
+        let remote_branch = merge.strip_prefix("refs/heads/").unwrap_or(merge);
+        Some((
+            format!("refs/remotes/{remote}/{remote_branch}"),
+            format!("{remote}/{remote_branch}"),
+        ))
+    }
+
+    /// Whether objects are SHA-1, the only format read here.
+    pub(super) fn uses_sha1(&self) -> bool {
+        self.config
+            .get("extensions", None, "objectformat")
+            .is_none_or(|format| format.eq_ignore_ascii_case("sha1"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn branches_resolve_through_loose_and_packed_refs() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let work_tree = temp_dir.path();
+        let git_dir = work_tree.join(".git");
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(
+            git_dir.join("refs/heads/main"),
+            format!("{}\n", "1".repeat(40)),
+        )
+        .unwrap();
+        fs::write(
+            git_dir.join("packed-refs"),
+            format!(
+                "# pack-refs with: peeled fully-peeled sorted\n{} refs/remotes/origin/main\n",
+                "2".repeat(40)
+            ),
+        )
+        .unwrap();
+        fs::write(
+            git_dir.join("config"),
+            "[core]\n\tfilemode = false\n[branch \"main\"]\n\tremote = origin\n\tmerge = refs/heads/main\n",
+        )
+        .unwrap();
+        fs::create_dir_all(work_tree.join("src/nested")).unwrap();
+
+        let repository = Repository::discover(&work_tree.join("src/nested")).expect("discover");
+        assert_eq!(repository.work_tree, work_tree);
+        assert_eq!(repository.config.get_bool("core", "filemode"), Some(false));
+
+        let head = repository.head();
+        assert_eq!(head.branch.as_deref(), Some("main"));
+        assert_eq!(head.id, Some([0x11; 20]));
+
+        let (upstream_ref, upstream_name) = repository.upstream("main").expect("upstream");
+        assert_eq!(upstream_name, "origin/main");
+        assert_eq!(repository.resolve_ref(&upstream_ref), Some([0x22; 20]));
+        assert!(Repository::discover(&git_dir.join("refs")).is_none());
+    }
+}
@@ -3,12 +3,21 @@
 // Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
 
 mod commands;
+mod content;
 mod ignore;
+mod ignore_files;
 mod index;
+mod live;
+mod pattern;
 mod query;
+mod report;
 mod scan;
+mod scope;
 mod scoring;
+mod shard;
 mod state;
+mod syntax;
+mod tokenizer;
 mod types;
 
 #[allow(unused_imports)]
@@ -3,10 +3,11 @@
 // Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
 
 use super::types::{
-    GlobalSearchQueryOptions, GlobalSearchResultEntry, GlobalSearchSettings, GlobalSearchStatus,
-    IndexPathsSettings,
+    GlobalSearchBreakdownEntry, GlobalSearchBreakdownOptions, GlobalSearchContentQueryOptions,
+    GlobalSearchContentResultEntry, GlobalSearchFileReport, GlobalSearchLiveUpdateSettings,
+    GlobalSearchQueryError, GlobalSearchQueryOptions, GlobalSearchReportOptions,
+    GlobalSearchResultEntry, GlobalSearchSettings, GlobalSearchStatus, IndexPathsSettings,
 };
-
 #[tauri::command]
 pub async fn global_search_init(app: tauri::AppHandle) -> Result<GlobalSearchStatus, String> {
     tauri::async_runtime::spawn_blocking(move || super::scan::global_search_init(app))
@@ -32,6 +33,31 @@ pub async fn global_search_start_scan(
     super::scan::global_search_start_scan(app, settings).await
 }
 
+#[tauri::command]
+pub async fn global_search_drop_shard(
+    app: tauri::AppHandle,
+    drive_root: String,
+) -> Result<GlobalSearchStatus, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        super::shard::global_search_drop_shard(app, drive_root)
+    })
+    .await
+    .map_err(|join_error| format!("Global search shard drop task failed: {join_error}"))?
+}
+
+#[tauri::command]
+pub fn global_search_start_live_updates(
+    app: tauri::AppHandle,
+    settings: GlobalSearchLiveUpdateSettings,
+) -> Result<(), String> {
+    super::live::global_search_start_live_updates(app, settings)
+}
+
+#[tauri::command]
+pub fn global_search_stop_live_updates() -> Result<(), String> {
+    super::live::global_search_stop_live_updates()
+}
+
 #[tauri::command]
 pub async fn global_search_index_paths(
     app: tauri::AppHandle,
@@ -45,7 +71,7 @@ pub async fn global_search_query(
     app: tauri::AppHandle,
     query: String,
     options: GlobalSearchQueryOptions,
-) -> Result<Vec<GlobalSearchResultEntry>, String> {
+) -> Result<Vec<GlobalSearchResultEntry>, GlobalSearchQueryError> {
     super::query::global_search_query(app, query, options).await
 }
 
@@ -54,6 +80,52 @@ pub async fn global_search_query_paths(
     paths: Vec<String>,
     query: String,
     options: GlobalSearchQueryOptions,
-) -> Result<Vec<GlobalSearchResultEntry>, String> {
+) -> Result<Vec<GlobalSearchResultEntry>, GlobalSearchQueryError> {
     super::query::global_search_query_paths(paths, query, options).await
 }
+
+#[tauri::command]
+pub fn global_search_cancel_scope_walk() -> Result<(), String> {
+    super::scope::global_search_cancel_scope_walk()
+}
+
+#[tauri::command]
+pub async fn global_search_content_query(
+    app: tauri::AppHandle,
+    query: String,
+    options: GlobalSearchContentQueryOptions,
+) -> Result<Vec<GlobalSearchContentResultEntry>, String> {
+    super::content::global_search_content_query(app, query, options).await
+}
+
+#[tauri::command]
+pub async fn global_search_report_largest_files(
+    app: tauri::AppHandle,
+    options: GlobalSearchReportOptions,
+) -> Result<GlobalSearchFileReport, String> {
+    super::report::global_search_report_largest_files(app, options).await
+}
+
+#[tauri::command]
+pub async fn global_search_report_stale_files(
+    app: tauri::AppHandle,
+    options: GlobalSearchReportOptions,
+) -> Result<GlobalSearchFileReport, String> {
+    super::report::global_search_report_stale_files(app, options).await
+}
+
+#[tauri::command]
+pub async fn global_search_report_recent_additions(
+    app: tauri::AppHandle,
+    options: GlobalSearchReportOptions,
+) -> Result<GlobalSearchFileReport, String> {
+    super::report::global_search_report_recent_additions(app, options).await
+}
+
+#[tauri::command]
+pub async fn global_search_report_size_breakdown(
+    app: tauri::AppHandle,
+    options: GlobalSearchBreakdownOptions,
+) -> Result<Vec<GlobalSearchBreakdownEntry>, String> {
+    super::report::global_search_report_size_breakdown(app, options).await
+}
@@ -0,0 +1,486 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use crate::utils::{metadata_modified_time_unix_ms, path_extension_lowercase};
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use std::fs::{File, Metadata};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tantivy::collector::TopDocs;
+use tantivy::query::QueryParser;
+use tantivy::schema::{
+    IndexRecordOption, Schema, TextFieldIndexing, TextOptions, Value, FAST, STORED, STRING,
+};
+use tantivy::snippet::{Snippet, SnippetGenerator};
+use tantivy::{doc, Index, IndexReader, IndexWriter};
+use tauri::Manager;
+
+use super::index::{
+    create_fresh_index_with_schema, open_or_create_index_with_schema,
+    validate_staged_index_with_schema,
+};
+use super::shard::ensure_shards_loaded;
+use super::state::{GlobalSearchContentFields, GlobalSearchContentShard, GLOBAL_SEARCH_STATE};
+use super::types::{
+    GlobalSearchContentQueryOptions, GlobalSearchContentResultEntry, GlobalSearchContentSnippet,
+    GlobalSearchSnippetRange,
+};
+
+pub(super) const DEFAULT_CONTENT_MAX_FILE_SIZE: u64 = 1024 * 1024;
+const CONTENT_SNIPPET_SOURCE_MAX_SIZE: u64 = 16 * 1024 * 1024;
+const DEFAULT_SNIPPET_MAX_CHARS: usize = 160;
+const BINARY_SNIFF_LEN: usize = 8192;
+
+const CONTENT_INDEXABLE_EXTENSIONS: &[&str] = &[
+    "txt",
+    "md",
+    "markdown",
+    "rst",
+    "adoc",
+    "log",
+    "csv",
+    "tsv",
+    "json",
+    "jsonc",
+    "json5",
+    "yaml",
+    "yml",
+    "toml",
+    "ini",
+    "cfg",
+    "conf",
+    "env",
+    "properties",
+    "xml",
+    "html",
+    "htm",
+    "css",
+    "scss",
+    "sass",
+    "less",
+    "js",
+    "mjs",
+    "cjs",
+    "jsx",
+    "ts",
+    "tsx",
+    "vue",
+    "svelte",
+    "rs",
+    "go",
+    "py",
+    "rb",
+    "php",
+    "java",
+    "kt",
+    "kts",
+    "scala",
+    "swift",
+    "c",
+    "h",
+    "cc",
+    "cpp",
+    "cxx",
+    "hpp",
+    "hxx",
+    "cs",
+    "fs",
+    "lua",
+    "pl",
+    "r",
+    "dart",
+    "sql",
+    "sh",
+    "bash",
+    "zsh",
+    "fish",
+    "ps1",
+    "bat",
+    "cmd",
+    "gradle",
+    "cmake",
+    "tex",
+];
+
+const CONTENT_INDEXABLE_FILE_NAMES: &[&str] = &[
+    "dockerfile",
+    "makefile",
+    "license",
+    "readme",
+    ".gitignore",
+    ".gitattributes",
+    ".editorconfig",
+    ".env",
+];
+
+pub(super) fn build_content_schema() -> (Schema, GlobalSearchContentFields) {
+    let mut schema_builder = Schema::builder();
+
+    let content_indexing = TextFieldIndexing::default()
+        .set_tokenizer("default")
+        .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+    let content_options = TextOptions::default().set_indexing_options(content_indexing);
+
+    let path = schema_builder.add_text_field("path", STRING | STORED);
+    let content = schema_builder.add_text_field("content", content_options);
+    let modified_time = schema_builder.add_u64_field("modified_time", FAST | STORED);
+    let size = schema_builder.add_u64_field("size", FAST | STORED);
+
+    let schema = schema_builder.build();
+    (
+        schema,
+        GlobalSearchContentFields {
+            path,
+            content,
+            modified_time,
+            size,
+        },
+    )
+}
+
+pub(super) fn open_or_create_content_index(
+    index_path: &Path,
+) -> Result<(Index, IndexReader, GlobalSearchContentFields), String> {
+    let (schema, fields) = build_content_schema();
+    let (index, reader) = open_or_create_index_with_schema(index_path, schema)?;
+    Ok((index, reader, fields))
+}
+
+pub(super) fn create_fresh_content_index(
+    index_path: &Path,
+) -> Result<(Index, GlobalSearchContentFields), String> {
+    let (schema, fields) = build_content_schema();
+    let index = create_fresh_index_with_schema(index_path, schema)?;
+    Ok((index, fields))
+}
+
+pub(super) fn validate_staged_content_index(
+    index_path: &Path,
+    expected_doc_count: u64,
+) -> Result<u64, String> {
+    let (schema, _) = build_content_schema();
+    validate_staged_index_with_schema(index_path, &schema, expected_doc_count)
+}
+
+/// Adds the text of scanned files to the content index alongside the name index.
+pub(super) struct ContentIndexer<'a> {
+    pub(super) writer: &'a IndexWriter,
+    pub(super) fields: GlobalSearchContentFields,
+    pub(super) max_file_size: u64,
+    pub(super) indexed_count: &'a AtomicU64,
+}
+
+impl ContentIndexer<'_> {
+    pub(super) fn index_file(&self, path: &Path, path_string: &str, metadata: &Metadata) {
+        if !metadata.is_file()
+            || metadata.len() > self.max_file_size
+            || !is_content_indexable_path(path)
+        {
+            return;
+        }
+
+        let Some(content) = read_text_content(path, self.max_file_size) else {
+            return;
+        };
+
+        let did_add_doc = self
+            .writer
+            .add_document(doc!(
+                self.fields.path => path_string.to_string(),
+                self.fields.content => content,
+                self.fields.modified_time => metadata_modified_time_unix_ms(metadata),
+                self.fields.size => metadata.len(),
+            ))
+            .is_ok();
+
+        if did_add_doc {
+            self.indexed_count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+pub(super) fn is_content_indexable_path(path: &Path) -> bool {
+    if let Some(ext) = path_extension_lowercase(path) {
+        if CONTENT_INDEXABLE_EXTENSIONS.contains(&ext.as_str()) {
+            return true;
+        }
+    }
+
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| CONTENT_INDEXABLE_FILE_NAMES.contains(&name.to_lowercase().as_str()))
+}
+
+fn read_text_content(path: &Path, max_file_size: u64) -> Option<String> {
+    let file = File::open(path).ok()?;
+    let mut bytes = Vec::new();
+    file.take(max_file_size.saturating_add(1))
+        .read_to_end(&mut bytes)
+        .ok()?;
+
+    if bytes.len() as u64 > max_file_size {
+        return None;
+    }
+
+    decode_text_content(&bytes)
+}
+
+fn decode_text_content(bytes: &[u8]) -> Option<String> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let (decoded, _had_errors) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return Some(decoded.into_owned());
+    }
+
+    let sniff_len = bytes.len().min(BINARY_SNIFF_LEN);
+    if bytes[..sniff_len].contains(&0) {
+        return None;
+    }
+
+    if let Some(decoded) = UTF_8.decode_without_bom_handling_and_without_replacement(bytes) {
+        return Some(decoded.into_owned());
+    }
+
+    // Legacy text carries no label, so the encoding is guessed from byte
+    // statistics the way browsers do for unlabeled pages.
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding = detector.guess(None, true);
+    let (decoded, _had_errors) = encoding.decode_without_bom_handling(bytes);
+    Some(decoded.into_owned())
+}
+
+fn snippet_to_result(snippet: &Snippet) -> Option<GlobalSearchContentSnippet> {
+    if snippet.is_empty() {
+        return None;
+    }
+
+    let fragment = snippet.fragment();
+    let utf16_offset = |byte_offset: usize| fragment[..byte_offset].encode_utf16().count();
+
+    Some(GlobalSearchContentSnippet {
+        fragment: fragment.to_string(),
+        highlighted: snippet
+            .highlighted()
+            .iter()
+            .map(|range| GlobalSearchSnippetRange {
+                start: utf16_offset(range.start),
+                end: utf16_offset(range.end),
+            })
+            .collect(),
+    })
+}
+
+pub async fn global_search_content_query(
+    app: tauri::AppHandle,
+    query: String,
+    options: GlobalSearchContentQueryOptions,
+) -> Result<Vec<GlobalSearchContentResultEntry>, String> {
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let base_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|error: tauri::Error| error.to_string())?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        global_search_content_query_blocking(base_dir, query, options)
+    })
+    .await
+    .map_err(|join_error| format!("Global search content query task failed: {join_error}"))?
+}
+
+fn global_search_content_query_blocking(
+    base_dir: PathBuf,
+    query: String,
+    options: GlobalSearchContentQueryOptions,
+) -> Result<Vec<GlobalSearchContentResultEntry>, String> {
+    ensure_shards_loaded(&base_dir)?;
+
+    let state = GLOBAL_SEARCH_STATE
+        .read()
+        .map_err(|error| error.to_string())?;
+
+    // Each shard ranks with its own term statistics; the merged list keeps the
+    // best hits across drives.
+    let mut results: Vec<GlobalSearchContentResultEntry> = Vec::new();
+    for content in state
+        .shards
+        .iter()
+        .filter_map(|shard| shard.content.as_ref())
+    {
+        results.extend(search_content_shard(
+            content,
+            state.content_fields,
+            &query,
+            &options,
+        )?);
+    }
+    drop(state);
+
+    results.sort_by(|entry_a, entry_b| {
+        entry_b
+            .score
+            .partial_cmp(&entry_a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    results.truncate(options.limit.max(1));
+
+    Ok(results)
+}
+
+fn search_content_shard(
+    content: &GlobalSearchContentShard,
+    fields: GlobalSearchContentFields,
+    query: &str,
+    options: &GlobalSearchContentQueryOptions,
+) -> Result<Vec<GlobalSearchContentResultEntry>, String> {
+    let searcher = content.reader.searcher();
+    let mut query_parser = QueryParser::for_index(&content.index, vec![fields.content]);
+    query_parser.set_conjunction_by_default();
+    let (parsed_query, _errors) = query_parser.parse_query_lenient(query);
+
+    let top_docs = searcher
+        .search(
+            &parsed_query,
+            &TopDocs::with_limit(options.limit.max(1)).order_by_score(),
+        )
+        .map_err(|error| error.to_string())?;
+
+    let mut snippet_generator = SnippetGenerator::create(&searcher, &*parsed_query, fields.content)
+        .map_err(|error| error.to_string())?;
+    snippet_generator.set_max_num_chars(
+        options
+            .snippet_max_chars
+            .unwrap_or(DEFAULT_SNIPPET_MAX_CHARS),
+    );
+
+    let results = top_docs
+        .into_iter()
+        .filter_map(|(score, doc_address)| {
+            let retrieved: tantivy::TantivyDocument = searcher.doc(doc_address).ok()?;
+
+            let path_value = retrieved
+                .get_first(fields.path)
+                .and_then(|value| value.as_str())?
+                .to_string();
+            let path = Path::new(&path_value);
+
+            // The index only stores terms, so snippets come from the file on disk;
+            // hits whose file is gone, unreadable or too large come without one.
+            let snippet = read_text_content(path, CONTENT_SNIPPET_SOURCE_MAX_SIZE)
+                .and_then(|text| snippet_to_result(&snippet_generator.snippet(&text)));
+
+            let name = path.file_name()?.to_string_lossy().to_string();
+            let modified_time = retrieved
+                .get_first(fields.modified_time)
+                .and_then(|value| value.as_u64())
+                .unwrap_or(0);
+            let size = retrieved
+                .get_first(fields.size)
+                .and_then(|value| value.as_u64())
+                .unwrap_or(0);
+
+            Some(GlobalSearchContentResultEntry {
+                name,
+                ext: path_extension_lowercase(path),
+                path: path_value,
+                size,
+                modified_time,
+                score,
+                snippet,
+            })
+        })
+        .collect();
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn decode_text_content_handles_bom_and_legacy_encodings() {
+        let mut utf16 = vec![0xFF, 0xFE];
+        for unit in "config".encode_utf16() {
+            utf16.extend_from_slice(&unit.to_le_bytes());
+        }
+        assert_eq!(decode_text_content(&utf16).as_deref(), Some("config"));
+
+        let (shift_jis, _, _) = encoding_rs::SHIFT_JIS.encode("設定ファイル");
+        assert_eq!(
+            decode_text_content(&shift_jis).as_deref(),
+            Some("設定ファイル")
+        );
+
+        let (latin1, _, _) = encoding_rs::WINDOWS_1252.encode("Café crème brûlée à la carte");
+        assert_eq!(
+            decode_text_content(&latin1).as_deref(),
+            Some("Café crème brûlée à la carte")
+        );
+
+        assert_eq!(decode_text_content(b"binary\0data"), None);
+    }
+
+    #[test]
+    fn content_indexer_skips_binary_and_oversized_files() {
+        let temp = TempDir::new().unwrap();
+        let (index, fields) = create_fresh_content_index(&temp.path().join("content")).unwrap();
+        let writer = index.writer(50_000_000).unwrap();
+        let indexed_count = AtomicU64::new(0);
+        let indexer = ContentIndexer {
+            writer: &writer,
+            fields,
+            max_file_size: 64,
+            indexed_count: &indexed_count,
+        };
+
+        let files = [
+            ("settings.json", b"{ \"needle\": true }".to_vec()),
+            ("photo.png", b"needle".to_vec()),
+            ("notes.txt", vec![b'a'; 128]),
+            ("dump.log", b"needle\0\0".to_vec()),
+        ];
+        for (name, bytes) in files {
+            let path = temp.path().join(name);
+            std::fs::write(&path, bytes).unwrap();
+            let metadata = std::fs::metadata(&path).unwrap();
+            indexer.index_file(&path, &path.to_string_lossy(), &metadata);
+        }
+
+        assert_eq!(indexed_count.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn snippet_highlights_use_utf16_offsets() {
+        let (schema, fields) = build_content_schema();
+        let index = Index::create_in_ram(schema);
+        let mut writer = index.writer(50_000_000).unwrap();
+        writer
+            .add_document(doc!(
+                fields.path => "/notes.txt",
+                fields.content => "日本 needle",
+                fields.modified_time => 0u64,
+                fields.size => 0u64,
+            ))
+            .unwrap();
+        writer.commit().unwrap();
+
+        let query_parser = QueryParser::for_index(&index, vec![fields.content]);
+        let query = query_parser.parse_query("needle").unwrap();
+        let searcher = index.reader().unwrap().searcher();
+        let generator = SnippetGenerator::create(&searcher, &*query, fields.content).unwrap();
+
+        let snippet = snippet_to_result(&generator.snippet("日本 needle")).unwrap();
+
+        assert_eq!(snippet.highlighted.len(), 1);
+        assert_eq!(snippet.highlighted[0].start, 3);
+        assert_eq!(snippet.highlighted[0].end, 9);
+    }
+}
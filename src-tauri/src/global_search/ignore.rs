@@ -36,6 +36,7 @@ pub(super) fn builtin_ignored_paths() -> &'static [&'static str] {
 pub(super) struct IgnoredPathMatcher {
     plain_patterns: Vec<String>,
     glob_set: Option<GlobSet>,
+    respects_ignore_files: bool,
 }
 
 impl IgnoredPathMatcher {
@@ -68,9 +69,21 @@ impl IgnoredPathMatcher {
         Self {
             plain_patterns,
             glob_set,
+            respects_ignore_files: false,
         }
     }
 
+    /// Also skips paths excluded by `.gitignore`, `.ignore` and `.sfmignore`
+    /// files found during walks.
+    pub(super) fn with_ignore_files(mut self, respects_ignore_files: bool) -> Self {
+        self.respects_ignore_files = respects_ignore_files;
+        self
+    }
+
+    pub(super) fn respects_ignore_files(&self) -> bool {
+        self.respects_ignore_files
+    }
+
     pub(super) fn is_ignored(&self, path: &str) -> bool {
         let normalized_path = normalize_match_text(path.trim_end_matches('/'));
 
@@ -0,0 +1,291 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! `.gitignore`-style ignore files found during a walk. The rules of a file
+//! apply to the directory it sits in and everything below it, and rules from
+//! deeper directories take precedence over rules from their ancestors.
+
+use crate::utils::normalize_path;
+use globset::{GlobBuilder, GlobMatcher};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Read in this order, so rules in later files win over earlier ones.
+const IGNORE_FILE_NAMES: &[&str] = &[".gitignore", ".ignore", ".sfmignore"];
+
+struct IgnoreRule {
+    matcher: GlobMatcher,
+    is_negated: bool,
+    is_dir_only: bool,
+}
+
+/// Rules from the ignore files of one directory, matched against paths
+/// relative to that directory.
+struct DirIgnoreRules {
+    dir: String,
+    rules: Vec<IgnoreRule>,
+}
+
+impl DirIgnoreRules {
+    fn load(dir: &Path, dir_string: String) -> Option<Self> {
+        let mut rules = Vec::new();
+        for file_name in IGNORE_FILE_NAMES {
+            if let Ok(content) = std::fs::read_to_string(dir.join(file_name)) {
+                parse_ignore_rules(&content, &mut rules);
+            }
+        }
+
+        (!rules.is_empty()).then_some(Self {
+            dir: dir_string,
+            rules,
+        })
+    }
+
+    /// `Some(true)` when the last matching rule ignores the path,
+    /// `Some(false)` when it re-includes it with `!`.
+    fn matched(&self, relative_path: &str, is_dir: bool) -> Option<bool> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.is_dir_only) && rule.matcher.is_match(relative_path))
+            .map(|rule| !rule.is_negated)
+    }
+}
+
+fn parse_ignore_rules(content: &str, rules: &mut Vec<IgnoreRule>) {
+    for line in content.lines() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (is_negated, pattern) = match line.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (is_dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        if pattern.is_empty() {
+            continue;
+        }
+
+        // A pattern with a slash is anchored to the ignore file's directory;
+        // a bare name matches at any depth below it.
+        let glob = match pattern.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if pattern.contains('/') => pattern.to_string(),
+            None => format!("**/{pattern}"),
+        };
+        let Ok(glob) = GlobBuilder::new(&glob)
+            .literal_separator(true)
+            .case_insensitive(cfg!(windows))
+            .build()
+        else {
+            continue;
+        };
+
+        rules.push(IgnoreRule {
+            matcher: glob.compile_matcher(),
+            is_negated,
+            is_dir_only,
+        });
+    }
+}
+
+fn relative_to<'a>(path: &'a str, dir: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix(dir)?;
+    let relative = if dir.ends_with('/') {
+        rest
+    } else {
+        rest.strip_prefix('/')?
+    };
+    (!relative.is_empty()).then_some(relative)
+}
+
+fn is_ignored_by_frames<'a>(
+    frames: impl DoubleEndedIterator<Item = &'a DirIgnoreRules>,
+    path_string: &str,
+    is_dir: bool,
+) -> bool {
+    frames
+        .rev()
+        .find_map(|frame| frame.matched(relative_to(path_string, &frame.dir)?, is_dir))
+        .unwrap_or(false)
+}
+
+/// Ignore rules of the directories on the current walk path. Entries must be
+/// checked in walk order, parents before their children.
+#[derive(Default)]
+pub(super) struct IgnoreFileStack {
+    frames: Vec<DirIgnoreRules>,
+    has_ignored_ancestor: bool,
+}
+
+impl IgnoreFileStack {
+    /// Stack for a walk starting at `start`, with the ignore files of every
+    /// ancestor of `start` already loaded.
+    pub(super) fn for_walk_from(start: &Path) -> Self {
+        let mut stack = Self::default();
+        let ancestors: Vec<&Path> = start.ancestors().skip(1).collect();
+        for ancestor in ancestors.into_iter().rev() {
+            if stack.is_ignored(ancestor, true) {
+                stack.has_ignored_ancestor = true;
+                break;
+            }
+        }
+        stack
+    }
+
+    /// Whether an ignore file excludes `path`. Directories that are not
+    /// excluded get their own ignore files loaded for their descendants.
+    pub(super) fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        if self.has_ignored_ancestor {
+            return true;
+        }
+        let Some(path_string) = path.to_str().map(normalize_path) else {
+            return false;
+        };
+
+        while self
+            .frames
+            .last()
+            .is_some_and(|frame| relative_to(&path_string, &frame.dir).is_none())
+        {
+            self.frames.pop();
+        }
+
+        let is_ignored = is_ignored_by_frames(self.frames.iter(), &path_string, is_dir);
+
+        if is_dir && !is_ignored {
+            if let Some(frame) = DirIgnoreRules::load(path, path_string) {
+                self.frames.push(frame);
+            }
+        }
+
+        is_ignored
+    }
+}
+
+/// Ignore rules that apply to the entries of one directory. Unlike
+/// [`IgnoreFileStack`] it does not depend on walk order, so directories of a
+/// parallel walk each carry their own scope.
+#[derive(Clone, Default)]
+pub(super) struct IgnoreFileScope {
+    frames: Vec<Arc<DirIgnoreRules>>,
+    has_ignored_ancestor: bool,
+}
+
+impl IgnoreFileScope {
+    /// Scope for the entries of `dir`, with the ignore files of `dir` and all
+    /// of its ancestors loaded.
+    pub(super) fn for_dir(dir: &Path) -> Self {
+        let mut stack = IgnoreFileStack::for_walk_from(dir);
+        let has_ignored_ancestor = stack.is_ignored(dir, true);
+        Self {
+            frames: stack.frames.into_iter().map(Arc::new).collect(),
+            has_ignored_ancestor,
+        }
+    }
+
+    /// Scope for the entries of `dir`, a directory inside this scope that is
+    /// not ignored itself.
+    pub(super) fn for_child_dir(&self, dir: &Path, dir_string: &str) -> Self {
+        let mut scope = self.clone();
+        if let Some(frame) = DirIgnoreRules::load(dir, dir_string.to_string()) {
+            scope.frames.push(Arc::new(frame));
+        }
+        scope
+    }
+
+    pub(super) fn is_ignored(&self, path_string: &str, is_dir: bool) -> bool {
+        self.has_ignored_ancestor
+            || is_ignored_by_frames(self.frames.iter().map(Arc::as_ref), path_string, is_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use walkdir::WalkDir;
+
+    fn walk_kept_paths(root: &Path) -> Vec<String> {
+        let mut ignore_files = IgnoreFileStack::for_walk_from(root);
+        let mut kept: Vec<String> = WalkDir::new(root)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| {
+                !ignore_files.is_ignored(entry.path(), entry.file_type().is_dir())
+            })
+            .flatten()
+            .filter(|entry| entry.depth() > 0)
+            .map(|entry| {
+                normalize_path(&entry.path().strip_prefix(root).unwrap().to_string_lossy())
+            })
+            .collect();
+        kept.sort();
+        kept
+    }
+
+    #[test]
+    fn nested_ignore_files_scope_to_their_directory() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("repo");
+        std::fs::create_dir_all(root.join("target/debug")).unwrap();
+        std::fs::create_dir_all(root.join("web/dist")).unwrap();
+        std::fs::create_dir_all(root.join("docs/dist")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n*.log\n!keep.log\n").unwrap();
+        std::fs::write(root.join("web/.ignore"), "/dist\n").unwrap();
+        std::fs::write(root.join("target/debug/app"), "").unwrap();
+        std::fs::write(root.join("web/dist/bundle.js"), "").unwrap();
+        std::fs::write(root.join("docs/dist/index.html"), "").unwrap();
+        std::fs::write(root.join("docs/build.log"), "").unwrap();
+        std::fs::write(root.join("docs/keep.log"), "").unwrap();
+
+        assert_eq!(
+            walk_kept_paths(&root),
+            vec![
+                ".gitignore",
+                "docs",
+                "docs/dist",
+                "docs/dist/index.html",
+                "docs/keep.log",
+                "web",
+                "web/.ignore",
+            ]
+        );
+    }
+
+    #[test]
+    fn walk_below_an_ignored_directory_ignores_everything() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("repo");
+        std::fs::create_dir_all(root.join("node_modules/pkg")).unwrap();
+        std::fs::write(root.join(".sfmignore"), "node_modules/\n").unwrap();
+
+        let mut ignore_files = IgnoreFileStack::for_walk_from(&root.join("node_modules/pkg"));
+        assert!(ignore_files.is_ignored(&root.join("node_modules/pkg/index.js"), false));
+    }
+
+    #[test]
+    fn child_scopes_add_their_own_ignore_files() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("repo");
+        std::fs::create_dir_all(root.join("web")).unwrap();
+        std::fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        std::fs::write(root.join("web/.ignore"), "dist/\n").unwrap();
+        let root_string = normalize_path(&root.to_string_lossy());
+        let web_string = format!("{root_string}/web");
+
+        let root_scope = IgnoreFileScope::for_dir(&root);
+        let web_scope = root_scope.for_child_dir(&root.join("web"), &web_string);
+
+        assert!(root_scope.is_ignored(&format!("{root_string}/build.log"), false));
+        assert!(!root_scope.is_ignored(&format!("{root_string}/dist"), true));
+        assert!(web_scope.is_ignored(&format!("{web_string}/dist"), true));
+        assert!(web_scope.is_ignored(&format!("{web_string}/debug.log"), false));
+    }
+}
@@ -3,21 +3,27 @@
 // Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
 
 use super::state::GlobalSearchIndexFields;
+use super::tokenizer::{register_name_tokenizer, NAME_TOKENIZER};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
+use std::ops::Bound;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tantivy::collector::DocSetCollector;
 use tantivy::indexer::{IndexWriter, NoMergePolicy};
+use tantivy::query::{BooleanQuery, Query, RangeQuery, TermQuery};
 use tantivy::schema::{
-    IndexRecordOption, Schema, TextFieldIndexing, TextOptions, FAST, STORED, STRING,
+    Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, Value, FAST, STORED, STRING,
 };
-use tantivy::{Index, IndexReader, ReloadPolicy};
+use tantivy::{Index, IndexReader, ReloadPolicy, Searcher, TantivyDocument, Term};
 
 pub(super) fn build_schema() -> (Schema, GlobalSearchIndexFields) {
     let mut schema_builder = Schema::builder();
 
     let name_indexing = TextFieldIndexing::default()
-        .set_tokenizer("default")
+        .set_tokenizer(NAME_TOKENIZER)
         .set_index_option(IndexRecordOption::WithFreqsAndPositions);
     let name_options = TextOptions::default()
         .set_indexing_options(name_indexing)
@@ -26,11 +32,13 @@ pub(super) fn build_schema() -> (Schema, GlobalSearchIndexFields) {
     let path = schema_builder.add_text_field("path", STRING | STORED);
     let name = schema_builder.add_text_field("name", name_options);
     let name_lower = schema_builder.add_text_field("name_lower", STRING | STORED);
+    let ext = schema_builder.add_text_field("ext", STRING | STORED | FAST);
 
     let is_file = schema_builder.add_u64_field("is_file", FAST | STORED);
     let is_dir = schema_builder.add_u64_field("is_dir", FAST | STORED);
     let modified_time = schema_builder.add_u64_field("modified_time", FAST | STORED);
     let size = schema_builder.add_u64_field("size", FAST | STORED);
+    let indexed_time = schema_builder.add_u64_field("indexed_time", FAST | STORED);
 
     let schema = schema_builder.build();
     (
@@ -39,39 +47,67 @@ pub(super) fn build_schema() -> (Schema, GlobalSearchIndexFields) {
             path,
             name,
             name_lower,
+            ext,
             is_file,
             is_dir,
             modified_time,
             size,
+            indexed_time,
         },
     )
 }
 
-pub(super) fn index_dir(base_dir: &Path) -> PathBuf {
-    global_search_dir(base_dir).join("index")
-}
+pub(super) const SHARDS_DIR_NAME: &str = "shards";
 
-fn global_search_dir(base_dir: &Path) -> PathBuf {
+pub(super) fn global_search_dir(base_dir: &Path) -> PathBuf {
     base_dir.join("global-search")
 }
 
-fn unique_index_sidecar_dir(base_dir: &Path, label: &str) -> PathBuf {
+/// Directories of the single index used before the index was split into
+/// per-drive shards.
+fn legacy_index_dirs(base_dir: &Path) -> [PathBuf; 2] {
+    [
+        global_search_dir(base_dir).join("index"),
+        global_search_dir(base_dir).join("content-index"),
+    ]
+}
+
+pub(super) fn remove_legacy_index_dirs(base_dir: &Path) {
+    for legacy_dir in legacy_index_dirs(base_dir) {
+        let _ = remove_dir_force(&legacy_dir);
+    }
+}
+
+/// Drives scanned in parallel stage their indexes at the same time, so the
+/// timestamp alone does not keep sidecar names apart.
+static SIDECAR_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+fn unique_sidecar_dir(parent: &Path, label: &str) -> PathBuf {
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map(|duration| duration.as_millis())
         .unwrap_or(0);
-    global_search_dir(base_dir).join(format!(
-        ".index.{}.{}.{}",
+    parent.join(format!(
+        ".index.{}.{}.{}.{}",
         label,
         timestamp,
-        std::process::id()
+        std::process::id(),
+        SIDECAR_SEQUENCE.fetch_add(1, Ordering::Relaxed)
     ))
 }
 
+fn unique_index_sidecar_dir(base_dir: &Path, label: &str) -> PathBuf {
+    unique_sidecar_dir(&global_search_dir(base_dir), label)
+}
+
 pub(super) fn staging_index_dir(base_dir: &Path) -> PathBuf {
     unique_index_sidecar_dir(base_dir, "staging")
 }
 
+pub(super) fn content_staging_index_dir(base_dir: &Path) -> PathBuf {
+    unique_index_sidecar_dir(base_dir, "content-staging")
+}
+
 pub(super) fn calculate_dir_size(path: &Path) -> u64 {
     if !path.exists() {
         return 0;
@@ -112,10 +148,18 @@ pub(super) struct GlobalSearchMeta {
     pub(super) last_scan_indexed_item_count: Option<u64>,
     pub(super) last_scan_error: Option<String>,
     #[serde(default)]
-    pub(super) indexed_drive_roots: Vec<String>,
+    pub(super) content_indexed_item_count: u64,
+    #[serde(default)]
+    pub(super) shards: Vec<GlobalSearchShardMeta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct GlobalSearchShardMeta {
+    pub(super) drive_root: String,
+    pub(super) last_scan_time: Option<u64>,
 }
 
-pub(super) const SCHEMA_VERSION: u32 = 1;
+pub(super) const SCHEMA_VERSION: u32 = 6;
 pub(super) const BULK_INDEX_MEMORY_BUDGET_BYTES: usize = 100_000_000;
 const INDEX_RENAME_MAX_ATTEMPTS: usize = 100;
 const INDEX_RENAME_RETRY_DELAY: Duration = Duration::from_millis(100);
@@ -149,9 +193,23 @@ fn ensure_dir(path: &Path) -> Result<(), String> {
     fs::create_dir_all(path).map_err(|error| error.to_string())
 }
 
+/// Removes leftover staging, backup and trash directories next to the index
+/// and inside every shard directory.
 pub(super) fn cleanup_orphan_index_dirs(base_dir: &Path) {
-    let dir = global_search_dir(base_dir);
-    let entries = match fs::read_dir(&dir) {
+    let global_dir = global_search_dir(base_dir);
+    cleanup_orphan_sidecar_dirs(&global_dir);
+
+    if let Ok(shard_entries) = fs::read_dir(global_dir.join(SHARDS_DIR_NAME)) {
+        for shard_entry in shard_entries.flatten() {
+            if shard_entry.path().is_dir() {
+                cleanup_orphan_sidecar_dirs(&shard_entry.path());
+            }
+        }
+    }
+}
+
+fn cleanup_orphan_sidecar_dirs(dir: &Path) {
+    let entries = match fs::read_dir(dir) {
         Ok(entries) => entries,
         Err(_) => return,
     };
@@ -162,6 +220,7 @@ pub(super) fn cleanup_orphan_index_dirs(base_dir: &Path) {
 
         if path.is_dir()
             && (name.starts_with(".index.staging.")
+                || name.starts_with(".index.content-staging.")
                 || name.starts_with(".index.backup.")
                 || name.starts_with(".index.trash."))
         {
@@ -250,38 +309,67 @@ fn rename_dir_with_retry(source: &Path, target: &Path, operation: &str) -> Resul
     ))
 }
 
-pub(super) fn validate_index(index_path: &Path, base_dir: &Path) -> bool {
-    let meta = match read_meta(base_dir) {
-        Some(meta) => meta,
-        None => return false,
-    };
+pub(super) fn clear_index(index_path: &Path) -> Result<(), String> {
+    remove_dir_force(index_path)
+}
 
-    if meta.schema_version != SCHEMA_VERSION {
-        return false;
+/// Matches the document stored under `path` along with every document below it.
+/// Paths are normalized with `/` separators, so descendants are exactly the terms
+/// in the `path/` .. `path0` range. Roots such as `/` or `C:/` already end in a
+/// separator and only need the range.
+pub(super) fn path_and_descendants_query(path_field: Field, path: &str) -> Box<dyn Query> {
+    let parent = path.strip_suffix('/').unwrap_or(path);
+    let descendants: Box<dyn Query> = Box::new(RangeQuery::new(
+        Bound::Included(Term::from_field_text(path_field, &format!("{parent}/"))),
+        Bound::Excluded(Term::from_field_text(path_field, &format!("{parent}0"))),
+    ));
+
+    if path.ends_with('/') {
+        return descendants;
     }
 
-    if !index_path.exists() {
-        return false;
-    }
+    let exact: Box<dyn Query> = Box::new(TermQuery::new(
+        Term::from_field_text(path_field, path),
+        IndexRecordOption::Basic,
+    ));
+    Box::new(BooleanQuery::union(vec![exact, descendants]))
+}
 
-    match Index::open_in_dir(index_path) {
-        Ok(index) => match index
-            .reader_builder()
-            .reload_policy(ReloadPolicy::Manual)
-            .try_into()
-        {
-            Ok(reader) => {
-                let searcher = reader.searcher();
-                searcher.num_docs() > 0
-            }
-            Err(_) => false,
-        },
-        Err(_) => false,
-    }
+/// When `path` and the documents below it were first indexed, so re-adding
+/// them after a change keeps their first-seen time.
+pub(super) fn first_indexed_times(
+    searcher: &Searcher,
+    fields: &GlobalSearchIndexFields,
+    path: &str,
+) -> Result<HashMap<String, u64>, String> {
+    let doc_addresses = searcher
+        .search(
+            &path_and_descendants_query(fields.path, path),
+            &DocSetCollector,
+        )
+        .map_err(|error| error.to_string())?;
+
+    Ok(doc_addresses
+        .into_iter()
+        .filter_map(|doc_address| {
+            let retrieved: TantivyDocument = searcher.doc(doc_address).ok()?;
+            let path = retrieved.get_first(fields.path)?.as_str()?.to_string();
+            let indexed_time = retrieved.get_first(fields.indexed_time)?.as_u64()?;
+            Some((path, indexed_time))
+        })
+        .collect())
 }
 
-pub(super) fn clear_index(index_path: &Path) -> Result<(), String> {
-    remove_dir_force(index_path)
+/// Deletes the document stored under `path` along with every document below it.
+pub(super) fn delete_path_and_descendants(
+    writer: &IndexWriter,
+    path_field: Field,
+    path: &str,
+) -> Result<(), String> {
+    writer
+        .delete_query(path_and_descendants_query(path_field, path))
+        .map(|_| ())
+        .map_err(|error| error.to_string())
 }
 
 pub(super) fn create_bulk_index_writer(index: &Index) -> Result<IndexWriter, String> {
@@ -295,8 +383,16 @@ pub(super) fn create_bulk_index_writer(index: &Index) -> Result<IndexWriter, Str
 pub(super) fn open_or_create_index(
     index_path: &Path,
 ) -> Result<(Index, IndexReader, GlobalSearchIndexFields), String> {
-    ensure_dir(index_path)?;
     let (schema, fields) = build_schema();
+    let (index, reader) = open_or_create_index_with_schema(index_path, schema)?;
+    Ok((index, reader, fields))
+}
+
+pub(super) fn open_or_create_index_with_schema(
+    index_path: &Path,
+    schema: Schema,
+) -> Result<(Index, IndexReader), String> {
+    ensure_dir(index_path)?;
 
     let index = match Index::open_in_dir(index_path) {
         Ok(existing) => {
@@ -313,27 +409,38 @@ pub(super) fn open_or_create_index(
         Err(_) => Index::create_in_dir(index_path, schema).map_err(|error| error.to_string())?,
     };
 
+    register_name_tokenizer(&index);
+
     let reader = index
         .reader_builder()
         .reload_policy(ReloadPolicy::Manual)
         .try_into()
         .map_err(|error| error.to_string())?;
 
-    Ok((index, reader, fields))
+    Ok((index, reader))
 }
 
 pub(super) fn create_fresh_index(
     index_path: &Path,
 ) -> Result<(Index, GlobalSearchIndexFields), String> {
+    let (schema, fields) = build_schema();
+    let index = create_fresh_index_with_schema(index_path, schema)?;
+
+    Ok((index, fields))
+}
+
+pub(super) fn create_fresh_index_with_schema(
+    index_path: &Path,
+    schema: Schema,
+) -> Result<Index, String> {
     if index_path.exists() {
         remove_dir_force(index_path)?;
     }
     ensure_dir(index_path)?;
 
-    let (schema, fields) = build_schema();
     let index = Index::create_in_dir(index_path, schema).map_err(|error| error.to_string())?;
-
-    Ok((index, fields))
+    register_name_tokenizer(&index);
+    Ok(index)
 }
 
 pub(super) fn validate_staged_index(
@@ -341,9 +448,17 @@ pub(super) fn validate_staged_index(
     expected_doc_count: u64,
 ) -> Result<u64, String> {
     let (schema, _) = build_schema();
+    validate_staged_index_with_schema(index_path, &schema, expected_doc_count)
+}
+
+pub(super) fn validate_staged_index_with_schema(
+    index_path: &Path,
+    schema: &Schema,
+    expected_doc_count: u64,
+) -> Result<u64, String> {
     let index = Index::open_in_dir(index_path).map_err(|error| error.to_string())?;
 
-    if index.schema() != schema {
+    if index.schema() != *schema {
         return Err("Staged search index schema does not match current schema".to_string());
     }
 
@@ -376,12 +491,10 @@ pub(super) fn replace_index_dir(staged_dir: &Path, target_dir: &Path) -> Result<
         ensure_dir(parent)?;
     }
 
-    let base_dir = target_dir
+    let target_parent = target_dir
         .parent()
-        .and_then(|parent| parent.parent())
-        .map(Path::to_path_buf)
-        .ok_or_else(|| "Cannot determine search index base directory".to_string())?;
-    let backup_dir = unique_index_sidecar_dir(&base_dir, "backup");
+        .ok_or_else(|| "Cannot determine search index parent directory".to_string())?;
+    let backup_dir = unique_sidecar_dir(target_parent, "backup");
     let had_existing_target = target_dir.exists();
 
     if had_existing_target {
@@ -446,7 +559,10 @@ mod tests {
     fn replace_index_dir_swaps_staged_index_into_live_path() {
         let temp = TempDir::new().unwrap();
         let base_dir = temp.path();
-        let live_path = index_dir(base_dir);
+        let live_path = global_search_dir(base_dir)
+            .join(SHARDS_DIR_NAME)
+            .join("root")
+            .join("index");
         let staged_path = staging_index_dir(base_dir);
 
         assert_eq!(write_single_doc(&live_path, "/old"), 1);
@@ -469,18 +585,61 @@ mod tests {
         assert!(error.contains("document count mismatch"));
     }
 
+    #[test]
+    fn delete_path_and_descendants_keeps_sibling_prefixes() {
+        let (schema, fields) = build_schema();
+        let index = Index::create_in_ram(schema);
+        register_name_tokenizer(&index);
+        let mut writer = index.writer(50_000_000).unwrap();
+
+        for path in [
+            "/docs",
+            "/docs/a.txt",
+            "/docs/sub/b.txt",
+            "/docs-old",
+            "/docs.txt",
+        ] {
+            writer
+                .add_document(doc!(
+                    fields.path => path,
+                    fields.name => path,
+                    fields.name_lower => path,
+                    fields.is_file => 1u64,
+                    fields.is_dir => 0u64,
+                    fields.modified_time => 0u64,
+                    fields.size => 0u64,
+                ))
+                .unwrap();
+        }
+        writer.commit().unwrap();
+
+        delete_path_and_descendants(&writer, fields.path, "/docs").unwrap();
+        writer.commit().unwrap();
+
+        let reader = index.reader().unwrap();
+        assert_eq!(reader.searcher().num_docs(), 2);
+    }
+
     #[test]
     fn cleanup_orphan_index_dirs_removes_staging_and_backup_dirs() {
         let temp = TempDir::new().unwrap();
         let staging_path = staging_index_dir(temp.path());
         let backup_path = unique_index_sidecar_dir(temp.path(), "backup");
+        let shard_dir = global_search_dir(temp.path())
+            .join(SHARDS_DIR_NAME)
+            .join("root");
+        let shard_backup_path = unique_sidecar_dir(&shard_dir, "backup");
 
         fs::create_dir_all(&staging_path).unwrap();
         fs::create_dir_all(&backup_path).unwrap();
+        fs::create_dir_all(&shard_backup_path).unwrap();
+        fs::create_dir_all(shard_dir.join("index")).unwrap();
 
         cleanup_orphan_index_dirs(temp.path());
 
         assert!(!staging_path.exists());
         assert!(!backup_path.exists());
+        assert!(!shard_backup_path.exists());
+        assert!(shard_dir.join("index").exists());
     }
 }
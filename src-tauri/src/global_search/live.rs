@@ -0,0 +1,769 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use crate::utils::normalize_path;
+use notify::{Config, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tantivy::{Index, IndexReader, IndexWriter, Searcher};
+use tauri::Manager;
+use walkdir::WalkDir;
+
+use super::content::ContentIndexer;
+use super::ignore::{builtin_ignored_paths, IgnoredPathMatcher};
+use super::ignore_files::IgnoreFileStack;
+use super::index::{
+    delete_path_and_descendants, first_indexed_times, global_search_dir, write_meta,
+};
+use super::scan::{
+    add_path_doc, content_index_max_file_size, is_excluded_by_ignore_files, meta_from_status,
+    should_skip_link_metadata,
+};
+use super::scope::normalize_scope_path;
+use super::shard::{find_shard_for_path, shard_index_size, update_shard_status};
+use super::state::{
+    now_millis, GlobalSearchContentFields, GlobalSearchIndexFields, GLOBAL_SEARCH_STATE,
+};
+use super::syntax::is_path_within;
+use super::types::{GlobalSearchLiveUpdateMode, GlobalSearchLiveUpdateSettings};
+
+const LIVE_UPDATE_TICK: Duration = Duration::from_millis(200);
+const LIVE_UPDATE_DEBOUNCE: Duration = Duration::from_millis(750);
+const LIVE_UPDATE_MAX_BATCH_DELAY: Duration = Duration::from_secs(5);
+const LIVE_UPDATE_MEMORY_BUDGET_BYTES: usize = 15_000_000;
+const DEFAULT_POLL_INTERVAL_MS: u64 = 30_000;
+
+type NotifyEventResult = notify::Result<notify::Event>;
+type LiveWatcher = (Box<dyn Watcher + Send>, GlobalSearchLiveUpdateMode);
+
+static LIVE_UPDATE_STOP_FLAG: Lazy<Mutex<Option<Arc<AtomicBool>>>> = Lazy::new(|| Mutex::new(None));
+
+pub fn global_search_start_live_updates(
+    app: tauri::AppHandle,
+    settings: GlobalSearchLiveUpdateSettings,
+) -> Result<(), String> {
+    let base_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|error: tauri::Error| error.to_string())?;
+
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    {
+        let mut active_stop_flag = LIVE_UPDATE_STOP_FLAG
+            .lock()
+            .map_err(|error| error.to_string())?;
+        if let Some(previous_stop_flag) = active_stop_flag.replace(stop_flag.clone()) {
+            previous_stop_flag.store(true, Ordering::SeqCst);
+        }
+    }
+
+    std::thread::spawn(move || run_live_updates(base_dir, settings, stop_flag));
+
+    Ok(())
+}
+
+pub fn global_search_stop_live_updates() -> Result<(), String> {
+    let mut active_stop_flag = LIVE_UPDATE_STOP_FLAG
+        .lock()
+        .map_err(|error| error.to_string())?;
+    if let Some(stop_flag) = active_stop_flag.take() {
+        stop_flag.store(true, Ordering::SeqCst);
+    }
+    set_live_update_mode(None);
+    Ok(())
+}
+
+fn set_live_update_mode(mode: Option<GlobalSearchLiveUpdateMode>) {
+    if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
+        state.status.live_update_mode = mode;
+    }
+}
+
+fn is_active_stop_flag(stop_flag: &Arc<AtomicBool>) -> bool {
+    LIVE_UPDATE_STOP_FLAG
+        .lock()
+        .ok()
+        .and_then(|active| active.as_ref().map(|active| Arc::ptr_eq(active, stop_flag)))
+        .unwrap_or(false)
+}
+
+fn is_index_relevant_event(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Any | EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(_)
+    )
+}
+
+fn watch_roots(watcher: &mut dyn Watcher, roots: &[String]) -> notify::Result<()> {
+    for root in roots {
+        watcher.watch(Path::new(root), RecursiveMode::Recursive)?;
+    }
+    Ok(())
+}
+
+/// Prefers native recursive watches and falls back to polling when the platform
+/// watcher cannot be created or runs out of watch handles (e.g. inotify limits).
+/// Polling only covers the configured poll roots inside the indexed drives;
+/// returns `None` when there are none, leaving live updates off.
+fn start_watcher(
+    roots: &[String],
+    settings: &GlobalSearchLiveUpdateSettings,
+    sender: &Sender<NotifyEventResult>,
+) -> Result<Option<LiveWatcher>, String> {
+    if !settings.force_polling {
+        match RecommendedWatcher::new(sender.clone(), Config::default()) {
+            Ok(mut watcher) => match watch_roots(&mut watcher, roots) {
+                Ok(()) => {
+                    return Ok(Some((
+                        Box::new(watcher),
+                        GlobalSearchLiveUpdateMode::Native,
+                    )))
+                }
+                Err(error) => {
+                    log::warn!(
+                        "Global search live updates falling back to polling: {}",
+                        error
+                    )
+                }
+            },
+            Err(error) => {
+                log::warn!(
+                    "Global search live updates falling back to polling: {}",
+                    error
+                )
+            }
+        }
+    }
+
+    let poll_roots = poll_roots_within(&settings.poll_roots, roots);
+    if poll_roots.is_empty() {
+        log::warn!(
+            "Global search live updates are off: native watching is unavailable and no poll roots are configured"
+        );
+        return Ok(None);
+    }
+
+    let poll_interval = Duration::from_millis(
+        settings
+            .poll_interval_ms
+            .unwrap_or(DEFAULT_POLL_INTERVAL_MS)
+            .max(1000),
+    );
+    let mut watcher = PollWatcher::new(
+        sender.clone(),
+        Config::default().with_poll_interval(poll_interval),
+    )
+    .map_err(|error| error.to_string())?;
+    watch_roots(&mut watcher, &poll_roots).map_err(|error| error.to_string())?;
+
+    Ok(Some((
+        Box::new(watcher),
+        GlobalSearchLiveUpdateMode::Polling,
+    )))
+}
+
+/// The configured poll roots that lie inside one of the indexed drive roots.
+fn poll_roots_within(poll_roots: &[String], drive_roots: &[String]) -> Vec<String> {
+    let drive_roots: Vec<String> = drive_roots
+        .iter()
+        .map(|root| normalize_scope_path(root))
+        .collect();
+    poll_roots
+        .iter()
+        .map(|root| normalize_scope_path(root))
+        .filter(|root| {
+            drive_roots
+                .iter()
+                .any(|drive_root| is_path_within(root, drive_root))
+        })
+        .collect()
+}
+
+fn current_indexed_drive_roots() -> Vec<String> {
+    GLOBAL_SEARCH_STATE
+        .read()
+        .map(|state| state.status.indexed_drive_roots.clone())
+        .unwrap_or_default()
+}
+
+fn run_live_updates(
+    base_dir: PathBuf,
+    settings: GlobalSearchLiveUpdateSettings,
+    stop_flag: Arc<AtomicBool>,
+) {
+    // The index lives under the app data dir, which may itself be below a watched
+    // root; without this every commit would trigger another batch.
+    let ignored_paths: Vec<String> = settings
+        .ignored_paths
+        .iter()
+        .map(|path| path.to_string())
+        .chain(builtin_ignored_paths().iter().map(|path| path.to_string()))
+        .chain(std::iter::once(normalize_path(
+            &global_search_dir(&base_dir).to_string_lossy(),
+        )))
+        .collect();
+    let ignored_matcher =
+        IgnoredPathMatcher::new(&ignored_paths).with_ignore_files(settings.respect_ignore_files);
+
+    let (sender, receiver) = channel::<NotifyEventResult>();
+    let mut watcher: Option<Box<dyn Watcher + Send>> = None;
+    let mut watched_roots: Vec<String> = Vec::new();
+    let mut pending_paths: BTreeSet<String> = BTreeSet::new();
+    let mut first_pending_time: Option<Instant> = None;
+    let mut last_event_time = Instant::now();
+
+    while !stop_flag.load(Ordering::SeqCst) {
+        let indexed_drive_roots = current_indexed_drive_roots();
+        if indexed_drive_roots != watched_roots {
+            watcher = None;
+            watched_roots = indexed_drive_roots;
+
+            if !watched_roots.is_empty() {
+                match start_watcher(&watched_roots, &settings, &sender) {
+                    Ok(Some((new_watcher, mode))) => {
+                        watcher = Some(new_watcher);
+                        set_live_update_mode(Some(mode));
+                    }
+                    Ok(None) => set_live_update_mode(None),
+                    Err(error) => {
+                        log::error!("Failed to start global search live updates: {}", error);
+                        set_live_update_mode(None);
+                    }
+                }
+            }
+        }
+
+        match receiver.recv_timeout(LIVE_UPDATE_TICK) {
+            Ok(Ok(event)) => {
+                if is_index_relevant_event(&event.kind) {
+                    for path in &event.paths {
+                        let Some(path_str) = path.to_str() else {
+                            continue;
+                        };
+                        let normalized = normalize_path(path_str);
+                        if ignored_matcher.is_ignored(&normalized) {
+                            continue;
+                        }
+                        pending_paths.insert(normalized);
+                        last_event_time = Instant::now();
+                        first_pending_time.get_or_insert(last_event_time);
+                    }
+                }
+            }
+            Ok(Err(error)) => {
+                log::warn!("Global search live update watcher error: {}", error);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let Some(first_pending) = first_pending_time else {
+            continue;
+        };
+        let now = Instant::now();
+        if now.duration_since(last_event_time) < LIVE_UPDATE_DEBOUNCE
+            && now.duration_since(first_pending) < LIVE_UPDATE_MAX_BATCH_DELAY
+        {
+            continue;
+        }
+
+        match apply_live_update_batch(&base_dir, &pending_paths, &settings, &ignored_matcher) {
+            Ok(true) => {
+                pending_paths.clear();
+                first_pending_time = None;
+            }
+            Ok(false) => {}
+            Err(error) => {
+                log::warn!("Failed to apply global search live update: {}", error);
+                pending_paths.clear();
+                first_pending_time = None;
+            }
+        }
+    }
+
+    drop(watcher);
+
+    if is_active_stop_flag(&stop_flag) {
+        set_live_update_mode(None);
+    }
+}
+
+/// Drops paths whose ancestor is also pending, since re-indexing the ancestor
+/// already covers its whole subtree.
+fn collapse_nested_paths(paths: &BTreeSet<String>) -> Vec<String> {
+    paths
+        .iter()
+        .filter(|path| {
+            !path
+                .match_indices('/')
+                .any(|(separator_index, _)| paths.contains(&path[..separator_index]))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Returns how many levels below its indexed root `path` sits, matching the
+/// depth numbering `WalkDir` uses during full scans.
+fn depth_below_roots(path: &str, roots: &[String]) -> Option<usize> {
+    roots
+        .iter()
+        .filter_map(|root| {
+            let root_prefix = if root.ends_with('/') {
+                root.clone()
+            } else {
+                format!("{root}/")
+            };
+            let relative = path.strip_prefix(&root_prefix)?;
+            if relative.is_empty() {
+                return None;
+            }
+            Some((root_prefix.len(), relative.split('/').count()))
+        })
+        .max_by_key(|(root_len, _depth)| *root_len)
+        .map(|(_root_len, depth)| depth)
+}
+
+fn should_walk_live_entry(path: &Path, ignored_matcher: &IgnoredPathMatcher) -> bool {
+    let Some(path_str) = path.to_str() else {
+        return false;
+    };
+    if ignored_matcher.is_ignored(&normalize_path(path_str)) {
+        return false;
+    }
+    std::fs::symlink_metadata(path).is_ok_and(|metadata| !should_skip_link_metadata(&metadata))
+}
+
+/// Replaces the indexed documents for each changed path with the current state on
+/// disk: removed paths only lose their documents, created or renamed directories
+/// are walked up to the configured scan depth.
+/// The name index a live batch writes to, with a searcher over its committed
+/// documents for the first-indexed times of the paths it replaces.
+struct LiveNameIndex<'a> {
+    writer: &'a IndexWriter,
+    searcher: Searcher,
+    fields: &'a GlobalSearchIndexFields,
+}
+
+fn apply_paths_to_index(
+    name_index: &LiveNameIndex,
+    content_indexer: Option<&ContentIndexer>,
+    paths: &[String],
+    roots: &[String],
+    scan_depth: usize,
+    ignored_matcher: &IgnoredPathMatcher,
+) -> Result<(), String> {
+    let LiveNameIndex {
+        writer,
+        searcher,
+        fields,
+    } = name_index;
+    let scan_depth = scan_depth.max(1);
+
+    for path_string in paths {
+        let Some(depth) = depth_below_roots(path_string, roots) else {
+            continue;
+        };
+        if depth > scan_depth {
+            continue;
+        }
+
+        let first_indexed = first_indexed_times(searcher, fields, path_string)?;
+        let indexed_time = now_millis();
+        let indexed_time_of = |path: &str| first_indexed.get(path).copied().unwrap_or(indexed_time);
+        delete_path_and_descendants(writer, fields.path, path_string)?;
+        if let Some(content_indexer) = content_indexer {
+            delete_path_and_descendants(
+                content_indexer.writer,
+                content_indexer.fields.path,
+                path_string,
+            )?;
+        }
+
+        let path = Path::new(path_string);
+        let mut ignore_files = ignored_matcher
+            .respects_ignore_files()
+            .then(|| IgnoreFileStack::for_walk_from(path));
+        if !should_walk_live_entry(path, ignored_matcher)
+            || ignore_files
+                .as_mut()
+                .is_some_and(|ignore_files| ignore_files.is_ignored(path, path.is_dir()))
+            || !add_path_doc(
+                writer,
+                fields,
+                content_indexer,
+                path,
+                path_string,
+                indexed_time_of(path_string),
+            )
+        {
+            continue;
+        }
+
+        if !path.is_dir() || depth >= scan_depth {
+            continue;
+        }
+
+        for entry in WalkDir::new(path)
+            .follow_links(false)
+            .min_depth(1)
+            .max_depth(scan_depth - depth)
+            .into_iter()
+            .filter_entry(|entry| {
+                should_walk_live_entry(entry.path(), ignored_matcher)
+                    && !is_excluded_by_ignore_files(ignore_files.as_mut(), entry)
+            })
+            .flatten()
+        {
+            let Some(entry_path) = entry.path().to_str().map(normalize_path) else {
+                continue;
+            };
+            add_path_doc(
+                writer,
+                fields,
+                content_indexer,
+                entry.path(),
+                &entry_path,
+                indexed_time_of(&entry_path),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Changed paths that fall under one shard, together with the handles needed
+/// to write them.
+struct LiveShardBatch {
+    drive_root: String,
+    index: Index,
+    reader: IndexReader,
+    content: Option<(Index, IndexReader)>,
+    paths: Vec<String>,
+}
+
+/// Applies one debounced batch of changes. Returns `Ok(false)` when the batch has
+/// to wait, e.g. while a full scan is rebuilding the index or another writer holds
+/// the index lock.
+fn apply_live_update_batch(
+    base_dir: &Path,
+    pending_paths: &BTreeSet<String>,
+    settings: &GlobalSearchLiveUpdateSettings,
+    ignored_matcher: &IgnoredPathMatcher,
+) -> Result<bool, String> {
+    let (shard_batches, fields, content_fields) = {
+        let state = GLOBAL_SEARCH_STATE
+            .read()
+            .map_err(|error| error.to_string())?;
+        if state.status.is_scan_in_progress {
+            return Ok(false);
+        }
+
+        let mut paths_by_shard: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for path in collapse_nested_paths(pending_paths) {
+            if let Some(shard_index) = find_shard_for_path(&state.shards, &path)
+                .filter(|shard_index| state.shards[*shard_index].is_online)
+            {
+                paths_by_shard.entry(shard_index).or_default().push(path);
+            }
+        }
+
+        let shard_batches: Vec<LiveShardBatch> = paths_by_shard
+            .into_iter()
+            .map(|(shard_index, paths)| {
+                let shard = &state.shards[shard_index];
+                LiveShardBatch {
+                    drive_root: shard.drive_root.clone(),
+                    index: shard.index.clone(),
+                    reader: shard.reader.clone(),
+                    content: shard
+                        .content
+                        .as_ref()
+                        .map(|content| (content.index.clone(), content.reader.clone())),
+                    paths,
+                }
+            })
+            .collect();
+
+        (shard_batches, state.fields, state.content_fields)
+    };
+
+    if shard_batches.is_empty() {
+        return Ok(true);
+    }
+
+    // Re-applying a shard that already committed is harmless, so a batch that
+    // has to wait for one shard is simply retried in full.
+    let mut shard_sizes: Vec<(&str, u64)> = Vec::with_capacity(shard_batches.len());
+    for shard_batch in &shard_batches {
+        if !apply_live_shard_batch(
+            shard_batch,
+            &fields,
+            content_fields,
+            settings,
+            ignored_matcher,
+        )? {
+            return Ok(false);
+        }
+        shard_sizes.push((
+            &shard_batch.drive_root,
+            shard_index_size(base_dir, &shard_batch.drive_root),
+        ));
+    }
+
+    let mut state = GLOBAL_SEARCH_STATE
+        .write()
+        .map_err(|error| error.to_string())?;
+    for (drive_root, index_size_bytes) in shard_sizes {
+        if let Some(shard) = state
+            .shards
+            .iter_mut()
+            .find(|shard| shard.drive_root == drive_root)
+        {
+            shard.index_size_bytes = index_size_bytes;
+        }
+    }
+    update_shard_status(&mut state);
+    state.status.last_live_update_time = Some(now_millis());
+    write_meta(base_dir, &meta_from_status(&state))?;
+
+    Ok(true)
+}
+
+fn apply_live_shard_batch(
+    shard_batch: &LiveShardBatch,
+    fields: &GlobalSearchIndexFields,
+    content_fields: GlobalSearchContentFields,
+    settings: &GlobalSearchLiveUpdateSettings,
+    ignored_matcher: &IgnoredPathMatcher,
+) -> Result<bool, String> {
+    let mut writer: IndexWriter = match shard_batch
+        .index
+        .writer_with_num_threads(1, LIVE_UPDATE_MEMORY_BUDGET_BYTES)
+    {
+        Ok(writer) => writer,
+        Err(tantivy::TantivyError::LockFailure(..)) => return Ok(false),
+        Err(error) => return Err(error.to_string()),
+    };
+
+    let mut content_writer: Option<IndexWriter> = match &shard_batch.content {
+        Some((content_index, _)) => {
+            match content_index.writer_with_num_threads(1, LIVE_UPDATE_MEMORY_BUDGET_BYTES) {
+                Ok(content_writer) => Some(content_writer),
+                Err(tantivy::TantivyError::LockFailure(..)) => return Ok(false),
+                Err(error) => return Err(error.to_string()),
+            }
+        }
+        None => None,
+    };
+
+    let content_indexed_count = AtomicU64::new(0);
+    let content_indexer = content_writer
+        .as_ref()
+        .map(|content_writer| ContentIndexer {
+            writer: content_writer,
+            fields: content_fields,
+            max_file_size: content_index_max_file_size(settings.content_max_file_size),
+            indexed_count: &content_indexed_count,
+        });
+
+    apply_paths_to_index(
+        &LiveNameIndex {
+            writer: &writer,
+            searcher: shard_batch.reader.searcher(),
+            fields,
+        },
+        content_indexer.as_ref(),
+        &shard_batch.paths,
+        std::slice::from_ref(&shard_batch.drive_root),
+        settings.scan_depth,
+        ignored_matcher,
+    )?;
+
+    writer.commit().map_err(|error| error.to_string())?;
+    shard_batch
+        .reader
+        .reload()
+        .map_err(|error| error.to_string())?;
+
+    if let (Some(content_writer), Some((_, content_reader))) =
+        (content_writer.as_mut(), &shard_batch.content)
+    {
+        content_writer.commit().map_err(|error| error.to_string())?;
+        content_reader.reload().map_err(|error| error.to_string())?;
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::global_search::index::build_schema;
+    use crate::global_search::tokenizer::register_name_tokenizer;
+    use tantivy::collector::Count;
+    use tantivy::query::TermQuery;
+    use tantivy::schema::IndexRecordOption;
+    use tantivy::Term;
+    use tempfile::TempDir;
+
+    #[test]
+    fn collapse_nested_paths_keeps_only_topmost_changes() {
+        let paths: BTreeSet<String> = ["/a/b", "/a/b/c", "/a/b-c", "/a/b/c/d", "/e"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        assert_eq!(
+            collapse_nested_paths(&paths),
+            vec!["/a/b".to_string(), "/a/b-c".to_string(), "/e".to_string()]
+        );
+    }
+
+    #[test]
+    fn depth_below_roots_uses_longest_matching_root() {
+        let roots = vec!["/".to_string(), "/mnt/data".to_string()];
+
+        assert_eq!(depth_below_roots("/home/user", &roots), Some(2));
+        assert_eq!(depth_below_roots("/mnt/data/file.txt", &roots), Some(1));
+        assert_eq!(depth_below_roots("/mnt/data", &roots), Some(2));
+        assert_eq!(depth_below_roots("/", &roots), None);
+    }
+
+    #[test]
+    fn poll_roots_within_keeps_configured_roots_inside_indexed_drives() {
+        let drive_roots = vec!["/".to_string(), "D:/".to_string()];
+        let poll_roots = vec![
+            "/home/user/Documents/".to_string(),
+            "D:\\Projects".to_string(),
+            "E:/Backups".to_string(),
+        ];
+
+        assert_eq!(
+            poll_roots_within(&poll_roots, &drive_roots),
+            vec![
+                "/home/user/Documents".to_string(),
+                "D:/Projects".to_string()
+            ]
+        );
+        assert!(poll_roots_within(&[], &drive_roots).is_empty());
+    }
+
+    #[test]
+    fn apply_paths_to_index_replaces_renamed_directory() {
+        let temp = TempDir::new().unwrap();
+        let root = normalize_path(&temp.path().to_string_lossy());
+        let old_dir = format!("{root}/old");
+        let new_dir = format!("{root}/new");
+        std::fs::create_dir_all(format!("{old_dir}/nested")).unwrap();
+        std::fs::write(format!("{old_dir}/nested/report.txt"), "report").unwrap();
+
+        let (schema, fields) = build_schema();
+        let index = tantivy::Index::create_in_ram(schema);
+        register_name_tokenizer(&index);
+        let mut writer = index.writer(50_000_000).unwrap();
+        let reader = index.reader().unwrap();
+        let matcher = IgnoredPathMatcher::new(&[]);
+        let roots = vec![root.clone()];
+
+        apply_paths_to_index(
+            &LiveNameIndex {
+                writer: &writer,
+
+                searcher: reader.searcher(),
+
+                fields: &fields,
+            },
+            None,
+            std::slice::from_ref(&old_dir),
+            &roots,
+            10,
+            &matcher,
+        )
+        .unwrap();
+        writer.commit().unwrap();
+        reader.reload().unwrap();
+
+        std::fs::rename(&old_dir, &new_dir).unwrap();
+        apply_paths_to_index(
+            &LiveNameIndex {
+                writer: &writer,
+                searcher: reader.searcher(),
+                fields: &fields,
+            },
+            None,
+            &[old_dir.clone(), new_dir.clone()],
+            &roots,
+            10,
+            &matcher,
+        )
+        .unwrap();
+        writer.commit().unwrap();
+        reader.reload().unwrap();
+
+        let searcher = reader.searcher();
+        let count_path = |path: String| {
+            let query = TermQuery::new(
+                Term::from_field_text(fields.path, &path),
+                IndexRecordOption::Basic,
+            );
+            searcher.search(&query, &Count).unwrap()
+        };
+
+        assert_eq!(searcher.num_docs(), 3);
+        assert_eq!(count_path(format!("{old_dir}/nested/report.txt")), 0);
+        assert_eq!(count_path(format!("{new_dir}/nested/report.txt")), 1);
+    }
+
+    #[test]
+    fn apply_paths_to_index_keeps_first_indexed_time_of_changed_files() {
+        let temp = TempDir::new().unwrap();
+        let root = normalize_path(&temp.path().to_string_lossy());
+        let existing = format!("{root}/existing.txt");
+        let added = format!("{root}/added.txt");
+        std::fs::write(&existing, "v1").unwrap();
+
+        let (schema, fields) = build_schema();
+        let index = tantivy::Index::create_in_ram(schema);
+        register_name_tokenizer(&index);
+        let mut writer = index.writer(50_000_000).unwrap();
+        let reader = index.reader().unwrap();
+        let matcher = IgnoredPathMatcher::new(&[]);
+        let roots = vec![root.clone()];
+        let mut apply = |paths: &[String]| {
+            apply_paths_to_index(
+                &LiveNameIndex {
+                    writer: &writer,
+                    searcher: reader.searcher(),
+                    fields: &fields,
+                },
+                None,
+                paths,
+                &roots,
+                10,
+                &matcher,
+            )
+            .unwrap();
+            writer.commit().unwrap();
+            reader.reload().unwrap();
+        };
+        let indexed_time =
+            |path: &str| first_indexed_times(&reader.searcher(), &fields, path).unwrap()[path];
+
+        apply(std::slice::from_ref(&existing));
+        let first_seen = indexed_time(&existing);
+
+        std::thread::sleep(Duration::from_millis(5));
+        std::fs::write(&existing, "v2").unwrap();
+        std::fs::write(&added, "new").unwrap();
+        apply(&[existing.clone(), added.clone()]);
+
+        assert_eq!(indexed_time(&existing), first_seen);
+        assert!(indexed_time(&added) > first_seen);
+    }
+}
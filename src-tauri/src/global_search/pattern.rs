@@ -0,0 +1,345 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Regex and glob name matching for global search.
+//!
+//! Patterns are matched case-insensitively against the whole file name or the
+//! whole normalized path. Where the pattern translates into the regex dialect
+//! of tantivy's term automaton it is pushed down as a `RegexQuery`; otherwise
+//! the term dictionary is scanned with the full matcher. Every hit is checked
+//! again with the full matcher, so the pushed-down pattern only has to be a
+//! superset.
+
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use std::collections::BTreeSet;
+use std::time::Instant;
+use tantivy::query::{Query, RegexQuery, TermSetQuery};
+use tantivy::schema::Field;
+use tantivy::{Searcher, Term};
+
+use super::state::GlobalSearchIndexFields;
+use super::types::{
+    GlobalSearchMatchMode, GlobalSearchMatchTarget, GlobalSearchQueryError,
+    GlobalSearchQueryErrorKind, GlobalSearchQueryOptions,
+};
+
+const REGEX_SIZE_LIMIT_BYTES: usize = 10 * (1 << 20);
+const MAX_SCANNED_TERM_MATCHES: usize = 100_000;
+const DEADLINE_CHECK_INTERVAL: usize = 4096;
+
+enum PatternMatcher {
+    Regex(Regex),
+    Glob(GlobMatcher),
+}
+
+pub(super) struct PatternQuery {
+    matcher: PatternMatcher,
+    target: GlobalSearchMatchTarget,
+    index_pattern: Option<String>,
+}
+
+fn invalid_pattern(message: String) -> GlobalSearchQueryError {
+    GlobalSearchQueryError {
+        kind: GlobalSearchQueryErrorKind::Syntax,
+        message,
+        token: None,
+        position: None,
+    }
+}
+
+/// Returns `None` in fuzzy mode, where the query goes through the structured
+/// query parser instead.
+pub(super) fn compile_pattern_query(
+    pattern: &str,
+    options: &GlobalSearchQueryOptions,
+) -> Result<Option<PatternQuery>, GlobalSearchQueryError> {
+    let target = match options.match_target {
+        GlobalSearchMatchTarget::Auto if pattern.contains('/') => GlobalSearchMatchTarget::Path,
+        GlobalSearchMatchTarget::Auto => GlobalSearchMatchTarget::Name,
+        target => target,
+    };
+
+    let (matcher, index_pattern) = match options.match_mode {
+        GlobalSearchMatchMode::Fuzzy => return Ok(None),
+        GlobalSearchMatchMode::Regex => {
+            let regex = RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .size_limit(REGEX_SIZE_LIMIT_BYTES)
+                .build()
+                .map_err(|error| invalid_pattern(format!("Invalid regex: {error}")))?;
+            (
+                PatternMatcher::Regex(regex),
+                Some(regex_to_index_pattern(pattern)),
+            )
+        }
+        GlobalSearchMatchMode::Glob => {
+            let glob = GlobBuilder::new(pattern)
+                .case_insensitive(true)
+                .literal_separator(true)
+                .build()
+                .map_err(|error| invalid_pattern(format!("Invalid glob: {error}")))?;
+            (
+                PatternMatcher::Glob(glob.compile_matcher()),
+                glob_to_index_pattern(pattern),
+            )
+        }
+    };
+
+    Ok(Some(PatternQuery {
+        matcher,
+        target,
+        index_pattern,
+    }))
+}
+
+/// Widens a user regex to a full-term match. Anchors are only dropped when the
+/// pattern has no alternation, because `^a|b` anchors just one branch.
+fn regex_to_index_pattern(pattern: &str) -> String {
+    let has_alternation = pattern.contains('|');
+    let (prefix, body) = match pattern.strip_prefix('^') {
+        Some(rest) if !has_alternation => ("", rest),
+        _ => (".*", pattern),
+    };
+    let (body, suffix) = match body.strip_suffix('$') {
+        Some(rest) if !has_alternation && !rest.ends_with('\\') => (rest, ""),
+        _ => (body, ".*"),
+    };
+    format!("(?i){prefix}(?:{body}){suffix}")
+}
+
+fn push_escaped(output: &mut String, character: char) {
+    if "\\.+*?()|[]{}^$#&-~".contains(character) {
+        output.push('\\');
+    }
+    output.push(character);
+}
+
+/// Translates a glob into an equivalent regex for the term automaton, or
+/// `None` when the glob uses something the translation does not cover.
+fn glob_to_index_pattern(glob: &str) -> Option<String> {
+    let characters: Vec<char> = glob.chars().collect();
+    let mut output = String::from("(?i)");
+    let mut alternation_depth = 0;
+    let mut index = 0;
+
+    while index < characters.len() {
+        let character = characters[index];
+        match character {
+            '*' if characters.get(index + 1) == Some(&'*') => {
+                while characters.get(index + 1) == Some(&'*') {
+                    index += 1;
+                }
+                if characters.get(index + 1) == Some(&'/') {
+                    index += 1;
+                    output.push_str("(?:.*/)?");
+                } else {
+                    output.push_str(".*");
+                }
+            }
+            '*' => output.push_str("[^/]*"),
+            '?' => output.push_str("[^/]"),
+            '[' => {
+                let mut class_end = index + 1;
+                if matches!(characters.get(class_end), Some('!') | Some('^')) {
+                    class_end += 1;
+                }
+                if characters.get(class_end) == Some(&']') {
+                    class_end += 1;
+                }
+                while class_end < characters.len() && characters[class_end] != ']' {
+                    class_end += 1;
+                }
+                if class_end >= characters.len() {
+                    return None;
+                }
+
+                output.push('[');
+                let mut class_start = index + 1;
+                if matches!(characters[class_start], '!' | '^') {
+                    output.push('^');
+                    class_start += 1;
+                }
+                for &class_character in &characters[class_start..class_end] {
+                    if matches!(class_character, '\\' | '[' | '&' | '~') {
+                        output.push('\\');
+                    }
+                    output.push(class_character);
+                }
+                output.push(']');
+                index = class_end;
+            }
+            '{' => {
+                alternation_depth += 1;
+                output.push_str("(?:");
+            }
+            ',' if alternation_depth > 0 => output.push('|'),
+            '}' if alternation_depth > 0 => {
+                alternation_depth -= 1;
+                output.push(')');
+            }
+            '\\' => {
+                index += 1;
+                push_escaped(&mut output, *characters.get(index)?);
+            }
+            _ => push_escaped(&mut output, character),
+        }
+        index += 1;
+    }
+
+    (alternation_depth == 0).then_some(output)
+}
+
+impl PatternQuery {
+    fn target_field(&self, fields: &GlobalSearchIndexFields) -> Field {
+        match self.target {
+            GlobalSearchMatchTarget::Path => fields.path,
+            _ => fields.name_lower,
+        }
+    }
+
+    pub(super) fn matches(&self, name: &str, path: &str) -> bool {
+        let value = match self.target {
+            GlobalSearchMatchTarget::Path => path,
+            _ => name,
+        };
+        match &self.matcher {
+            PatternMatcher::Regex(regex) => regex.is_match(value),
+            PatternMatcher::Glob(glob) => glob.is_match(value),
+        }
+    }
+
+    /// Builds the index query for this pattern. Falls back to scanning the
+    /// term dictionary when the pattern cannot be pushed down; the scan stops
+    /// at the deadline and searches whatever it matched so far.
+    pub(super) fn build_index_query(
+        &self,
+        searcher: &Searcher,
+        fields: &GlobalSearchIndexFields,
+        deadline: Instant,
+    ) -> Result<Box<dyn Query>, String> {
+        let field = self.target_field(fields);
+
+        if let Some(index_pattern) = &self.index_pattern {
+            if let Ok(regex_query) = RegexQuery::from_pattern(index_pattern, field) {
+                return Ok(Box::new(regex_query));
+            }
+        }
+
+        let mut matched_terms = BTreeSet::new();
+        let mut scanned_count = 0;
+
+        'segments: for segment_reader in searcher.segment_readers() {
+            let inverted_index = segment_reader
+                .inverted_index(field)
+                .map_err(|error| error.to_string())?;
+            let mut term_stream = inverted_index
+                .terms()
+                .stream()
+                .map_err(|error| error.to_string())?;
+
+            while term_stream.advance() {
+                scanned_count += 1;
+                if scanned_count % DEADLINE_CHECK_INTERVAL == 0 && Instant::now() >= deadline {
+                    break 'segments;
+                }
+
+                let Ok(term_text) = std::str::from_utf8(term_stream.key()) else {
+                    continue;
+                };
+                let is_match = match self.target {
+                    GlobalSearchMatchTarget::Path => self.matches("", term_text),
+                    _ => self.matches(term_text, ""),
+                };
+                if is_match {
+                    matched_terms.insert(term_text.to_string());
+                    if matched_terms.len() >= MAX_SCANNED_TERM_MATCHES {
+                        break 'segments;
+                    }
+                }
+            }
+        }
+
+        Ok(Box::new(TermSetQuery::new(
+            matched_terms
+                .iter()
+                .map(|term_text| Term::from_field_text(field, term_text)),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(match_mode: GlobalSearchMatchMode) -> GlobalSearchQueryOptions {
+        GlobalSearchQueryOptions {
+            limit: 10,
+            include_files: true,
+            include_directories: true,
+            exact_match: false,
+            typo_tolerance: false,
+            min_score_threshold: None,
+            match_mode,
+            match_target: GlobalSearchMatchTarget::Auto,
+            timeout_ms: None,
+            scope_path: None,
+            request_id: None,
+        }
+    }
+
+    #[test]
+    fn regex_mode_matches_names_case_insensitively() {
+        let pattern =
+            compile_pattern_query(r"^test_.*\.rs$", &options(GlobalSearchMatchMode::Regex))
+                .unwrap()
+                .unwrap();
+
+        assert!(pattern.matches("Test_parser.rs", "/src/Test_parser.rs"));
+        assert!(!pattern.matches("my_test_parser.rs", "/src/my_test_parser.rs"));
+        assert_eq!(
+            pattern.index_pattern.as_deref(),
+            Some(r"(?i)(?:test_.*\.rs)")
+        );
+    }
+
+    #[test]
+    fn glob_with_separator_matches_against_path() {
+        let pattern =
+            compile_pattern_query("**/migrations/*.sql", &options(GlobalSearchMatchMode::Glob))
+                .unwrap()
+                .unwrap();
+
+        assert!(pattern.matches("001.sql", "/app/db/migrations/001.sql"));
+        assert!(!pattern.matches("001.sql", "/app/db/migrations/old/001.sql"));
+        assert_eq!(
+            pattern.index_pattern.as_deref(),
+            Some(r"(?i)(?:.*/)?migrations/[^/]*\.sql")
+        );
+    }
+
+    #[test]
+    fn glob_translation_handles_classes_and_alternation() {
+        assert_eq!(
+            glob_to_index_pattern("[!a-c]?.{jpg,png}").as_deref(),
+            Some(r"(?i)[^a-c][^/]\.(?:jpg|png)")
+        );
+        assert_eq!(glob_to_index_pattern("{a,b"), None);
+    }
+
+    #[test]
+    fn invalid_patterns_return_syntax_errors() {
+        let error = compile_pattern_query("(unclosed", &options(GlobalSearchMatchMode::Regex))
+            .err()
+            .unwrap();
+        assert_eq!(error.kind, GlobalSearchQueryErrorKind::Syntax);
+
+        assert!(compile_pattern_query("a[", &options(GlobalSearchMatchMode::Glob)).is_err());
+        assert!(
+            compile_pattern_query("a[", &options(GlobalSearchMatchMode::Fuzzy))
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -2,24 +2,158 @@
 // License: GNU GPLv3 or later. See the license file in the project root for more information.
 // Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
 
+use crate::frecency;
 use crate::utils::{
     is_hidden_path, metadata_times_unix_ms, normalize_path, path_extension_lowercase,
 };
 use rayon::prelude::*;
+use std::fs::Metadata;
+use std::ops::Bound;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use tantivy::collector::TopDocs;
-use tantivy::query::{AllQuery, BooleanQuery, FuzzyTermQuery, Occur, Query, TermQuery};
-use tantivy::schema::{IndexRecordOption, Value};
+use tantivy::query::{
+    AllQuery, BooleanQuery, FuzzyTermQuery, Occur, Query, RangeQuery, RegexQuery, TermQuery,
+};
+use tantivy::schema::{Field, IndexRecordOption, Value};
 use tantivy::Term;
 use tauri::Manager;
 
 use super::ignore::{builtin_ignored_paths, get_drive_root, normalize_case, IgnoredPathMatcher};
-use super::index::{index_dir, open_or_create_index};
+use super::index::path_and_descendants_query;
+use super::pattern::{compile_pattern_query, PatternQuery};
+use super::scope::{
+    live_scope_search_blocking, normalize_scope_path, scope_descendants_query, unindexed_scope,
+};
 use super::scoring::{calculate_similarity_score, get_min_score_for_query_length};
-use super::state::{GlobalSearchIndexFields, GLOBAL_SEARCH_STATE};
-use super::types::{GlobalSearchQueryOptions, GlobalSearchResultEntry};
+use super::shard::ensure_shards_loaded;
+use super::state::{now_millis, GlobalSearchIndexFields, GLOBAL_SEARCH_STATE};
+use super::syntax::{
+    parse_structured_query, EntryFacts, EntryTypeFilter, QueryBranch, QueryFilter, StructuredQuery,
+};
+use super::tokenizer::{analyze_name, contains_cjk};
+use super::types::{GlobalSearchQueryError, GlobalSearchQueryOptions, GlobalSearchResultEntry};
+
+const DEFAULT_QUERY_TIMEOUT_MS: u64 = 5_000;
+/// Largest score boost a frequently and recently visited path can receive.
+const FRECENCY_BOOST_WEIGHT: f32 = 0.25;
 
 pub(super) fn build_query(
+    fields: &GlobalSearchIndexFields,
+    parsed: &StructuredQuery,
+    options: &GlobalSearchQueryOptions,
+) -> Result<Box<dyn Query>, GlobalSearchQueryError> {
+    let mut branch_queries: Vec<Box<dyn Query>> = Vec::new();
+    for branch in &parsed.branches {
+        branch_queries.push(build_branch_query(fields, branch, options)?);
+    }
+
+    if branch_queries.len() == 1 {
+        return Ok(branch_queries.remove(0));
+    }
+
+    Ok(Box::new(BooleanQuery::union(branch_queries)))
+}
+
+fn build_branch_query(
+    fields: &GlobalSearchIndexFields,
+    branch: &QueryBranch,
+    options: &GlobalSearchQueryOptions,
+) -> Result<Box<dyn Query>, GlobalSearchQueryError> {
+    let text_query = build_text_query(fields, &branch.text_query(), options);
+
+    if branch.filters.is_empty()
+        && branch.excluded_filters.is_empty()
+        && branch.excluded_text.is_empty()
+    {
+        return Ok(text_query);
+    }
+
+    let mut subqueries: Vec<(Occur, Box<dyn Query>)> = vec![(Occur::Must, text_query)];
+
+    for filter in &branch.filters {
+        subqueries.push((Occur::Must, build_filter_query(fields, filter)?));
+    }
+
+    for filter in &branch.excluded_filters {
+        subqueries.push((Occur::MustNot, build_filter_query(fields, filter)?));
+    }
+
+    for excluded in &branch.excluded_text {
+        let normalized = normalize_case(excluded);
+        if !analyze_name(&normalized).is_empty() {
+            subqueries.push((Occur::MustNot, build_exact_match_query(fields, &normalized)));
+        }
+    }
+
+    Ok(Box::new(BooleanQuery::from(subqueries)))
+}
+
+pub(super) fn u64_range_query(
+    field: Field,
+    lower: Bound<u64>,
+    upper: Bound<u64>,
+) -> Box<dyn Query> {
+    Box::new(RangeQuery::new(
+        lower.map(|value| Term::from_field_u64(field, value)),
+        upper.map(|value| Term::from_field_u64(field, value)),
+    ))
+}
+
+fn flag_query(field: Field) -> Box<dyn Query> {
+    u64_range_query(field, Bound::Included(1), Bound::Included(1))
+}
+
+fn escape_regex(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        if "\\.+*?()|[]{}^$#&-~".contains(character) {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+fn build_filter_query(
+    fields: &GlobalSearchIndexFields,
+    filter: &QueryFilter,
+) -> Result<Box<dyn Query>, GlobalSearchQueryError> {
+    let query: Box<dyn Query> = match filter {
+        QueryFilter::Extension(extensions) => Box::new(BooleanQuery::union(
+            extensions
+                .iter()
+                .map(|ext| {
+                    Box::new(TermQuery::new(
+                        Term::from_field_text(fields.ext, ext),
+                        IndexRecordOption::Basic,
+                    )) as Box<dyn Query>
+                })
+                .collect(),
+        )),
+        QueryFilter::Size(lower, upper) => Box::new(BooleanQuery::intersection(vec![
+            flag_query(fields.is_file),
+            u64_range_query(fields.size, *lower, *upper),
+        ])),
+        QueryFilter::Modified(lower, upper) => {
+            u64_range_query(fields.modified_time, *lower, *upper)
+        }
+        QueryFilter::PathPrefix(prefix) => path_and_descendants_query(fields.path, prefix),
+        QueryFilter::PathSegment(segments) => {
+            let pattern = format!(".*/{}(/.*)?", escape_regex(segments));
+            Box::new(
+                RegexQuery::from_pattern(&pattern, fields.path)
+                    .map_err(|error| GlobalSearchQueryError::from(error.to_string()))?,
+            )
+        }
+        QueryFilter::Type(EntryTypeFilter::File) => flag_query(fields.is_file),
+        QueryFilter::Type(EntryTypeFilter::Dir) => flag_query(fields.is_dir),
+    };
+
+    Ok(query)
+}
+
+fn build_text_query(
     fields: &GlobalSearchIndexFields,
     query: &str,
     options: &GlobalSearchQueryOptions,
@@ -34,7 +168,7 @@ pub(super) fn build_query(
         return build_exact_match_query(fields, &normalized);
     }
 
-    let words = split_query_tokens(&normalized);
+    let words = analyze_name(&normalized);
 
     let max_distance = if options.typo_tolerance { 2 } else { 1 };
 
@@ -42,17 +176,14 @@ pub(super) fn build_query(
 
     for word in &words {
         let term = Term::from_field_text(fields.name, word);
-        let fuzzy = FuzzyTermQuery::new(term, max_distance, true);
-        subqueries.push((Occur::Should, Box::new(fuzzy)));
-    }
-
-    let whitespace_words: Vec<&str> = normalized.split_whitespace().collect();
-    for word in whitespace_words {
-        if word.contains('.') || word.contains('_') || word.contains('-') {
-            let term = Term::from_field_text(fields.name, word);
-            let fuzzy = FuzzyTermQuery::new(term, max_distance, true);
-            subqueries.push((Occur::Should, Box::new(fuzzy)));
-        }
+        // CJK n-grams and one or two character parts would fuzzy-match almost
+        // anything, so they only match exactly.
+        let query: Box<dyn Query> = if contains_cjk(word) || word.chars().count() <= 2 {
+            Box::new(TermQuery::new(term, IndexRecordOption::Basic))
+        } else {
+            Box::new(FuzzyTermQuery::new(term, max_distance, true))
+        };
+        subqueries.push((Occur::Should, query));
     }
 
     Box::new(BooleanQuery::from(subqueries))
@@ -62,7 +193,7 @@ fn build_exact_match_query(
     fields: &GlobalSearchIndexFields,
     normalized_query: &str,
 ) -> Box<dyn Query> {
-    let words = split_query_tokens(normalized_query);
+    let words = analyze_name(normalized_query);
 
     if words.is_empty() {
         return Box::new(AllQuery);
@@ -87,23 +218,13 @@ fn build_exact_match_query(
     Box::new(BooleanQuery::from(subqueries))
 }
 
-fn split_query_tokens(value: &str) -> Vec<String> {
-    value
-        .split(|character: char| {
-            character.is_whitespace() || character == '.' || character == '_' || character == '-'
-        })
-        .filter(|segment| !segment.is_empty())
-        .map(|segment| segment.to_string())
-        .collect()
-}
-
 fn matches_exact_name(query: &str, name: &str) -> bool {
-    let query_tokens = split_query_tokens(&normalize_case(query));
+    let query_tokens = analyze_name(query);
     if query_tokens.is_empty() {
         return true;
     }
 
-    let name_tokens = split_query_tokens(&normalize_case(name));
+    let name_tokens = analyze_name(name);
     query_tokens.iter().all(|query_token| {
         name_tokens
             .iter()
@@ -122,18 +243,105 @@ pub(super) fn matches_type(
     (options.include_files && is_file) || (options.include_directories && is_dir)
 }
 
+/// Scores an entry against every branch it satisfies and keeps the best one.
+/// Branches without free text only filter, so they match with a full score.
+fn score_entry(
+    parsed: &StructuredQuery,
+    entry: &EntryFacts,
+    options: &GlobalSearchQueryOptions,
+) -> Option<f32> {
+    parsed
+        .branches
+        .iter()
+        .filter(|branch| branch.matches_filters(entry, matches_exact_name))
+        .filter_map(|branch| {
+            let normalized_query = normalize_case(&branch.text_query());
+            if normalized_query.is_empty() {
+                return Some(1.0);
+            }
+
+            if options.exact_match && !matches_exact_name(&normalized_query, entry.name) {
+                return None;
+            }
+
+            let name_score = calculate_similarity_score(&normalized_query, entry.name);
+            let min_score = options
+                .min_score_threshold
+                .unwrap_or_else(|| get_min_score_for_query_length(normalized_query.len()));
+
+            (name_score >= min_score).then_some(name_score)
+        })
+        .max_by(|score_a, score_b| {
+            score_a
+                .partial_cmp(score_b)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+}
+
+pub(super) enum CompiledQuery {
+    Structured(StructuredQuery),
+    Pattern(PatternQuery),
+}
+
+pub(super) fn compile_query(
+    query: &str,
+    options: &GlobalSearchQueryOptions,
+) -> Result<CompiledQuery, GlobalSearchQueryError> {
+    match compile_pattern_query(query, options)? {
+        Some(pattern) => Ok(CompiledQuery::Pattern(pattern)),
+        None => Ok(CompiledQuery::Structured(parse_structured_query(
+            query,
+            now_millis(),
+        )?)),
+    }
+}
+
+impl CompiledQuery {
+    pub(super) fn score(
+        &self,
+        entry: &EntryFacts,
+        options: &GlobalSearchQueryOptions,
+    ) -> Option<f32> {
+        match self {
+            CompiledQuery::Structured(parsed) => score_entry(parsed, entry, options),
+            CompiledQuery::Pattern(pattern) => {
+                pattern.matches(entry.name, entry.path).then_some(1.0)
+            }
+        }
+    }
+}
+
+fn restrict_to_scope(
+    fields: &GlobalSearchIndexFields,
+    query: Box<dyn Query>,
+    options: &GlobalSearchQueryOptions,
+) -> Box<dyn Query> {
+    match options.scope_path.as_deref() {
+        Some(scope_path) => Box::new(BooleanQuery::intersection(vec![
+            query,
+            scope_descendants_query(fields, &normalize_scope_path(scope_path)),
+        ])),
+        None => query,
+    }
+}
+
+fn query_deadline(options: &GlobalSearchQueryOptions) -> Instant {
+    Instant::now() + Duration::from_millis(options.timeout_ms.unwrap_or(DEFAULT_QUERY_TIMEOUT_MS))
+}
+
 pub async fn global_search_query(
     app: tauri::AppHandle,
     query: String,
     options: GlobalSearchQueryOptions,
-) -> Result<Vec<GlobalSearchResultEntry>, String> {
+) -> Result<Vec<GlobalSearchResultEntry>, GlobalSearchQueryError> {
     let base_dir = app
         .path()
         .app_data_dir()
         .map_err(|error: tauri::Error| error.to_string())?;
 
-    tauri::async_runtime::spawn_blocking(move || {
-        global_search_query_blocking(base_dir, query, options)
+    tauri::async_runtime::spawn_blocking(move || match unindexed_scope(&base_dir, &options)? {
+        Some(scope_path) => live_scope_search_blocking(app, scope_path, query, options),
+        None => global_search_query_blocking(base_dir, query, options),
     })
     .await
     .map_err(|join_error| format!("Global search query task failed: {join_error}"))?
@@ -143,60 +351,15 @@ fn global_search_query_blocking(
     base_dir: PathBuf,
     query: String,
     options: GlobalSearchQueryOptions,
-) -> Result<Vec<GlobalSearchResultEntry>, String> {
-    let index_path = index_dir(&base_dir);
-
-    let needs_open = {
-        let state = GLOBAL_SEARCH_STATE
-            .read()
-            .map_err(|error| error.to_string())?;
-        if state.status.is_committing {
-            return Ok(Vec::new());
-        }
-        state.index.is_none() || state.reader.is_none() || state.fields.is_none()
-    };
-
-    if needs_open {
-        let (index, reader, fields) = open_or_create_index(&index_path)?;
-        let mut state = GLOBAL_SEARCH_STATE
-            .write()
-            .map_err(|error| error.to_string())?;
-
-        if state.status.is_committing {
-            return Ok(Vec::new());
-        }
-
-        if state.index.is_none() || state.reader.is_none() || state.fields.is_none() {
-            state.index = Some(index);
-            state.reader = Some(reader);
-            state.fields = Some(fields);
-        }
-    }
+) -> Result<Vec<GlobalSearchResultEntry>, GlobalSearchQueryError> {
+    let compiled = compile_query(&query, &options)?;
+    let deadline = query_deadline(&options);
+    ensure_shards_loaded(&base_dir)?;
 
     let state = GLOBAL_SEARCH_STATE
         .read()
         .map_err(|error| error.to_string())?;
-
-    if state.status.is_committing {
-        return Ok(Vec::new());
-    }
-
-    let reader = state
-        .reader
-        .as_ref()
-        .ok_or_else(|| "Search index reader is not initialized".to_string())?;
-    let fields = *state
-        .fields
-        .as_ref()
-        .ok_or_else(|| "Search index fields are not initialized".to_string())?;
-
-    let searcher = reader.searcher();
-    let normalized_query = normalize_case(&query);
-
-    let query_boxed = build_query(&fields, &query, &options);
-    let top_docs = searcher
-        .search(&query_boxed, &TopDocs::with_limit(100_000).order_by_score())
-        .map_err(|error| error.to_string())?;
+    let fields = state.fields;
 
     let internal_ignored: Vec<String> = builtin_ignored_paths()
         .iter()
@@ -204,81 +367,106 @@ fn global_search_query_blocking(
         .collect();
     let internal_ignored_matcher = IgnoredPathMatcher::new(&internal_ignored);
 
-    let min_score = options
-        .min_score_threshold
-        .unwrap_or_else(|| get_min_score_for_query_length(normalized_query.len()));
+    // Every shard is searched with the same query; name scores do not depend on
+    // shard-local statistics, so the hits merge into one ranking directly.
+    let mut results: Vec<GlobalSearchResultEntry> = Vec::new();
+    for shard in &state.shards {
+        let searcher = shard.reader.searcher();
 
-    let results: Vec<GlobalSearchResultEntry> = top_docs
-        .par_iter()
-        .filter_map(|(_tantivy_score, doc_address)| {
-            let retrieved: tantivy::TantivyDocument = searcher.doc(*doc_address).ok()?;
-
-            let path_value = retrieved
-                .get_first(fields.path)
-                .and_then(|value| value.as_str())?
-                .to_string();
-
-            if internal_ignored_matcher.is_ignored(&path_value) {
-                return None;
+        let query_boxed = match &compiled {
+            CompiledQuery::Structured(parsed) => build_query(&fields, parsed, &options)?,
+            CompiledQuery::Pattern(pattern) => {
+                pattern.build_index_query(&searcher, &fields, deadline)?
             }
+        };
+        let query_boxed = restrict_to_scope(&fields, query_boxed, &options);
+        let top_docs = searcher
+            .search(&query_boxed, &TopDocs::with_limit(100_000).order_by_score())
+            .map_err(|error| error.to_string())?;
 
-            let name_value = retrieved
-                .get_first(fields.name)
-                .and_then(|value| value.as_str())?
-                .to_string();
+        results.par_extend(
+            top_docs
+                .par_iter()
+                .filter_map(|(_tantivy_score, doc_address)| {
+                    if Instant::now() >= deadline {
+                        return None;
+                    }
 
-            if options.exact_match && !matches_exact_name(&normalized_query, &name_value) {
-                return None;
-            }
+                    let retrieved: tantivy::TantivyDocument = searcher.doc(*doc_address).ok()?;
 
-            let name_score = calculate_similarity_score(&normalized_query, &name_value);
+                    let path_value = retrieved
+                        .get_first(fields.path)
+                        .and_then(|value| value.as_str())?
+                        .to_string();
 
-            if name_score < min_score {
-                return None;
-            }
+                    if internal_ignored_matcher.is_ignored(&path_value) {
+                        return None;
+                    }
 
-            let doc_is_file = retrieved
-                .get_first(fields.is_file)
-                .and_then(|value| value.as_u64())
-                .unwrap_or(0);
-            let doc_is_dir = retrieved
-                .get_first(fields.is_dir)
-                .and_then(|value| value.as_u64())
-                .unwrap_or(0);
+                    let name_value = retrieved
+                        .get_first(fields.name)
+                        .and_then(|value| value.as_str())?
+                        .to_string();
+
+                    let doc_is_file = retrieved
+                        .get_first(fields.is_file)
+                        .and_then(|value| value.as_u64())
+                        .unwrap_or(0);
+                    let doc_is_dir = retrieved
+                        .get_first(fields.is_dir)
+                        .and_then(|value| value.as_u64())
+                        .unwrap_or(0);
+
+                    if !matches_type(doc_is_file, doc_is_dir, &options) {
+                        return None;
+                    }
 
-            if !matches_type(doc_is_file, doc_is_dir, &options) {
-                return None;
-            }
+                    let modified_time = retrieved
+                        .get_first(fields.modified_time)
+                        .and_then(|value| value.as_u64())
+                        .unwrap_or(0);
+                    let size = retrieved
+                        .get_first(fields.size)
+                        .and_then(|value| value.as_u64())
+                        .unwrap_or(0);
+
+                    let ext = path_extension_lowercase(Path::new(&path_value));
+
+                    let name_score = compiled.score(
+                        &EntryFacts {
+                            name: &name_value,
+                            path: &path_value,
+                            ext: ext.as_deref().filter(|_| doc_is_file == 1),
+                            size,
+                            modified_time,
+                            is_file: doc_is_file == 1,
+                            is_dir: doc_is_dir == 1,
+                        },
+                        &options,
+                    )?;
+
+                    Some(GlobalSearchResultEntry {
+                        name: name_value,
+                        ext,
+                        path: path_value,
+                        size,
+                        item_count: None,
+                        modified_time,
+                        accessed_time: 0,
+                        created_time: 0,
+                        mime: None,
+                        is_file: doc_is_file == 1,
+                        is_dir: doc_is_dir == 1,
+                        is_symlink: false,
+                        is_hidden: false,
+                        score: name_score,
+                    })
+                }),
+        );
+    }
+    drop(state);
 
-            let modified_time = retrieved
-                .get_first(fields.modified_time)
-                .and_then(|value| value.as_u64())
-                .unwrap_or(0);
-            let size = retrieved
-                .get_first(fields.size)
-                .and_then(|value| value.as_u64())
-                .unwrap_or(0);
-
-            let ext = path_extension_lowercase(Path::new(&path_value));
-
-            Some(GlobalSearchResultEntry {
-                name: name_value,
-                ext,
-                path: path_value,
-                size,
-                item_count: None,
-                modified_time,
-                accessed_time: 0,
-                created_time: 0,
-                mime: None,
-                is_file: doc_is_file == 1,
-                is_dir: doc_is_dir == 1,
-                is_symlink: false,
-                is_hidden: false,
-                score: name_score,
-            })
-        })
-        .collect();
+    apply_frecency_boost(&base_dir, &mut results);
 
     let mut drive_groups: std::collections::HashMap<String, Vec<GlobalSearchResultEntry>> =
         std::collections::HashMap::new();
@@ -310,17 +498,27 @@ fn global_search_query_blocking(
     Ok(final_results)
 }
 
+fn apply_frecency_boost(base_dir: &Path, entries: &mut [GlobalSearchResultEntry]) {
+    let paths: Vec<&str> = entries.iter().map(|entry| entry.path.as_str()).collect();
+    let boosts = frecency::normalized_scores(base_dir, &paths);
+    for (entry, boost) in entries.iter_mut().zip(boosts) {
+        entry.score += FRECENCY_BOOST_WEIGHT * boost as f32;
+    }
+}
+
 pub async fn global_search_query_paths(
     paths: Vec<String>,
     query: String,
     options: GlobalSearchQueryOptions,
-) -> Result<Vec<GlobalSearchResultEntry>, String> {
+) -> Result<Vec<GlobalSearchResultEntry>, GlobalSearchQueryError> {
     if paths.is_empty() || query.trim().is_empty() {
         return Ok(Vec::new());
     }
 
+    let compiled = compile_query(&query, &options)?;
+
     tauri::async_runtime::spawn_blocking(move || {
-        global_search_query_paths_blocking(paths, query, options)
+        global_search_query_paths_blocking(paths, compiled, options)
     })
     .await
     .map_err(|join_error| format!("Global search path query task failed: {join_error}"))?
@@ -328,11 +526,10 @@ pub async fn global_search_query_paths(
 
 fn global_search_query_paths_blocking(
     paths: Vec<String>,
-    query: String,
+    compiled: CompiledQuery,
     options: GlobalSearchQueryOptions,
-) -> Result<Vec<GlobalSearchResultEntry>, String> {
-    let normalized_query = normalize_case(&query);
-    let min_score = get_min_score_for_query_length(normalized_query.len());
+) -> Result<Vec<GlobalSearchResultEntry>, GlobalSearchQueryError> {
+    let deadline = query_deadline(&options);
 
     let all_searchable_paths: Vec<PathBuf> = paths
         .par_iter()
@@ -359,81 +556,105 @@ fn global_search_query_paths_blocking(
     let results: Vec<GlobalSearchResultEntry> = all_searchable_paths
         .par_iter()
         .filter_map(|path| {
-            let name = path
-                .file_name()
-                .and_then(|segment| segment.to_str())?
-                .to_string();
-
-            if options.exact_match && !matches_exact_name(&normalized_query, &name) {
-                return None;
-            }
-
-            let name_score = calculate_similarity_score(&normalized_query, &name);
-
-            if name_score < min_score {
+            if Instant::now() >= deadline {
                 return None;
             }
 
             let metadata = std::fs::metadata(path).ok()?;
-
-            let is_file = metadata.is_file();
-            let is_dir = metadata.is_dir();
-
-            if !matches_type(
-                if is_file { 1 } else { 0 },
-                if is_dir { 1 } else { 0 },
-                &options,
-            ) {
-                return None;
-            }
-
-            let (modified_time, accessed_time, created_time) = metadata_times_unix_ms(&metadata);
-
-            let size = if is_file { metadata.len() } else { 0 };
-
-            let ext = path_extension_lowercase(path);
-
-            let path_string = path.to_string_lossy().to_string();
-            let normalized_path = normalize_path(&path_string);
-
-            Some(GlobalSearchResultEntry {
-                name,
-                ext,
-                path: normalized_path,
-                size,
-                item_count: None,
-                modified_time,
-                accessed_time,
-                created_time,
-                mime: None,
-                is_file,
-                is_dir,
-                is_symlink: metadata.is_symlink(),
-                is_hidden: is_hidden_path(path),
-                score: name_score,
-            })
+            entry_from_path(path, &metadata, &compiled, &options)
         })
         .collect();
 
     let mut sorted_results = results;
-    sorted_results.par_sort_by(|entry_a, entry_b| {
+    sort_and_truncate_by_score(&mut sorted_results, options.limit);
+
+    Ok(sorted_results)
+}
+
+pub(super) fn sort_and_truncate_by_score(entries: &mut Vec<GlobalSearchResultEntry>, limit: usize) {
+    entries.par_sort_by(|entry_a, entry_b| {
         entry_b
             .score
             .partial_cmp(&entry_a.score)
             .unwrap_or(std::cmp::Ordering::Equal)
     });
 
-    if sorted_results.len() > options.limit {
-        sorted_results.truncate(options.limit);
+    if entries.len() > limit {
+        entries.truncate(limit);
     }
+}
 
-    Ok(sorted_results)
+/// Builds a result entry for a path read from disk, or `None` when it does not
+/// match the query or the requested entry types.
+pub(super) fn entry_from_path(
+    path: &Path,
+    metadata: &Metadata,
+    compiled: &CompiledQuery,
+    options: &GlobalSearchQueryOptions,
+) -> Option<GlobalSearchResultEntry> {
+    let name = path
+        .file_name()
+        .and_then(|segment| segment.to_str())?
+        .to_string();
+
+    let is_file = metadata.is_file();
+    let is_dir = metadata.is_dir();
+
+    if !matches_type(
+        if is_file { 1 } else { 0 },
+        if is_dir { 1 } else { 0 },
+        options,
+    ) {
+        return None;
+    }
+
+    let (modified_time, accessed_time, created_time) = metadata_times_unix_ms(metadata);
+
+    let size = if is_file { metadata.len() } else { 0 };
+
+    let ext = path_extension_lowercase(path);
+
+    let path_string = path.to_string_lossy().to_string();
+    let normalized_path = normalize_path(&path_string);
+
+    let name_score = compiled.score(
+        &EntryFacts {
+            name: &name,
+            path: &normalized_path,
+            ext: ext.as_deref().filter(|_| is_file),
+            size,
+            modified_time,
+            is_file,
+            is_dir,
+        },
+        options,
+    )?;
+
+    Some(GlobalSearchResultEntry {
+        name,
+        ext,
+        path: normalized_path,
+        size,
+        item_count: None,
+        modified_time,
+        accessed_time,
+        created_time,
+        mime: None,
+        is_file,
+        is_dir,
+        is_symlink: metadata.is_symlink(),
+        is_hidden: is_hidden_path(path),
+        score: name_score,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::global_search::index::build_schema;
+    use crate::global_search::state::{GlobalSearchShard, GLOBAL_SEARCH_TEST_LOCK};
+    use crate::global_search::tokenizer::register_name_tokenizer;
+    use crate::global_search::types::{GlobalSearchMatchMode, GlobalSearchMatchTarget};
     use tantivy::doc;
 
     fn create_options(exact_match: bool) -> GlobalSearchQueryOptions {
@@ -444,19 +665,33 @@ mod tests {
             exact_match,
             typo_tolerance: true,
             min_score_threshold: Some(0.0),
+            match_mode: GlobalSearchMatchMode::Fuzzy,
+            match_target: GlobalSearchMatchTarget::Auto,
+            timeout_ms: None,
+            scope_path: None,
+            request_id: None,
         }
     }
 
     fn search_names(query_text: &str, exact_match: bool) -> Vec<String> {
+        search_names_in(
+            &[
+                "Exile by Aleksey Hoffman.jpg",
+                "Exiled by Aleksey Hoffman.jpg",
+                "Example.jpg",
+            ],
+            query_text,
+            exact_match,
+        )
+    }
+
+    fn search_names_in(names: &[&str], query_text: &str, exact_match: bool) -> Vec<String> {
         let (schema, fields) = build_schema();
         let index = tantivy::Index::create_in_ram(schema);
+        register_name_tokenizer(&index);
         let mut writer = index.writer(50_000_000).unwrap();
 
-        for name in [
-            "Exile by Aleksey Hoffman.jpg",
-            "Exiled by Aleksey Hoffman.jpg",
-            "Example.jpg",
-        ] {
+        for &name in names {
             writer
                 .add_document(doc!(
                     fields.path => format!("C:/test/{name}"),
@@ -474,7 +709,12 @@ mod tests {
 
         let reader = index.reader().unwrap();
         let searcher = reader.searcher();
-        let query = build_query(&fields, query_text, &create_options(exact_match));
+        let query = build_query(
+            &fields,
+            &parse_structured_query(query_text, 0).unwrap(),
+            &create_options(exact_match),
+        )
+        .unwrap();
         let top_docs = searcher
             .search(&query, &TopDocs::with_limit(10).order_by_score())
             .unwrap();
@@ -504,6 +744,248 @@ mod tests {
         assert!(!names.contains(&"Exiled by Aleksey Hoffman.jpg".to_string()));
     }
 
+    fn search_paths_with_filters(query_text: &str) -> Vec<String> {
+        search_paths_with_options(query_text, create_options(false))
+    }
+
+    fn search_paths_with_options(
+        query_text: &str,
+        options: GlobalSearchQueryOptions,
+    ) -> Vec<String> {
+        let (schema, fields) = build_schema();
+        let index = tantivy::Index::create_in_ram(schema);
+        register_name_tokenizer(&index);
+        let mut writer = index.writer(50_000_000).unwrap();
+
+        for (path, is_file, size) in [
+            ("/work/invoice 2024.pdf", true, 8 * 1024 * 1024),
+            ("/work/invoice draft.pdf", true, 8 * 1024 * 1024),
+            ("/work/invoice small.pdf", true, 1024),
+            ("/work/invoice.txt", true, 8 * 1024 * 1024),
+            ("/home/invoice big.pdf", true, 8 * 1024 * 1024),
+            ("/work/invoices", false, 0),
+        ] {
+            let name = path.rsplit('/').next().unwrap();
+            let mut document = doc!(
+                fields.path => path.to_string(),
+                fields.name => name.to_string(),
+                fields.name_lower => name.to_lowercase(),
+                fields.is_file => u64::from(is_file),
+                fields.is_dir => u64::from(!is_file),
+                fields.modified_time => 0u64,
+                fields.size => size as u64,
+            );
+            if is_file {
+                if let Some(ext) = path_extension_lowercase(Path::new(path)) {
+                    document.add_text(fields.ext, ext);
+                }
+            }
+            writer.add_document(document).unwrap();
+        }
+
+        writer.commit().unwrap();
+
+        let reader = index.reader().unwrap();
+        let searcher = reader.searcher();
+        let query = match compile_query(query_text, &options).unwrap() {
+            CompiledQuery::Structured(parsed) => build_query(&fields, &parsed, &options).unwrap(),
+            CompiledQuery::Pattern(pattern) => pattern
+                .build_index_query(&searcher, &fields, query_deadline(&options))
+                .unwrap(),
+        };
+        let query = restrict_to_scope(&fields, query, &options);
+        let top_docs = searcher
+            .search(&query, &TopDocs::with_limit(10).order_by_score())
+            .unwrap();
+
+        let mut paths: Vec<String> = top_docs
+            .into_iter()
+            .filter_map(|(_score, address)| {
+                let doc: tantivy::TantivyDocument = searcher.doc(address).ok()?;
+                doc.get_first(fields.path)
+                    .and_then(|value| value.as_str())
+                    .map(|value| value.to_string())
+            })
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn structured_query_applies_filters_and_negation() {
+        let paths = search_paths_with_filters("invoice ext:pdf size:>5MB path:/work -draft");
+
+        assert_eq!(paths, vec!["/work/invoice 2024.pdf".to_string()]);
+    }
+
+    #[test]
+    fn structured_query_combines_or_branches() {
+        let paths = search_paths_with_filters("type:dir OR ext:txt");
+
+        assert_eq!(
+            paths,
+            vec![
+                "/work/invoice.txt".to_string(),
+                "/work/invoices".to_string()
+            ]
+        );
+    }
+
+    fn create_pattern_options(match_mode: GlobalSearchMatchMode) -> GlobalSearchQueryOptions {
+        GlobalSearchQueryOptions {
+            match_mode,
+            ..create_options(false)
+        }
+    }
+
+    #[test]
+    fn regex_mode_pushes_anchored_pattern_to_index() {
+        let paths = search_paths_with_options(
+            r"^invoice \d+\.pdf$",
+            create_pattern_options(GlobalSearchMatchMode::Regex),
+        );
+
+        assert_eq!(paths, vec!["/work/invoice 2024.pdf".to_string()]);
+    }
+
+    #[test]
+    fn regex_mode_scans_terms_when_pattern_cannot_be_pushed_down() {
+        let paths = search_paths_with_options(
+            r"\bdraft\b",
+            create_pattern_options(GlobalSearchMatchMode::Regex),
+        );
+
+        assert_eq!(paths, vec!["/work/invoice draft.pdf".to_string()]);
+    }
+
+    #[test]
+    fn glob_mode_matches_full_path_with_separator() {
+        let paths = search_paths_with_options(
+            "/work/*.{txt,PDF}",
+            GlobalSearchQueryOptions {
+                match_target: GlobalSearchMatchTarget::Path,
+                ..create_pattern_options(GlobalSearchMatchMode::Glob)
+            },
+        );
+
+        assert_eq!(
+            paths,
+            vec![
+                "/work/invoice 2024.pdf".to_string(),
+                "/work/invoice draft.pdf".to_string(),
+                "/work/invoice small.pdf".to_string(),
+                "/work/invoice.txt".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn scoped_query_only_matches_below_the_folder() {
+        let paths = search_paths_with_options(
+            "invoice ext:pdf",
+            GlobalSearchQueryOptions {
+                scope_path: Some("/home/".to_string()),
+                ..create_options(false)
+            },
+        );
+
+        assert_eq!(paths, vec!["/home/invoice big.pdf".to_string()]);
+    }
+
+    #[test]
+    fn name_tokens_split_camel_case_cjk_and_fold_accents() {
+        let names = [
+            "reportFinal.pdf",
+            "年度報告書.docx",
+            "Café menu.txt",
+            "unrelated.txt",
+        ];
+
+        assert_eq!(
+            search_names_in(&names, "final", true),
+            vec!["reportFinal.pdf"]
+        );
+        assert_eq!(
+            search_names_in(&names, "報告書", true),
+            vec!["年度報告書.docx"]
+        );
+        assert_eq!(search_names_in(&names, "cafe", true), vec!["Café menu.txt"]);
+    }
+
+    fn ram_shard(drive_root: &str, names: &[&str]) -> GlobalSearchShard {
+        let (schema, fields) = build_schema();
+        let index = tantivy::Index::create_in_ram(schema);
+        register_name_tokenizer(&index);
+        let mut writer = index.writer(50_000_000).unwrap();
+        for &name in names {
+            writer
+                .add_document(doc!(
+                    fields.path => format!("{drive_root}/{name}"),
+                    fields.name => name.to_string(),
+                    fields.name_lower => name.to_lowercase(),
+                    fields.is_file => 1u64,
+                    fields.is_dir => 0u64,
+                    fields.modified_time => 0u64,
+                    fields.size => 0u64,
+                ))
+                .unwrap();
+        }
+        writer.commit().unwrap();
+
+        GlobalSearchShard {
+            drive_root: drive_root.to_string(),
+            reader: index.reader().unwrap(),
+            index,
+            content: None,
+            is_online: true,
+            last_scan_time: None,
+            index_size_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn query_merges_hits_from_every_shard_and_honors_scope() {
+        let _lock_guard = GLOBAL_SEARCH_TEST_LOCK.lock().unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        {
+            let mut state = GLOBAL_SEARCH_STATE.write().unwrap();
+            state.shards = vec![
+                ram_shard("/mnt/photos", &["holiday.jpg", "portrait.jpg"]),
+                ram_shard("/mnt/work", &["holiday plan.txt", "budget.xlsx"]),
+            ];
+            state.are_shards_loaded = true;
+        }
+        let search = |options: GlobalSearchQueryOptions| {
+            let mut paths: Vec<String> = global_search_query_blocking(
+                temp_dir.path().to_path_buf(),
+                "holiday".to_string(),
+                options,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+            paths.sort();
+            paths
+        };
+
+        assert_eq!(
+            search(create_options(false)),
+            vec!["/mnt/photos/holiday.jpg", "/mnt/work/holiday plan.txt"]
+        );
+        assert_eq!(
+            search(GlobalSearchQueryOptions {
+                scope_path: Some("/mnt/work".to_string()),
+                ..create_options(false)
+            }),
+            vec!["/mnt/work/holiday plan.txt"]
+        );
+
+        let mut state = GLOBAL_SEARCH_STATE.write().unwrap();
+        state.shards = vec![];
+        state.are_shards_loaded = false;
+    }
+
     #[test]
     fn exact_name_filter_matches_all_query_tokens() {
         assert!(matches_exact_name(
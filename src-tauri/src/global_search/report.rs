@@ -0,0 +1,588 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Cleanup reports answered from the index alone: sizes and modified times
+//! come from fast fields, so no report touches the disk.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::ops::Bound;
+use std::path::Path;
+use tantivy::aggregation::agg_req::Aggregations;
+use tantivy::aggregation::{AggregationCollector, AggregationLimitsGuard};
+use tantivy::collector::DocSetCollector;
+use tantivy::columnar::Column;
+use tantivy::query::{BooleanQuery, Query};
+use tantivy::schema::Value;
+use tantivy::{DocAddress, Searcher};
+use tauri::Manager;
+
+use super::query::u64_range_query;
+use super::scope::{normalize_scope_path, scope_descendants_query};
+use super::shard::ensure_shards_loaded;
+use super::state::{now_millis, GlobalSearchIndexFields, GlobalSearchShard, GLOBAL_SEARCH_STATE};
+use super::types::{
+    GlobalSearchBreakdownEntry, GlobalSearchBreakdownGroup, GlobalSearchBreakdownOptions,
+    GlobalSearchFileReport, GlobalSearchReportFile, GlobalSearchReportOptions,
+};
+
+const DEFAULT_REPORT_LIMIT: usize = 100;
+const DEFAULT_STALE_DAYS: u64 = 365;
+const DEFAULT_RECENT_ADDITION_MIN_SIZE: u64 = 100 * 1024 * 1024;
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+/// Upper bound on distinct extensions per shard, matching tantivy's default
+/// aggregation bucket limit.
+const MAX_BREAKDOWN_GROUPS: u32 = 65_000;
+
+const ARCHIVE_SUBTYPES: &[&str] = &[
+    "zip",
+    "gzip",
+    "x-gzip",
+    "x-tar",
+    "x-7z-compressed",
+    "vnd.rar",
+    "x-rar-compressed",
+    "x-bzip",
+    "x-bzip2",
+    "x-xz",
+    "x-compress",
+    "zstd",
+    "x-zstd",
+    "java-archive",
+    "vnd.android.package-archive",
+    "x-iso9660-image",
+    "x-apple-diskimage",
+];
+
+const DOCUMENT_SUBTYPE_PREFIXES: &[&str] = &[
+    "pdf",
+    "msword",
+    "rtf",
+    "epub+zip",
+    "vnd.ms-excel",
+    "vnd.ms-powerpoint",
+    "vnd.openxmlformats-officedocument",
+    "vnd.oasis.opendocument",
+];
+
+const TEXT_SUBTYPES: &[&str] = &["json", "xml", "javascript", "x-sh", "toml", "yaml"];
+
+#[derive(Debug, Clone, Copy)]
+enum ReportOrder {
+    LargestFirst,
+    OldestFirst,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct ReportFilter {
+    modified_before: Option<u64>,
+    modified_since: Option<u64>,
+    indexed_since: Option<u64>,
+    min_size: Option<u64>,
+}
+
+fn report_query(
+    fields: &GlobalSearchIndexFields,
+    root_path: Option<&str>,
+    filter: &ReportFilter,
+) -> Box<dyn Query> {
+    let mut clauses: Vec<Box<dyn Query>> = vec![u64_range_query(
+        fields.is_file,
+        Bound::Included(1),
+        Bound::Included(1),
+    )];
+
+    if let Some(root_path) = root_path {
+        clauses.push(scope_descendants_query(fields, root_path));
+    }
+    if filter.modified_before.is_some() || filter.modified_since.is_some() {
+        clauses.push(u64_range_query(
+            fields.modified_time,
+            filter
+                .modified_since
+                .map_or(Bound::Unbounded, Bound::Included),
+            filter
+                .modified_before
+                .map_or(Bound::Unbounded, Bound::Excluded),
+        ));
+    }
+    if let Some(indexed_since) = filter.indexed_since {
+        clauses.push(u64_range_query(
+            fields.indexed_time,
+            Bound::Excluded(indexed_since),
+            Bound::Unbounded,
+        ));
+    }
+    if let Some(min_size) = filter.min_size {
+        clauses.push(u64_range_query(
+            fields.size,
+            Bound::Included(min_size),
+            Bound::Unbounded,
+        ));
+    }
+
+    Box::new(BooleanQuery::intersection(clauses))
+}
+
+fn segment_u64_columns(searcher: &Searcher, field_name: &str) -> Result<Vec<Column<u64>>, String> {
+    searcher
+        .segment_readers()
+        .iter()
+        .map(|segment_reader| {
+            segment_reader
+                .fast_fields()
+                .u64(field_name)
+                .map_err(|error| error.to_string())
+        })
+        .collect()
+}
+
+fn report_file(
+    searcher: &Searcher,
+    fields: &GlobalSearchIndexFields,
+    doc_address: DocAddress,
+) -> Option<GlobalSearchReportFile> {
+    let retrieved: tantivy::TantivyDocument = searcher.doc(doc_address).ok()?;
+    let text_value = |field| {
+        retrieved
+            .get_first(field)
+            .and_then(|value| value.as_str())
+            .map(str::to_string)
+    };
+    let u64_value = |field| {
+        retrieved
+            .get_first(field)
+            .and_then(|value| value.as_u64())
+            .unwrap_or(0)
+    };
+
+    Some(GlobalSearchReportFile {
+        name: text_value(fields.name)?,
+        ext: text_value(fields.ext),
+        path: text_value(fields.path)?,
+        size: u64_value(fields.size),
+        modified_time: u64_value(fields.modified_time),
+    })
+}
+
+/// Collects the files matching each shard's filter and keeps the top `limit`
+/// by `order`. Shards for which `shard_filter` returns `None` are skipped.
+fn file_report(
+    base_dir: &Path,
+    options: &GlobalSearchReportOptions,
+    order: ReportOrder,
+    shard_filter: impl Fn(&GlobalSearchShard) -> Option<ReportFilter>,
+) -> Result<GlobalSearchFileReport, String> {
+    ensure_shards_loaded(base_dir)?;
+
+    let state = GLOBAL_SEARCH_STATE
+        .read()
+        .map_err(|error| error.to_string())?;
+    let fields = state.fields;
+    let root_path = options.root_path.as_deref().map(normalize_scope_path);
+    let limit = options.limit.unwrap_or(DEFAULT_REPORT_LIMIT).max(1);
+
+    let mut searchers: Vec<Searcher> = Vec::new();
+    let mut top_files: BinaryHeap<Reverse<(u64, usize, DocAddress)>> = BinaryHeap::new();
+    let mut matched_count: u64 = 0;
+    let mut matched_size_bytes: u64 = 0;
+
+    for shard in &state.shards {
+        let Some(filter) = shard_filter(shard) else {
+            continue;
+        };
+
+        let searcher = shard.reader.searcher();
+        let doc_addresses = searcher
+            .search(
+                &report_query(&fields, root_path.as_deref(), &filter),
+                &DocSetCollector,
+            )
+            .map_err(|error| error.to_string())?;
+        let size_columns = segment_u64_columns(&searcher, "size")?;
+        let modified_time_columns = segment_u64_columns(&searcher, "modified_time")?;
+        let searcher_index = searchers.len();
+
+        for doc_address in doc_addresses {
+            let segment_ord = doc_address.segment_ord as usize;
+            let size = size_columns[segment_ord]
+                .first(doc_address.doc_id)
+                .unwrap_or(0);
+            let modified_time = modified_time_columns[segment_ord]
+                .first(doc_address.doc_id)
+                .unwrap_or(0);
+
+            matched_count += 1;
+            matched_size_bytes += size;
+
+            let rank = match order {
+                ReportOrder::LargestFirst => size,
+                ReportOrder::OldestFirst => u64::MAX - modified_time,
+            };
+            top_files.push(Reverse((rank, searcher_index, doc_address)));
+            if top_files.len() > limit {
+                top_files.pop();
+            }
+        }
+
+        searchers.push(searcher);
+    }
+    drop(state);
+
+    let files = top_files
+        .into_sorted_vec()
+        .into_iter()
+        .filter_map(|Reverse((_, searcher_index, doc_address))| {
+            report_file(&searchers[searcher_index], &fields, doc_address)
+        })
+        .collect();
+
+    Ok(GlobalSearchFileReport {
+        files,
+        matched_count,
+        matched_size_bytes,
+    })
+}
+
+fn largest_files_blocking(
+    base_dir: &Path,
+    options: &GlobalSearchReportOptions,
+) -> Result<GlobalSearchFileReport, String> {
+    file_report(base_dir, options, ReportOrder::LargestFirst, |_| {
+        Some(ReportFilter::default())
+    })
+}
+
+fn stale_files_blocking(
+    base_dir: &Path,
+    options: &GlobalSearchReportOptions,
+) -> Result<GlobalSearchFileReport, String> {
+    let older_than_days = options.older_than_days.unwrap_or(DEFAULT_STALE_DAYS);
+    let modified_before = now_millis().saturating_sub(older_than_days.saturating_mul(DAY_MS));
+
+    file_report(base_dir, options, ReportOrder::OldestFirst, |_| {
+        Some(ReportFilter {
+            modified_before: Some(modified_before),
+            ..ReportFilter::default()
+        })
+    })
+}
+
+/// Large files first indexed after the last scan of their drive, i.e. files
+/// that reached the index through live updates or path reindexing since then.
+/// Files copied in with an old modified time count; edits to known files do not.
+fn recent_additions_blocking(
+    base_dir: &Path,
+    options: &GlobalSearchReportOptions,
+) -> Result<GlobalSearchFileReport, String> {
+    let min_size = options.min_size.unwrap_or(DEFAULT_RECENT_ADDITION_MIN_SIZE);
+
+    file_report(base_dir, options, ReportOrder::LargestFirst, |shard| {
+        let indexed_since = options.since_time.or(shard.last_scan_time)?;
+        Some(ReportFilter {
+            indexed_since: Some(indexed_since),
+            min_size: Some(min_size),
+            ..ReportFilter::default()
+        })
+    })
+}
+
+fn mime_category(ext: &str) -> &'static str {
+    let Some(mime) = mime_guess::from_ext(ext).first() else {
+        return "other";
+    };
+
+    match (mime.type_().as_str(), mime.subtype().as_str()) {
+        ("image", _) => "image",
+        ("video", _) => "video",
+        ("audio", _) => "audio",
+        ("font", _) => "font",
+        ("text", _) => "text",
+        ("application", subtype) if ARCHIVE_SUBTYPES.contains(&subtype) => "archive",
+        ("application", subtype)
+            if DOCUMENT_SUBTYPE_PREFIXES
+                .iter()
+                .any(|prefix| subtype.starts_with(prefix)) =>
+        {
+            "document"
+        }
+        ("application", subtype) if TEXT_SUBTYPES.contains(&subtype) => "text",
+        _ => "other",
+    }
+}
+
+fn extension_breakdown_aggregation() -> Result<Aggregations, String> {
+    serde_json::from_value(serde_json::json!({
+        "by_ext": {
+            "terms": { "field": "ext", "size": MAX_BREAKDOWN_GROUPS, "missing": "" },
+            "aggs": { "total_size": { "sum": { "field": "size" } } }
+        }
+    }))
+    .map_err(|error| error.to_string())
+}
+
+fn size_breakdown_blocking(
+    base_dir: &Path,
+    options: &GlobalSearchBreakdownOptions,
+) -> Result<Vec<GlobalSearchBreakdownEntry>, String> {
+    ensure_shards_loaded(base_dir)?;
+
+    let aggregations = extension_breakdown_aggregation()?;
+    let root_path = options.root_path.as_deref().map(normalize_scope_path);
+    let mut totals: HashMap<String, (u64, u64)> = HashMap::new();
+
+    let state = GLOBAL_SEARCH_STATE
+        .read()
+        .map_err(|error| error.to_string())?;
+    for shard in &state.shards {
+        let searcher = shard.reader.searcher();
+        let collector = AggregationCollector::from_aggs(
+            aggregations.clone(),
+            AggregationLimitsGuard::default(),
+        );
+        let results = searcher
+            .search(
+                &report_query(
+                    &state.fields,
+                    root_path.as_deref(),
+                    &ReportFilter::default(),
+                ),
+                &collector,
+            )
+            .map_err(|error| error.to_string())?;
+        let results = serde_json::to_value(results).map_err(|error| error.to_string())?;
+
+        let buckets = results["by_ext"]["buckets"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        for bucket in buckets {
+            let ext = bucket["key"].as_str().unwrap_or_default();
+            let key = match options.group_by {
+                GlobalSearchBreakdownGroup::Extension => ext.to_string(),
+                GlobalSearchBreakdownGroup::MimeCategory => mime_category(ext).to_string(),
+            };
+            let total = totals.entry(key).or_default();
+            total.0 += bucket["doc_count"].as_u64().unwrap_or(0);
+            total.1 += bucket["total_size"]["value"].as_f64().unwrap_or(0.0) as u64;
+        }
+    }
+    drop(state);
+
+    let mut entries: Vec<GlobalSearchBreakdownEntry> = totals
+        .into_iter()
+        .map(
+            |(key, (item_count, total_size_bytes))| GlobalSearchBreakdownEntry {
+                key,
+                item_count,
+                total_size_bytes,
+            },
+        )
+        .collect();
+    entries.sort_by(|entry_a, entry_b| {
+        entry_b
+            .total_size_bytes
+            .cmp(&entry_a.total_size_bytes)
+            .then_with(|| entry_a.key.cmp(&entry_b.key))
+    });
+
+    Ok(entries)
+}
+
+async fn run_report<T, F>(app: tauri::AppHandle, report: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&Path) -> Result<T, String> + Send + 'static,
+{
+    let base_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|error: tauri::Error| error.to_string())?;
+
+    tauri::async_runtime::spawn_blocking(move || report(&base_dir))
+        .await
+        .map_err(|join_error| format!("Global search report task failed: {join_error}"))?
+}
+
+pub async fn global_search_report_largest_files(
+    app: tauri::AppHandle,
+    options: GlobalSearchReportOptions,
+) -> Result<GlobalSearchFileReport, String> {
+    run_report(app, move |base_dir| {
+        largest_files_blocking(base_dir, &options)
+    })
+    .await
+}
+
+pub async fn global_search_report_stale_files(
+    app: tauri::AppHandle,
+    options: GlobalSearchReportOptions,
+) -> Result<GlobalSearchFileReport, String> {
+    run_report(app, move |base_dir| {
+        stale_files_blocking(base_dir, &options)
+    })
+    .await
+}
+
+pub async fn global_search_report_recent_additions(
+    app: tauri::AppHandle,
+    options: GlobalSearchReportOptions,
+) -> Result<GlobalSearchFileReport, String> {
+    run_report(app, move |base_dir| {
+        recent_additions_blocking(base_dir, &options)
+    })
+    .await
+}
+
+pub async fn global_search_report_size_breakdown(
+    app: tauri::AppHandle,
+    options: GlobalSearchBreakdownOptions,
+) -> Result<Vec<GlobalSearchBreakdownEntry>, String> {
+    run_report(app, move |base_dir| {
+        size_breakdown_blocking(base_dir, &options)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::global_search::index::build_schema;
+    use crate::global_search::tokenizer::register_name_tokenizer;
+    use tantivy::{doc, Index};
+
+    fn add_file(
+        writer: &tantivy::IndexWriter,
+        fields: &GlobalSearchIndexFields,
+        path: &str,
+        size: u64,
+        modified_time: u64,
+    ) {
+        let name = path.rsplit('/').next().unwrap();
+        let mut document = doc!(
+            fields.path => path,
+            fields.name => name,
+            fields.name_lower => name.to_lowercase(),
+            fields.is_file => 1u64,
+            fields.is_dir => 0u64,
+            fields.modified_time => modified_time,
+            fields.size => size,
+            fields.indexed_time => modified_time * 10,
+        );
+        if let Some((_, ext)) = name.rsplit_once('.') {
+            document.add_text(fields.ext, ext);
+        }
+        writer.add_document(document).unwrap();
+    }
+
+    fn test_index() -> (Index, GlobalSearchIndexFields) {
+        let (schema, fields) = build_schema();
+        let index = Index::create_in_ram(schema);
+        register_name_tokenizer(&index);
+        let mut writer = index.writer(15_000_000).unwrap();
+        add_file(&writer, &fields, "/data/movie.mp4", 4_000, 10);
+        add_file(&writer, &fields, "/data/clip.mp4", 1_000, 30);
+        add_file(&writer, &fields, "/data/photo.jpg", 500, 20);
+        add_file(&writer, &fields, "/data/notes/readme", 50, 40);
+        add_file(&writer, &fields, "/other/big.zip", 9_000, 5);
+        writer.commit().unwrap();
+        (index, fields)
+    }
+
+    #[test]
+    fn report_query_filters_by_root_time_and_size() {
+        let (index, fields) = test_index();
+        let searcher = index.reader().unwrap().searcher();
+        let count = |root_path: Option<&str>, filter: ReportFilter| {
+            searcher
+                .search(&report_query(&fields, root_path, &filter), &DocSetCollector)
+                .unwrap()
+                .len()
+        };
+
+        assert_eq!(count(None, ReportFilter::default()), 5);
+        assert_eq!(count(Some("/data"), ReportFilter::default()), 4);
+        assert_eq!(
+            count(
+                Some("/data"),
+                ReportFilter {
+                    modified_before: Some(30),
+                    ..ReportFilter::default()
+                }
+            ),
+            2
+        );
+        assert_eq!(
+            count(
+                None,
+                ReportFilter {
+                    modified_since: Some(20),
+                    min_size: Some(600),
+                    ..ReportFilter::default()
+                }
+            ),
+            1
+        );
+        assert_eq!(
+            count(
+                None,
+                ReportFilter {
+                    indexed_since: Some(100),
+                    min_size: Some(600),
+                    ..ReportFilter::default()
+                }
+            ),
+            1
+        );
+    }
+
+    #[test]
+    fn extension_breakdown_sums_sizes_and_keeps_files_without_extension() {
+        let (index, fields) = test_index();
+        let searcher = index.reader().unwrap().searcher();
+        let collector = AggregationCollector::from_aggs(
+            extension_breakdown_aggregation().unwrap(),
+            AggregationLimitsGuard::default(),
+        );
+        let results = searcher
+            .search(
+                &report_query(&fields, Some("/data"), &ReportFilter::default()),
+                &collector,
+            )
+            .unwrap();
+        let results = serde_json::to_value(results).unwrap();
+
+        let mut buckets: Vec<(String, u64, f64)> = results["by_ext"]["buckets"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|bucket| {
+                (
+                    bucket["key"].as_str().unwrap().to_string(),
+                    bucket["doc_count"].as_u64().unwrap(),
+                    bucket["total_size"]["value"].as_f64().unwrap(),
+                )
+            })
+            .collect();
+        buckets.sort_by(|bucket_a, bucket_b| bucket_a.0.cmp(&bucket_b.0));
+
+        assert_eq!(
+            buckets,
+            vec![
+                (String::new(), 1, 50.0),
+                ("jpg".to_string(), 1, 500.0),
+                ("mp4".to_string(), 2, 5_000.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn mime_categories_group_common_extensions() {
+        assert_eq!(mime_category("jpg"), "image");
+        assert_eq!(mime_category("mp4"), "video");
+        assert_eq!(mime_category("zip"), "archive");
+        assert_eq!(mime_category("docx"), "document");
+        assert_eq!(mime_category(""), "other");
+    }
+}
@@ -2,28 +2,57 @@
 // License: GNU GPLv3 or later. See the license file in the project root for more information.
 // Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
 
-use crate::utils::{metadata_modified_time_unix_ms, normalize_path};
+use crate::utils::{metadata_modified_time_unix_ms, normalize_path, path_extension_lowercase};
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
 use std::fs::Metadata;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use tantivy::{doc, Index, IndexReader, IndexWriter, Term};
+use std::sync::mpsc::{self, SyncSender};
+use tantivy::{doc, IndexWriter};
 use tauri::Manager;
 use walkdir::WalkDir;
 
+use super::content::{
+    create_fresh_content_index, open_or_create_content_index, validate_staged_content_index,
+    ContentIndexer, DEFAULT_CONTENT_MAX_FILE_SIZE,
+};
 use super::ignore::{builtin_ignored_paths, normalize_case, IgnoredPathMatcher};
+use super::ignore_files::{IgnoreFileScope, IgnoreFileStack};
 use super::index::{
-    calculate_dir_size, cleanup_orphan_index_dirs, clear_index, create_bulk_index_writer,
-    create_fresh_index, index_dir, open_or_create_index, read_meta, remove_dir_force,
-    replace_index_dir, staging_index_dir, validate_index, validate_staged_index, write_meta,
-    GlobalSearchMeta, SCHEMA_VERSION,
+    cleanup_orphan_index_dirs, clear_index, content_staging_index_dir, create_bulk_index_writer,
+    create_fresh_index, delete_path_and_descendants, first_indexed_times, read_meta,
+    remove_dir_force, remove_legacy_index_dirs, staging_index_dir, validate_staged_index,
+    write_meta, GlobalSearchMeta, GlobalSearchShardMeta, SCHEMA_VERSION,
+};
+use super::shard::{
+    commit_staged_shard, drop_shard, ensure_shards_loaded, find_shard_for_path, load_shards,
+    refresh_online_flags, shard_content_index_dir, shard_index_size, shards_dir,
+    update_shard_status, StagedShard,
+};
+use super::state::{
+    now_millis, GlobalSearchContentShard, GlobalSearchIndexFields, GlobalSearchState,
+    GLOBAL_SEARCH_STATE,
 };
-use super::state::{now_millis, GlobalSearchIndexFields, GlobalSearchState, GLOBAL_SEARCH_STATE};
 use super::types::{
     GlobalSearchDriveScanError, GlobalSearchScanOutcome, GlobalSearchScanPhase,
-    GlobalSearchSettings, GlobalSearchStatus, IndexPathsSettings,
+    GlobalSearchScanTarget, GlobalSearchSettings, GlobalSearchStatus, IndexPathsSettings,
 };
 
 const STATUS_UPDATE_INTERVAL: u64 = 500;
+const SCAN_BATCH_SIZE: usize = 256;
+const SCAN_CHANNEL_CAPACITY: usize = 64;
+const MAX_SCAN_WORKERS: usize = 32;
+
+/// One pool for every drive, so drives scanned in parallel share the workers
+/// instead of each starting a pool of its own.
+static SCAN_POOL: Lazy<Result<rayon::ThreadPool, String>> = Lazy::new(|| {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(scan_worker_count())
+        .thread_name(|index| format!("global-search-scan-{index}"))
+        .build()
+        .map_err(|error| error.to_string())
+});
 
 #[cfg(windows)]
 fn is_reparse_point(metadata: &Metadata) -> bool {
@@ -37,16 +66,7 @@ fn is_reparse_point(metadata: &Metadata) -> bool {
 fn is_reparse_point(_metadata: &Metadata) -> bool {
     false
 }
-struct CommittedIndexUpdate {
-    doc_count: u64,
-    index_size_bytes: u64,
-    index: Index,
-    reader: IndexReader,
-    fields: GlobalSearchIndexFields,
-    indexed_drive_roots: Option<Vec<String>>,
-}
-
-fn meta_from_status(state: &GlobalSearchState) -> GlobalSearchMeta {
+pub(super) fn meta_from_status(state: &GlobalSearchState) -> GlobalSearchMeta {
     GlobalSearchMeta {
         last_scan_time: state.status.last_scan_time,
         indexed_item_count: state.status.indexed_item_count,
@@ -58,7 +78,15 @@ fn meta_from_status(state: &GlobalSearchState) -> GlobalSearchMeta {
         last_scan_duration_ms: state.status.last_scan_duration_ms,
         last_scan_indexed_item_count: state.status.last_scan_indexed_item_count,
         last_scan_error: state.status.last_scan_error.clone(),
-        indexed_drive_roots: state.status.indexed_drive_roots.clone(),
+        content_indexed_item_count: state.status.content_indexed_item_count,
+        shards: state
+            .shards
+            .iter()
+            .map(|shard| GlobalSearchShardMeta {
+                drive_root: shard.drive_root.clone(),
+                last_scan_time: shard.last_scan_time,
+            })
+            .collect(),
     }
 }
 
@@ -78,7 +106,7 @@ fn set_current_scan_path(path: Option<String>) {
     }
 }
 
-fn should_skip_link_metadata(metadata: &Metadata) -> bool {
+pub(super) fn should_skip_link_metadata(metadata: &Metadata) -> bool {
     metadata.file_type().is_symlink() || is_reparse_point(metadata)
 }
 
@@ -109,33 +137,19 @@ fn should_scan_walk_entry(
     }
 }
 
-fn apply_committed_index_status(
-    state: &mut GlobalSearchState,
-    base_dir: &Path,
-    update: CommittedIndexUpdate,
-    advance_last_scan_time: bool,
-) {
-    let CommittedIndexUpdate {
-        doc_count,
-        index_size_bytes,
-        index,
-        reader,
-        fields,
-        indexed_drive_roots,
-    } = update;
-    state.index = Some(index);
-    state.reader = Some(reader);
-    state.fields = Some(fields);
-    state.status.indexed_item_count = doc_count;
-    state.status.index_size_bytes = index_size_bytes;
-    state.status.is_index_valid = doc_count > 0;
-    if let Some(drive_roots) = indexed_drive_roots {
-        state.status.indexed_drive_roots = drive_roots;
-    }
-    if advance_last_scan_time {
-        state.status.last_scan_time = Some(now_millis());
-    }
-    let _ = write_meta(base_dir, &meta_from_status(state));
+pub(super) fn is_excluded_by_ignore_files(
+    ignore_files: Option<&mut IgnoreFileStack>,
+    entry: &walkdir::DirEntry,
+) -> bool {
+    ignore_files.is_some_and(|ignore_files| {
+        ignore_files.is_ignored(entry.path(), entry.file_type().is_dir())
+    })
+}
+
+pub(super) fn content_index_max_file_size(max_file_size: Option<u64>) -> u64 {
+    max_file_size
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_CONTENT_MAX_FILE_SIZE)
 }
 
 pub fn global_search_init(app: tauri::AppHandle) -> Result<GlobalSearchStatus, String> {
@@ -145,26 +159,18 @@ pub fn global_search_init(app: tauri::AppHandle) -> Result<GlobalSearchStatus, S
         .map_err(|error: tauri::Error| error.to_string())?;
 
     cleanup_orphan_index_dirs(&base_dir);
+    remove_legacy_index_dirs(&base_dir);
 
-    let index_path = index_dir(&base_dir);
-    let is_valid = validate_index(&index_path, &base_dir);
-
-    if !is_valid {
-        let _ = clear_index(&index_path);
+    let meta = read_meta(&base_dir).filter(|meta| meta.schema_version == SCHEMA_VERSION);
+    if meta.is_none() {
+        let _ = clear_index(&shards_dir(&base_dir));
     }
-
-    let (index, reader, fields) = open_or_create_index(&index_path)?;
-
-    let indexed_item_count = reader.searcher().num_docs();
-    let index_size = calculate_dir_size(&index_path);
-    let meta = read_meta(&base_dir);
+    let shards = load_shards(&base_dir);
 
     let mut state = GLOBAL_SEARCH_STATE
         .write()
         .map_err(|error| error.to_string())?;
 
-    state.status.indexed_item_count = indexed_item_count;
-    state.status.index_size_bytes = index_size;
     state.status.last_scan_time = meta
         .as_ref()
         .and_then(|meta_entry| meta_entry.last_scan_time);
@@ -189,30 +195,24 @@ pub fn global_search_init(app: tauri::AppHandle) -> Result<GlobalSearchStatus, S
     state.status.last_scan_error = meta
         .as_ref()
         .and_then(|meta_entry| meta_entry.last_scan_error.clone());
-    state.status.indexed_drive_roots = if is_valid {
-        meta.as_ref()
-            .map(|meta_entry| meta_entry.indexed_drive_roots.clone())
-            .unwrap_or_default()
-    } else {
-        vec![]
-    };
-    state.status.is_index_valid = is_valid && indexed_item_count > 0;
     state.status.scan_phase = GlobalSearchScanPhase::Idle;
     state.status.scan_reason = None;
     state.status.scan_indexed_item_count = 0;
     state.status.current_scan_path = None;
-    state.index = Some(index);
-    state.reader = Some(reader);
-    state.fields = Some(fields);
+    state.shards = shards;
+    state.are_shards_loaded = true;
+    update_shard_status(&mut state);
 
     Ok(state.status.clone())
 }
 
-fn add_path_doc(
+pub(super) fn add_path_doc(
     writer: &IndexWriter,
     fields: &GlobalSearchIndexFields,
+    content_indexer: Option<&ContentIndexer>,
     path: &Path,
     path_string: &str,
+    indexed_time: u64,
 ) -> bool {
     let metadata = match std::fs::symlink_metadata(path) {
         Ok(meta) => meta,
@@ -223,6 +223,26 @@ fn add_path_doc(
         return false;
     }
 
+    add_metadata_doc(
+        writer,
+        fields,
+        content_indexer,
+        path,
+        path_string,
+        &metadata,
+        indexed_time,
+    )
+}
+
+fn add_metadata_doc(
+    writer: &IndexWriter,
+    fields: &GlobalSearchIndexFields,
+    content_indexer: Option<&ContentIndexer>,
+    path: &Path,
+    path_string: &str,
+    metadata: &Metadata,
+    indexed_time: u64,
+) -> bool {
     let is_dir = metadata.is_dir();
     let is_file = metadata.is_file();
 
@@ -232,21 +252,35 @@ fn add_path_doc(
     };
 
     let name_lower = normalize_case(&name);
-    let modified_time = metadata_modified_time_unix_ms(&metadata);
+    let modified_time = metadata_modified_time_unix_ms(metadata);
 
     let size = if is_file { metadata.len() } else { 0 };
 
-    writer
-        .add_document(doc!(
-            fields.path => path_string.to_string(),
-            fields.name => name,
-            fields.name_lower => name_lower,
-            fields.is_file => if is_file { 1u64 } else { 0u64 },
-            fields.is_dir => if is_dir { 1u64 } else { 0u64 },
-            fields.modified_time => modified_time,
-            fields.size => size,
-        ))
-        .is_ok()
+    let mut document = doc!(
+        fields.path => path_string.to_string(),
+        fields.name => name,
+        fields.name_lower => name_lower,
+        fields.is_file => if is_file { 1u64 } else { 0u64 },
+        fields.is_dir => if is_dir { 1u64 } else { 0u64 },
+        fields.modified_time => modified_time,
+        fields.size => size,
+        fields.indexed_time => indexed_time,
+    );
+    if is_file {
+        if let Some(ext) = path_extension_lowercase(path) {
+            document.add_text(fields.ext, ext);
+        }
+    }
+
+    let did_add_doc = writer.add_document(document).is_ok();
+
+    if did_add_doc {
+        if let Some(content_indexer) = content_indexer {
+            content_indexer.index_file(path, path_string, metadata);
+        }
+    }
+
+    did_add_doc
 }
 
 pub fn global_search_get_status() -> Result<GlobalSearchStatus, String> {
@@ -267,6 +301,112 @@ pub fn global_search_cancel_scan() -> Result<(), String> {
     Ok(())
 }
 
+/// An entry found by a scan worker, with the metadata it was filtered by.
+struct ScannedEntry {
+    path: PathBuf,
+    path_string: String,
+    metadata: Metadata,
+}
+
+/// State shared by the workers walking one drive.
+struct DriveWalkContext<'a> {
+    max_depth: usize,
+    ignored_matcher: &'a IgnoredPathMatcher,
+    path_update_counter: &'a AtomicU64,
+    cancel_flag: &'a AtomicBool,
+    sender: SyncSender<Vec<ScannedEntry>>,
+}
+
+fn scan_worker_count() -> usize {
+    // Directory reads mostly wait on the disk or the network rather than
+    // the CPU, so slow mounts benefit from more workers than cores. The pool
+    // is shared, so this bounds the workers of all drives together.
+    std::thread::available_parallelism()
+        .map_or(4, |count| count.get() * 2)
+        .clamp(4, MAX_SCAN_WORKERS)
+}
+
+/// Reads one directory and spawns a task for each subdirectory, so idle
+/// workers steal subtrees from busy ones.
+fn walk_scan_dir<'scope>(
+    scope: &rayon::Scope<'scope>,
+    context: &'scope DriveWalkContext<'scope>,
+    dir: PathBuf,
+    depth: usize,
+    ignore_files: Option<IgnoreFileScope>,
+) {
+    if context.cancel_flag.load(Ordering::SeqCst) {
+        return;
+    }
+    let Ok(read_dir) = std::fs::read_dir(&dir) else {
+        return;
+    };
+
+    let mut batch = Vec::with_capacity(SCAN_BATCH_SIZE);
+    for entry in read_dir.flatten() {
+        // The file type comes with the directory listing, so links and
+        // ignored entries are skipped before the stat below. Windows listings
+        // carry the full metadata, so there the stat is free as well.
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_symlink() {
+            continue;
+        }
+        let is_dir = file_type.is_dir();
+
+        let path = entry.path();
+        let Some(path_string) = path.to_str().map(normalize_path) else {
+            continue;
+        };
+        maybe_set_current_scan_path(path_string.clone(), context.path_update_counter);
+        if context.ignored_matcher.is_ignored(&path_string) {
+            continue;
+        }
+
+        if ignore_files
+            .as_ref()
+            .is_some_and(|ignore_files| ignore_files.is_ignored(&path_string, is_dir))
+        {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if should_skip_link_metadata(&metadata) {
+            continue;
+        }
+
+        if is_dir && depth + 1 < context.max_depth {
+            let child_ignore_files = ignore_files
+                .as_ref()
+                .map(|ignore_files| ignore_files.for_child_dir(&path, &path_string));
+            let child_dir = path.clone();
+            scope.spawn(move |scope| {
+                walk_scan_dir(scope, context, child_dir, depth + 1, child_ignore_files)
+            });
+        }
+
+        batch.push(ScannedEntry {
+            path,
+            path_string,
+            metadata,
+        });
+        if batch.len() >= SCAN_BATCH_SIZE {
+            let full_batch = std::mem::replace(&mut batch, Vec::with_capacity(SCAN_BATCH_SIZE));
+            if context.sender.send(full_batch).is_err() {
+                return;
+            }
+        }
+    }
+
+    if !batch.is_empty() {
+        let _ = context.sender.send(batch);
+    }
+}
+
+/// Walks a drive on a pool of workers and feeds the entries through a
+/// bounded channel into `writer` on the calling thread.
 fn scan_drive(
     root: &str,
     scan_depth: usize,
@@ -274,78 +414,395 @@ fn scan_drive(
     path_update_counter: &AtomicU64,
     fields: &GlobalSearchIndexFields,
     writer: &IndexWriter,
+    content_indexer: Option<&ContentIndexer>,
     indexed_count: &AtomicU64,
     cancel_flag: &AtomicBool,
-) -> Result<(), GlobalSearchDriveScanError> {
+) -> Result<u64, GlobalSearchDriveScanError> {
     let root_path = PathBuf::from(root);
     let root_string = normalize_path(root);
+    let drive_error = |message: String| GlobalSearchDriveScanError {
+        drive_root: root_string.clone(),
+        message,
+    };
 
     if let Err(error) = std::fs::read_dir(&root_path) {
-        return Err(GlobalSearchDriveScanError {
-            drive_root: root_string,
-            message: error.to_string(),
-        });
+        return Err(drive_error(error.to_string()));
     }
+    if !should_scan_walk_entry(&root_path, ignored_matcher, path_update_counter) {
+        return Ok(0);
+    }
+
+    let pool = SCAN_POOL
+        .as_ref()
+        .map_err(|error| drive_error(error.clone()))?;
+    let (sender, receiver) = mpsc::sync_channel(SCAN_CHANNEL_CAPACITY);
+    let walk_context = DriveWalkContext {
+        max_depth: scan_depth.max(1),
+        ignored_matcher,
+        path_update_counter,
+        cancel_flag,
+        sender,
+    };
+    let ignore_files = ignored_matcher
+        .respects_ignore_files()
+        .then(|| IgnoreFileScope::for_dir(&root_path));
 
+    let mut drive_indexed_count: u64 = 0;
     let mut items_since_last_update: u64 = 0;
 
-    for entry_result in WalkDir::new(&root_path)
-        .follow_links(false)
-        .max_depth(scan_depth.max(1))
-        .into_iter()
-        .filter_entry(|entry| {
-            should_scan_walk_entry(entry.path(), ignored_matcher, path_update_counter)
-        })
+    std::thread::scope(|thread_scope| {
+        thread_scope.spawn(move || {
+            // The sender is dropped with the context once every worker is
+            // done, which ends the receive loop below.
+            let walk_context = walk_context;
+            pool.scope(|scope| walk_scan_dir(scope, &walk_context, root_path, 0, ignore_files));
+        });
+
+        // Dropping the receiver on cancel makes the workers' sends fail, so
+        // they stop instead of blocking on a full channel.
+        for batch in receiver {
+            if cancel_flag.load(Ordering::SeqCst) {
+                break;
+            }
+
+            let indexed_time = now_millis();
+            for entry in batch {
+                if !add_metadata_doc(
+                    writer,
+                    fields,
+                    content_indexer,
+                    &entry.path,
+                    &entry.path_string,
+                    &entry.metadata,
+                    indexed_time,
+                ) {
+                    continue;
+                }
+
+                drive_indexed_count += 1;
+                indexed_count.fetch_add(1, Ordering::Relaxed);
+                items_since_last_update += 1;
+
+                if items_since_last_update >= STATUS_UPDATE_INTERVAL {
+                    if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
+                        state.status.scan_indexed_item_count =
+                            indexed_count.load(Ordering::Relaxed);
+                    }
+                    items_since_last_update = 0;
+                }
+            }
+        }
+    });
+
+    Ok(drive_indexed_count)
+}
+
+fn should_skip_commit_after_failed_scan(
+    valid_drive_count: usize,
+    indexed_drive_roots: &[String],
+    errors: &[GlobalSearchDriveScanError],
+) -> bool {
+    valid_drive_count > 0 && indexed_drive_roots.is_empty() && errors.len() == valid_drive_count
+}
+
+/// Settings shared by every drive of one full scan.
+struct DriveScanContext<'a> {
+    base_dir: &'a Path,
+    settings: &'a GlobalSearchSettings,
+    ignored_matcher: &'a IgnoredPathMatcher,
+    path_update_counter: &'a AtomicU64,
+    indexed_count: &'a AtomicU64,
+    cancel_flag: &'a AtomicBool,
+}
+
+/// Scans one drive into its own staging indexes. Returns `Ok(None)` when the
+/// scan was cancelled before the drive finished.
+fn stage_drive_shard(
+    context: &DriveScanContext,
+    root: &str,
+    staging_path: &Path,
+    content_staging_path: Option<&Path>,
+) -> Result<Option<StagedShard>, GlobalSearchDriveScanError> {
+    let drive_root = normalize_path(root);
+    let drive_error = |message: String| GlobalSearchDriveScanError {
+        drive_root: drive_root.clone(),
+        message,
+    };
+
+    let (index, fields) = create_fresh_index(staging_path).map_err(drive_error)?;
+    let mut writer = create_bulk_index_writer(&index).map_err(drive_error)?;
+
+    let mut content_staging = match content_staging_path {
+        Some(content_staging_path) => {
+            let (content_index, content_fields) =
+                create_fresh_content_index(content_staging_path).map_err(drive_error)?;
+            let content_writer = create_bulk_index_writer(&content_index).map_err(drive_error)?;
+            Some((content_index, content_writer, content_fields))
+        }
+        None => None,
+    };
+    let content_indexed_count = AtomicU64::new(0);
+    let content_indexer = content_staging
+        .as_ref()
+        .map(|(_, content_writer, content_fields)| ContentIndexer {
+            writer: content_writer,
+            fields: *content_fields,
+            max_file_size: content_index_max_file_size(context.settings.content_max_file_size),
+            indexed_count: &content_indexed_count,
+        });
+
+    let drive_indexed_count = scan_drive(
+        root,
+        context.settings.scan_depth,
+        context.ignored_matcher,
+        context.path_update_counter,
+        &fields,
+        &writer,
+        content_indexer.as_ref(),
+        context.indexed_count,
+        context.cancel_flag,
+    )?;
+
+    if context.cancel_flag.load(Ordering::SeqCst) {
+        return Ok(None);
+    }
+
+    writer
+        .commit()
+        .map_err(|error| drive_error(error.to_string()))?;
+    drop(writer);
+    drop(index);
+    validate_staged_index(staging_path, drive_indexed_count).map_err(drive_error)?;
+
+    if let (Some((content_index, mut content_writer, _)), Some(content_staging_path)) =
+        (content_staging.take(), content_staging_path)
     {
-        if cancel_flag.load(Ordering::SeqCst) {
-            break;
+        content_writer
+            .commit()
+            .map_err(|error| drive_error(error.to_string()))?;
+        drop(content_writer);
+        drop(content_index);
+        validate_staged_content_index(
+            content_staging_path,
+            content_indexed_count.load(Ordering::Relaxed),
+        )
+        .map_err(drive_error)?;
+    }
+
+    Ok(Some(StagedShard {
+        drive_root: drive_root.clone(),
+        index_path: staging_path.to_path_buf(),
+        content_index_path: content_staging_path.map(Path::to_path_buf),
+    }))
+}
+
+/// Scans one drive and swaps the result into its shard. Returns whether the
+/// shard was replaced; a cancelled drive keeps its previous shard.
+fn scan_drive_shard(
+    context: &DriveScanContext,
+    root: &str,
+) -> Result<bool, GlobalSearchDriveScanError> {
+    let staging_path = staging_index_dir(context.base_dir);
+    let content_staging_path = context
+        .settings
+        .index_content
+        .then(|| content_staging_index_dir(context.base_dir));
+
+    let result = stage_drive_shard(
+        context,
+        root,
+        &staging_path,
+        content_staging_path.as_deref(),
+    )
+    .and_then(|staged| match staged {
+        Some(staged) => commit_staged_shard(context.base_dir, staged)
+            .map(|_| true)
+            .map_err(|message| GlobalSearchDriveScanError {
+                drive_root: normalize_path(root),
+                message,
+            }),
+        None => Ok(false),
+    });
+
+    if !matches!(result, Ok(true)) {
+        for staging_dir in std::iter::once(&staging_path).chain(content_staging_path.as_ref()) {
+            if staging_dir.exists() {
+                let _ = remove_dir_force(staging_dir);
+            }
         }
+    }
 
-        let entry = match entry_result {
-            Ok(entry) => entry,
-            Err(_) => continue,
-        };
+    result
+}
 
-        let path = entry.path();
-        let path_string = match path.to_str() {
-            Some(path_str) => normalize_path(path_str),
-            None => continue,
-        };
+/// Drive roots this scan should visit, in the order they were requested.
+fn scan_target_roots(
+    state: &GlobalSearchState,
+    settings: &GlobalSearchSettings,
+    valid_drive_roots: &[String],
+) -> Vec<String> {
+    match settings.scan_target {
+        GlobalSearchScanTarget::AddedDrives => valid_drive_roots
+            .iter()
+            .filter(|root| {
+                let drive_root = normalize_path(root);
+                !state
+                    .shards
+                    .iter()
+                    .any(|shard| shard.is_online && shard.drive_root == drive_root)
+            })
+            .cloned()
+            .collect(),
+        GlobalSearchScanTarget::AllDrives | GlobalSearchScanTarget::ListedDrives => {
+            valid_drive_roots.to_vec()
+        }
+    }
+}
 
-        if ignored_matcher.is_ignored(&path_string) {
-            continue;
+/// Online shards whose drives were left out of a full scan of all drives.
+/// Offline shards are kept so an unplugged drive stays searchable.
+fn unlisted_online_shard_roots(state: &GlobalSearchState, drive_roots: &[String]) -> Vec<String> {
+    let listed_roots: Vec<String> = drive_roots
+        .iter()
+        .map(|root| normalize_path(root))
+        .collect();
+    state
+        .shards
+        .iter()
+        .filter(|shard| shard.is_online && !listed_roots.contains(&shard.drive_root))
+        .map(|shard| shard.drive_root.clone())
+        .collect()
+}
+
+fn run_scan(
+    base_dir: &Path,
+    settings: &GlobalSearchSettings,
+    cancel_flag: &AtomicBool,
+) -> Result<u64, String> {
+    let ignored_paths: Vec<String> = settings
+        .ignored_paths
+        .iter()
+        .map(|path| path.to_string())
+        .chain(builtin_ignored_paths().iter().map(|path| path.to_string()))
+        .collect();
+    let ignored_matcher =
+        IgnoredPathMatcher::new(&ignored_paths).with_ignore_files(settings.respect_ignore_files);
+
+    let valid_drive_roots: Vec<String> = settings
+        .drive_roots
+        .iter()
+        .filter(|root| {
+            let path = std::path::Path::new(root);
+            path.exists() && path.is_dir()
+        })
+        .cloned()
+        .collect();
+
+    if valid_drive_roots.is_empty() {
+        if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
+            state.status.is_scan_in_progress = false;
+            state.status.total_drives_count = 0;
+            state.status.current_drive_root = None;
         }
+        return Err("No valid drives found to scan".to_string());
+    }
 
-        if entry.depth() == 0 {
-            continue;
+    let target_roots = {
+        let mut state = GLOBAL_SEARCH_STATE
+            .write()
+            .map_err(|error| error.to_string())?;
+        let target_roots = scan_target_roots(&state, settings, &valid_drive_roots);
+        refresh_online_flags(&mut state);
+        state.status.total_drives_count = target_roots.len() as u32;
+        state.status.is_parallel_scan = settings.parallel_scan && target_roots.len() > 1;
+        target_roots
+    };
+
+    let indexed_count = AtomicU64::new(0);
+    let path_update_counter = AtomicU64::new(0);
+    let context = DriveScanContext {
+        base_dir,
+        settings,
+        ignored_matcher: &ignored_matcher,
+        path_update_counter: &path_update_counter,
+        indexed_count: &indexed_count,
+        cancel_flag,
+    };
+    let mut errors: Vec<GlobalSearchDriveScanError> = Vec::new();
+    let mut indexed_drive_roots: Vec<String> = Vec::new();
+
+    let scan_root = |root: &String| {
+        if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
+            state.status.current_drive_root = Some(normalize_path(root));
         }
 
-        let did_add_doc = add_path_doc(writer, fields, path, &path_string);
+        let result = scan_drive_shard(&context, root);
 
-        if !did_add_doc {
-            continue;
+        if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
+            state.status.scanned_drives_count += 1;
+            state.status.scan_indexed_item_count = indexed_count.load(Ordering::Relaxed);
         }
 
-        indexed_count.fetch_add(1, Ordering::Relaxed);
-        items_since_last_update += 1;
+        (normalize_path(root), result)
+    };
+
+    let results: Vec<(String, Result<bool, GlobalSearchDriveScanError>)> =
+        if settings.parallel_scan && target_roots.len() > 1 {
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = target_roots
+                    .iter()
+                    .map(|root| scope.spawn(|| scan_root(root)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .filter_map(|handle| handle.join().ok())
+                    .collect()
+            })
+        } else {
+            target_roots
+                .iter()
+                .take_while(|_| !cancel_flag.load(Ordering::SeqCst))
+                .map(scan_root)
+                .collect()
+        };
+
+    for (root, result) in results {
+        match result {
+            Ok(true) => indexed_drive_roots.push(root),
+            Ok(false) => {}
+            Err(error) => errors.push(error),
+        }
+    }
 
-        if items_since_last_update >= STATUS_UPDATE_INTERVAL {
-            if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
-                state.status.scan_indexed_item_count = indexed_count.load(Ordering::Relaxed);
+    let was_cancelled = cancel_flag.load(Ordering::SeqCst);
+    let mut state = GLOBAL_SEARCH_STATE
+        .write()
+        .map_err(|error| error.to_string())?;
+    state.status.drive_scan_errors = errors;
+    state.status.current_drive_root = None;
+
+    if settings.scan_target == GlobalSearchScanTarget::AllDrives && !was_cancelled {
+        refresh_online_flags(&mut state);
+        for drive_root in unlisted_online_shard_roots(&state, &settings.drive_roots) {
+            if let Err(error) = drop_shard(&mut state, base_dir, &drive_root) {
+                log::warn!("Failed to drop search index shard for {drive_root}: {error}");
             }
-            items_since_last_update = 0;
         }
     }
 
-    Ok(())
-}
+    if was_cancelled {
+        return Err("Scan cancelled".to_string());
+    }
 
-fn should_skip_commit_after_failed_scan(
-    valid_drive_count: usize,
-    indexed_drive_roots: &[String],
-    errors: &[GlobalSearchDriveScanError],
-) -> bool {
-    valid_drive_count > 0 && indexed_drive_roots.is_empty() && errors.len() == valid_drive_count
+    if should_skip_commit_after_failed_scan(
+        target_roots.len(),
+        &indexed_drive_roots,
+        &state.status.drive_scan_errors,
+    ) {
+        return Err("All selected drives failed to scan".to_string());
+    }
+
+    Ok(indexed_count.load(Ordering::Relaxed))
 }
 
 pub async fn global_search_start_scan(
@@ -380,8 +837,6 @@ pub async fn global_search_start_scan(
         .path()
         .app_data_dir()
         .map_err(|error: tauri::Error| error.to_string())?;
-    let index_path = index_dir(&base_dir);
-    let staging_path = staging_index_dir(&base_dir);
 
     let cancel_flag = {
         let state = GLOBAL_SEARCH_STATE
@@ -391,203 +846,8 @@ pub async fn global_search_start_scan(
     };
 
     tauri::async_runtime::spawn_blocking(move || {
-        let cleanup_staging_path = staging_path.clone();
-        let result =
-            (|| -> Result<(u64, Vec<String>, Index, IndexReader, GlobalSearchIndexFields, u64), String> {
-                let (index, fields) = create_fresh_index(&staging_path)?;
-
-                let mut writer = create_bulk_index_writer(&index)?;
-
-                let ignored_paths: Vec<String> = settings
-                    .ignored_paths
-                    .iter()
-                    .map(|path| path.to_string())
-                    .chain(builtin_ignored_paths().iter().map(|path| path.to_string()))
-                    .collect();
-                let ignored_matcher = IgnoredPathMatcher::new(&ignored_paths);
-
-                let valid_drive_roots: Vec<String> = settings
-                    .drive_roots
-                    .iter()
-                    .filter(|root| {
-                        let path = std::path::Path::new(root);
-                        path.exists() && path.is_dir()
-                    })
-                    .cloned()
-                    .collect();
-
-                if valid_drive_roots.is_empty() {
-                    if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
-                        state.status.is_scan_in_progress = false;
-                        state.status.total_drives_count = 0;
-                        state.status.current_drive_root = None;
-                    }
-                    return Err("No valid drives found to scan".to_string());
-                }
-
-                if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
-                    state.status.total_drives_count = valid_drive_roots.len() as u32;
-                }
-
-                let indexed_count = AtomicU64::new(0);
-                let path_update_counter = AtomicU64::new(0);
-                let mut errors: Vec<GlobalSearchDriveScanError> = Vec::new();
-                let mut scanned_count: u32 = 0;
-                let mut indexed_drive_roots: Vec<String> = Vec::new();
-
-                if settings.parallel_scan && valid_drive_roots.len() > 1 {
-                    use std::thread;
-
-                    thread::scope(|scope| {
-                        let results: Vec<_> = valid_drive_roots
-                            .iter()
-                            .map(|root| {
-                                let root = root.clone();
-                                let ignored_matcher_ref = &ignored_matcher;
-                                let writer_ref: &IndexWriter = &writer;
-                                let indexed_count_ref = &indexed_count;
-                                let path_update_counter_ref = &path_update_counter;
-                                let cancel_flag_ref = &cancel_flag;
-                                let scan_depth = settings.scan_depth;
-
-                                scope.spawn(move || {
-                                    if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
-                                        state.status.current_drive_root =
-                                            Some(normalize_path(&root));
-                                    }
-
-                                    let result = scan_drive(
-                                        &root,
-                                        scan_depth,
-                                        ignored_matcher_ref,
-                                        path_update_counter_ref,
-                                        &fields,
-                                        writer_ref,
-                                        indexed_count_ref,
-                                        cancel_flag_ref,
-                                    );
-
-                                    if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
-                                        state.status.scanned_drives_count += 1;
-                                        state.status.scan_indexed_item_count =
-                                            indexed_count_ref.load(Ordering::Relaxed);
-                                    }
-
-                                    (root, result)
-                                })
-                            })
-                            .collect();
-
-                        for handle in results {
-                            match handle.join() {
-                                Ok((root, Ok(()))) => {
-                                    indexed_drive_roots.push(normalize_path(&root));
-                                }
-                                Ok((_root, Err(error))) => {
-                                    errors.push(error);
-                                }
-                                Err(_) => {}
-                            }
-                        }
-                    });
-                } else {
-                    for root in valid_drive_roots.iter() {
-                        if cancel_flag.load(Ordering::SeqCst) {
-                            break;
-                        }
-
-                        let root_string = normalize_path(root);
-
-                        if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
-                            state.status.current_drive_root = Some(root_string.clone());
-                        }
-
-                        let result = scan_drive(
-                            root,
-                            settings.scan_depth,
-                            &ignored_matcher,
-                            &path_update_counter,
-                            &fields,
-                            &writer,
-                            &indexed_count,
-                            &cancel_flag,
-                        );
-
-                        scanned_count += 1;
-                        if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
-                            state.status.scanned_drives_count = scanned_count;
-                            state.status.scan_indexed_item_count =
-                                indexed_count.load(Ordering::Relaxed);
-                        }
-
-                        match result {
-                            Ok(()) => {
-                                indexed_drive_roots.push(root_string);
-                            }
-                            Err(error) => {
-                                errors.push(error);
-                            }
-                        }
-                    }
-                }
-
-                let should_skip_commit = should_skip_commit_after_failed_scan(
-                    valid_drive_roots.len(),
-                    &indexed_drive_roots,
-                    &errors,
-                );
-
-                {
-                    let mut state = GLOBAL_SEARCH_STATE
-                        .write()
-                        .map_err(|error| error.to_string())?;
-
-                    if cancel_flag.load(Ordering::SeqCst) {
-                        return Err("Scan cancelled".to_string());
-                    }
-
-                    state.status.drive_scan_errors = errors;
-                    state.status.current_drive_root = None;
-
-                    if should_skip_commit {
-                        return Err("All selected drives failed to scan".to_string());
-                    }
-
-                    state.status.is_committing = true;
-                    state.status.scan_phase = GlobalSearchScanPhase::Committing;
-                }
-
-                writer.commit().map_err(|error| error.to_string())?;
-                drop(writer);
-                drop(index);
-
-                let final_count = indexed_count.load(Ordering::Relaxed);
-                validate_staged_index(&staging_path, final_count)?;
-
-                let mut state = GLOBAL_SEARCH_STATE
-                    .write()
-                    .map_err(|error| error.to_string())?;
-                state.index = None;
-                state.reader = None;
-                state.fields = None;
-
-                replace_index_dir(&staging_path, &index_path)?;
-                let (live_index, live_reader, live_fields) = open_or_create_index(&index_path)?;
-                let index_size = calculate_dir_size(&index_path);
-
-                Ok((
-                    final_count,
-                    indexed_drive_roots,
-                    live_index,
-                    live_reader,
-                    live_fields,
-                    index_size,
-                ))
-            })();
-
-        if result.is_err() && cleanup_staging_path.exists() {
-            let _ = remove_dir_force(&cleanup_staging_path);
-        }
+        let result = ensure_shards_loaded(&base_dir)
+            .and_then(|_| run_scan(&base_dir, &settings, &cancel_flag));
 
         if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
             let scan_finished_time = now_millis();
@@ -609,45 +869,19 @@ pub async fn global_search_start_scan(
             state.status.last_scan_started_time = Some(scan_started_time);
             state.status.last_scan_finished_time = Some(scan_finished_time);
             state.status.last_scan_duration_ms = Some(scan_duration_ms);
-            state.status.last_scan_indexed_item_count = Some(progress_doc_count);
+            state.status.last_scan_indexed_item_count =
+                Some(result.as_ref().copied().unwrap_or(progress_doc_count));
             state.status.last_scan_error = match scan_outcome {
                 GlobalSearchScanOutcome::Failed => scan_error,
                 _ => None,
             };
             state.status.scan_indexed_item_count = 0;
-
-            if let Ok((count, indexed_drive_roots, index, reader, fields, index_size)) = result {
-                state.status.last_scan_indexed_item_count = Some(count);
-                apply_committed_index_status(
-                    &mut state,
-                    &base_dir,
-                    CommittedIndexUpdate {
-                        doc_count: count,
-                        index_size_bytes: index_size,
-                        index,
-                        reader,
-                        fields,
-                        indexed_drive_roots: Some(indexed_drive_roots),
-                    },
-                    !was_cancelled,
-                );
-            } else {
-                if state.reader.is_none() {
-                    if let Ok((index, reader, fields)) = open_or_create_index(&index_path) {
-                        state.index = Some(index);
-                        state.reader = Some(reader);
-                        state.fields = Some(fields);
-                    }
-                }
-                let committed_doc_count = state
-                    .reader
-                    .as_ref()
-                    .map_or(0, |reader| reader.searcher().num_docs());
-                state.status.indexed_item_count = committed_doc_count;
-                state.status.index_size_bytes = calculate_dir_size(&index_path);
-                state.status.is_index_valid = committed_doc_count > 0;
-                let _ = write_meta(&base_dir, &meta_from_status(&state));
+            if result.is_ok() && !was_cancelled {
+                state.status.last_scan_time = Some(scan_finished_time);
             }
+
+            update_shard_status(&mut state);
+            let _ = write_meta(&base_dir, &meta_from_status(&state));
         }
     });
 
@@ -687,11 +921,7 @@ fn global_search_index_paths_blocking(
     base_dir: PathBuf,
     settings: IndexPathsSettings,
 ) -> Result<u64, String> {
-    let index_path = index_dir(&base_dir);
-
-    let (index, reader, fields) = open_or_create_index(&index_path)?;
-
-    let mut writer = create_bulk_index_writer(&index)?;
+    ensure_shards_loaded(&base_dir)?;
 
     let ignored_paths: Vec<String> = settings
         .ignored_paths
@@ -699,39 +929,135 @@ fn global_search_index_paths_blocking(
         .map(|path| path.to_string())
         .chain(builtin_ignored_paths().iter().map(|path| path.to_string()))
         .collect();
-    let ignored_matcher = IgnoredPathMatcher::new(&ignored_paths);
-    let path_update_counter = AtomicU64::new(0);
+    let ignored_matcher =
+        IgnoredPathMatcher::new(&ignored_paths).with_ignore_files(settings.respect_ignore_files);
 
-    let mut indexed_count: u64 = 0;
-    let mut did_mutate_index = false;
-    let scan_depth = settings.scan_depth.max(1);
+    // Paths outside every indexed drive have no shard to go into and are
+    // picked up when their drive is scanned.
+    let mut paths_by_shard: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    {
+        let state = GLOBAL_SEARCH_STATE
+            .read()
+            .map_err(|error| error.to_string())?;
+        for dir_path in &settings.paths {
+            let path = Path::new(dir_path);
+            if !path.exists() || !path.is_dir() {
+                continue;
+            }
 
-    for dir_path in &settings.paths {
-        let path = Path::new(dir_path);
+            let normalized_dir = normalize_path(dir_path);
+            if ignored_matcher.is_ignored(&normalized_dir) {
+                continue;
+            }
 
-        if !path.exists() || !path.is_dir() {
-            continue;
+            if let Some(shard_index) = find_shard_for_path(&state.shards, &normalized_dir) {
+                paths_by_shard
+                    .entry(state.shards[shard_index].drive_root.clone())
+                    .or_default()
+                    .push(normalized_dir);
+            }
         }
+    }
 
-        let normalized_dir = normalize_path(dir_path);
+    let mut indexed_count: u64 = 0;
+    for (drive_root, dir_paths) in paths_by_shard {
+        indexed_count += index_paths_into_shard(
+            &base_dir,
+            &drive_root,
+            &dir_paths,
+            &settings,
+            &ignored_matcher,
+        )?;
+    }
 
-        if ignored_matcher.is_ignored(&normalized_dir) {
-            continue;
-        }
+    Ok(indexed_count)
+}
+
+fn index_paths_into_shard(
+    base_dir: &Path,
+    drive_root: &str,
+    dir_paths: &[String],
+    settings: &IndexPathsSettings,
+    ignored_matcher: &IgnoredPathMatcher,
+) -> Result<u64, String> {
+    let (index, reader, content, fields, content_fields) = {
+        let state = GLOBAL_SEARCH_STATE
+            .read()
+            .map_err(|error| error.to_string())?;
+        let Some(shard) = state
+            .shards
+            .iter()
+            .find(|shard| shard.drive_root == drive_root)
+        else {
+            return Ok(0);
+        };
+        (
+            shard.index.clone(),
+            shard.reader.clone(),
+            shard
+                .content
+                .as_ref()
+                .map(|content| (content.index.clone(), content.reader.clone())),
+            state.fields,
+            state.content_fields,
+        )
+    };
 
-        let prefix_term = Term::from_field_text(fields.path, &format!("{}/", normalized_dir));
-        writer.delete_term(prefix_term);
+    let mut writer = create_bulk_index_writer(&index)?;
 
-        let exact_term = Term::from_field_text(fields.path, &normalized_dir);
-        writer.delete_term(exact_term);
-        did_mutate_index = true;
+    let mut content_live = if settings.index_content {
+        let (content_index, content_reader) = match content {
+            Some(content) => content,
+            None => {
+                let (content_index, content_reader, _) =
+                    open_or_create_content_index(&shard_content_index_dir(base_dir, drive_root))?;
+                (content_index, content_reader)
+            }
+        };
+        let content_writer = create_bulk_index_writer(&content_index)?;
+        Some((content_index, content_reader, content_writer))
+    } else {
+        None
+    };
+    let content_indexed_count = AtomicU64::new(0);
+    let content_indexer = content_live
+        .as_ref()
+        .map(|(_, _, content_writer)| ContentIndexer {
+            writer: content_writer,
+            fields: content_fields,
+            max_file_size: content_index_max_file_size(settings.content_max_file_size),
+            indexed_count: &content_indexed_count,
+        });
 
-        for entry_result in WalkDir::new(path)
+    let path_update_counter = AtomicU64::new(0);
+    let mut indexed_count: u64 = 0;
+    let scan_depth = settings.scan_depth.max(1);
+
+    let searcher = reader.searcher();
+    for normalized_dir in dir_paths {
+        let first_indexed = first_indexed_times(&searcher, &fields, normalized_dir)?;
+        let indexed_time = now_millis();
+        delete_path_and_descendants(&writer, fields.path, normalized_dir)?;
+
+        if let Some(content_indexer) = content_indexer.as_ref() {
+            delete_path_and_descendants(
+                content_indexer.writer,
+                content_indexer.fields.path,
+                normalized_dir,
+            )?;
+        }
+
+        let mut ignore_files = ignored_matcher
+            .respects_ignore_files()
+            .then(|| IgnoreFileStack::for_walk_from(Path::new(normalized_dir)));
+
+        for entry_result in WalkDir::new(normalized_dir)
             .follow_links(false)
             .max_depth(scan_depth)
             .into_iter()
             .filter_entry(|entry| {
-                should_scan_walk_entry(entry.path(), &ignored_matcher, &path_update_counter)
+                should_scan_walk_entry(entry.path(), ignored_matcher, &path_update_counter)
+                    && !is_excluded_by_ignore_files(ignore_files.as_mut(), entry)
             })
         {
             let entry = match entry_result {
@@ -753,7 +1079,17 @@ fn global_search_index_paths_blocking(
                 continue;
             }
 
-            let did_add_doc = add_path_doc(&writer, &fields, entry_path, &path_string);
+            let did_add_doc = add_path_doc(
+                &writer,
+                &fields,
+                content_indexer.as_ref(),
+                entry_path,
+                &path_string,
+                first_indexed
+                    .get(&path_string)
+                    .copied()
+                    .unwrap_or(indexed_time),
+            );
 
             if did_add_doc {
                 indexed_count += 1;
@@ -761,29 +1097,38 @@ fn global_search_index_paths_blocking(
         }
     }
 
-    if did_mutate_index {
-        writer.commit().map_err(|error| error.to_string())?;
-        reader.reload().map_err(|error| error.to_string())?;
+    writer.commit().map_err(|error| error.to_string())?;
+    reader.reload().map_err(|error| error.to_string())?;
 
-        let new_total = reader.searcher().num_docs();
-        let index_size = calculate_dir_size(&index_path);
+    let committed_content = match content_live.take() {
+        Some((content_index, content_reader, mut content_writer)) => {
+            content_writer.commit().map_err(|error| error.to_string())?;
+            content_reader.reload().map_err(|error| error.to_string())?;
+            Some(GlobalSearchContentShard {
+                index: content_index,
+                reader: content_reader,
+            })
+        }
+        None => None,
+    };
+    let index_size_bytes = shard_index_size(base_dir, drive_root);
 
-        if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
-            apply_committed_index_status(
-                &mut state,
-                &base_dir,
-                CommittedIndexUpdate {
-                    doc_count: new_total,
-                    index_size_bytes: index_size,
-                    index,
-                    reader,
-                    fields,
-                    indexed_drive_roots: None,
-                },
-                true,
-            );
+    let mut state = GLOBAL_SEARCH_STATE
+        .write()
+        .map_err(|error| error.to_string())?;
+    if let Some(shard) = state
+        .shards
+        .iter_mut()
+        .find(|shard| shard.drive_root == drive_root)
+    {
+        if committed_content.is_some() {
+            shard.content = committed_content;
         }
+        shard.index_size_bytes = index_size_bytes;
     }
+    state.status.last_scan_time = Some(now_millis());
+    update_shard_status(&mut state);
+    let _ = write_meta(base_dir, &meta_from_status(&state));
 
     Ok(indexed_count)
 }
@@ -791,75 +1136,209 @@ fn global_search_index_paths_blocking(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use once_cell::sync::Lazy;
+    use crate::global_search::shard::shard_dir;
+    use crate::global_search::state::GLOBAL_SEARCH_TEST_LOCK;
+    use crate::global_search::types::GlobalSearchScanReason;
     use std::sync::atomic::AtomicBool;
-    use std::sync::{Arc, Mutex as StdMutex};
     use tempfile::TempDir;
 
-    static GLOBAL_SEARCH_TEST_LOCK: Lazy<StdMutex<()>> = Lazy::new(|| StdMutex::new(()));
+    fn test_settings(drive_roots: &[String]) -> GlobalSearchSettings {
+        GlobalSearchSettings {
+            scan_depth: 10,
+            ignored_paths: vec![],
+            drive_roots: drive_roots.to_vec(),
+            parallel_scan: false,
+            scan_reason: GlobalSearchScanReason::Manual,
+            index_content: false,
+            content_max_file_size: None,
+            scan_target: GlobalSearchScanTarget::AllDrives,
+            respect_ignore_files: false,
+        }
+    }
+
+    /// Creates `files` below `temp` and returns the normalized drive roots,
+    /// one per top-level folder, with the shared state emptied.
+    fn create_drives(temp: &TempDir, drives: &[(&str, &[&str])]) -> Vec<String> {
+        {
+            let mut state = GLOBAL_SEARCH_STATE.write().unwrap();
+            state.shards = vec![];
+            state.are_shards_loaded = true;
+        }
+
+        drives
+            .iter()
+            .map(|(drive, files)| {
+                let drive_path = temp.path().join(drive);
+                std::fs::create_dir_all(&drive_path).unwrap();
+                for file in *files {
+                    let file_path = drive_path.join(file);
+                    std::fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+                    std::fs::write(file_path, file).unwrap();
+                }
+                normalize_path(&drive_path.to_string_lossy())
+            })
+            .collect()
+    }
+
+    /// Scans each root into its shard. The temp dir sits under a built-in
+    /// ignored path, so the drives are scanned with an empty matcher instead
+    /// of going through `run_scan`.
+    fn scan_drives(base_dir: &Path, roots: &[String]) {
+        let settings = test_settings(roots);
+        let ignored_matcher = IgnoredPathMatcher::new(&[]);
+        let path_update_counter = AtomicU64::new(0);
+        let indexed_count = AtomicU64::new(0);
+        let cancel_flag = AtomicBool::new(false);
+        let context = DriveScanContext {
+            base_dir,
+            settings: &settings,
+            ignored_matcher: &ignored_matcher,
+            path_update_counter: &path_update_counter,
+            indexed_count: &indexed_count,
+            cancel_flag: &cancel_flag,
+        };
+        for root in roots {
+            assert!(scan_drive_shard(&context, root).unwrap());
+        }
+    }
+
+    fn shard_counts() -> Vec<(String, u64)> {
+        let state = GLOBAL_SEARCH_STATE.read().unwrap();
+        state
+            .status
+            .shards
+            .iter()
+            .map(|shard| (shard.drive_root.clone(), shard.indexed_item_count))
+            .collect()
+    }
+
+    fn reset_state() {
+        let mut state = GLOBAL_SEARCH_STATE.write().unwrap();
+        state.shards = vec![];
+        state.are_shards_loaded = false;
+        update_shard_status(&mut state);
+    }
 
     #[test]
-    fn committed_index_status_clears_valid_flag_and_persists_meta_for_empty_index() {
+    fn drives_rescan_and_drop_as_independent_shards() {
+        let _lock_guard = GLOBAL_SEARCH_TEST_LOCK.lock().unwrap();
         let temp = TempDir::new().unwrap();
-        let base_dir = temp.path();
-        let index_path = index_dir(base_dir);
-        let (index, reader, fields) = open_or_create_index(&index_path).unwrap();
-
-        let mut state = GlobalSearchState {
-            status: GlobalSearchStatus {
-                is_scan_in_progress: false,
-                is_committing: false,
-                is_parallel_scan: false,
-                scan_phase: GlobalSearchScanPhase::Idle,
-                scan_reason: None,
-                last_scan_time: Some(1),
-                last_scan_outcome: None,
-                last_scan_reason: None,
-                last_scan_started_time: None,
-                last_scan_finished_time: None,
-                last_scan_duration_ms: None,
-                last_scan_indexed_item_count: None,
-                last_scan_error: None,
-                indexed_item_count: 999,
-                scan_indexed_item_count: 0,
-                indexed_drive_roots: vec!["C:/".to_string()],
-                index_size_bytes: 0,
-                current_drive_root: None,
-                current_scan_path: None,
-                drive_scan_errors: vec![],
-                is_index_valid: true,
-                scanned_drives_count: 0,
-                total_drives_count: 0,
-            },
-            index: None,
-            reader: None,
-            fields: None,
-            cancel_flag: Arc::new(AtomicBool::new(false)),
-        };
+        let base_dir = temp.path().join("app-data");
+        let roots = create_drives(
+            &temp,
+            &[
+                ("drive-a", &["docs/report.txt"]),
+                ("drive-b", &["photo.jpg"]),
+            ],
+        );
+        let (root_a, root_b) = (roots[0].clone(), roots[1].clone());
 
-        apply_committed_index_status(
-            &mut state,
-            base_dir,
-            CommittedIndexUpdate {
-                doc_count: 0,
-                index_size_bytes: 0,
-                index,
-                reader,
-                fields,
-                indexed_drive_roots: Some(vec![]),
-            },
-            true,
+        scan_drives(&base_dir, &roots);
+        assert_eq!(
+            shard_counts(),
+            vec![(root_a.clone(), 2), (root_b.clone(), 1)]
+        );
+
+        std::fs::write(temp.path().join("drive-a/notes.txt"), "notes").unwrap();
+        scan_drives(&base_dir, std::slice::from_ref(&root_a));
+        assert_eq!(
+            shard_counts(),
+            vec![(root_a.clone(), 3), (root_b.clone(), 1)]
         );
 
-        assert!(!state.status.is_index_valid);
-        assert_eq!(state.status.indexed_item_count, 0);
-        assert!(state.status.indexed_drive_roots.is_empty());
-        assert!(state.status.last_scan_time.unwrap() > 1);
-        let meta = read_meta(base_dir).expect("meta written");
-        assert_eq!(meta.indexed_item_count, 0);
-        assert_eq!(meta.indexed_item_count, state.status.indexed_item_count);
-        assert!(meta.indexed_drive_roots.is_empty());
-        assert_eq!(meta.last_scan_time, state.status.last_scan_time);
+        let mut state = GLOBAL_SEARCH_STATE.write().unwrap();
+        let unlisted_roots = unlisted_online_shard_roots(&state, std::slice::from_ref(&root_a));
+        assert_eq!(unlisted_roots, vec![root_b.clone()]);
+        drop_shard(&mut state, &base_dir, &root_b).unwrap();
+        update_shard_status(&mut state);
+        assert_eq!(state.status.indexed_drive_roots, vec![root_a.clone()]);
+        assert!(!shard_dir(&base_dir, &root_b).exists());
+        drop(state);
+
+        reset_state();
+    }
+
+    #[test]
+    fn shards_reload_from_disk_with_merged_status() {
+        let _lock_guard = GLOBAL_SEARCH_TEST_LOCK.lock().unwrap();
+        let temp = TempDir::new().unwrap();
+        let base_dir = temp.path().join("app-data");
+        let roots = create_drives(
+            &temp,
+            &[
+                ("drive-a", &["docs/report.txt", "notes.txt"]),
+                ("drive-b", &["photo.jpg"]),
+            ],
+        );
+        scan_drives(&base_dir, &roots);
+        let scanned_counts = shard_counts();
+
+        reset_state();
+        ensure_shards_loaded(&base_dir).unwrap();
+
+        assert_eq!(shard_counts(), scanned_counts);
+        let state = GLOBAL_SEARCH_STATE.read().unwrap();
+        assert!(state.are_shards_loaded);
+        assert_eq!(state.status.indexed_item_count, 4);
+        assert_eq!(state.status.indexed_drive_roots, roots);
+        assert!(state
+            .status
+            .shards
+            .iter()
+            .all(|shard| shard.index_size_bytes > 0 && shard.last_scan_time.is_some()));
+        assert_eq!(
+            state.status.index_size_bytes,
+            state
+                .status
+                .shards
+                .iter()
+                .map(|shard| shard.index_size_bytes)
+                .sum::<u64>()
+        );
+        drop(state);
+
+        reset_state();
+    }
+
+    #[test]
+    fn full_scan_drops_shards_of_removed_drives_but_keeps_unplugged_ones() {
+        let _lock_guard = GLOBAL_SEARCH_TEST_LOCK.lock().unwrap();
+        let temp = TempDir::new().unwrap();
+        let base_dir = temp.path().join("app-data");
+        let roots = create_drives(
+            &temp,
+            &[
+                ("drive-a", &["report.txt"]),
+                ("drive-b", &["photo.jpg"]),
+                ("drive-c", &["backup.zip"]),
+            ],
+        );
+        let (root_a, root_b, root_c) = (roots[0].clone(), roots[1].clone(), roots[2].clone());
+        scan_drives(&base_dir, &roots);
+        std::fs::remove_dir_all(temp.path().join("drive-c")).unwrap();
+
+        run_scan(
+            &base_dir,
+            &test_settings(std::slice::from_ref(&root_a)),
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+
+        let state = GLOBAL_SEARCH_STATE.read().unwrap();
+        let shard_roots: Vec<(&str, bool)> = state
+            .shards
+            .iter()
+            .map(|shard| (shard.drive_root.as_str(), shard.is_online))
+            .collect();
+        assert_eq!(
+            shard_roots,
+            vec![(root_a.as_str(), true), (root_c.as_str(), false)]
+        );
+        assert!(!shard_dir(&base_dir, &root_b).exists());
+        assert!(shard_dir(&base_dir, &root_c).exists());
+        drop(state);
+
+        reset_state();
     }
 
     #[test]
@@ -913,6 +1392,130 @@ mod tests {
         state.cancel_flag.store(false, Ordering::SeqCst);
     }
 
+    /// Times a full scan of a tree into a fresh index, commit included, so
+    /// builds before and after a change to the scan can be compared on the
+    /// same tree. Run with a large folder or network mount, e.g.
+    /// `SCAN_BENCH_ROOT=/home/me cargo test --release scan_benchmark -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn scan_benchmark() {
+        let root = std::env::var("SCAN_BENCH_ROOT").expect("SCAN_BENCH_ROOT is not set");
+        let temp = TempDir::new().unwrap();
+        let (index, fields) = create_fresh_index(&temp.path().join("names")).unwrap();
+        let mut writer = create_bulk_index_writer(&index).unwrap();
+        let ignored_matcher = IgnoredPathMatcher::new(&[]);
+
+        let started = std::time::Instant::now();
+        let scanned_count = scan_drive(
+            &root,
+            usize::MAX,
+            &ignored_matcher,
+            &AtomicU64::new(0),
+            &fields,
+            &writer,
+            None,
+            &AtomicU64::new(0),
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        let scan_elapsed = started.elapsed();
+        writer.commit().unwrap();
+
+        println!(
+            "{scanned_count} entries with {} workers: scanned in {scan_elapsed:?}, \
+             committed in {:?}",
+            scan_worker_count(),
+            started.elapsed()
+        );
+    }
+
+    #[test]
+    fn scan_drive_indexes_text_content_alongside_names() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("root");
+        std::fs::create_dir_all(root.join("config")).unwrap();
+        std::fs::write(root.join("config/app.toml"), "database_url = \"postgres\"").unwrap();
+        std::fs::write(root.join("image.png"), "database_url").unwrap();
+
+        let (index, fields) = create_fresh_index(&temp.path().join("names")).unwrap();
+        let writer = index.writer(50_000_000).unwrap();
+        let (content_index, content_fields) =
+            create_fresh_content_index(&temp.path().join("content")).unwrap();
+        let mut content_writer = content_index.writer(50_000_000).unwrap();
+        let indexed_count = AtomicU64::new(0);
+        let content_indexed_count = AtomicU64::new(0);
+        let content_indexer = ContentIndexer {
+            writer: &content_writer,
+            fields: content_fields,
+            max_file_size: DEFAULT_CONTENT_MAX_FILE_SIZE,
+            indexed_count: &content_indexed_count,
+        };
+
+        scan_drive(
+            &root.to_string_lossy(),
+            10,
+            &IgnoredPathMatcher::new(&[]),
+            &AtomicU64::new(0),
+            &fields,
+            &writer,
+            Some(&content_indexer),
+            &indexed_count,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        content_writer.commit().unwrap();
+
+        assert_eq!(indexed_count.load(Ordering::Relaxed), 3);
+        assert_eq!(content_indexed_count.load(Ordering::Relaxed), 1);
+
+        let searcher = content_index.reader().unwrap().searcher();
+        let query =
+            tantivy::query::QueryParser::for_index(&content_index, vec![content_fields.content])
+                .parse_query("postgres")
+                .unwrap();
+        let hits = searcher.search(&query, &tantivy::collector::Count).unwrap();
+        assert_eq!(hits, 1);
+    }
+
+    #[test]
+    fn parallel_scan_respects_depth_links_and_ignore_files() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("root");
+        std::fs::create_dir_all(root.join("many")).unwrap();
+        std::fs::create_dir_all(root.join("a/b/c/too-deep")).unwrap();
+        std::fs::create_dir_all(root.join("build")).unwrap();
+        std::fs::write(root.join(".ignore"), "build/\n").unwrap();
+        std::fs::write(root.join("build/output.bin"), "").unwrap();
+        for index in 0..1000 {
+            std::fs::write(root.join(format!("many/file-{index}.txt")), "").unwrap();
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(root.join("many"), root.join("link")).unwrap();
+
+        let (index, fields) = create_fresh_index(&temp.path().join("names")).unwrap();
+        let mut writer = index.writer(50_000_000).unwrap();
+        let indexed_count = AtomicU64::new(0);
+        let drive_indexed_count = scan_drive(
+            &root.to_string_lossy(),
+            4,
+            &IgnoredPathMatcher::new(&[]).with_ignore_files(true),
+            &AtomicU64::new(0),
+            &fields,
+            &writer,
+            None,
+            &indexed_count,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        writer.commit().unwrap();
+
+        // `.ignore`, `many`, 1000 files, `a`, `a/b`, `a/b/c`, `a/b/c/too-deep`
+        assert_eq!(drive_indexed_count, 1006);
+        assert_eq!(indexed_count.load(Ordering::Relaxed), 1006);
+        let searcher = index.reader().unwrap().searcher();
+        assert_eq!(searcher.num_docs(), 1006);
+    }
+
     #[test]
     fn cancelled_outcome_wins_even_if_result_succeeded() {
         assert_eq!(
@@ -0,0 +1,321 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Folder-scoped global search. Folders below an indexed drive root are
+//! restricted inside the index with a path range query; other folders are
+//! walked live and their results are streamed to the frontend as events.
+
+use crate::utils::normalize_path;
+use once_cell::sync::Lazy;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tantivy::query::Query;
+use tauri::Emitter;
+use walkdir::WalkDir;
+
+use super::ignore::{builtin_ignored_paths, IgnoredPathMatcher};
+use super::index::path_and_descendants_query;
+use super::query::{compile_query, entry_from_path, sort_and_truncate_by_score, CompiledQuery};
+use super::shard::ensure_shards_loaded;
+use super::state::{GlobalSearchIndexFields, GLOBAL_SEARCH_STATE};
+use super::syntax::is_path_within;
+use super::types::{
+    GlobalSearchQueryError, GlobalSearchQueryOptions, GlobalSearchResultEntry,
+    GlobalSearchScopeResultsEvent,
+};
+
+const SCOPE_RESULTS_EVENT: &str = "global-search-scope-results";
+const SCOPE_BATCH_MAX_ENTRIES: usize = 200;
+const SCOPE_BATCH_MAX_DELAY: Duration = Duration::from_millis(250);
+
+static SCOPE_WALK_CANCEL_FLAG: Lazy<Mutex<Option<Arc<AtomicBool>>>> =
+    Lazy::new(|| Mutex::new(None));
+
+pub(super) fn normalize_scope_path(path: &str) -> String {
+    let normalized = normalize_path(path);
+    if normalized.len() > 1 && !normalized.ends_with(":/") {
+        normalized.trim_end_matches('/').to_string()
+    } else {
+        normalized
+    }
+}
+
+pub(super) fn is_scope_indexed(scope_path: &str, indexed_drive_roots: &[String]) -> bool {
+    indexed_drive_roots
+        .iter()
+        .any(|root| is_path_within(scope_path, &normalize_scope_path(root)))
+}
+
+/// Matches everything strictly below the scope folder, but not the folder itself.
+pub(super) fn scope_descendants_query(
+    fields: &GlobalSearchIndexFields,
+    scope_path: &str,
+) -> Box<dyn Query> {
+    let parent = scope_path.trim_end_matches('/');
+    path_and_descendants_query(fields.path, &format!("{parent}/"))
+}
+
+/// Returns the scope when it lies outside every indexed drive root and so has
+/// to be searched with a live walk.
+pub(super) fn unindexed_scope(
+    base_dir: &Path,
+    options: &GlobalSearchQueryOptions,
+) -> Result<Option<String>, String> {
+    let Some(scope_path) = options.scope_path.as_deref() else {
+        return Ok(None);
+    };
+    let scope_path = normalize_scope_path(scope_path);
+    ensure_shards_loaded(base_dir)?;
+
+    let state = GLOBAL_SEARCH_STATE
+        .read()
+        .map_err(|error| error.to_string())?;
+
+    if is_scope_indexed(&scope_path, &state.status.indexed_drive_roots) {
+        Ok(None)
+    } else {
+        Ok(Some(scope_path))
+    }
+}
+
+fn replace_cancel_flag() -> Result<Arc<AtomicBool>, String> {
+    let mut current = SCOPE_WALK_CANCEL_FLAG
+        .lock()
+        .map_err(|error| error.to_string())?;
+    if let Some(previous) = current.take() {
+        previous.store(true, Ordering::Relaxed);
+    }
+    let cancel_flag = Arc::new(AtomicBool::new(false));
+    *current = Some(cancel_flag.clone());
+    Ok(cancel_flag)
+}
+
+pub fn global_search_cancel_scope_walk() -> Result<(), String> {
+    let mut current = SCOPE_WALK_CANCEL_FLAG
+        .lock()
+        .map_err(|error| error.to_string())?;
+    if let Some(cancel_flag) = current.take() {
+        cancel_flag.store(true, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+/// Walks the scope folder and matches every entry against the query. Starting
+/// a new walk cancels the previous one. Batches of matches are emitted as
+/// `global-search-scope-results` events; the best `limit` matches are returned.
+pub(super) fn live_scope_search_blocking(
+    app: tauri::AppHandle,
+    scope_path: String,
+    query: String,
+    options: GlobalSearchQueryOptions,
+) -> Result<Vec<GlobalSearchResultEntry>, GlobalSearchQueryError> {
+    let compiled = compile_query(&query, &options)?;
+    let cancel_flag = replace_cancel_flag()?;
+
+    let emit_batch = |entries: Vec<GlobalSearchResultEntry>, scanned_count: u64, is_done: bool| {
+        let payload = GlobalSearchScopeResultsEvent {
+            request_id: options.request_id,
+            scope_path: scope_path.clone(),
+            entries,
+            scanned_count,
+            is_done,
+        };
+        let _ = app.emit(SCOPE_RESULTS_EVENT, &payload);
+    };
+
+    let (results, scanned_count) =
+        walk_scope(&scope_path, &compiled, &options, &cancel_flag, &emit_batch);
+
+    if !cancel_flag.load(Ordering::Relaxed) {
+        emit_batch(Vec::new(), scanned_count, true);
+    }
+
+    Ok(results)
+}
+
+/// Strips a Windows drive prefix, so `C:/Users/Alex` becomes `/Users/Alex`.
+fn root_relative_path(path: &str) -> &str {
+    match path.as_bytes() {
+        [letter, b':', ..] if letter.is_ascii_alphabetic() => &path[2..],
+        _ => path,
+    }
+}
+
+/// Returns the best matches and the number of entries scanned.
+fn walk_scope(
+    scope_path: &str,
+    compiled: &CompiledQuery,
+    options: &GlobalSearchQueryOptions,
+    cancel_flag: &AtomicBool,
+    emit_batch: &dyn Fn(Vec<GlobalSearchResultEntry>, u64, bool),
+) -> (Vec<GlobalSearchResultEntry>, u64) {
+    let internal_ignored: Vec<String> = builtin_ignored_paths()
+        .iter()
+        .map(|path| path.to_string())
+        .collect();
+    let internal_ignored_matcher = IgnoredPathMatcher::new(&internal_ignored);
+    // Rules match paths from the drive root, as in a scan. When the user picked
+    // a folder inside an ignored one, only the part below the scope is checked.
+    let scope_prefix = if internal_ignored_matcher.is_ignored(root_relative_path(scope_path)) {
+        scope_path.trim_end_matches('/')
+    } else {
+        ""
+    };
+
+    let mut scanned_count = 0;
+    let mut results: Vec<GlobalSearchResultEntry> = Vec::new();
+    let mut batch: Vec<GlobalSearchResultEntry> = Vec::new();
+    let mut last_emit = Instant::now();
+
+    let walker = WalkDir::new(Path::new(scope_path))
+        .follow_links(false)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| {
+            let path_string = normalize_path(&entry.path().to_string_lossy());
+            let relative_path = path_string
+                .strip_prefix(scope_prefix)
+                .unwrap_or(&path_string);
+            !internal_ignored_matcher.is_ignored(root_relative_path(relative_path))
+        });
+
+    for entry in walker {
+        if cancel_flag.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let Ok(entry) = entry else {
+            continue;
+        };
+        scanned_count += 1;
+
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+
+        if let Some(result) = entry_from_path(entry.path(), &metadata, compiled, options) {
+            batch.push(result);
+        }
+
+        if !batch.is_empty()
+            && (batch.len() >= SCOPE_BATCH_MAX_ENTRIES
+                || last_emit.elapsed() >= SCOPE_BATCH_MAX_DELAY)
+        {
+            emit_batch(batch.clone(), scanned_count, false);
+            results.append(&mut batch);
+            last_emit = Instant::now();
+
+            if results.len() > options.limit.saturating_mul(4) {
+                sort_and_truncate_by_score(&mut results, options.limit);
+            }
+        }
+    }
+
+    if !batch.is_empty() && !cancel_flag.load(Ordering::Relaxed) {
+        emit_batch(batch.clone(), scanned_count, false);
+    }
+    results.append(&mut batch);
+    sort_and_truncate_by_score(&mut results, options.limit);
+
+    (results, scanned_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::global_search::types::{GlobalSearchMatchMode, GlobalSearchMatchTarget};
+    use std::cell::RefCell;
+
+    fn create_options() -> GlobalSearchQueryOptions {
+        GlobalSearchQueryOptions {
+            limit: 10,
+            include_files: true,
+            include_directories: true,
+            exact_match: false,
+            typo_tolerance: true,
+            min_score_threshold: None,
+            match_mode: GlobalSearchMatchMode::Fuzzy,
+            match_target: GlobalSearchMatchTarget::Auto,
+            timeout_ms: None,
+            scope_path: None,
+            request_id: None,
+        }
+    }
+
+    #[test]
+    fn scope_is_indexed_only_below_an_indexed_root() {
+        let roots = vec!["/".to_string(), "C:\\".to_string()];
+        assert!(is_scope_indexed("/home/user", &roots));
+        assert!(is_scope_indexed("C:/Users", &roots));
+        assert!(!is_scope_indexed("D:/Photos", &roots));
+
+        let roots = vec!["/home".to_string()];
+        assert!(!is_scope_indexed("/media/usb", &roots));
+        assert!(!is_scope_indexed("/homework", &roots));
+    }
+
+    #[test]
+    fn ignore_rules_match_from_the_drive_root() {
+        assert_eq!(root_relative_path("C:/Users/Alex"), "/Users/Alex");
+        assert_eq!(root_relative_path("/home/alex"), "/home/alex");
+
+        // Temp dirs sit below the ignored `/tmp`, so the walk falls back to
+        // checking only the part below the scope.
+        let temp_dir = tempfile::tempdir().unwrap();
+        let scope_root = temp_dir.path().join("Library");
+        std::fs::create_dir_all(scope_root.join("Caches")).unwrap();
+        std::fs::create_dir_all(scope_root.join("node_modules")).unwrap();
+        std::fs::write(scope_root.join("Caches/holiday.jpg"), b"").unwrap();
+        std::fs::write(scope_root.join("node_modules/holiday.jpg"), b"").unwrap();
+
+        let scope_path = normalize_scope_path(&scope_root.to_string_lossy());
+        let options = create_options();
+        let compiled = compile_query("holiday", &options).unwrap();
+        let (results, _) = walk_scope(
+            &scope_path,
+            &compiled,
+            &options,
+            &AtomicBool::new(false),
+            &|_, _, _| {},
+        );
+
+        let paths: Vec<String> = results.into_iter().map(|entry| entry.path).collect();
+        assert_eq!(paths, vec![format!("{scope_path}/Caches/holiday.jpg")]);
+    }
+
+    #[test]
+    fn walk_scope_streams_matches_below_the_folder() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("photos/2024")).unwrap();
+        std::fs::write(root.join("photos/2024/holiday.jpg"), b"").unwrap();
+        std::fs::write(root.join("photos/holiday notes.txt"), b"").unwrap();
+        std::fs::write(root.join("readme.md"), b"").unwrap();
+
+        let scope_path = normalize_scope_path(&root.to_string_lossy());
+        let options = create_options();
+        let compiled = compile_query("holiday ext:jpg", &options).unwrap();
+        let streamed = RefCell::new(Vec::new());
+        let emit_batch = |entries: Vec<GlobalSearchResultEntry>, _scanned: u64, _done: bool| {
+            streamed
+                .borrow_mut()
+                .extend(entries.into_iter().map(|entry| entry.name));
+        };
+
+        let (results, scanned_count) = walk_scope(
+            &scope_path,
+            &compiled,
+            &options,
+            &AtomicBool::new(false),
+            &emit_batch,
+        );
+
+        assert_eq!(scanned_count, 5);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "holiday.jpg");
+        assert_eq!(*streamed.borrow(), vec!["holiday.jpg".to_string()]);
+    }
+}
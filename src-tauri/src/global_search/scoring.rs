@@ -2,9 +2,11 @@
 // License: GNU GPLv3 or later. See the license file in the project root for more information.
 // Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
 
+use super::tokenizer::fold_text;
+
 pub(super) fn calculate_similarity_score(query: &str, name: &str) -> f32 {
-    let query_lower = query.to_lowercase();
-    let name_lower = name.to_lowercase();
+    let query_lower = fold_text(query);
+    let name_lower = fold_text(name);
 
     if name_lower == query_lower {
         return 1.0;
@@ -0,0 +1,348 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Per-drive index shards. Every indexed drive root has its own name index and
+//! optional content index under `global-search/shards/<shard id>`, so a drive
+//! can be rescanned, committed or dropped without touching the others. A shard
+//! whose drive is unplugged stays loaded and searchable but is marked offline.
+
+use crate::utils::normalize_path;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+use super::content::open_or_create_content_index;
+use super::index::{
+    calculate_dir_size, clear_index, global_search_dir, open_or_create_index, read_meta,
+    remove_dir_force, replace_index_dir, write_meta, SCHEMA_VERSION, SHARDS_DIR_NAME,
+};
+use super::scan::meta_from_status;
+use super::scope::normalize_scope_path;
+use super::state::{
+    now_millis, GlobalSearchContentShard, GlobalSearchShard, GlobalSearchState, GLOBAL_SEARCH_STATE,
+};
+use super::syntax::is_path_within;
+use super::types::{GlobalSearchShardStatus, GlobalSearchStatus};
+
+/// A scanned drive whose index was written to staging directories and still
+/// has to be swapped into its shard directory.
+pub(super) struct StagedShard {
+    pub(super) drive_root: String,
+    pub(super) index_path: PathBuf,
+    pub(super) content_index_path: Option<PathBuf>,
+}
+
+/// Stable directory name for a drive root: a readable slug plus an FNV-1a
+/// hash so roots that slug to the same text (`C:/` and `C/`) stay apart.
+fn shard_id(drive_root: &str) -> String {
+    let slug: String = drive_root
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let slug = slug.trim_matches('_');
+    let slug = if slug.is_empty() { "root" } else { slug };
+
+    let hash = drive_root
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+    format!("{slug}-{hash:016x}")
+}
+
+pub(super) fn shards_dir(base_dir: &Path) -> PathBuf {
+    global_search_dir(base_dir).join(SHARDS_DIR_NAME)
+}
+
+pub(super) fn shard_dir(base_dir: &Path, drive_root: &str) -> PathBuf {
+    shards_dir(base_dir).join(shard_id(&normalize_path(drive_root)))
+}
+
+pub(super) fn shard_index_dir(base_dir: &Path, drive_root: &str) -> PathBuf {
+    shard_dir(base_dir, drive_root).join("index")
+}
+
+pub(super) fn shard_content_index_dir(base_dir: &Path, drive_root: &str) -> PathBuf {
+    shard_dir(base_dir, drive_root).join("content-index")
+}
+
+pub(super) fn is_drive_root_available(drive_root: &str) -> bool {
+    Path::new(drive_root).is_dir()
+}
+
+/// Bytes on disk of a shard's indexes. Walks the shard directory, so callers
+/// measure before taking the state lock.
+pub(super) fn shard_index_size(base_dir: &Path, drive_root: &str) -> u64 {
+    calculate_dir_size(&shard_dir(base_dir, drive_root))
+}
+
+pub(super) fn open_shard(
+    base_dir: &Path,
+    drive_root: &str,
+    last_scan_time: Option<u64>,
+    index_size_bytes: u64,
+) -> Result<GlobalSearchShard, String> {
+    let index_path = shard_index_dir(base_dir, drive_root);
+    if !index_path.exists() {
+        return Err(format!(
+            "Search index shard for {drive_root} does not exist"
+        ));
+    }
+
+    let (index, reader, _) = open_or_create_index(&index_path)?;
+
+    let content_index_path = shard_content_index_dir(base_dir, drive_root);
+    let content = if content_index_path.exists() {
+        open_or_create_content_index(&content_index_path)
+            .ok()
+            .map(|(index, reader, _)| GlobalSearchContentShard { index, reader })
+    } else {
+        None
+    };
+
+    Ok(GlobalSearchShard {
+        drive_root: normalize_path(drive_root),
+        index,
+        reader,
+        content,
+        is_online: is_drive_root_available(drive_root),
+        last_scan_time,
+        index_size_bytes,
+    })
+}
+
+/// Opens the shards listed in the status metadata. Shards that fail to open
+/// are skipped; their drives are scanned again by the next scan.
+pub(super) fn load_shards(base_dir: &Path) -> Vec<GlobalSearchShard> {
+    let Some(meta) = read_meta(base_dir).filter(|meta| meta.schema_version == SCHEMA_VERSION)
+    else {
+        return Vec::new();
+    };
+
+    meta.shards
+        .iter()
+        .filter_map(|shard_meta| {
+            open_shard(
+                base_dir,
+                &shard_meta.drive_root,
+                shard_meta.last_scan_time,
+                shard_index_size(base_dir, &shard_meta.drive_root),
+            )
+            .ok()
+        })
+        .collect()
+}
+
+/// Loads the shards on first use when a query arrives before `global_search_init`.
+pub(super) fn ensure_shards_loaded(base_dir: &Path) -> Result<(), String> {
+    {
+        let state = GLOBAL_SEARCH_STATE
+            .read()
+            .map_err(|error| error.to_string())?;
+        if state.are_shards_loaded {
+            return Ok(());
+        }
+    }
+
+    let shards = load_shards(base_dir);
+    let mut state = GLOBAL_SEARCH_STATE
+        .write()
+        .map_err(|error| error.to_string())?;
+    if !state.are_shards_loaded {
+        state.shards = shards;
+        state.are_shards_loaded = true;
+        update_shard_status(&mut state);
+    }
+    Ok(())
+}
+
+/// Index of the shard whose drive root contains `path`, preferring the
+/// longest root when roots are nested.
+pub(super) fn find_shard_for_path(shards: &[GlobalSearchShard], path: &str) -> Option<usize> {
+    shards
+        .iter()
+        .enumerate()
+        .filter(|(_, shard)| is_path_within(path, &normalize_scope_path(&shard.drive_root)))
+        .max_by_key(|(_, shard)| shard.drive_root.len())
+        .map(|(shard_index, _)| shard_index)
+}
+
+pub(super) fn refresh_online_flags(state: &mut GlobalSearchState) {
+    for shard in &mut state.shards {
+        shard.is_online = is_drive_root_available(&shard.drive_root);
+    }
+}
+
+fn take_shard(state: &mut GlobalSearchState, drive_root: &str) -> Option<GlobalSearchShard> {
+    let drive_root = normalize_path(drive_root);
+    let position = state
+        .shards
+        .iter()
+        .position(|shard| shard.drive_root == drive_root)?;
+    Some(state.shards.remove(position))
+}
+
+fn insert_shard(state: &mut GlobalSearchState, shard: GlobalSearchShard) {
+    let position = state
+        .shards
+        .partition_point(|existing| existing.drive_root < shard.drive_root);
+    state.shards.insert(position, shard);
+}
+
+/// Recomputes the per-shard and total counts, sizes and the online drive
+/// roots that live updates watch and scoped queries search in the index.
+pub(super) fn update_shard_status(state: &mut GlobalSearchState) {
+    let shard_statuses: Vec<GlobalSearchShardStatus> = state
+        .shards
+        .iter()
+        .map(|shard| GlobalSearchShardStatus {
+            drive_root: shard.drive_root.clone(),
+            is_online: shard.is_online,
+            indexed_item_count: shard.reader.searcher().num_docs(),
+            content_indexed_item_count: shard
+                .content
+                .as_ref()
+                .map_or(0, |content| content.reader.searcher().num_docs()),
+            index_size_bytes: shard.index_size_bytes,
+            last_scan_time: shard.last_scan_time,
+        })
+        .collect();
+
+    let status = &mut state.status;
+    status.indexed_item_count = shard_statuses
+        .iter()
+        .map(|shard| shard.indexed_item_count)
+        .sum();
+    status.content_indexed_item_count = shard_statuses
+        .iter()
+        .map(|shard| shard.content_indexed_item_count)
+        .sum();
+    status.index_size_bytes = shard_statuses
+        .iter()
+        .map(|shard| shard.index_size_bytes)
+        .sum();
+    status.indexed_drive_roots = shard_statuses
+        .iter()
+        .filter(|shard| shard.is_online)
+        .map(|shard| shard.drive_root.clone())
+        .collect();
+    status.is_index_valid = status.indexed_item_count > 0;
+    status.shards = shard_statuses;
+}
+
+/// Swaps a staged drive index into its shard directory and reloads the shard.
+/// When the swap fails the previous shard is reopened if it is still intact.
+pub(super) fn commit_staged_shard(base_dir: &Path, staged: StagedShard) -> Result<(), String> {
+    let staged_size = calculate_dir_size(&staged.index_path)
+        + staged
+            .content_index_path
+            .as_deref()
+            .map_or(0, calculate_dir_size);
+
+    let mut state = GLOBAL_SEARCH_STATE
+        .write()
+        .map_err(|error| error.to_string())?;
+
+    let previous_shard = take_shard(&mut state, &staged.drive_root);
+    let previous_scan_time = previous_shard
+        .as_ref()
+        .and_then(|previous_shard| previous_shard.last_scan_time);
+    let previous_size = previous_shard.map_or(0, |previous_shard| previous_shard.index_size_bytes);
+
+    let index_path = shard_index_dir(base_dir, &staged.drive_root);
+    let content_index_path = shard_content_index_dir(base_dir, &staged.drive_root);
+    let result = replace_index_dir(&staged.index_path, &index_path)
+        .and_then(|_| match &staged.content_index_path {
+            Some(staged_content_path) => {
+                replace_index_dir(staged_content_path, &content_index_path)
+            }
+            None => clear_index(&content_index_path),
+        })
+        .and_then(|_| {
+            open_shard(
+                base_dir,
+                &staged.drive_root,
+                Some(now_millis()),
+                staged_size,
+            )
+        });
+
+    let result = match result {
+        Ok(shard) => {
+            insert_shard(&mut state, shard);
+            Ok(())
+        }
+        Err(error) => {
+            if let Ok(previous_shard) = open_shard(
+                base_dir,
+                &staged.drive_root,
+                previous_scan_time,
+                previous_size,
+            ) {
+                insert_shard(&mut state, previous_shard);
+            }
+            Err(error)
+        }
+    };
+
+    update_shard_status(&mut state);
+    let _ = write_meta(base_dir, &meta_from_status(&state));
+    result
+}
+
+/// Removes a shard and deletes its files.
+pub(super) fn drop_shard(
+    state: &mut GlobalSearchState,
+    base_dir: &Path,
+    drive_root: &str,
+) -> Result<(), String> {
+    if let Some(shard) = take_shard(state, drive_root) {
+        drop(shard);
+    }
+    remove_dir_force(&shard_dir(base_dir, drive_root))
+}
+
+pub fn global_search_drop_shard(
+    app: tauri::AppHandle,
+    drive_root: String,
+) -> Result<GlobalSearchStatus, String> {
+    let base_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|error: tauri::Error| error.to_string())?;
+    ensure_shards_loaded(&base_dir)?;
+
+    let mut state = GLOBAL_SEARCH_STATE
+        .write()
+        .map_err(|error| error.to_string())?;
+    if state.status.is_scan_in_progress {
+        return Err("A full scan is already in progress".to_string());
+    }
+
+    let result = drop_shard(&mut state, &base_dir, &drive_root);
+    update_shard_status(&mut state);
+    write_meta(&base_dir, &meta_from_status(&state))?;
+    result?;
+
+    Ok(state.status.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shard_ids_are_stable_and_distinct() {
+        assert!(shard_id("/").starts_with("root-"));
+        assert!(shard_id("C:/").starts_with("C-"));
+        assert!(shard_id("/media/usb").starts_with("media_usb-"));
+        assert_eq!(shard_id("/media/usb"), shard_id("/media/usb"));
+        assert_ne!(shard_id("C:/"), shard_id("C/"));
+    }
+}
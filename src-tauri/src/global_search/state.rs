@@ -2,6 +2,8 @@
 // License: GNU GPLv3 or later. See the license file in the project root for more information.
 // Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
 
+use super::content::build_content_schema;
+use super::index::build_schema;
 use super::types::{GlobalSearchScanPhase, GlobalSearchStatus};
 use once_cell::sync::Lazy;
 use std::sync::atomic::AtomicBool;
@@ -15,17 +17,48 @@ pub(super) struct GlobalSearchIndexFields {
     pub(super) path: Field,
     pub(super) name: Field,
     pub(super) name_lower: Field,
+    pub(super) ext: Field,
     pub(super) is_file: Field,
     pub(super) is_dir: Field,
     pub(super) modified_time: Field,
     pub(super) size: Field,
+    /// When the path was first indexed, in Unix milliseconds.
+    pub(super) indexed_time: Field,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct GlobalSearchContentFields {
+    pub(super) path: Field,
+    pub(super) content: Field,
+    pub(super) modified_time: Field,
+    pub(super) size: Field,
+}
+
+pub(super) struct GlobalSearchContentShard {
+    pub(super) index: Index,
+    pub(super) reader: IndexReader,
+}
+
+/// Name index of one drive root, plus its content index when content
+/// indexing was enabled for the last scan of that drive.
+pub(super) struct GlobalSearchShard {
+    pub(super) drive_root: String,
+    pub(super) index: Index,
+    pub(super) reader: IndexReader,
+    pub(super) content: Option<GlobalSearchContentShard>,
+    pub(super) is_online: bool,
+    pub(super) last_scan_time: Option<u64>,
+    /// Bytes on disk of the shard directory, measured when the shard is opened
+    /// or committed so status updates never walk it under the state lock.
+    pub(super) index_size_bytes: u64,
 }
 
 pub(super) struct GlobalSearchState {
     pub(super) status: GlobalSearchStatus,
-    pub(super) index: Option<Index>,
-    pub(super) reader: Option<IndexReader>,
-    pub(super) fields: Option<GlobalSearchIndexFields>,
+    pub(super) shards: Vec<GlobalSearchShard>,
+    pub(super) are_shards_loaded: bool,
+    pub(super) fields: GlobalSearchIndexFields,
+    pub(super) content_fields: GlobalSearchContentFields,
     pub(super) cancel_flag: Arc<AtomicBool>,
 }
 
@@ -49,20 +82,29 @@ pub(super) static GLOBAL_SEARCH_STATE: Lazy<Arc<RwLock<GlobalSearchState>>> = La
             scan_indexed_item_count: 0,
             indexed_drive_roots: vec![],
             index_size_bytes: 0,
+            content_indexed_item_count: 0,
+            live_update_mode: None,
+            last_live_update_time: None,
             current_drive_root: None,
             current_scan_path: None,
             drive_scan_errors: vec![],
             is_index_valid: false,
             scanned_drives_count: 0,
             total_drives_count: 0,
+            shards: vec![],
         },
-        index: None,
-        reader: None,
-        fields: None,
+        shards: vec![],
+        are_shards_loaded: false,
+        fields: build_schema().1,
+        content_fields: build_content_schema().1,
         cancel_flag: Arc::new(AtomicBool::new(false)),
     }))
 });
 
+/// Serializes tests that replace the shared search state.
+#[cfg(test)]
+pub(super) static GLOBAL_SEARCH_TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
 pub(super) fn now_millis() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
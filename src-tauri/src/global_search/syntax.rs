@@ -0,0 +1,603 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Parser for the structured global search syntax, e.g.
+//! `invoice ext:pdf size:>5MB modified:<30d path:~/Work -draft type:dir`.
+//!
+//! Terms are ANDed, `OR` separates alternatives (AND binds tighter) and a
+//! leading `-` negates a term or filter. The parsed query is kept in
+//! disjunctive form so it can be compiled into a tantivy query and evaluated
+//! against entries that never went through the index.
+
+use crate::utils::normalize_path;
+use std::ops::Bound;
+
+use super::ignore::normalize_case;
+use super::types::{GlobalSearchQueryError, GlobalSearchQueryErrorKind};
+
+const MILLIS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum EntryTypeFilter {
+    File,
+    Dir,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum QueryFilter {
+    Extension(Vec<String>),
+    Size(Bound<u64>, Bound<u64>),
+    Modified(Bound<u64>, Bound<u64>),
+    PathPrefix(String),
+    PathSegment(String),
+    Type(EntryTypeFilter),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct QueryBranch {
+    pub(super) text: Vec<String>,
+    pub(super) excluded_text: Vec<String>,
+    pub(super) filters: Vec<QueryFilter>,
+    pub(super) excluded_filters: Vec<QueryFilter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct StructuredQuery {
+    pub(super) branches: Vec<QueryBranch>,
+}
+
+/// Values of an entry that the query filters are evaluated against.
+pub(super) struct EntryFacts<'a> {
+    pub(super) name: &'a str,
+    pub(super) path: &'a str,
+    pub(super) ext: Option<&'a str>,
+    pub(super) size: u64,
+    pub(super) modified_time: u64,
+    pub(super) is_file: bool,
+    pub(super) is_dir: bool,
+}
+
+struct QueryToken {
+    value: String,
+    field: Option<String>,
+    is_negated: bool,
+    is_quoted: bool,
+    position: usize,
+    raw: String,
+}
+
+fn syntax_error(message: impl Into<String>, token: &QueryToken) -> GlobalSearchQueryError {
+    GlobalSearchQueryError {
+        kind: GlobalSearchQueryErrorKind::Syntax,
+        message: message.into(),
+        token: Some(token.raw.clone()),
+        position: Some(token.position),
+    }
+}
+
+/// Splits the query into whitespace separated tokens, keeping quoted runs
+/// together. Positions are UTF-16 offsets so the frontend can point at them.
+fn tokenize(query: &str) -> Result<Vec<QueryToken>, GlobalSearchQueryError> {
+    let mut tokens = Vec::new();
+    let mut current: Option<QueryToken> = None;
+    let mut in_quotes = false;
+    let mut quote_position = 0;
+    let mut position = 0;
+
+    for character in query.chars() {
+        let character_position = position;
+        position += character.len_utf16();
+
+        if character.is_whitespace() && !in_quotes {
+            if let Some(token) = current.take() {
+                tokens.push(token);
+            }
+            continue;
+        }
+
+        let token = current.get_or_insert_with(|| QueryToken {
+            value: String::new(),
+            field: None,
+            is_negated: false,
+            is_quoted: false,
+            position: character_position,
+            raw: String::new(),
+        });
+        token.raw.push(character);
+
+        match character {
+            '"' => {
+                in_quotes = !in_quotes;
+                token.is_quoted = true;
+                quote_position = character_position;
+            }
+            '-' if !in_quotes && token.raw.len() == 1 => {
+                token.is_negated = true;
+            }
+            ':' if !in_quotes
+                && token.field.is_none()
+                && !token.is_quoted
+                && !token.value.is_empty()
+                && token.value.chars().all(|c| c.is_ascii_alphabetic()) =>
+            {
+                token.field = Some(std::mem::take(&mut token.value));
+            }
+            _ => token.value.push(character),
+        }
+    }
+
+    if in_quotes {
+        return Err(GlobalSearchQueryError {
+            kind: GlobalSearchQueryErrorKind::Syntax,
+            message: "Unterminated quote".to_string(),
+            token: current.map(|token| token.raw),
+            position: Some(quote_position),
+        });
+    }
+
+    if let Some(token) = current.take() {
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn is_or_operator(token: &QueryToken) -> bool {
+    !token.is_quoted && !token.is_negated && token.field.is_none() && token.value == "OR"
+}
+
+pub(super) fn parse_structured_query(
+    query: &str,
+    now_ms: u64,
+) -> Result<StructuredQuery, GlobalSearchQueryError> {
+    let tokens = tokenize(query)?;
+    let mut branches = Vec::new();
+    let mut branch = QueryBranch::default();
+    let mut branch_is_empty = true;
+
+    for (token_index, token) in tokens.iter().enumerate() {
+        if is_or_operator(token) {
+            if branch_is_empty || token_index + 1 == tokens.len() {
+                return Err(syntax_error("OR needs a term on both sides", token));
+            }
+            branches.push(std::mem::take(&mut branch));
+            branch_is_empty = true;
+            continue;
+        }
+
+        branch_is_empty = false;
+
+        match token.field.as_deref().map(str::to_lowercase) {
+            Some(field) if is_known_field(&field) => {
+                let filter = parse_filter(&field, token, now_ms)?;
+                if token.is_negated {
+                    branch.excluded_filters.push(filter);
+                } else {
+                    branch.filters.push(filter);
+                }
+            }
+            field => {
+                let text = match field {
+                    Some(_) => format!("{}:{}", token.field.as_deref().unwrap_or(""), token.value),
+                    None => token.value.clone(),
+                };
+
+                if text.trim().is_empty() {
+                    if token.is_negated {
+                        return Err(syntax_error("Nothing to exclude after '-'", token));
+                    }
+                    continue;
+                }
+
+                if token.is_negated {
+                    branch.excluded_text.push(text);
+                } else {
+                    branch.text.push(text);
+                }
+            }
+        }
+    }
+
+    branches.push(branch);
+
+    Ok(StructuredQuery { branches })
+}
+
+fn is_known_field(field: &str) -> bool {
+    matches!(field, "ext" | "size" | "modified" | "path" | "type")
+}
+
+fn parse_filter(
+    field: &str,
+    token: &QueryToken,
+    now_ms: u64,
+) -> Result<QueryFilter, GlobalSearchQueryError> {
+    let value = token.value.trim();
+    if value.is_empty() {
+        return Err(syntax_error(format!("Missing value for '{field}:'"), token));
+    }
+
+    match field {
+        "ext" => {
+            let extensions: Vec<String> = value
+                .split(',')
+                .map(|ext| normalize_case(ext.trim_start_matches('.')))
+                .filter(|ext| !ext.is_empty())
+                .collect();
+            if extensions.is_empty() {
+                return Err(syntax_error("Missing value for 'ext:'", token));
+            }
+            Ok(QueryFilter::Extension(extensions))
+        }
+        "size" => {
+            let (lower, upper) = parse_range(value, parse_size)
+                .ok_or_else(|| syntax_error(format!("Invalid size: {value}"), token))?;
+            Ok(QueryFilter::Size(lower, upper))
+        }
+        "modified" => {
+            let (lower, upper) = parse_modified(value, now_ms)
+                .ok_or_else(|| syntax_error(format!("Invalid date or age: {value}"), token))?;
+            Ok(QueryFilter::Modified(lower, upper))
+        }
+        "path" => Ok(parse_path_filter(value)),
+        "type" => match normalize_case(value).as_str() {
+            "file" | "files" | "f" => Ok(QueryFilter::Type(EntryTypeFilter::File)),
+            "dir" | "dirs" | "directory" | "folder" | "folders" | "d" => {
+                Ok(QueryFilter::Type(EntryTypeFilter::Dir))
+            }
+            _ => Err(syntax_error(
+                format!("Unknown type '{value}', expected 'file' or 'dir'"),
+                token,
+            )),
+        },
+        _ => Err(syntax_error(format!("Unknown filter '{field}:'"), token)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+}
+
+fn split_comparison(value: &str) -> (Comparison, &str) {
+    for (prefix, comparison) in [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+        ("=", Comparison::Equal),
+    ] {
+        if let Some(rest) = value.strip_prefix(prefix) {
+            return (comparison, rest.trim());
+        }
+    }
+    (Comparison::Equal, value)
+}
+
+fn comparison_bounds(comparison: Comparison, value: u64) -> (Bound<u64>, Bound<u64>) {
+    match comparison {
+        Comparison::Greater => (Bound::Excluded(value), Bound::Unbounded),
+        Comparison::GreaterOrEqual => (Bound::Included(value), Bound::Unbounded),
+        Comparison::Less => (Bound::Unbounded, Bound::Excluded(value)),
+        Comparison::LessOrEqual => (Bound::Unbounded, Bound::Included(value)),
+        Comparison::Equal => (Bound::Included(value), Bound::Included(value)),
+    }
+}
+
+/// Parses `>N`, `<=N`, `N` or `A..B` (inclusive) using `parse_value` for each side.
+fn parse_range(
+    value: &str,
+    parse_value: fn(&str) -> Option<u64>,
+) -> Option<(Bound<u64>, Bound<u64>)> {
+    if let Some((start, end)) = value.split_once("..") {
+        let lower = match start.trim() {
+            "" => Bound::Unbounded,
+            start => Bound::Included(parse_value(start)?),
+        };
+        let upper = match end.trim() {
+            "" => Bound::Unbounded,
+            end => Bound::Included(parse_value(end)?),
+        };
+        if lower == Bound::Unbounded && upper == Bound::Unbounded {
+            return None;
+        }
+        return Some((lower, upper));
+    }
+
+    let (comparison, rest) = split_comparison(value);
+    Some(comparison_bounds(comparison, parse_value(rest)?))
+}
+
+fn split_number_and_unit(value: &str) -> Option<(f64, String)> {
+    let unit_start = value
+        .find(|character: char| !(character.is_ascii_digit() || character == '.'))
+        .unwrap_or(value.len());
+    let number: f64 = value[..unit_start].parse().ok()?;
+    if !number.is_finite() || number < 0.0 {
+        return None;
+    }
+    Some((number, value[unit_start..].trim().to_lowercase()))
+}
+
+fn parse_size(value: &str) -> Option<u64> {
+    let (number, unit) = split_number_and_unit(value)?;
+    let multiplier: u64 = match unit.as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+fn parse_age_millis(value: &str) -> Option<u64> {
+    let (number, unit) = split_number_and_unit(value)?;
+    let unit_millis: u64 = match unit.as_str() {
+        "min" | "mins" | "minute" | "minutes" => 60 * 1000,
+        "h" | "hr" | "hour" | "hours" => 60 * 60 * 1000,
+        "d" | "day" | "days" => MILLIS_PER_DAY,
+        "w" | "week" | "weeks" => 7 * MILLIS_PER_DAY,
+        "mo" | "month" | "months" => 30 * MILLIS_PER_DAY,
+        "y" | "year" | "years" => 365 * MILLIS_PER_DAY,
+        _ => return None,
+    };
+    Some((number * unit_millis as f64) as u64)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Parses a `YYYY-MM-DD` date into the UTC start of that day in unix milliseconds.
+fn parse_date_millis(value: &str) -> Option<u64> {
+    let mut parts = value.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day).max(0) as u64;
+    Some(days * MILLIS_PER_DAY)
+}
+
+fn date_bounds(comparison: Comparison, day_start: u64) -> (Bound<u64>, Bound<u64>) {
+    let day_end = day_start + MILLIS_PER_DAY;
+    match comparison {
+        Comparison::Greater => (Bound::Included(day_end), Bound::Unbounded),
+        Comparison::GreaterOrEqual => (Bound::Included(day_start), Bound::Unbounded),
+        Comparison::Less => (Bound::Unbounded, Bound::Excluded(day_start)),
+        Comparison::LessOrEqual => (Bound::Unbounded, Bound::Excluded(day_end)),
+        Comparison::Equal => (Bound::Included(day_start), Bound::Excluded(day_end)),
+    }
+}
+
+/// Dates compare against the modification time itself (`>2024-01-01` is after
+/// that day), while ages compare against how long ago the entry was modified
+/// (`<30d` is newer than 30 days; a bare age means the same).
+fn parse_modified(value: &str, now_ms: u64) -> Option<(Bound<u64>, Bound<u64>)> {
+    if let Some((start, end)) = value.split_once("..") {
+        let lower = parse_date_millis(start.trim())?;
+        let upper = parse_date_millis(end.trim())? + MILLIS_PER_DAY;
+        return Some((Bound::Included(lower), Bound::Excluded(upper)));
+    }
+
+    let (comparison, rest) = split_comparison(value);
+
+    if let Some(day_start) = parse_date_millis(rest) {
+        return Some(date_bounds(comparison, day_start));
+    }
+
+    let threshold = now_ms.saturating_sub(parse_age_millis(rest)?);
+    Some(match comparison {
+        Comparison::Less | Comparison::Equal => (Bound::Excluded(threshold), Bound::Unbounded),
+        Comparison::LessOrEqual => (Bound::Included(threshold), Bound::Unbounded),
+        Comparison::Greater => (Bound::Unbounded, Bound::Excluded(threshold)),
+        Comparison::GreaterOrEqual => (Bound::Unbounded, Bound::Included(threshold)),
+    })
+}
+
+fn parse_path_filter(value: &str) -> QueryFilter {
+    let expanded = match value.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\') => {
+            match dirs::home_dir() {
+                Some(home_dir) => format!("{}{}", home_dir.to_string_lossy(), rest),
+                None => value.to_string(),
+            }
+        }
+        _ => value.to_string(),
+    };
+
+    let normalized = normalize_path(&expanded);
+    let is_absolute =
+        normalized.starts_with('/') || (normalized.len() >= 2 && normalized.as_bytes()[1] == b':');
+    let trimmed = if normalized.len() > 1 && !normalized.ends_with(":/") {
+        normalized.trim_end_matches('/').to_string()
+    } else {
+        normalized
+    };
+
+    if is_absolute {
+        QueryFilter::PathPrefix(trimmed)
+    } else {
+        QueryFilter::PathSegment(trimmed.trim_matches('/').to_string())
+    }
+}
+
+fn bounds_contain(lower: &Bound<u64>, upper: &Bound<u64>, value: u64) -> bool {
+    let above_lower = match lower {
+        Bound::Included(lower) => value >= *lower,
+        Bound::Excluded(lower) => value > *lower,
+        Bound::Unbounded => true,
+    };
+    let below_upper = match upper {
+        Bound::Included(upper) => value <= *upper,
+        Bound::Excluded(upper) => value < *upper,
+        Bound::Unbounded => true,
+    };
+    above_lower && below_upper
+}
+
+pub(super) fn is_path_within(path: &str, prefix: &str) -> bool {
+    if prefix.ends_with('/') {
+        return path.starts_with(prefix);
+    }
+    path == prefix
+        || path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn contains_path_segments(path: &str, segments: &str) -> bool {
+    let segment_count = segments.split('/').count();
+    let parent_segments: Vec<&str> = path.split('/').collect();
+    parent_segments
+        .windows(segment_count)
+        .take(parent_segments.len().saturating_sub(segment_count))
+        .any(|window| window.join("/") == segments)
+        || path.ends_with(&format!("/{segments}"))
+}
+
+impl QueryFilter {
+    pub(super) fn matches(&self, entry: &EntryFacts) -> bool {
+        match self {
+            QueryFilter::Extension(extensions) => entry
+                .ext
+                .is_some_and(|ext| extensions.iter().any(|candidate| candidate == ext)),
+            QueryFilter::Size(lower, upper) => {
+                entry.is_file && bounds_contain(lower, upper, entry.size)
+            }
+            QueryFilter::Modified(lower, upper) => {
+                bounds_contain(lower, upper, entry.modified_time)
+            }
+            QueryFilter::PathPrefix(prefix) => is_path_within(entry.path, prefix),
+            QueryFilter::PathSegment(segments) => contains_path_segments(entry.path, segments),
+            QueryFilter::Type(EntryTypeFilter::File) => entry.is_file,
+            QueryFilter::Type(EntryTypeFilter::Dir) => entry.is_dir,
+        }
+    }
+}
+
+impl QueryBranch {
+    pub(super) fn text_query(&self) -> String {
+        self.text.join(" ")
+    }
+
+    pub(super) fn matches_filters(
+        &self,
+        entry: &EntryFacts,
+        is_excluded_name: impl Fn(&str, &str) -> bool,
+    ) -> bool {
+        self.filters.iter().all(|filter| filter.matches(entry))
+            && !self
+                .excluded_filters
+                .iter()
+                .any(|filter| filter.matches(entry))
+            && !self
+                .excluded_text
+                .iter()
+                .any(|excluded| is_excluded_name(excluded, entry.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000_000;
+
+    #[test]
+    fn parses_filters_negation_and_text() {
+        let parsed = parse_structured_query(
+            "invoice ext:pdf size:>5MB modified:<30d path:/home/user/Work -draft type:dir",
+            NOW,
+        )
+        .unwrap();
+
+        assert_eq!(parsed.branches.len(), 1);
+        let branch = &parsed.branches[0];
+        assert_eq!(branch.text, vec!["invoice".to_string()]);
+        assert_eq!(branch.excluded_text, vec!["draft".to_string()]);
+        assert_eq!(
+            branch.filters,
+            vec![
+                QueryFilter::Extension(vec!["pdf".to_string()]),
+                QueryFilter::Size(Bound::Excluded(5 * 1024 * 1024), Bound::Unbounded),
+                QueryFilter::Modified(Bound::Excluded(NOW - 30 * MILLIS_PER_DAY), Bound::Unbounded),
+                QueryFilter::PathPrefix("/home/user/Work".to_string()),
+                QueryFilter::Type(EntryTypeFilter::Dir),
+            ]
+        );
+    }
+
+    #[test]
+    fn or_splits_branches_and_quotes_keep_literals() {
+        let parsed = parse_structured_query("report ext:pdf OR \"OR\" -ext:tmp", NOW).unwrap();
+
+        assert_eq!(parsed.branches.len(), 2);
+        assert_eq!(parsed.branches[0].text, vec!["report".to_string()]);
+        assert_eq!(parsed.branches[1].text, vec!["OR".to_string()]);
+        assert_eq!(
+            parsed.branches[1].excluded_filters,
+            vec![QueryFilter::Extension(vec!["tmp".to_string()])]
+        );
+    }
+
+    #[test]
+    fn invalid_syntax_returns_structured_error() {
+        let error = parse_structured_query("notes size:>lots", NOW).unwrap_err();
+        assert_eq!(error.kind, GlobalSearchQueryErrorKind::Syntax);
+        assert_eq!(error.token.as_deref(), Some("size:>lots"));
+        assert_eq!(error.position, Some(6));
+
+        assert!(parse_structured_query("OR notes", NOW).is_err());
+        assert!(parse_structured_query("notes OR", NOW).is_err());
+        assert!(parse_structured_query("\"unterminated", NOW).is_err());
+        assert!(parse_structured_query("type:socket", NOW).is_err());
+    }
+
+    #[test]
+    fn unknown_fields_stay_plain_text() {
+        let parsed = parse_structured_query("C:/Users note:todo", NOW).unwrap();
+
+        assert_eq!(
+            parsed.branches[0].text,
+            vec!["C:/Users".to_string(), "note:todo".to_string()]
+        );
+    }
+
+    #[test]
+    fn date_filters_cover_whole_days() {
+        let parsed = parse_structured_query("modified:2024-03-01", NOW).unwrap();
+        let QueryFilter::Modified(lower, upper) = &parsed.branches[0].filters[0] else {
+            panic!("expected modified filter");
+        };
+
+        let day_start = 1_709_251_200_000;
+        assert!(bounds_contain(lower, upper, day_start));
+        assert!(bounds_contain(lower, upper, day_start + MILLIS_PER_DAY - 1));
+        assert!(!bounds_contain(lower, upper, day_start + MILLIS_PER_DAY));
+    }
+
+    #[test]
+    fn path_filters_match_prefix_and_segments() {
+        assert!(is_path_within("/home/user/Work/a.txt", "/home/user/Work"));
+        assert!(!is_path_within("/home/user/Workshop", "/home/user/Work"));
+        assert!(contains_path_segments("/home/user/Work/a.txt", "Work"));
+        assert!(contains_path_segments("/home/user/Work", "Work"));
+        assert!(!contains_path_segments("/home/user/Workshop/a.txt", "Work"));
+    }
+}
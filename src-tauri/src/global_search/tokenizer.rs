@@ -0,0 +1,243 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Tokenizer for the `name` field.
+//!
+//! Names are split on separators, camelCase boundaries and letter/digit
+//! boundaries, so `reportFinal_v2` yields `report`, `final`, `v` and `2` along
+//! with the unsplit runs `reportfinal` and `v2`. CJK text has no spaces, so it
+//! is indexed as overlapping unigrams and bigrams. Tokens are lowercased and
+//! ASCII-folded.
+
+use tantivy::tokenizer::{
+    AsciiFoldingFilter, LowerCaser, RawTokenizer, RemoveLongFilter, TextAnalyzer, Token,
+    TokenStream, Tokenizer,
+};
+use tantivy::Index;
+
+pub(super) const NAME_TOKENIZER: &str = "global_search_name";
+const MAX_TOKEN_LENGTH: usize = 40;
+
+#[derive(Clone, Default)]
+pub(super) struct NameTokenizer;
+
+pub(super) struct NameTokenStream {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl TokenStream for NameTokenStream {
+    fn advance(&mut self) -> bool {
+        if self.index < self.tokens.len() {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.index - 1]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.index - 1]
+    }
+}
+
+impl Tokenizer for NameTokenizer {
+    type TokenStream<'a> = NameTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> {
+        NameTokenStream {
+            tokens: split_name(text),
+            index: 0,
+        }
+    }
+}
+
+fn is_cjk(character: char) -> bool {
+    matches!(
+        character,
+        '\u{1100}'..='\u{11FF}'
+            | '\u{3040}'..='\u{30FF}'
+            | '\u{3130}'..='\u{318F}'
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{AC00}'..='\u{D7AF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FF66}'..='\u{FF9F}'
+            | '\u{20000}'..='\u{2FA1F}'
+    )
+}
+
+fn is_word_character(character: char) -> bool {
+    character.is_alphanumeric() && !is_cjk(character)
+}
+
+fn push_token(tokens: &mut Vec<Token>, text: &str, offset_from: usize, offset_to: usize) {
+    let position = tokens.len();
+    tokens.push(Token {
+        offset_from,
+        offset_to,
+        position,
+        text: text[offset_from..offset_to].to_string(),
+        position_length: 1,
+    });
+}
+
+/// Whether a new part starts at `current` inside a run of word characters.
+fn is_part_boundary(previous: char, current: char, next: Option<char>) -> bool {
+    if previous.is_numeric() != current.is_numeric() {
+        return true;
+    }
+    if previous.is_lowercase() && current.is_uppercase() {
+        return true;
+    }
+    previous.is_uppercase() && current.is_uppercase() && next.is_some_and(char::is_lowercase)
+}
+
+fn split_word_run(tokens: &mut Vec<Token>, text: &str, run: &[(usize, char)], run_end: usize) {
+    let mut part_starts = vec![run[0].0];
+    for index in 1..run.len() {
+        let next = run.get(index + 1).map(|(_, character)| *character);
+        if is_part_boundary(run[index - 1].1, run[index].1, next) {
+            part_starts.push(run[index].0);
+        }
+    }
+
+    if part_starts.len() > 1 {
+        push_token(tokens, text, run[0].0, run_end);
+    }
+
+    for (part_index, &part_start) in part_starts.iter().enumerate() {
+        let part_end = part_starts.get(part_index + 1).copied().unwrap_or(run_end);
+        push_token(tokens, text, part_start, part_end);
+    }
+}
+
+fn split_cjk_run(tokens: &mut Vec<Token>, text: &str, run: &[(usize, char)], run_end: usize) {
+    for index in 0..run.len() {
+        let start = run[index].0;
+        let unigram_end = run.get(index + 1).map_or(run_end, |(offset, _)| *offset);
+        push_token(tokens, text, start, unigram_end);
+
+        if index + 1 < run.len() {
+            let bigram_end = run.get(index + 2).map_or(run_end, |(offset, _)| *offset);
+            push_token(tokens, text, start, bigram_end);
+        }
+    }
+}
+
+fn split_name(text: &str) -> Vec<Token> {
+    let characters: Vec<(usize, char)> = text.char_indices().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < characters.len() {
+        let character = characters[index].1;
+        let run_matches: fn(char) -> bool = if is_cjk(character) {
+            is_cjk
+        } else if is_word_character(character) {
+            is_word_character
+        } else {
+            index += 1;
+            continue;
+        };
+
+        let run_start = index;
+        while index < characters.len() && run_matches(characters[index].1) {
+            index += 1;
+        }
+        let run = &characters[run_start..index];
+        let run_end = characters
+            .get(index)
+            .map_or(text.len(), |(offset, _)| *offset);
+
+        if is_cjk(character) {
+            split_cjk_run(&mut tokens, text, run, run_end);
+        } else {
+            split_word_run(&mut tokens, text, run, run_end);
+        }
+    }
+
+    tokens
+}
+
+pub(super) fn name_analyzer() -> TextAnalyzer {
+    TextAnalyzer::builder(NameTokenizer)
+        .filter(RemoveLongFilter::limit(MAX_TOKEN_LENGTH))
+        .filter(LowerCaser)
+        .filter(AsciiFoldingFilter)
+        .build()
+}
+
+pub(super) fn register_name_tokenizer(index: &Index) {
+    index.tokenizers().register(NAME_TOKENIZER, name_analyzer());
+}
+
+/// Tokens the name field would index for `text`, used to tokenize queries the
+/// same way as names.
+pub(super) fn analyze_name(text: &str) -> Vec<String> {
+    let mut analyzer = name_analyzer();
+    let mut token_stream = analyzer.token_stream(text);
+    let mut tokens = Vec::new();
+    while token_stream.advance() {
+        tokens.push(token_stream.token().text.clone());
+    }
+    tokens
+}
+
+/// Lowercases and ASCII-folds `text` without splitting it.
+pub(super) fn fold_text(text: &str) -> String {
+    let mut analyzer = TextAnalyzer::builder(RawTokenizer::default())
+        .filter(LowerCaser)
+        .filter(AsciiFoldingFilter)
+        .build();
+    let mut token_stream = analyzer.token_stream(text);
+    if token_stream.advance() {
+        token_stream.token().text.clone()
+    } else {
+        String::new()
+    }
+}
+
+pub(super) fn contains_cjk(text: &str) -> bool {
+    text.chars().any(is_cjk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_camel_case_digits_and_separators() {
+        assert_eq!(
+            analyze_name("reportFinal_v2.PDF"),
+            vec!["reportfinal", "report", "final", "v2", "v", "2", "pdf"]
+        );
+        assert_eq!(
+            analyze_name("XMLHttpRequest"),
+            vec!["xmlhttprequest", "xml", "http", "request"]
+        );
+    }
+
+    #[test]
+    fn folds_accents() {
+        assert_eq!(analyze_name("Café Déjà-vu"), vec!["cafe", "deja", "vu"]);
+        assert_eq!(fold_text("Crème Brûlée"), "creme brulee");
+    }
+
+    #[test]
+    fn indexes_cjk_as_unigrams_and_bigrams() {
+        assert_eq!(
+            analyze_name("報告書2024"),
+            vec!["報", "報告", "告", "告書", "書", "2024"]
+        );
+        assert_eq!(
+            analyze_name("レポート"),
+            vec!["レ", "レポ", "ポ", "ポー", "ー", "ート", "ト"]
+        );
+    }
+}
@@ -35,6 +35,28 @@ pub enum GlobalSearchScanOutcome {
     Failed,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum GlobalSearchLiveUpdateMode {
+    Native,
+    Polling,
+}
+
+/// Which of the listed drives a scan rebuilds. Every drive root has its own
+/// index shard, so drives that are not rescanned keep their current shard.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum GlobalSearchScanTarget {
+    /// Rescans every listed drive and drops the shards of available drives
+    /// that are no longer listed.
+    #[default]
+    AllDrives,
+    /// Rescans only the listed drives.
+    ListedDrives,
+    /// Scans listed drives that have no shard yet or whose shard is offline.
+    AddedDrives,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GlobalSearchSettings {
     pub scan_depth: usize,
@@ -43,6 +65,35 @@ pub struct GlobalSearchSettings {
     pub parallel_scan: bool,
     #[serde(default = "default_scan_reason")]
     pub scan_reason: GlobalSearchScanReason,
+    #[serde(default)]
+    pub index_content: bool,
+    #[serde(default)]
+    pub content_max_file_size: Option<u64>,
+    #[serde(default)]
+    pub scan_target: GlobalSearchScanTarget,
+    /// Honours `.gitignore`, `.ignore` and `.sfmignore` files in addition to
+    /// `ignored_paths`.
+    #[serde(default)]
+    pub respect_ignore_files: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalSearchLiveUpdateSettings {
+    pub scan_depth: usize,
+    pub ignored_paths: Vec<String>,
+    #[serde(default)]
+    pub force_polling: bool,
+    #[serde(default)]
+    pub poll_interval_ms: Option<u64>,
+    /// Folders to poll when native watching is unavailable. Polling re-stats
+    /// every file under its roots, so whole drives are never polled; without
+    /// poll roots live updates stay off in that case.
+    #[serde(default)]
+    pub poll_roots: Vec<String>,
+    #[serde(default)]
+    pub content_max_file_size: Option<u64>,
+    #[serde(default)]
+    pub respect_ignore_files: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,6 +102,26 @@ pub struct GlobalSearchDriveScanError {
     pub message: String,
 }
 
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum GlobalSearchMatchMode {
+    #[default]
+    Fuzzy,
+    Regex,
+    Glob,
+}
+
+/// What a regex or glob pattern is matched against. `Auto` uses the full path
+/// when the pattern contains a `/` and the file name otherwise.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum GlobalSearchMatchTarget {
+    #[default]
+    Auto,
+    Name,
+    Path,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GlobalSearchQueryOptions {
     pub limit: usize,
@@ -59,6 +130,46 @@ pub struct GlobalSearchQueryOptions {
     pub exact_match: bool,
     pub typo_tolerance: bool,
     pub min_score_threshold: Option<f32>,
+    #[serde(default)]
+    pub match_mode: GlobalSearchMatchMode,
+    #[serde(default)]
+    pub match_target: GlobalSearchMatchTarget,
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Restricts results to entries below this folder.
+    #[serde(default)]
+    pub scope_path: Option<String>,
+    /// Echoed back in scope walk events so stale batches can be dropped.
+    #[serde(default)]
+    pub request_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum GlobalSearchQueryErrorKind {
+    Syntax,
+    Search,
+}
+
+/// Error returned by `global_search_query`. Syntax errors point at the
+/// offending token; `position` is a UTF-16 offset into the query string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalSearchQueryError {
+    pub kind: GlobalSearchQueryErrorKind,
+    pub message: String,
+    pub token: Option<String>,
+    pub position: Option<usize>,
+}
+
+impl From<String> for GlobalSearchQueryError {
+    fn from(message: String) -> Self {
+        Self {
+            kind: GlobalSearchQueryErrorKind::Search,
+            message,
+            token: None,
+            position: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,6 +190,126 @@ pub struct GlobalSearchResultEntry {
     pub score: f32,
 }
 
+/// Batch of live walk results for a scoped search outside the indexed drives.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalSearchScopeResultsEvent {
+    pub request_id: Option<u64>,
+    pub scope_path: String,
+    pub entries: Vec<GlobalSearchResultEntry>,
+    pub scanned_count: u64,
+    pub is_done: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalSearchContentQueryOptions {
+    pub limit: usize,
+    #[serde(default)]
+    pub snippet_max_chars: Option<usize>,
+}
+
+/// Highlighted range within a snippet fragment, in UTF-16 code units so the
+/// frontend can slice the fragment string directly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalSearchSnippetRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalSearchContentSnippet {
+    pub fragment: String,
+    pub highlighted: Vec<GlobalSearchSnippetRange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalSearchContentResultEntry {
+    pub name: String,
+    pub ext: Option<String>,
+    pub path: String,
+    pub size: u64,
+    pub modified_time: u64,
+    pub score: f32,
+    pub snippet: Option<GlobalSearchContentSnippet>,
+}
+
+/// Options shared by the file reports. Every field is optional; reports
+/// cover all indexed drives and return 100 files unless told otherwise.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GlobalSearchReportOptions {
+    /// Restricts the report to files below this folder.
+    #[serde(default)]
+    pub root_path: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Stale files report: files not modified for this many days.
+    #[serde(default)]
+    pub older_than_days: Option<u64>,
+    /// Recent additions report: smallest file size to include.
+    #[serde(default)]
+    pub min_size: Option<u64>,
+    /// Recent additions report: unix time in ms to look back to. Defaults to
+    /// the last scan of each drive.
+    #[serde(default)]
+    pub since_time: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalSearchReportFile {
+    pub name: String,
+    pub ext: Option<String>,
+    pub path: String,
+    pub size: u64,
+    pub modified_time: u64,
+}
+
+/// Files of a report in report order, plus the count and total size of every
+/// matching file, not only the returned ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalSearchFileReport {
+    pub files: Vec<GlobalSearchReportFile>,
+    pub matched_count: u64,
+    pub matched_size_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum GlobalSearchBreakdownGroup {
+    #[default]
+    Extension,
+    /// Groups extensions into `image`, `video`, `audio`, `text`, `document`,
+    /// `archive`, `font` and `other`.
+    MimeCategory,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GlobalSearchBreakdownOptions {
+    #[serde(default)]
+    pub root_path: Option<String>,
+    #[serde(default)]
+    pub group_by: GlobalSearchBreakdownGroup,
+}
+
+/// Files of one extension or category. Files without an extension are
+/// grouped under an empty key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalSearchBreakdownEntry {
+    pub key: String,
+    pub item_count: u64,
+    pub total_size_bytes: u64,
+}
+
+/// Index shard of one drive root. Shards of unplugged drives stay searchable
+/// and are reported with `is_online: false`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalSearchShardStatus {
+    pub drive_root: String,
+    pub is_online: bool,
+    pub indexed_item_count: u64,
+    pub content_indexed_item_count: u64,
+    pub index_size_bytes: u64,
+    pub last_scan_time: Option<u64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GlobalSearchStatus {
     pub is_scan_in_progress: bool,
@@ -98,12 +329,16 @@ pub struct GlobalSearchStatus {
     pub scan_indexed_item_count: u64,
     pub indexed_drive_roots: Vec<String>,
     pub index_size_bytes: u64,
+    pub content_indexed_item_count: u64,
+    pub live_update_mode: Option<GlobalSearchLiveUpdateMode>,
+    pub last_live_update_time: Option<u64>,
     pub current_drive_root: Option<String>,
     pub current_scan_path: Option<String>,
     pub drive_scan_errors: Vec<GlobalSearchDriveScanError>,
     pub is_index_valid: bool,
     pub scanned_drives_count: u32,
     pub total_drives_count: u32,
+    pub shards: Vec<GlobalSearchShardStatus>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -111,4 +346,10 @@ pub struct IndexPathsSettings {
     pub paths: Vec<String>,
     pub scan_depth: usize,
     pub ignored_paths: Vec<String>,
+    #[serde(default)]
+    pub index_content: bool,
+    #[serde(default)]
+    pub content_max_file_size: Option<u64>,
+    #[serde(default)]
+    pub respect_ignore_files: bool,
 }
@@ -13,15 +13,21 @@ mod delete_job;
 mod dir_reader;
 mod dir_size;
 mod dir_watcher;
+mod disk_usage;
 mod extensions;
 mod file_operations;
+mod frecency;
+mod git_status;
 mod global_search;
 mod image_thumbnails;
 mod input_simulation;
 mod lan_share;
 mod link_operations;
+mod media_metadata;
+mod mime_detect;
 mod open_with;
 mod process_runner;
+mod smart_folders;
 mod startup_storage_bootstrap;
 mod system_clipboard;
 mod system_icons;
@@ -287,7 +293,12 @@ pub fn run() {
             system_tray::update_tray_shortcut,
             dir_reader::read_dir,
             dir_reader::read_dir_with_timeout,
+            dir_reader::start_read_dir_stream,
+            dir_reader::cancel_read_dir_stream,
+            dir_reader::read_dir_flat,
+            dir_reader::start_read_dir_flat_stream,
             dir_reader::get_dir_entry_with_timeout,
+            dir_reader::get_item_properties,
             dir_reader::get_link_metadata_batch,
             dir_reader::get_dir_item_counts_batch,
             dir_reader::resolve_windows_directory_shortcut,
@@ -326,16 +337,35 @@ pub fn run() {
             copy_move_job::cancel_copy_move_job,
             delete_job::start_delete_job,
             delete_job::cancel_delete_job,
+            disk_usage::start_disk_usage_job,
+            disk_usage::cancel_disk_usage_job,
+            disk_usage::get_disk_usage_report,
+            frecency::jump_query,
+            frecency::frecency_import_zoxide,
             global_search::global_search_init,
             global_search::global_search_get_status,
             global_search::global_search_start_scan,
             global_search::global_search_cancel_scan,
             global_search::global_search_index_paths,
+            global_search::global_search_drop_shard,
             global_search::global_search_query,
             global_search::global_search_query_paths,
+            global_search::global_search_cancel_scope_walk,
+            global_search::global_search_content_query,
+            global_search::global_search_report_largest_files,
+            global_search::global_search_report_stale_files,
+            global_search::global_search_report_recent_additions,
+            global_search::global_search_report_size_breakdown,
+            global_search::global_search_start_live_updates,
+            global_search::global_search_stop_live_updates,
+            smart_folders::list_smart_folders,
+            smart_folders::save_smart_folder,
+            smart_folders::delete_smart_folder,
+            smart_folders::read_smart_folder,
             image_thumbnails::cache_video_thumbnail,
             image_thumbnails::generate_image_thumbnail,
             image_thumbnails::get_cached_video_thumbnail,
+            media_metadata::get_media_metadata_batch,
             open_with::get_associated_programs,
             open_with::open_with_program,
             open_with::open_with_default,
@@ -427,6 +457,7 @@ pub fn run() {
             }
             if let tauri::WindowEvent::Destroyed = event {
                 if window.label() == "main" {
+                    frecency::flush();
                     tokio::spawn(async { lan_share::stop_lan_share().await.ok() });
                 }
             }
@@ -446,6 +477,7 @@ fn setup_handler(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>>
     }
 
     system_tray::setup_system_tray(app.handle())?;
+    dir_size::load_persistent_cache(app.handle());
     startup_storage_bootstrap::migrate_legacy_user_storage_filenames(app.handle());
     #[cfg(windows)]
     if let Err(error) = default_file_manager::migrate_legacy_default_file_manager(app.handle()) {
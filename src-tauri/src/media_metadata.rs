@@ -0,0 +1,330 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Metadata of photos, music and videos for list columns and sorting.
+//!
+//! Images get their dimensions and EXIF data, audio its tags and duration,
+//! and MP4, Matroska and WebM videos their duration, resolution and codecs.
+//! Results are cached on disk by path, modification time and size, like
+//! image thumbnails.
+
+mod audio;
+mod photo;
+mod video;
+
+use crate::mime_detect::detect_mime_type;
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::Manager;
+
+const MEDIA_METADATA_CACHE_DIR: &str = "media-metadata";
+const MEDIA_METADATA_CACHE_VERSION: u32 = 1;
+const MAX_MEDIA_METADATA_CACHE_ITEM_COUNT: usize = 50_000;
+const MEDIA_METADATA_CACHE_LIMIT_CHECK_INTERVAL: usize = 500;
+
+static MEDIA_METADATA_CACHE_MAINTENANCE: Lazy<Mutex<MediaMetadataCacheMaintenanceState>> =
+    Lazy::new(|| Mutex::new(MediaMetadataCacheMaintenanceState::default()));
+static TEMPORARY_MEDIA_METADATA_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Default)]
+struct MediaMetadataCacheMaintenanceState {
+    checked_once: bool,
+    written_since_check: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MediaMetadataRequest {
+    pub path: String,
+    pub modified_time: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    Image,
+    Audio,
+    Video,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExifMetadata {
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    /// Local time as "YYYY-MM-DDTHH:MM:SS", with the offset appended when
+    /// the camera recorded one.
+    pub capture_time: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Meters above sea level.
+    pub altitude: Option<f64>,
+    /// EXIF orientation, 1 to 8.
+    pub orientation: Option<u16>,
+    /// Seconds.
+    pub exposure_time: Option<f64>,
+    pub f_number: Option<f64>,
+    pub iso: Option<u32>,
+    /// Millimeters.
+    pub focal_length: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AudioTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub date: Option<String>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MediaMetadata {
+    pub path: String,
+    pub kind: Option<MediaKind>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Seconds.
+    pub duration: Option<f64>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+    pub exif: Option<ExifMetadata>,
+    pub tags: Option<AudioTags>,
+    /// Why the file could not be read; the other fields are then empty.
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedMediaMetadata {
+    version: u32,
+    metadata: MediaMetadata,
+}
+
+fn hash_to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>()
+}
+
+fn media_metadata_cache_key(path: &str, modified_time: u64, size: u64) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(path.as_bytes());
+    hasher.update([0]);
+    hasher.update(modified_time.to_le_bytes());
+    hasher.update(size.to_le_bytes());
+    hash_to_hex(&hasher.finalize())
+}
+
+fn media_metadata_cache_path(cache_dir: &Path, request: &MediaMetadataRequest) -> PathBuf {
+    cache_dir.join(format!(
+        "{}.json",
+        media_metadata_cache_key(&request.path, request.modified_time, request.size)
+    ))
+}
+
+fn media_metadata_cache_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(app
+        .path()
+        .app_data_dir()
+        .map_err(|error| format!("Failed to resolve app data directory: {error}"))?
+        .join(MEDIA_METADATA_CACHE_DIR))
+}
+
+fn media_kind(path: &Path) -> Option<MediaKind> {
+    let mime = detect_mime_type(path, false)?;
+    match mime.split('/').next()? {
+        "image" => Some(MediaKind::Image),
+        "audio" => Some(MediaKind::Audio),
+        "video" => Some(MediaKind::Video),
+        _ => None,
+    }
+}
+
+/// Reads the metadata of `path` without touching the cache.
+fn read_media_metadata(path: &Path) -> MediaMetadata {
+    let mut metadata = MediaMetadata {
+        path: path.to_string_lossy().into_owned(),
+        kind: media_kind(path),
+        ..Default::default()
+    };
+
+    let result = match metadata.kind {
+        Some(MediaKind::Image) => photo::read_image_metadata(path, &mut metadata),
+        Some(MediaKind::Audio) => audio::read_audio_metadata(path, &mut metadata),
+        Some(MediaKind::Video) => video::read_video_metadata(path, &mut metadata),
+        None => Ok(()),
+    };
+    if let Err(error) = result {
+        metadata = MediaMetadata {
+            path: metadata.path,
+            kind: metadata.kind,
+            error: Some(error),
+            ..Default::default()
+        };
+    }
+
+    metadata
+}
+
+fn read_cached_media_metadata(cache_path: &Path) -> Option<MediaMetadata> {
+    let text = fs::read_to_string(cache_path).ok()?;
+    serde_json::from_str::<CachedMediaMetadata>(&text)
+        .ok()
+        .filter(|cached| cached.version == MEDIA_METADATA_CACHE_VERSION)
+        .map(|cached| cached.metadata)
+}
+
+fn write_cached_media_metadata(cache_path: &Path, metadata: &MediaMetadata) -> Result<(), String> {
+    let json = serde_json::to_string(&CachedMediaMetadata {
+        version: MEDIA_METADATA_CACHE_VERSION,
+        metadata: metadata.clone(),
+    })
+    .map_err(|error| format!("Failed to serialize media metadata: {error}"))?;
+    let temporary_id = TEMPORARY_MEDIA_METADATA_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let temporary_path =
+        cache_path.with_extension(format!("{}.{temporary_id}.tmp", std::process::id()));
+
+    fs::write(&temporary_path, json)
+        .and_then(|_| fs::rename(&temporary_path, cache_path))
+        .map_err(|error| {
+            let _ = fs::remove_file(&temporary_path);
+            format!("Failed to write media metadata cache: {error}")
+        })
+}
+
+/// Clears the cache once it holds too many items, checking on first use
+/// and then every `MEDIA_METADATA_CACHE_LIMIT_CHECK_INTERVAL` writes.
+fn enforce_media_metadata_cache_limits(cache_dir: &Path, written: usize) -> Result<(), String> {
+    {
+        let mut state = MEDIA_METADATA_CACHE_MAINTENANCE
+            .lock()
+            .map_err(|error| format!("Failed to lock media metadata cache maintenance: {error}"))?;
+        state.written_since_check += written;
+        if state.checked_once
+            && state.written_since_check < MEDIA_METADATA_CACHE_LIMIT_CHECK_INTERVAL
+        {
+            return Ok(());
+        }
+        state.checked_once = true;
+        state.written_since_check = 0;
+    }
+
+    let Ok(entries) = fs::read_dir(cache_dir) else {
+        return Ok(());
+    };
+    if entries.count() >= MAX_MEDIA_METADATA_CACHE_ITEM_COUNT {
+        fs::remove_dir_all(cache_dir)
+            .map_err(|error| format!("Failed to clear media metadata cache: {error}"))?;
+    }
+
+    Ok(())
+}
+
+fn get_media_metadata_batch_from_cache(
+    cache_dir: &Path,
+    items: Vec<MediaMetadataRequest>,
+) -> Vec<MediaMetadata> {
+    let _ = fs::create_dir_all(cache_dir);
+    let written = AtomicU64::new(0);
+
+    let results = items
+        .par_iter()
+        .map(|request| {
+            let cache_path = media_metadata_cache_path(cache_dir, request);
+            if let Some(metadata) = read_cached_media_metadata(&cache_path) {
+                return metadata;
+            }
+
+            let metadata = read_media_metadata(Path::new(&request.path));
+            // Files that are not media are cheap to recognize again.
+            if metadata.kind.is_some()
+                && write_cached_media_metadata(&cache_path, &metadata).is_ok()
+            {
+                written.fetch_add(1, Ordering::Relaxed);
+            }
+            metadata
+        })
+        .collect();
+
+    if let Err(error) =
+        enforce_media_metadata_cache_limits(cache_dir, written.into_inner() as usize)
+    {
+        log::warn!("{error}");
+    }
+
+    results
+}
+
+/// Reads image, audio and video metadata of `items`, in the same order.
+/// Files that are not media come back with no `kind`.
+#[tauri::command]
+pub async fn get_media_metadata_batch(
+    app: tauri::AppHandle,
+    items: Vec<MediaMetadataRequest>,
+) -> Result<Vec<MediaMetadata>, String> {
+    let cache_dir = media_metadata_cache_dir(&app)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        get_media_metadata_batch_from_cache(&cache_dir, items)
+    })
+    .await
+    .map_err(|error| format!("Failed to read media metadata: {error}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_are_cached_by_path_modified_time_and_size() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let cache_dir = temp_dir.path().join("cache");
+        let image_path = temp_dir.path().join("photo.png");
+        let text_path = temp_dir.path().join("notes.txt");
+        image::RgbImage::new(3, 2)
+            .save(&image_path)
+            .expect("write image");
+        fs::write(&text_path, b"notes").expect("write text");
+
+        let request = |path: &Path| MediaMetadataRequest {
+            path: path.to_string_lossy().into_owned(),
+            modified_time: 1,
+            size: 2,
+        };
+        let results = get_media_metadata_batch_from_cache(
+            &cache_dir,
+            vec![request(&image_path), request(&text_path)],
+        );
+
+        assert_eq!(results[0].kind, Some(MediaKind::Image));
+        assert_eq!((results[0].width, results[0].height), (Some(3), Some(2)));
+        assert_eq!(results[1].kind, None);
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 1);
+
+        fs::write(&image_path, b"no longer an image").expect("overwrite image");
+        let cached = get_media_metadata_batch_from_cache(&cache_dir, vec![request(&image_path)]);
+        assert_eq!(cached[0].width, Some(3));
+
+        let changed = get_media_metadata_batch_from_cache(
+            &cache_dir,
+            vec![MediaMetadataRequest {
+                size: 18,
+                ..request(&image_path)
+            }],
+        );
+        assert!(changed[0].error.is_some());
+    }
+}
@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::{AudioTags, MediaMetadata};
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::Hint;
+
+/// Reads "3" and "3/12" as 3.
+fn parse_position(value: &str) -> Option<u32> {
+    value.split('/').next()?.trim().parse().ok()
+}
+
+fn apply_tags(tags: &mut AudioTags, revision: &MetadataRevision) {
+    for tag in revision.tags() {
+        let Some(key) = tag.std_key else {
+            continue;
+        };
+        let value = tag.value.to_string().trim().to_string();
+        if value.is_empty() {
+            continue;
+        }
+
+        let field = match key {
+            StandardTagKey::TrackTitle => &mut tags.title,
+            StandardTagKey::Artist => &mut tags.artist,
+            StandardTagKey::Album => &mut tags.album,
+            StandardTagKey::AlbumArtist => &mut tags.album_artist,
+            StandardTagKey::Genre => &mut tags.genre,
+            StandardTagKey::Date => &mut tags.date,
+            StandardTagKey::TrackNumber => {
+                tags.track_number = tags.track_number.or_else(|| parse_position(&value));
+                continue;
+            }
+            StandardTagKey::DiscNumber => {
+                tags.disc_number = tags.disc_number.or_else(|| parse_position(&value));
+                continue;
+            }
+            _ => continue,
+        };
+        field.get_or_insert(value);
+    }
+}
+
+/// Fills in duration, stream properties and tags. Tags found before the
+/// audio stream, such as ID3v2, take precedence over the container's own.
+pub(super) fn read_audio_metadata(path: &Path, metadata: &mut MediaMetadata) -> Result<(), String> {
+    let file = File::open(path).map_err(|error| format!("Failed to open audio file: {error}"))?;
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let mut probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            MediaSourceStream::new(Box::new(file), Default::default()),
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|error| format!("Failed to read audio file: {error}"))?;
+
+    let mut tags = AudioTags::default();
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|log| log.current()) {
+        apply_tags(&mut tags, revision);
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        apply_tags(&mut tags, revision);
+    }
+    if tags != AudioTags::default() {
+        metadata.tags = Some(tags);
+    }
+
+    let Some(track) = probed.format.default_track() else {
+        return Ok(());
+    };
+    let params = &track.codec_params;
+    metadata.sample_rate = params.sample_rate;
+    metadata.channels = params.channels.map(|channels| channels.count() as u32);
+    metadata.audio_codec = symphonia::default::get_codecs()
+        .get_codec(params.codec)
+        .map(|codec| codec.short_name.to_string());
+    metadata.duration = params
+        .time_base
+        .zip(params.n_frames)
+        .map(|(time_base, frames)| {
+            let time = time_base.calc_time(frames);
+            time.seconds as f64 + time.frac
+        });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wav_duration_and_stream_properties_are_read() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let path = temp_dir.path().join("tone.wav");
+        let sample_rate = 8000u32;
+        let data_len = sample_rate * 2 * 2 / 2;
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(sample_rate * 4).to_le_bytes());
+        wav.extend_from_slice(&4u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        wav.resize(wav.len() + data_len as usize, 0);
+        std::fs::write(&path, wav).expect("write wav");
+
+        let mut metadata = MediaMetadata::default();
+        read_audio_metadata(&path, &mut metadata).expect("read wav");
+
+        assert_eq!(metadata.sample_rate, Some(sample_rate));
+        assert_eq!(metadata.channels, Some(2));
+        assert_eq!(metadata.duration, Some(0.5));
+        assert!(metadata.audio_codec.is_some());
+        assert_eq!(parse_position(" 3/12"), Some(3));
+    }
+}
@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::{ExifMetadata, MediaMetadata};
+use exif::{Exif, In, Tag, Value};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+fn ascii_field(exif: &Exif, tag: Tag) -> Option<String> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values
+            .first()
+            .map(|value| String::from_utf8_lossy(value).trim().to_string())
+            .filter(|value| !value.is_empty()),
+        _ => None,
+    }
+}
+
+fn rational_field(exif: &Exif, tag: Tag) -> Option<f64> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Rational(values) => values.first().map(|value| value.to_f64()),
+        Value::SRational(values) => values.first().map(|value| value.to_f64()),
+        _ => None,
+    }
+    .filter(|value| value.is_finite())
+}
+
+fn uint_field(exif: &Exif, tag: Tag) -> Option<u32> {
+    exif.get_field(tag, In::PRIMARY)?.value.get_uint(0)
+}
+
+/// Converts degrees, minutes and seconds to signed decimal degrees.
+fn gps_coordinate(
+    exif: &Exif,
+    tag: Tag,
+    reference_tag: Tag,
+    negative_reference: &str,
+) -> Option<f64> {
+    let Value::Rational(parts) = &exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+    let degrees = parts
+        .iter()
+        .take(3)
+        .zip([1.0, 60.0, 3600.0])
+        .map(|(part, divisor)| part.to_f64() / divisor)
+        .sum::<f64>();
+    let sign = match ascii_field(exif, reference_tag) {
+        Some(reference) if reference.eq_ignore_ascii_case(negative_reference) => -1.0,
+        _ => 1.0,
+    };
+    Some(degrees * sign).filter(|degrees| degrees.is_finite())
+}
+
+/// Turns EXIF's "YYYY:MM:DD HH:MM:SS" into ISO 8601, which sorts as text.
+fn exif_datetime_to_iso(datetime: &str, offset: Option<&str>) -> Option<String> {
+    let (date, time) = datetime.trim().split_once(' ')?;
+    let date = date.replace(':', "-");
+    if date.len() != 10 || time.len() < 8 || date.starts_with("0000") {
+        return None;
+    }
+    Some(format!(
+        "{date}T{}{}",
+        &time[..8],
+        offset.unwrap_or_default()
+    ))
+}
+
+fn read_exif(path: &Path) -> Option<Exif> {
+    let file = File::open(path).ok()?;
+    exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()
+}
+
+fn exif_metadata(exif: &Exif) -> ExifMetadata {
+    let capture_time = ascii_field(exif, Tag::DateTimeOriginal)
+        .or_else(|| ascii_field(exif, Tag::DateTime))
+        .and_then(|datetime| {
+            exif_datetime_to_iso(
+                &datetime,
+                ascii_field(exif, Tag::OffsetTimeOriginal).as_deref(),
+            )
+        });
+    let altitude = rational_field(exif, Tag::GPSAltitude).map(|altitude| {
+        if uint_field(exif, Tag::GPSAltitudeRef) == Some(1) {
+            -altitude
+        } else {
+            altitude
+        }
+    });
+
+    ExifMetadata {
+        camera_make: ascii_field(exif, Tag::Make),
+        camera_model: ascii_field(exif, Tag::Model),
+        lens_model: ascii_field(exif, Tag::LensModel),
+        capture_time,
+        latitude: gps_coordinate(exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, "S"),
+        longitude: gps_coordinate(exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, "W"),
+        altitude,
+        orientation: uint_field(exif, Tag::Orientation).and_then(|value| u16::try_from(value).ok()),
+        exposure_time: rational_field(exif, Tag::ExposureTime),
+        f_number: rational_field(exif, Tag::FNumber),
+        iso: uint_field(exif, Tag::PhotographicSensitivity),
+        focal_length: rational_field(exif, Tag::FocalLength),
+    }
+}
+
+/// Fills in dimensions and EXIF data. Formats the `image` crate cannot
+/// decode, such as HEIC, still get the dimensions EXIF records.
+pub(super) fn read_image_metadata(path: &Path, metadata: &mut MediaMetadata) -> Result<(), String> {
+    let exif = read_exif(path);
+    let dimensions = image::ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|error| error.to_string())
+        .and_then(|reader| reader.into_dimensions().map_err(|error| error.to_string()));
+
+    let (width, height) = match (dimensions, &exif) {
+        (Ok((width, height)), _) => (Some(width), Some(height)),
+        (Err(_), Some(exif)) => (
+            uint_field(exif, Tag::PixelXDimension),
+            uint_field(exif, Tag::PixelYDimension),
+        ),
+        (Err(error), None) => return Err(format!("Failed to read image: {error}")),
+    };
+
+    metadata.width = width;
+    metadata.height = height;
+    metadata.exif = exif.as_ref().map(exif_metadata);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exif_datetimes_become_sortable_iso_strings() {
+        assert_eq!(
+            exif_datetime_to_iso("2024:07:15 18:30:05", None).as_deref(),
+            Some("2024-07-15T18:30:05")
+        );
+        assert_eq!(
+            exif_datetime_to_iso("2024:07:15 18:30:05", Some("+02:00")).as_deref(),
+            Some("2024-07-15T18:30:05+02:00")
+        );
+        assert_eq!(exif_datetime_to_iso("0000:00:00 00:00:00", None), None);
+        assert_eq!(exif_datetime_to_iso("    :  :     :  :  ", None), None);
+    }
+}
@@ -0,0 +1,570 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Reads the headers of MP4 and Matroska files, skipping over the media
+//! data, so large videos cost a few small reads.
+
+use super::MediaMetadata;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Upper bound on a header element read into memory, e.g. `moov`.
+const MAX_HEADER_LEN: u64 = 64 * 1024 * 1024;
+
+const EBML_HEADER: u32 = 0x1A45_DFA3;
+const SEGMENT: u32 = 0x1853_8067;
+const INFO: u32 = 0x1549_A966;
+const TRACKS: u32 = 0x1654_AE6B;
+const CLUSTER: u32 = 0x1F43_B675;
+const TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+const DURATION: u32 = 0x4489;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const AUDIO: u32 = 0xE1;
+const SAMPLING_FREQUENCY: u32 = 0xB5;
+const CHANNELS: u32 = 0x9F;
+
+fn read_header_element<R: Read>(reader: &mut R, len: u64) -> Result<Vec<u8>, String> {
+    if len > MAX_HEADER_LEN {
+        return Err("Video header is too large".to_string());
+    }
+    let mut data = vec![0; len as usize];
+    reader
+        .read_exact(&mut data)
+        .map_err(|error| format!("Failed to read video header: {error}"))?;
+    Ok(data)
+}
+
+fn be_uint(data: &[u8]) -> u64 {
+    data.iter()
+        .take(8)
+        .fold(0, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+fn field(data: &[u8], offset: usize, len: usize) -> Option<u64> {
+    data.get(offset..offset + len).map(be_uint)
+}
+
+fn set_if_unset<T>(slot: &mut Option<T>, value: Option<T>) {
+    if slot.is_none() {
+        *slot = value;
+    }
+}
+
+// MP4 (ISO base media file format)
+
+fn mp4_codec_name(fourcc: &[u8]) -> String {
+    match fourcc {
+        b"avc1" | b"avc3" => "h264",
+        b"hvc1" | b"hev1" => "hevc",
+        b"av01" => "av1",
+        b"vp08" => "vp8",
+        b"vp09" => "vp9",
+        b"mp4v" => "mpeg4",
+        b"mp4a" => "aac",
+        b"Opus" => "opus",
+        b"fLaC" => "flac",
+        b"alac" => "alac",
+        b"ac-3" => "ac3",
+        b"ec-3" => "eac3",
+        b".mp3" => "mp3",
+        other => return String::from_utf8_lossy(other).trim().to_lowercase(),
+    }
+    .to_string()
+}
+
+/// Iterates over the `(type, content)` of the boxes in `data`.
+struct Mp4Boxes<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Mp4Boxes<'a> {
+    type Item = (&'a [u8], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let size = field(self.data, 0, 4)?;
+        let kind = self.data.get(4..8)?;
+        let (header_len, size) = match size {
+            0 => (8, self.data.len() as u64),
+            1 => (16, field(self.data, 8, 8)?),
+            size => (8, size),
+        };
+        let size = usize::try_from(size).ok()?;
+        if size < header_len || size > self.data.len() {
+            return None;
+        }
+
+        let content = &self.data[header_len..size];
+        self.data = &self.data[size..];
+        Some((kind, content))
+    }
+}
+
+fn mp4_boxes(data: &[u8]) -> Mp4Boxes<'_> {
+    Mp4Boxes { data }
+}
+
+fn mp4_child<'a>(data: &'a [u8], kind: &[u8]) -> Option<&'a [u8]> {
+    mp4_boxes(data)
+        .find(|(child_kind, _)| *child_kind == kind)
+        .map(|(_, content)| content)
+}
+
+fn read_mp4_track(trak: &[u8], metadata: &mut MediaMetadata) {
+    let Some(mdia) = mp4_child(trak, b"mdia") else {
+        return;
+    };
+    let handler = mp4_child(mdia, b"hdlr").and_then(|hdlr| hdlr.get(8..12));
+    let entry = mp4_child(mdia, b"minf")
+        .and_then(|minf| mp4_child(minf, b"stbl"))
+        .and_then(|stbl| mp4_child(stbl, b"stsd"))
+        .and_then(|stsd| stsd.get(8..));
+    let fourcc = entry.and_then(|entry| entry.get(4..8));
+
+    match handler {
+        Some(b"vide") if metadata.video_codec.is_none() => {
+            metadata.video_codec = fourcc.map(mp4_codec_name);
+            // Display size from the track header, as 16.16 fixed point.
+            let tkhd = mp4_child(trak, b"tkhd").unwrap_or_default();
+            let dimensions_offset = if tkhd.first() == Some(&1) { 88 } else { 76 };
+            let width = field(tkhd, dimensions_offset, 4).map(|width| (width >> 16) as u32);
+            let height = field(tkhd, dimensions_offset + 4, 4).map(|height| (height >> 16) as u32);
+            // Coded size from the sample entry when the header has none.
+            metadata.width = width.filter(|width| *width > 0).or_else(|| {
+                entry
+                    .and_then(|entry| field(entry, 32, 2))
+                    .map(|width| width as u32)
+            });
+            metadata.height = height.filter(|height| *height > 0).or_else(|| {
+                entry
+                    .and_then(|entry| field(entry, 34, 2))
+                    .map(|height| height as u32)
+            });
+        }
+        Some(b"soun") if metadata.audio_codec.is_none() => {
+            metadata.audio_codec = fourcc.map(mp4_codec_name);
+            metadata.channels = entry
+                .and_then(|entry| field(entry, 24, 2))
+                .map(|channels| channels as u32);
+            metadata.sample_rate = entry
+                .and_then(|entry| field(entry, 32, 4))
+                .map(|sample_rate| (sample_rate >> 16) as u32);
+        }
+        _ => {}
+    }
+}
+
+fn read_mp4_moov(moov: &[u8], metadata: &mut MediaMetadata) {
+    if let Some(mvhd) = mp4_child(moov, b"mvhd") {
+        let (timescale, duration) = match mvhd.first() {
+            Some(1) => (field(mvhd, 20, 4), field(mvhd, 24, 8)),
+            _ => (field(mvhd, 12, 4), field(mvhd, 16, 4)),
+        };
+        metadata.duration = timescale
+            .zip(duration)
+            .filter(|(timescale, duration)| {
+                *timescale > 0 && *duration != u64::from(u32::MAX) && *duration != u64::MAX
+            })
+            .map(|(timescale, duration)| duration as f64 / timescale as f64);
+    }
+
+    for (kind, content) in mp4_boxes(moov) {
+        if kind == b"trak" {
+            read_mp4_track(content, metadata);
+        }
+    }
+}
+
+/// Walks the top-level boxes, skipping `mdat`, until `moov` is found.
+fn read_mp4<R: Read + Seek>(reader: &mut R, metadata: &mut MediaMetadata) -> Result<(), String> {
+    let mut header = [0; 16];
+    loop {
+        if reader.read_exact(&mut header[..8]).is_err() {
+            return Err("Video has no movie header".to_string());
+        }
+        let (header_len, size) = match be_uint(&header[..4]) {
+            1 => {
+                reader
+                    .read_exact(&mut header[8..16])
+                    .map_err(|error| format!("Failed to read video header: {error}"))?;
+                (16, be_uint(&header[8..16]))
+            }
+            size => (8, size),
+        };
+
+        if &header[4..8] == b"moov" {
+            let len = if size == 0 {
+                MAX_HEADER_LEN
+            } else {
+                size.saturating_sub(header_len)
+            };
+            let mut moov = Vec::new();
+            reader
+                .take(len.min(MAX_HEADER_LEN))
+                .read_to_end(&mut moov)
+                .map_err(|error| format!("Failed to read video header: {error}"))?;
+            read_mp4_moov(&moov, metadata);
+            return Ok(());
+        }
+        if size == 0 || size < header_len {
+            return Err("Video has no movie header".to_string());
+        }
+        reader
+            .seek(SeekFrom::Current((size - header_len) as i64))
+            .map_err(|error| format!("Failed to read video: {error}"))?;
+    }
+}
+
+// Matroska and WebM
+
+/// Reads an element ID, which keeps its length marker, from a stream.
+fn read_ebml_id<R: Read>(reader: &mut R) -> Option<u32> {
+    let mut first = [0];
+    reader.read_exact(&mut first).ok()?;
+    let len = first[0].leading_zeros() as usize + 1;
+    if len > 4 {
+        return None;
+    }
+    let mut rest = [0; 3];
+    reader.read_exact(&mut rest[..len - 1]).ok()?;
+    Some(
+        rest[..len - 1]
+            .iter()
+            .fold(u32::from(first[0]), |id, byte| (id << 8) | u32::from(*byte)),
+    )
+}
+
+/// Reads an element size from a stream; `None` inside means unknown.
+fn read_ebml_size<R: Read>(reader: &mut R) -> Option<Option<u64>> {
+    let mut first = [0];
+    reader.read_exact(&mut first).ok()?;
+    let len = first[0].leading_zeros() as usize + 1;
+    if len > 8 {
+        return None;
+    }
+    let mut rest = [0; 7];
+    reader.read_exact(&mut rest[..len - 1]).ok()?;
+    let marker_mask = if len == 8 { 0 } else { 0xFF >> len };
+    let size = rest[..len - 1]
+        .iter()
+        .fold(u64::from(first[0] & marker_mask), |size, byte| {
+            (size << 8) | u64::from(*byte)
+        });
+    let unknown = (1u64 << (7 * len)) - 1;
+    Some((size != unknown).then_some(size))
+}
+
+/// Iterates over the `(id, content)` of the elements in `data`.
+struct EbmlElements<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for EbmlElements<'a> {
+    type Item = (u32, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut cursor = self.data;
+        let id = read_ebml_id(&mut cursor)?;
+        let size = read_ebml_size(&mut cursor)?.unwrap_or(cursor.len() as u64);
+        let size = usize::try_from(size)
+            .ok()
+            .filter(|size| *size <= cursor.len())?;
+        self.data = &cursor[size..];
+        Some((id, &cursor[..size]))
+    }
+}
+
+fn ebml_elements(data: &[u8]) -> EbmlElements<'_> {
+    EbmlElements { data }
+}
+
+fn ebml_float(data: &[u8]) -> Option<f64> {
+    match data.len() {
+        4 => Some(f64::from(f32::from_bits(be_uint(data) as u32))),
+        8 => Some(f64::from_bits(be_uint(data))),
+        _ => None,
+    }
+    .filter(|value| value.is_finite())
+}
+
+fn matroska_codec_name(codec_id: &str) -> String {
+    match codec_id {
+        "V_MPEG4/ISO/AVC" => "h264",
+        "V_MPEGH/ISO/HEVC" => "hevc",
+        "V_AV1" => "av1",
+        "V_VP8" => "vp8",
+        "V_VP9" => "vp9",
+        "V_THEORA" => "theora",
+        "A_OPUS" => "opus",
+        "A_VORBIS" => "vorbis",
+        "A_FLAC" => "flac",
+        "A_AC3" => "ac3",
+        "A_EAC3" => "eac3",
+        "A_MPEG/L3" => "mp3",
+        codec_id if codec_id.starts_with("A_AAC") => "aac",
+        codec_id => {
+            let name = codec_id.split_once('_').map_or(codec_id, |(_, name)| name);
+            return name.to_lowercase();
+        }
+    }
+    .to_string()
+}
+
+fn read_matroska_info(info: &[u8], metadata: &mut MediaMetadata) {
+    let mut timestamp_scale = 1_000_000;
+    let mut duration = None;
+    for (id, content) in ebml_elements(info) {
+        match id {
+            TIMESTAMP_SCALE => timestamp_scale = be_uint(content),
+            DURATION => duration = ebml_float(content),
+            _ => {}
+        }
+    }
+    metadata.duration = duration.map(|duration| duration * timestamp_scale as f64 / 1e9);
+}
+
+fn read_matroska_tracks(tracks: &[u8], metadata: &mut MediaMetadata) {
+    for (_, entry) in ebml_elements(tracks).filter(|(id, _)| *id == TRACK_ENTRY) {
+        let mut track_type = None;
+        let mut codec = None;
+        let mut settings: &[u8] = &[];
+        for (id, content) in ebml_elements(entry) {
+            match id {
+                TRACK_TYPE => track_type = Some(be_uint(content)),
+                CODEC_ID => {
+                    let codec_id = String::from_utf8_lossy(content);
+                    codec = Some(matroska_codec_name(codec_id.trim_end_matches('\0')));
+                }
+                VIDEO | AUDIO => settings = content,
+                _ => {}
+            }
+        }
+
+        match track_type {
+            Some(1) if metadata.video_codec.is_none() => {
+                metadata.video_codec = codec;
+                for (id, content) in ebml_elements(settings) {
+                    match id {
+                        PIXEL_WIDTH => {
+                            set_if_unset(&mut metadata.width, Some(be_uint(content) as u32))
+                        }
+                        PIXEL_HEIGHT => {
+                            set_if_unset(&mut metadata.height, Some(be_uint(content) as u32))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Some(2) if metadata.audio_codec.is_none() => {
+                metadata.audio_codec = codec;
+                for (id, content) in ebml_elements(settings) {
+                    match id {
+                        SAMPLING_FREQUENCY => set_if_unset(
+                            &mut metadata.sample_rate,
+                            ebml_float(content).map(|rate| rate as u32),
+                        ),
+                        CHANNELS => {
+                            set_if_unset(&mut metadata.channels, Some(be_uint(content) as u32))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Walks the segment's top-level elements, skipping clusters, until both
+/// the segment info and the tracks are read.
+fn read_matroska<R: Read + Seek>(
+    reader: &mut R,
+    metadata: &mut MediaMetadata,
+) -> Result<(), String> {
+    let invalid = || "Video is not a valid Matroska file".to_string();
+    let mut read_info = false;
+    let mut read_tracks = false;
+
+    while !(read_info && read_tracks) {
+        let Some(id) = read_ebml_id(reader) else {
+            break;
+        };
+        let size = read_ebml_size(reader).ok_or_else(invalid)?;
+        match (id, size) {
+            // Segment content is walked in place.
+            (SEGMENT, _) => {}
+            (INFO, Some(size)) => {
+                read_matroska_info(&read_header_element(reader, size)?, metadata);
+                read_info = true;
+            }
+            (TRACKS, Some(size)) => {
+                read_matroska_tracks(&read_header_element(reader, size)?, metadata);
+                read_tracks = true;
+            }
+            (CLUSTER, None) => break,
+            (_, Some(size)) => {
+                reader
+                    .seek(SeekFrom::Current(size as i64))
+                    .map_err(|error| format!("Failed to read video: {error}"))?;
+            }
+            (_, None) => return Err(invalid()),
+        }
+    }
+
+    if read_info || read_tracks {
+        Ok(())
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Fills in duration, resolution and codecs of MP4, QuickTime, Matroska
+/// and WebM videos.
+pub(super) fn read_video_metadata(path: &Path, metadata: &mut MediaMetadata) -> Result<(), String> {
+    let file = File::open(path).map_err(|error| format!("Failed to open video file: {error}"))?;
+    let mut reader = BufReader::new(file);
+    let mut head = [0; 8];
+    reader
+        .read_exact(&mut head)
+        .map_err(|error| format!("Failed to read video: {error}"))?;
+    reader
+        .rewind()
+        .map_err(|error| format!("Failed to read video: {error}"))?;
+
+    if be_uint(&head[..4]) as u32 == EBML_HEADER {
+        read_matroska(&mut reader, metadata)
+    } else if matches!(&head[4..8], b"ftyp" | b"moov" | b"mdat" | b"free" | b"wide") {
+        read_mp4(&mut reader, metadata)
+    } else {
+        Err("Unsupported video format".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn mp4_box(kind: &[u8], content: &[u8]) -> Vec<u8> {
+        let mut data = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(content);
+        data
+    }
+
+    fn ebml_element(id: &[u8], content: &[u8]) -> Vec<u8> {
+        let mut data = id.to_vec();
+        data.push(0x80 | content.len() as u8);
+        data.extend_from_slice(content);
+        data
+    }
+
+    #[test]
+    fn mp4_tracks_are_read_past_the_media_data() {
+        let mut mvhd = vec![0; 100];
+        mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&2500u32.to_be_bytes());
+        let mut tkhd = vec![0; 84];
+        tkhd[76..80].copy_from_slice(&(1920u32 << 16).to_be_bytes());
+        tkhd[80..84].copy_from_slice(&(1080u32 << 16).to_be_bytes());
+        let video_entry = mp4_box(b"avc1", &[0; 78]);
+        let mut audio_entry = mp4_box(b"mp4a", &[0; 28]);
+        audio_entry[24..26].copy_from_slice(&2u16.to_be_bytes());
+        audio_entry[32..36].copy_from_slice(&(48000u32 << 16).to_be_bytes());
+        let track = |handler: &[u8], entry: &[u8], tkhd: &[u8]| {
+            let hdlr = [&[0; 8][..], handler, &[0; 12]].concat();
+            let stsd = [&[0, 0, 0, 0, 0, 0, 0, 1][..], entry].concat();
+            let stbl = mp4_box(b"stbl", &mp4_box(b"stsd", &stsd));
+            let mdia = [mp4_box(b"hdlr", &hdlr), mp4_box(b"minf", &stbl)].concat();
+            mp4_box(
+                b"trak",
+                &[mp4_box(b"tkhd", tkhd), mp4_box(b"mdia", &mdia)].concat(),
+            )
+        };
+        let moov = [
+            mp4_box(b"mvhd", &mvhd),
+            track(b"vide", &video_entry, &tkhd),
+            track(b"soun", &audio_entry, &[0; 84]),
+        ]
+        .concat();
+        let file = [
+            mp4_box(b"ftyp", b"isom"),
+            mp4_box(b"mdat", &[0; 4096]),
+            mp4_box(b"moov", &moov),
+        ]
+        .concat();
+
+        let mut metadata = MediaMetadata::default();
+        read_mp4(&mut Cursor::new(file), &mut metadata).expect("read mp4");
+
+        assert_eq!(metadata.duration, Some(2.5));
+        assert_eq!((metadata.width, metadata.height), (Some(1920), Some(1080)));
+        assert_eq!(metadata.video_codec.as_deref(), Some("h264"));
+        assert_eq!(metadata.audio_codec.as_deref(), Some("aac"));
+        assert_eq!(metadata.channels, Some(2));
+        assert_eq!(metadata.sample_rate, Some(48000));
+    }
+
+    #[test]
+    fn matroska_info_and_tracks_are_read_from_an_unknown_size_segment() {
+        let info = [
+            ebml_element(&[0x2A, 0xD7, 0xB1], &[0x0F, 0x42, 0x40]),
+            ebml_element(&[0x44, 0x89], &1500f64.to_be_bytes()),
+        ]
+        .concat();
+        let video = [
+            ebml_element(&[0xB0], &[0x05, 0x00]),
+            ebml_element(&[0xBA], &[0x02, 0xD0]),
+        ]
+        .concat();
+        let video_track = [
+            ebml_element(&[0x83], &[1]),
+            ebml_element(&[0x86], b"V_VP9"),
+            ebml_element(&[0xE0], &video),
+        ]
+        .concat();
+        let audio = [
+            ebml_element(&[0xB5], &44100f32.to_be_bytes()),
+            ebml_element(&[0x9F], &[2]),
+        ]
+        .concat();
+        let audio_track = [
+            ebml_element(&[0x83], &[2]),
+            ebml_element(&[0x86], b"A_OPUS"),
+            ebml_element(&[0xE1], &audio),
+        ]
+        .concat();
+        let tracks = [
+            ebml_element(&[0xAE], &video_track),
+            ebml_element(&[0xAE], &audio_track),
+        ]
+        .concat();
+        let file = [
+            ebml_element(
+                &[0x1A, 0x45, 0xDF, 0xA3],
+                &ebml_element(&[0x42, 0x82], b"webm"),
+            ),
+            vec![0x18, 0x53, 0x80, 0x67, 0xFF],
+            ebml_element(&[0xEC], &[0; 16]),
+            ebml_element(&[0x15, 0x49, 0xA9, 0x66], &info),
+            ebml_element(&[0x16, 0x54, 0xAE, 0x6B], &tracks),
+        ]
+        .concat();
+
+        let mut metadata = MediaMetadata::default();
+        read_matroska(&mut Cursor::new(file), &mut metadata).expect("read matroska");
+
+        assert_eq!(metadata.duration, Some(1.5));
+        assert_eq!((metadata.width, metadata.height), (Some(1280), Some(720)));
+        assert_eq!(metadata.video_codec.as_deref(), Some("vp9"));
+        assert_eq!(metadata.audio_codec.as_deref(), Some("opus"));
+        assert_eq!(metadata.sample_rate, Some(44100));
+        assert_eq!(metadata.channels, Some(2));
+    }
+}
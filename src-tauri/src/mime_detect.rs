@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! In-process MIME type detection.
+//!
+//! Names are matched against the freedesktop.org shared-mime-info globs and
+//! content against its magic rules, when the database is installed. A
+//! built-in table of extensions and signatures covers the rest, and content
+//! that matches nothing is sniffed as text or binary.
+
+mod builtin;
+mod database;
+
+use database::MimeDatabase;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Upper bound on the bytes read for magic, however far the rules reach.
+const MAX_SNIFF_LEN: usize = 8192;
+
+static SHARED_MIME_INFO: LazyLock<Option<MimeDatabase>> =
+    LazyLock::new(|| MimeDatabase::load(&shared_mime_info_dirs()));
+static BUILTIN: LazyLock<MimeDatabase> = LazyLock::new(builtin::builtin_database);
+
+/// `mime` directories of the XDG data dirs, user data first.
+#[cfg(unix)]
+fn shared_mime_info_dirs() -> Vec<PathBuf> {
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|data_dirs| !data_dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    dirs::data_dir()
+        .into_iter()
+        .chain(data_dirs.split(':').map(PathBuf::from))
+        .map(|data_dir| data_dir.join("mime"))
+        .collect()
+}
+
+#[cfg(not(unix))]
+fn shared_mime_info_dirs() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+fn databases() -> impl Iterator<Item = &'static MimeDatabase> {
+    SHARED_MIME_INFO.as_ref().into_iter().chain([&*BUILTIN])
+}
+
+fn read_head(path: &Path) -> Option<Vec<u8>> {
+    let sniff_len = databases()
+        .map(MimeDatabase::magic_extent)
+        .max()
+        .unwrap_or(0)
+        .min(MAX_SNIFF_LEN);
+    let mut head = Vec::with_capacity(sniff_len);
+    fs::File::open(path)
+        .ok()?
+        .take(sniff_len as u64)
+        .read_to_end(&mut head)
+        .ok()?;
+    Some(head)
+}
+
+fn looks_like_text(data: &[u8]) -> bool {
+    !data.contains(&0)
+        && std::str::from_utf8(data).map_or_else(|error| error.error_len().is_none(), |_| true)
+}
+
+/// Types the first database that knows `name` gives it.
+fn name_candidates(databases: &[&MimeDatabase], name: &str) -> Vec<String> {
+    databases
+        .iter()
+        .map(|database| database.match_name(name))
+        .find(|candidates| !candidates.is_empty())
+        .unwrap_or_default()
+}
+
+/// Combines what the name and the content say about a file.
+///
+/// Content wins when it contradicts the name, which happens when the named
+/// types all have magic and none of it matched. Otherwise the name wins,
+/// preferring a type that the content confirms.
+fn resolve_mime_type(
+    databases: &[&MimeDatabase],
+    name: &str,
+    content: Option<&[u8]>,
+) -> Option<String> {
+    let candidates = name_candidates(databases, name);
+    let content_type = content.and_then(|content| {
+        databases
+            .iter()
+            .find_map(|database| database.match_magic(content))
+    });
+    let has_magic = |mime: &str| databases.iter().any(|database| database.has_magic(mime));
+
+    if let Some(content_type) = content_type {
+        let confirmed = candidates.iter().find(|candidate| {
+            databases
+                .iter()
+                .any(|database| database.is_subclass(candidate, content_type))
+        });
+        if let Some(confirmed) = confirmed {
+            return Some(confirmed.clone());
+        }
+        if candidates.iter().all(|candidate| has_magic(candidate)) {
+            return Some(content_type.to_string());
+        }
+        return candidates
+            .into_iter()
+            .find(|candidate| !has_magic(candidate));
+    }
+
+    if candidates.len() > 1 {
+        // Nothing told the tied globs apart; the built-in table knows what
+        // a file manager user most likely means, e.g. TypeScript for `.ts`.
+        if let Some(preferred) = databases
+            .last()
+            .and_then(|database| database.match_name(name).into_iter().next())
+        {
+            return Some(preferred);
+        }
+    }
+    if let Some(candidate) = candidates.into_iter().next() {
+        return Some(candidate);
+    }
+
+    content.map(|content| {
+        if looks_like_text(content) {
+            "text/plain".to_string()
+        } else {
+            "application/octet-stream".to_string()
+        }
+    })
+}
+
+/// Detects the MIME type of the file at `path`.
+///
+/// The content is read when the name gives no single answer, or always
+/// with `check_content`, which catches files with a wrong extension.
+pub fn detect_mime_type(path: &Path, check_content: bool) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy();
+    let databases: Vec<&MimeDatabase> = databases().collect();
+
+    if !check_content {
+        if let [mime] = name_candidates(&databases, &name).as_slice() {
+            return Some(mime.clone());
+        }
+    }
+
+    resolve_mime_type(&databases, &name, read_head(path).as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_settles_missing_wrong_and_ambiguous_names() {
+        let builtin = builtin::builtin_database();
+        let mut shared = MimeDatabase::default();
+        shared.add_glob(50, "text/vnd.trolltech.linguist", "*.ts", false);
+        shared.add_glob(50, "video/mp2t", "*.ts", false);
+        shared.add_glob(50, "image/png", "*.png", false);
+        shared.add_magic(50, "video/mp2t", &[(0, b"G")]);
+        shared.add_magic(50, "text/vnd.trolltech.linguist", &[(0, b"<TS")]);
+        let databases = [&shared, &builtin];
+        let png = b"\x89PNG\r\n\x1a\n....";
+        let jpeg = b"\xff\xd8\xff\xe0....";
+
+        let resolve = |name: &str, content: Option<&[u8]>| {
+            resolve_mime_type(&databases, name, content).unwrap_or_default()
+        };
+        assert_eq!(resolve("photo.png", None), "image/png");
+        assert_eq!(resolve("photo.png", Some(png)), "image/png");
+        assert_eq!(resolve("photo.png", Some(jpeg)), "image/jpeg");
+        assert_eq!(resolve("photo", Some(jpeg)), "image/jpeg");
+        assert_eq!(
+            resolve("report.docx", Some(b"PK\x03\x04....")),
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+        );
+        assert_eq!(resolve("notes.txt", Some(b"<?xml version")), "text/plain");
+        assert_eq!(resolve("main.ts", Some(b"export {}")), "text/typescript");
+        assert_eq!(resolve("stream.ts", Some(b"G\x40\x11")), "video/mp2t");
+        assert_eq!(resolve("LICENSE", Some(b"MIT License")), "text/plain");
+        assert_eq!(
+            resolve("blob", Some(b"\x00\x01\x02")),
+            "application/octet-stream"
+        );
+        assert_eq!(resolve_mime_type(&databases, "blob", None), None);
+    }
+
+    #[test]
+    fn files_without_extensions_are_sniffed() {
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let image_path = temp_dir.path().join("image");
+        fs::write(&image_path, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").expect("write image");
+        let renamed_path = temp_dir.path().join("image.gif");
+        fs::copy(&image_path, &renamed_path).expect("copy image");
+
+        assert_eq!(
+            detect_mime_type(&image_path, false).as_deref(),
+            Some("image/png")
+        );
+        assert_eq!(
+            detect_mime_type(&renamed_path, true).as_deref(),
+            Some("image/png")
+        );
+    }
+}
@@ -0,0 +1,187 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Types known without a shared-mime-info database, as on Windows and
+//! macOS, and for names the database has no glob for.
+
+use super::database::MimeDatabase;
+
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("txt", "text/plain"),
+    ("text", "text/plain"),
+    ("log", "text/plain"),
+    ("ini", "text/plain"),
+    ("conf", "text/plain"),
+    ("cfg", "text/plain"),
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("jsx", "text/javascript"),
+    ("json", "application/json"),
+    ("xml", "application/xml"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("tar", "application/x-tar"),
+    ("gz", "application/gzip"),
+    ("gzip", "application/gzip"),
+    ("rar", "application/vnd.rar"),
+    ("7z", "application/x-7z-compressed"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("bmp", "image/bmp"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("mp3", "audio/mpeg"),
+    ("wav", "audio/wav"),
+    ("ogg", "audio/ogg"),
+    ("flac", "audio/flac"),
+    ("m4a", "audio/mp4"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("avi", "video/x-msvideo"),
+    ("mkv", "video/x-matroska"),
+    ("mov", "video/quicktime"),
+    ("wmv", "video/x-ms-wmv"),
+    ("doc", "application/msword"),
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    ("xls", "application/vnd.ms-excel"),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    ("ppt", "application/vnd.ms-powerpoint"),
+    (
+        "pptx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    ),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("ods", "application/vnd.oasis.opendocument.spreadsheet"),
+    ("odp", "application/vnd.oasis.opendocument.presentation"),
+    ("rs", "text/x-rust"),
+    ("ts", "text/typescript"),
+    ("tsx", "text/typescript"),
+    ("vue", "text/x-vue"),
+    ("py", "text/x-python"),
+    ("rb", "text/x-ruby"),
+    ("go", "text/x-go"),
+    ("java", "text/x-java"),
+    ("c", "text/x-c"),
+    ("h", "text/x-c"),
+    ("cpp", "text/x-c++"),
+    ("hpp", "text/x-c++"),
+    ("cc", "text/x-c++"),
+    ("cxx", "text/x-c++"),
+    ("md", "text/markdown"),
+    ("markdown", "text/markdown"),
+    ("yaml", "text/yaml"),
+    ("yml", "text/yaml"),
+    ("toml", "text/x-toml"),
+    ("sh", "application/x-shellscript"),
+    ("bash", "application/x-shellscript"),
+    ("sql", "application/sql"),
+    ("exe", "application/x-msdownload"),
+    ("dll", "application/x-msdownload"),
+    ("so", "application/x-sharedlib"),
+    ("deb", "application/vnd.debian.binary-package"),
+    ("rpm", "application/x-rpm"),
+    ("appimage", "application/x-executable"),
+    ("iso", "application/x-iso9660-image"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+];
+
+/// `(offset, bytes)` parts that must all match.
+type Signature = &'static [(usize, &'static [u8])];
+
+/// Leading bytes of common formats.
+const SIGNATURES: &[(&str, Signature)] = &[
+    ("image/png", &[(0, b"\x89PNG\r\n\x1a\n")]),
+    ("image/jpeg", &[(0, b"\xff\xd8\xff")]),
+    ("image/gif", &[(0, b"GIF87a")]),
+    ("image/gif", &[(0, b"GIF89a")]),
+    ("image/webp", &[(0, b"RIFF"), (8, b"WEBP")]),
+    ("image/bmp", &[(0, b"BM")]),
+    ("image/tiff", &[(0, b"II*\0")]),
+    ("image/tiff", &[(0, b"MM\0*")]),
+    ("image/x-icon", &[(0, b"\0\0\x01\0")]),
+    ("application/pdf", &[(0, b"%PDF-")]),
+    ("application/zip", &[(0, b"PK\x03\x04")]),
+    ("application/gzip", &[(0, b"\x1f\x8b")]),
+    ("application/x-7z-compressed", &[(0, b"7z\xbc\xaf\x27\x1c")]),
+    ("application/vnd.rar", &[(0, b"Rar!\x1a\x07")]),
+    ("application/x-tar", &[(257, b"ustar")]),
+    ("application/x-executable", &[(0, b"\x7fELF")]),
+    ("application/x-msdownload", &[(0, b"MZ")]),
+    ("application/vnd.sqlite3", &[(0, b"SQLite format 3\0")]),
+    ("application/xml", &[(0, b"<?xml")]),
+    ("audio/mpeg", &[(0, b"ID3")]),
+    ("audio/flac", &[(0, b"fLaC")]),
+    ("audio/ogg", &[(0, b"OggS")]),
+    ("audio/wav", &[(0, b"RIFF"), (8, b"WAVE")]),
+    ("video/x-msvideo", &[(0, b"RIFF"), (8, b"AVI ")]),
+    ("video/x-matroska", &[(0, b"\x1a\x45\xdf\xa3")]),
+    ("video/mp4", &[(4, b"ftyp")]),
+    ("font/woff", &[(0, b"wOFF")]),
+    ("font/woff2", &[(0, b"wOF2")]),
+];
+
+/// Containers that other types are built on, so their signatures confirm
+/// rather than contradict the more specific name.
+const PARENTS: &[(&str, &str)] = &[
+    (
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "application/zip",
+    ),
+    (
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "application/zip",
+    ),
+    (
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "application/zip",
+    ),
+    ("application/vnd.oasis.opendocument.text", "application/zip"),
+    (
+        "application/vnd.oasis.opendocument.spreadsheet",
+        "application/zip",
+    ),
+    (
+        "application/vnd.oasis.opendocument.presentation",
+        "application/zip",
+    ),
+    ("image/svg+xml", "application/xml"),
+    ("video/webm", "video/x-matroska"),
+    ("audio/mp4", "video/mp4"),
+    ("video/quicktime", "video/mp4"),
+    ("application/x-sharedlib", "application/x-executable"),
+];
+
+pub(super) fn builtin_database() -> MimeDatabase {
+    let mut database = MimeDatabase::default();
+
+    for (extension, mime) in EXTENSIONS {
+        database.add_glob(50, mime, &format!("*.{extension}"), false);
+    }
+    for (mime, parts) in SIGNATURES {
+        database.add_magic(50, mime, parts);
+    }
+    for (mime, parent) in PARENTS {
+        database.add_parent(mime, parent);
+    }
+
+    database
+}
@@ -0,0 +1,493 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use globset::{GlobBuilder, GlobMatcher};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MAGIC_HEADER: &[u8] = b"MIME-Magic\0\n";
+
+#[derive(Debug, Clone)]
+struct GlobRule {
+    weight: u32,
+    mime: String,
+    /// Original pattern, compared exactly for case-sensitive rules.
+    pattern: String,
+    case_sensitive: bool,
+}
+
+struct PatternGlob {
+    rule: GlobRule,
+    matcher: GlobMatcher,
+}
+
+#[derive(Debug, Default)]
+struct MagicMatch {
+    offset: usize,
+    /// Number of start offsets to try, from `offset` on.
+    range: usize,
+    value: Vec<u8>,
+    mask: Option<Vec<u8>>,
+    /// Any child must match too, when there are children.
+    children: Vec<MagicMatch>,
+}
+
+impl MagicMatch {
+    fn matches(&self, data: &[u8]) -> bool {
+        let found = (self.offset..self.offset + self.range).any(|start| {
+            let Some(window) = data.get(start..start + self.value.len()) else {
+                return false;
+            };
+            match &self.mask {
+                Some(mask) => window
+                    .iter()
+                    .zip(&self.value)
+                    .zip(mask)
+                    .all(|((byte, value), mask)| byte & mask == value & mask),
+                None => window == self.value,
+            }
+        });
+
+        found && (self.children.is_empty() || self.children.iter().any(|child| child.matches(data)))
+    }
+
+    fn extent(&self) -> usize {
+        self.children
+            .iter()
+            .map(MagicMatch::extent)
+            .fold(self.offset + self.range + self.value.len(), usize::max)
+    }
+}
+
+#[derive(Debug)]
+struct MagicRule {
+    priority: u32,
+    mime: String,
+    matches: Vec<MagicMatch>,
+}
+
+/// Name globs, content magic and type hierarchy, in the shape of the
+/// freedesktop.org shared-mime-info database.
+#[derive(Default)]
+pub(super) struct MimeDatabase {
+    /// Patterns without wildcards, keyed by lowercase file name.
+    literals: HashMap<String, Vec<GlobRule>>,
+    /// `*.ext` patterns, keyed by the lowercase suffix including the dot.
+    suffixes: HashMap<String, Vec<GlobRule>>,
+    patterns: Vec<PatternGlob>,
+    /// Sorted by descending priority.
+    magic: Vec<MagicRule>,
+    magic_types: HashSet<String>,
+    parents: HashMap<String, Vec<String>>,
+    aliases: HashMap<String, String>,
+}
+
+fn has_wildcards(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Splits a leading run of ASCII digits off `bytes`.
+fn take_number(bytes: &[u8]) -> Option<(usize, &[u8])> {
+    let digit_count = bytes
+        .iter()
+        .take_while(|byte| byte.is_ascii_digit())
+        .count();
+    let number = std::str::from_utf8(&bytes[..digit_count])
+        .ok()?
+        .parse()
+        .ok()?;
+    Some((number, &bytes[digit_count..]))
+}
+
+/// Parses one `[indent]>offset=value[&mask][~word-size][+range]` line of a
+/// magic section. Returns the indent, the match and the rest of the file.
+fn parse_magic_line(bytes: &[u8]) -> Option<(usize, MagicMatch, &[u8])> {
+    let (indent, rest) = match bytes.first()? {
+        b'>' => (0, bytes),
+        _ => take_number(bytes)?,
+    };
+    let (offset, rest) = take_number(rest.strip_prefix(b">")?)?;
+    let rest = rest.strip_prefix(b"=")?;
+    let value_len = u16::from_be_bytes([*rest.first()?, *rest.get(1)?]) as usize;
+    let mut value = rest.get(2..2 + value_len)?.to_vec();
+    let mut rest = &rest[2 + value_len..];
+
+    let mut mask = None;
+    if let Some(mask_rest) = rest.strip_prefix(b"&") {
+        mask = Some(mask_rest.get(..value_len)?.to_vec());
+        rest = &mask_rest[value_len..];
+    }
+
+    let mut word_size = 1;
+    let mut range = 1;
+    loop {
+        match rest.first()? {
+            b'~' => (word_size, rest) = take_number(&rest[1..])?,
+            b'+' => (range, rest) = take_number(&rest[1..])?,
+            b'\n' => {
+                rest = &rest[1..];
+                break;
+            }
+            _ => {
+                let line_end = rest.iter().position(|&byte| byte == b'\n')?;
+                rest = &rest[line_end + 1..];
+                break;
+            }
+        }
+    }
+
+    // Values of multi-byte words are stored big-endian.
+    if cfg!(target_endian = "little") && word_size > 1 {
+        for bytes in std::iter::once(&mut value).chain(mask.as_mut()) {
+            for word in bytes.chunks_mut(word_size) {
+                word.reverse();
+            }
+        }
+    }
+
+    Some((
+        indent,
+        MagicMatch {
+            offset,
+            range: range.max(1),
+            value,
+            mask,
+            children: Vec::new(),
+        },
+        rest,
+    ))
+}
+
+fn parse_magic(bytes: &[u8]) -> Option<Vec<MagicRule>> {
+    let mut rest = bytes.strip_prefix(MAGIC_HEADER)?;
+    let mut rules = Vec::new();
+
+    while !rest.is_empty() {
+        let header_end = rest.iter().position(|&byte| byte == b'\n')?;
+        let header = std::str::from_utf8(&rest[..header_end]).ok()?;
+        let (priority, mime) = header
+            .strip_prefix('[')?
+            .strip_suffix(']')?
+            .split_once(':')?;
+        rest = &rest[header_end + 1..];
+
+        let mut rule = MagicRule {
+            priority: priority.parse().ok()?,
+            mime: mime.to_string(),
+            matches: Vec::new(),
+        };
+        while rest.first().is_some_and(|&byte| byte != b'[') {
+            let (indent, magic_match, remaining) = parse_magic_line(rest)?;
+            rest = remaining;
+
+            let mut siblings = &mut rule.matches;
+            for _ in 0..indent {
+                siblings = &mut siblings.last_mut()?.children;
+            }
+            siblings.push(magic_match);
+        }
+        rules.push(rule);
+    }
+
+    Some(rules)
+}
+
+impl MimeDatabase {
+    /// Loads and merges the `mime` directories in `mime_dirs`, most
+    /// important first. Returns `None` when none of them has a database.
+    pub(super) fn load(mime_dirs: &[PathBuf]) -> Option<Self> {
+        let mut database = Self::default();
+        let mut loaded = false;
+
+        for mime_dir in mime_dirs {
+            loaded |= database.load_dir(mime_dir);
+        }
+
+        loaded.then_some(database)
+    }
+
+    fn load_dir(&mut self, mime_dir: &Path) -> bool {
+        let globs = fs::read_to_string(mime_dir.join("globs2"));
+        let magic = fs::read(mime_dir.join("magic"));
+        if globs.is_err() && magic.is_err() {
+            return false;
+        }
+
+        for line in globs.iter().flat_map(|globs| globs.lines()) {
+            let mut fields = line.split(':');
+            let (Some(weight), Some(mime), Some(pattern)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let Ok(weight) = weight.parse() else {
+                continue;
+            };
+            if pattern == "__NOGLOBS__" {
+                continue;
+            }
+            let case_sensitive = fields
+                .next()
+                .is_some_and(|flags| flags.split(',').any(|flag| flag == "cs"));
+            self.add_glob(weight, mime, pattern, case_sensitive);
+        }
+
+        if let Some(rules) = magic.ok().and_then(|magic| parse_magic(&magic)) {
+            self.magic_types
+                .extend(rules.iter().map(|rule| rule.mime.clone()));
+            self.magic.extend(rules);
+            self.magic.sort_by_key(|rule| Reverse(rule.priority));
+        }
+
+        for (file_name, is_alias) in [("subclasses", false), ("aliases", true)] {
+            let Ok(text) = fs::read_to_string(mime_dir.join(file_name)) else {
+                continue;
+            };
+            for (mime, other) in text.lines().filter_map(|line| line.split_once(' ')) {
+                if is_alias {
+                    self.aliases.insert(mime.to_string(), other.to_string());
+                } else {
+                    self.add_parent(mime, other);
+                }
+            }
+        }
+
+        true
+    }
+
+    pub(super) fn add_glob(
+        &mut self,
+        weight: u32,
+        mime: &str,
+        pattern: &str,
+        case_sensitive: bool,
+    ) {
+        let rule = GlobRule {
+            weight,
+            mime: mime.to_string(),
+            pattern: pattern.to_string(),
+            case_sensitive,
+        };
+
+        if !has_wildcards(pattern) {
+            self.literals
+                .entry(pattern.to_lowercase())
+                .or_default()
+                .push(rule);
+        } else if let Some(suffix) = pattern
+            .strip_prefix('*')
+            .filter(|suffix| suffix.starts_with('.') && !has_wildcards(suffix))
+        {
+            self.suffixes
+                .entry(suffix.to_lowercase())
+                .or_default()
+                .push(rule);
+        } else if let Ok(glob) = GlobBuilder::new(pattern)
+            .case_insensitive(!case_sensitive)
+            .literal_separator(true)
+            .build()
+        {
+            self.patterns.push(PatternGlob {
+                rule,
+                matcher: glob.compile_matcher(),
+            });
+        }
+    }
+
+    /// Adds a rule that matches when every `(offset, bytes)` part matches.
+    pub(super) fn add_magic(&mut self, priority: u32, mime: &str, parts: &[(usize, &[u8])]) {
+        let mut matches = Vec::new();
+        for &(offset, value) in parts.iter().rev() {
+            matches = vec![MagicMatch {
+                offset,
+                range: 1,
+                value: value.to_vec(),
+                mask: None,
+                children: matches,
+            }];
+        }
+
+        self.magic_types.insert(mime.to_string());
+        let index = self.magic.partition_point(|rule| rule.priority >= priority);
+        self.magic.insert(
+            index,
+            MagicRule {
+                priority,
+                mime: mime.to_string(),
+                matches,
+            },
+        );
+    }
+
+    pub(super) fn add_parent(&mut self, mime: &str, parent: &str) {
+        self.parents
+            .entry(mime.to_string())
+            .or_default()
+            .push(parent.to_string());
+    }
+
+    fn canonical<'a>(&'a self, mime: &'a str) -> &'a str {
+        self.aliases.get(mime).map_or(mime, String::as_str)
+    }
+
+    /// Types whose globs match `name`, best first: literal names beat the
+    /// longest matching extension, which beats other patterns. Several
+    /// types come back only when they tie on weight.
+    pub(super) fn match_name(&self, name: &str) -> Vec<String> {
+        let mut rules: Vec<&GlobRule> = self
+            .literals
+            .get(&name.to_lowercase())
+            .into_iter()
+            .flatten()
+            .filter(|rule| !rule.case_sensitive || rule.pattern == name)
+            .collect();
+
+        if rules.is_empty() {
+            rules = name
+                .match_indices('.')
+                .find_map(|(index, _)| {
+                    let suffix = &name[index..];
+                    let rules: Vec<&GlobRule> = self
+                        .suffixes
+                        .get(&suffix.to_lowercase())?
+                        .iter()
+                        .filter(|rule| !rule.case_sensitive || rule.pattern[1..] == *suffix)
+                        .collect();
+                    (!rules.is_empty()).then_some(rules)
+                })
+                .unwrap_or_default();
+        }
+
+        if rules.is_empty() {
+            rules = self
+                .patterns
+                .iter()
+                .filter(|pattern| pattern.matcher.is_match(name))
+                .map(|pattern| &pattern.rule)
+                .collect();
+        }
+
+        // An exact-case rule is more specific than one that ignores case.
+        if rules.iter().any(|rule| rule.case_sensitive) {
+            rules.retain(|rule| rule.case_sensitive);
+        }
+
+        let Some(best_weight) = rules.iter().map(|rule| rule.weight).max() else {
+            return Vec::new();
+        };
+        let mut mimes: Vec<String> = Vec::new();
+        for rule in rules.into_iter().filter(|rule| rule.weight == best_weight) {
+            let mime = self.canonical(&rule.mime);
+            if !mimes.iter().any(|existing| existing == mime) {
+                mimes.push(mime.to_string());
+            }
+        }
+        mimes
+    }
+
+    /// The highest-priority type whose magic matches `data`.
+    pub(super) fn match_magic(&self, data: &[u8]) -> Option<&str> {
+        self.magic
+            .iter()
+            .find(|rule| {
+                rule.matches
+                    .iter()
+                    .any(|magic_match| magic_match.matches(data))
+            })
+            .map(|rule| self.canonical(&rule.mime))
+    }
+
+    pub(super) fn has_magic(&self, mime: &str) -> bool {
+        self.magic_types.contains(mime)
+    }
+
+    /// How many leading bytes the magic rules look at.
+    pub(super) fn magic_extent(&self) -> usize {
+        self.magic
+            .iter()
+            .flat_map(|rule| &rule.matches)
+            .map(MagicMatch::extent)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Whether `mime` is `ancestor` or inherits from it. Every `text/*`
+    /// type is a kind of `text/plain`.
+    pub(super) fn is_subclass(&self, mime: &str, ancestor: &str) -> bool {
+        let ancestor = self.canonical(ancestor);
+        let mut pending = vec![self.canonical(mime)];
+        let mut visited = HashSet::new();
+
+        while let Some(mime) = pending.pop() {
+            if mime == ancestor || (ancestor == "text/plain" && mime.starts_with("text/")) {
+                return true;
+            }
+            if visited.insert(mime) {
+                pending.extend(
+                    self.parents
+                        .get(mime)
+                        .into_iter()
+                        .flatten()
+                        .map(|parent| self.canonical(parent)),
+                );
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magic_files_parse_into_nested_rules() {
+        let mut magic = MAGIC_HEADER.to_vec();
+        magic.extend_from_slice(b"[80:image/x-test]\n>0=\x00\x02AB\n1>4=\x00\x01C+2\n");
+        magic.extend_from_slice(b"[50:application/x-masked]\n>1=\x00\x01\x10&\xf0\n");
+
+        let rules = parse_magic(&magic).expect("parse magic");
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].matches[0].children[0].range, 2);
+
+        let database = MimeDatabase {
+            magic: rules,
+            ..Default::default()
+        };
+        assert_eq!(database.match_magic(b"AB??C"), Some("image/x-test"));
+        assert_eq!(database.match_magic(b"AB???C"), Some("image/x-test"));
+        assert_eq!(database.match_magic(b"AB????C"), None);
+        assert_eq!(database.match_magic(b"x\x1f"), Some("application/x-masked"));
+        assert_eq!(database.magic_extent(), 7);
+    }
+
+    #[test]
+    fn names_prefer_literals_then_the_longest_extension() {
+        let mut database = MimeDatabase::default();
+        database.add_glob(50, "text/x-makefile", "Makefile", false);
+        database.add_glob(50, "application/gzip", "*.gz", false);
+        database.add_glob(50, "application/x-compressed-tar", "*.tar.gz", false);
+        database.add_glob(50, "text/x-readme", "README*", false);
+        database.add_glob(50, "text/x-c++src", "*.C", true);
+        database.add_glob(50, "text/x-csrc", "*.c", false);
+        database.add_parent("application/x-compressed-tar", "application/gzip");
+
+        assert_eq!(database.match_name("makefile"), vec!["text/x-makefile"]);
+        assert_eq!(
+            database.match_name("backup.TAR.GZ"),
+            vec!["application/x-compressed-tar"]
+        );
+        assert_eq!(database.match_name("notes.gz"), vec!["application/gzip"]);
+        assert_eq!(database.match_name("README.first"), vec!["text/x-readme"]);
+        assert_eq!(database.match_name("main.C"), vec!["text/x-c++src"]);
+        assert_eq!(database.match_name("main.c"), vec!["text/x-csrc"]);
+        assert!(database.match_name("photo").is_empty());
+        assert!(database.is_subclass("application/x-compressed-tar", "application/gzip"));
+        assert!(database.is_subclass("text/x-csrc", "text/plain"));
+        assert!(!database.is_subclass("application/gzip", "application/x-compressed-tar"));
+    }
+}
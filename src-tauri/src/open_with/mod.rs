@@ -48,16 +48,29 @@ pub fn get_associated_programs(file_path: String) -> GetAssociatedProgramsResult
 
 #[tauri::command]
 pub fn open_with_program(
+    app: tauri::AppHandle,
     file_path: String,
     program_path: String,
     arguments: Vec<String>,
 ) -> OpenWithResult {
+    let result = spawn_with_program(&file_path, &program_path, &arguments);
+    record_opened_path(&app, &file_path, &result);
+    result
+}
+
+fn record_opened_path(app: &tauri::AppHandle, file_path: &str, result: &OpenWithResult) {
+    if result.success {
+        crate::frecency::record_visit(app, file_path, Path::new(file_path).is_dir());
+    }
+}
+
+fn spawn_with_program(file_path: &str, program_path: &str, arguments: &[String]) -> OpenWithResult {
     #[cfg(windows)]
-    let native_file_path = path_for_selection(&file_path);
+    let native_file_path = path_for_selection(file_path);
     #[cfg(windows)]
     let file = Path::new(&native_file_path);
     #[cfg(not(windows))]
-    let file = Path::new(&file_path);
+    let file = Path::new(file_path);
 
     if !file.exists() {
         return OpenWithResult {
@@ -67,7 +80,7 @@ pub fn open_with_program(
     }
 
     #[cfg(windows)]
-    let absolute_file_path = prepare_shell_path(&file_path);
+    let absolute_file_path = prepare_shell_path(file_path);
     #[cfg(not(windows))]
     let absolute_file_path = canonicalize_path(file);
 
@@ -75,7 +88,7 @@ pub fn open_with_program(
     {
         if arguments.is_empty() {
             let handler_result =
-                windows::invoke_handler_for_file(&program_path, &absolute_file_path);
+                windows::invoke_handler_for_file(program_path, &absolute_file_path);
             if handler_result.success {
                 return handler_result;
             }
@@ -85,7 +98,7 @@ pub fn open_with_program(
     #[cfg(target_os = "linux")]
     {
         if arguments.is_empty() {
-            if let Some(result) = linux::open_with_desktop_id(&program_path, &absolute_file_path) {
+            if let Some(result) = linux::open_with_desktop_id(program_path, &absolute_file_path) {
                 if result.success {
                     return result;
                 }
@@ -93,7 +106,7 @@ pub fn open_with_program(
         }
     }
 
-    let program = Path::new(&program_path);
+    let program = Path::new(program_path);
     if !program.exists() {
         return OpenWithResult {
             success: false,
@@ -101,13 +114,13 @@ pub fn open_with_program(
         };
     }
 
-    let mut command = Command::new(&program_path);
+    let mut command = Command::new(program_path);
 
     if arguments.is_empty() {
         command.arg(&absolute_file_path);
     } else {
         let mut file_arg_added = false;
-        for arg in &arguments {
+        for arg in arguments {
             if arg.contains("%1") {
                 command.arg(arg.replace("%1", &absolute_file_path));
                 file_arg_added = true;
@@ -149,14 +162,20 @@ pub fn open_with_program(
 }
 
 #[tauri::command]
-pub fn open_with_default(file_path: String) -> OpenWithResult {
+pub fn open_with_default(app: tauri::AppHandle, file_path: String) -> OpenWithResult {
+    let result = open_path_default(&file_path);
+    record_opened_path(&app, &file_path, &result);
+    result
+}
+
+fn open_path_default(file_path: &str) -> OpenWithResult {
     #[cfg(target_os = "windows")]
     {
-        windows::open_path_default_impl(&file_path)
+        windows::open_path_default_impl(file_path)
     }
     #[cfg(target_os = "macos")]
     {
-        match Command::new("open").arg(&file_path).spawn() {
+        match Command::new("open").arg(file_path).spawn() {
             Ok(_) => OpenWithResult {
                 success: true,
                 error: None,
@@ -169,7 +188,7 @@ pub fn open_with_default(file_path: String) -> OpenWithResult {
     }
     #[cfg(target_os = "linux")]
     {
-        match Command::new("xdg-open").arg(&file_path).spawn() {
+        match Command::new("xdg-open").arg(file_path).spawn() {
             Ok(_) => OpenWithResult {
                 success: true,
                 error: None,
@@ -0,0 +1,300 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Saved global searches that open like directories.
+//!
+//! A smart folder stores a query with its search options. Reading it runs the
+//! query and returns the hits in the same shape as `read_dir`, under a virtual
+//! `sfm://smart-folder/<id>` path.
+
+use crate::dir_reader::{read_entries, DirContents, OpenedDirectoryTimes, ReadDirOptions};
+use crate::global_search::GlobalSearchQueryOptions;
+use crate::utils::normalize_path;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+
+const SMART_FOLDERS_FILE_NAME: &str = "smart-folders.json";
+const SMART_FOLDERS_SCHEMA_VERSION: u32 = 1;
+const SMART_FOLDER_PATH_PREFIX: &str = "sfm://smart-folder/";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmartFolder {
+    /// Assigned on first save when empty.
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub query: String,
+    pub options: GlobalSearchQueryOptions,
+    /// Folder the search is limited to; every indexed drive is searched when
+    /// unset.
+    #[serde(default)]
+    pub scope_path: Option<String>,
+    #[serde(default)]
+    pub created_time: u64,
+    #[serde(default)]
+    pub modified_time: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SmartFolderDatabase {
+    version: u32,
+    folders: Vec<SmartFolder>,
+}
+
+struct SmartFolderStore {
+    file_path: PathBuf,
+    database: SmartFolderDatabase,
+}
+
+static SMART_FOLDER_STORE: Lazy<Mutex<Option<SmartFolderStore>>> = Lazy::new(|| Mutex::new(None));
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn smart_folder_path(id: &str) -> String {
+    format!("{SMART_FOLDER_PATH_PREFIX}{id}")
+}
+
+fn app_data_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|error| format!("Failed to resolve app data directory: {error}"))
+}
+
+impl SmartFolderDatabase {
+    fn find(&self, id: &str) -> Option<&SmartFolder> {
+        self.folders.iter().find(|folder| folder.id == id)
+    }
+
+    /// Inserts a new folder or replaces the one with the same id, keeping its
+    /// creation time. Returns the stored folder.
+    fn upsert(&mut self, mut folder: SmartFolder, now: u64) -> SmartFolder {
+        folder.scope_path = folder
+            .scope_path
+            .filter(|scope_path| !scope_path.trim().is_empty())
+            .map(|scope_path| normalize_path(&scope_path));
+        folder.options.scope_path = None;
+        folder.options.request_id = None;
+        folder.modified_time = now;
+
+        match self
+            .folders
+            .iter_mut()
+            .find(|existing| !folder.id.is_empty() && existing.id == folder.id)
+        {
+            Some(existing) => {
+                folder.created_time = existing.created_time;
+                *existing = folder.clone();
+            }
+            None => {
+                if folder.id.is_empty() {
+                    folder.id = self.next_id(now);
+                }
+                folder.created_time = now;
+                self.folders.push(folder.clone());
+            }
+        }
+
+        folder
+    }
+
+    fn next_id(&self, now: u64) -> String {
+        (now..)
+            .map(|candidate| format!("{candidate:x}"))
+            .find(|candidate| self.find(candidate).is_none())
+            .expect("an unused smart folder id exists")
+    }
+
+    fn remove(&mut self, id: &str) -> bool {
+        let folder_count = self.folders.len();
+        self.folders.retain(|folder| folder.id != id);
+        self.folders.len() != folder_count
+    }
+}
+
+fn load_database(file_path: &Path) -> SmartFolderDatabase {
+    fs::read_to_string(file_path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<SmartFolderDatabase>(&text).ok())
+        .filter(|database| database.version == SMART_FOLDERS_SCHEMA_VERSION)
+        .unwrap_or_else(|| SmartFolderDatabase {
+            version: SMART_FOLDERS_SCHEMA_VERSION,
+            folders: Vec::new(),
+        })
+}
+
+fn save_database(file_path: &Path, database: &SmartFolderDatabase) -> Result<(), String> {
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+    let json = serde_json::to_string(database).map_err(|error| error.to_string())?;
+    let tmp_path = file_path.with_extension("json.tmp");
+    fs::write(&tmp_path, json).map_err(|error| error.to_string())?;
+    fs::rename(&tmp_path, file_path).or_else(|rename_error| {
+        let _ = fs::remove_file(file_path);
+        fs::rename(&tmp_path, file_path).map_err(|replace_error| {
+            format!(
+                "Failed to replace smart folder store: {}; {}",
+                rename_error, replace_error
+            )
+        })
+    })
+}
+
+/// Runs `action` on the store for `app_data_dir`, loading it on first use.
+fn with_database<T>(
+    app_data_dir: &Path,
+    action: impl FnOnce(&mut SmartFolderDatabase, &Path) -> T,
+) -> Result<T, String> {
+    let file_path = app_data_dir.join(SMART_FOLDERS_FILE_NAME);
+    let mut store = SMART_FOLDER_STORE
+        .lock()
+        .map_err(|error| error.to_string())?;
+
+    if store
+        .as_ref()
+        .is_none_or(|store| store.file_path != file_path)
+    {
+        *store = Some(SmartFolderStore {
+            database: load_database(&file_path),
+            file_path: file_path.clone(),
+        });
+    }
+
+    let store = store.as_mut().expect("smart folder store was just loaded");
+    Ok(action(&mut store.database, &store.file_path))
+}
+
+#[tauri::command]
+pub fn list_smart_folders(app: tauri::AppHandle) -> Result<Vec<SmartFolder>, String> {
+    with_database(&app_data_dir(&app)?, |database, _| database.folders.clone())
+}
+
+/// Creates a smart folder, or updates the one with the same id.
+#[tauri::command]
+pub fn save_smart_folder(
+    app: tauri::AppHandle,
+    folder: SmartFolder,
+) -> Result<SmartFolder, String> {
+    if folder.name.trim().is_empty() {
+        return Err("Smart folder name cannot be empty".to_string());
+    }
+
+    with_database(&app_data_dir(&app)?, |database, file_path| {
+        let folder = database.upsert(folder, now_millis());
+        save_database(file_path, database).map(|_| folder)
+    })?
+}
+
+#[tauri::command]
+pub fn delete_smart_folder(app: tauri::AppHandle, id: String) -> Result<(), String> {
+    with_database(&app_data_dir(&app)?, |database, file_path| {
+        if database.remove(&id) {
+            save_database(file_path, database)
+        } else {
+            Err(format!("Smart folder not found: {id}"))
+        }
+    })?
+}
+
+/// Runs a smart folder's search and lists the hits like a directory.
+#[tauri::command]
+pub async fn read_smart_folder(
+    app: tauri::AppHandle,
+    id: String,
+    options: Option<ReadDirOptions>,
+) -> Result<DirContents, String> {
+    let folder = with_database(&app_data_dir(&app)?, |database, _| {
+        database.find(&id).cloned()
+    })?
+    .ok_or_else(|| format!("Smart folder not found: {id}"))?;
+
+    let mut query_options = folder.options.clone();
+    query_options.scope_path = folder.scope_path.clone();
+    let results =
+        crate::global_search::global_search_query(app, folder.query.clone(), query_options)
+            .await
+            .map_err(|error| error.message)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let paths: Vec<String> = results.into_iter().map(|result| result.path).collect();
+        let entries = read_entries(&paths, options)?;
+        let dir_count = entries.iter().filter(|entry| entry.is_dir).count();
+        let file_count = entries.iter().filter(|entry| entry.is_file).count();
+
+        Ok(DirContents {
+            path: smart_folder_path(&folder.id),
+            entries,
+            total_count: dir_count + file_count,
+            dir_count,
+            file_count,
+            opened_directory_times: OpenedDirectoryTimes {
+                modified_time: folder.modified_time,
+                accessed_time: now_millis(),
+                created_time: folder.created_time,
+            },
+            git: None,
+        })
+    })
+    .await
+    .map_err(|join_error| format!("Failed to read smart folder: {join_error}"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn smart_folder(id: &str, name: &str) -> SmartFolder {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": name,
+            "query": "ext:pdf modified:thisweek",
+            "options": {
+                "limit": 500,
+                "include_files": true,
+                "include_directories": false,
+                "exact_match": false,
+                "typo_tolerance": false,
+                "min_score_threshold": null,
+                "scope_path": "/ignored",
+                "request_id": 7
+            },
+            "scope_path": "C:\\Users\\me\\Documents"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn saving_assigns_ids_and_updates_keep_the_creation_time() {
+        let mut database = SmartFolderDatabase::default();
+
+        let created = database.upsert(smart_folder("", "PDFs this week"), 1_000);
+        assert_eq!(created.id, format!("{:x}", 1_000));
+        assert_eq!(created.scope_path.as_deref(), Some("C:/Users/me/Documents"));
+        assert_eq!(created.options.scope_path, None);
+        assert_eq!(created.options.request_id, None);
+
+        let second = database.upsert(smart_folder("", "Env files"), 1_000);
+        assert_ne!(second.id, created.id);
+
+        let updated = database.upsert(smart_folder(&created.id, "Recent PDFs"), 2_000);
+        assert_eq!(updated.created_time, 1_000);
+        assert_eq!(updated.modified_time, 2_000);
+        assert_eq!(database.folders.len(), 2);
+        assert_eq!(database.find(&created.id).unwrap().name, "Recent PDFs");
+
+        assert!(database.remove(&second.id));
+        assert!(!database.remove(&second.id));
+        assert_eq!(database.folders.len(), 1);
+    }
+}
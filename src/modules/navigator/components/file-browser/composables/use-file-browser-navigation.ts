@@ -13,6 +13,7 @@ import { invoke } from '@tauri-apps/api/core';
 import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { homeDir } from '@tauri-apps/api/path';
 import { openPathDefault } from '@/utils/open-path-default';
+import { readDirStream, type ReadDirStream } from '@/utils/read-dir-stream';
 import type { DirEntry, DirContents, ReadDirOptions } from '@/types/dir-entry';
 import type { Tab } from '@/types/workspaces';
 import { useWorkspacesStore } from '@/stores/storage/workspaces';
@@ -46,6 +47,7 @@ interface DirChangePayload {
 
 const DIRECTORY_DWELL_TIME_MS = 3000;
 const WATCHER_DEBOUNCE_MS = 500;
+const STREAM_PARTIAL_UPDATE_INTERVAL_MS = 250;
 
 function dirWatcherDiagEnabled(): boolean {
   return import.meta.env.DEV && localStorage.getItem('SFM_DIR_WATCHER_DIAG') === '1';
@@ -121,6 +123,77 @@ export function useFileBrowserNavigation(
     return resolveDirectoryContents(path, options ?? createReadDirOptions());
   }
 
+  function cancelActiveReadDirStream(): void {
+    if (activeReadDirStream) {
+      void activeReadDirStream.cancel();
+      activeReadDirStream = null;
+    }
+  }
+
+  /**
+   * Reads a real directory as a stream so huge directories show their first
+   * entries while the rest is still being read. Partial contents are only
+   * shown when `showPartialContents` is set, i.e. nothing is displayed yet.
+   */
+  async function streamDirectoryContents(
+    path: string,
+    options: ReadDirOptions,
+    requestGeneration: number,
+    showPartialContents: boolean,
+  ): Promise<DirContents> {
+    const normalizedPath = normalizePath(path);
+    const entries: DirEntry[] = [];
+    let lastPartialUpdateTime = 0;
+
+    const stream = await readDirStream(path, {
+      onEntries: (batch) => {
+        entries.push(...batch);
+
+        if (!showPartialContents || isDisposed || requestGeneration !== readGeneration) {
+          return;
+        }
+
+        const now = Date.now();
+
+        if (now - lastPartialUpdateTime < STREAM_PARTIAL_UPDATE_INTERVAL_MS) {
+          return;
+        }
+
+        lastPartialUpdateTime = now;
+        dirContents.value = buildDirContentsFromEntries(normalizedPath, entries.slice());
+        currentPath.value = normalizedPath;
+        pathInput.value = normalizedPath;
+        isLoading.value = false;
+        isRefreshing.value = true;
+      },
+    }, options);
+    activeReadDirStream = stream;
+
+    const finished = await stream.finished;
+
+    if (activeReadDirStream === stream) {
+      activeReadDirStream = null;
+    }
+
+    if (!finished.success && !finished.cancelled) {
+      throw new Error(finished.error ?? `Failed to read directory: ${path}`);
+    }
+
+    return {
+      path: finished.path,
+      entries: finished.entries ?? entries,
+      total_count: finished.totalCount,
+      dir_count: finished.dirCount,
+      file_count: finished.fileCount,
+      opened_directory_times: finished.openedDirectoryTimes ?? {
+        modified_time: 0,
+        accessed_time: 0,
+        created_time: 0,
+      },
+      git: finished.git ?? undefined,
+    };
+  }
+
   async function directoryPathExists(path: string): Promise<boolean> {
     if (isVirtualDirectoryPath(path)) {
       return true;
@@ -162,6 +235,7 @@ export function useFileBrowserNavigation(
   let iconPrefetchTimer: ReturnType<typeof setTimeout> | null = null;
   let watcherOperation = Promise.resolve();
   let readGeneration = 0;
+  let activeReadDirStream: ReadDirStream | null = null;
   let silentRefreshGeneration = 0;
   let isDisposed = false;
 
@@ -371,6 +445,7 @@ export function useFileBrowserNavigation(
   onUnmounted(() => {
     isDisposed = true;
     readGeneration += 1;
+    cancelActiveReadDirStream();
     cancelPendingDirectoryRecord();
     stopWatching();
 
@@ -614,6 +689,7 @@ export function useFileBrowserNavigation(
     invalidateLinkMetadata = false,
   ) {
     const requestGeneration = ++readGeneration;
+    cancelActiveReadDirStream();
     const normalizedPath = normalizePath(path);
     const isNewDirectory = normalizedPath !== currentPath.value;
     const cachedContents = !forceLoading
@@ -648,8 +724,14 @@ export function useFileBrowserNavigation(
     try {
       logDirWatcherDiag('readDir loading directory', { path: normalizedPath });
       const readOptions = createReadDirOptions();
+      const loadOptions = {
+        ...readOptions,
+        recordVisit: isNewDirectory,
+      };
       const [result] = await Promise.all([
-        loadDirectoryContents(path, readOptions),
+        isVirtualDirectoryPath(path)
+          ? loadDirectoryContents(path, loadOptions)
+          : streamDirectoryContents(path, loadOptions, requestGeneration, !cachedContents),
         stopWatcherPromise,
       ]);
 
@@ -26,10 +26,39 @@ export type StartArchiveJobRequest
     password?: string;
     encoding?: string;
   }
+  | {
+    kind: 'smartExtract';
+    archivePaths: string[];
+    destinationDir?: string;
+    password?: string;
+    encoding?: string;
+    extractNested?: boolean;
+  }
   | {
     kind: 'compress';
     sourcePaths: string[];
     destinationZipPath: string;
+    volumeSize?: number;
+  }
+  | {
+    kind: 'addToArchive' | 'replaceInArchive';
+    archivePath: string;
+    sourcePaths: string[];
+    entryDir?: string;
+    encoding?: string;
+  }
+  | {
+    kind: 'deleteFromArchive';
+    archivePath: string;
+    entryPaths: string[];
+    encoding?: string;
+  }
+  | {
+    kind: 'renameInArchive';
+    archivePath: string;
+    entryPath: string;
+    newName: string;
+    encoding?: string;
   };
 
 interface ArchiveJobProgressPayload {
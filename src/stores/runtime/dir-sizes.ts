@@ -12,6 +12,7 @@ export type SizeStatus = 'Complete' | 'Error' | 'Loading';
 
 export interface DirSizeInfo {
   size: number;
+  allocatedSize: number;
   status: SizeStatus;
   fileCount: number;
   dirCount: number;
@@ -21,10 +22,13 @@ export interface DirSizeInfo {
 export interface DirSizeResult {
   path: string;
   size: number;
+  allocated_size: number;
   status: 'Complete' | 'Partial' | 'Timeout' | 'Error' | 'Cancelled';
   file_count: number;
   dir_count: number;
   error: string | null;
+  /** From a cache entry that may be out of date, e.g. from an earlier session. */
+  is_stale?: boolean;
 }
 
 export type CopyMoveSourceForSizeRefresh = {
@@ -36,6 +40,7 @@ export const useDirSizesStore = defineStore('dir-sizes', () => {
   const sizes = ref<Map<string, DirSizeInfo>>(new Map());
   const pendingPaths = ref<Set<string>>(new Set());
   const progressIntervals = ref<Map<string, ReturnType<typeof setInterval>>>(new Map());
+  const staleRefreshPaths = new Set<string>();
 
   const pendingCount = computed(() => pendingPaths.value.size);
 
@@ -71,6 +76,7 @@ export const useDirSizesStore = defineStore('dir-sizes', () => {
 
     sizes.value.set(path, {
       size: result.size,
+      allocatedSize: result.allocated_size,
       status: 'Complete',
       fileCount: result.file_count,
       dirCount: result.dir_count,
@@ -87,6 +93,7 @@ export const useDirSizesStore = defineStore('dir-sizes', () => {
     if (!existing) {
       sizes.value.set(path, {
         size: 0,
+        allocatedSize: 0,
         status: 'Loading',
         fileCount: 0,
         dirCount: 0,
@@ -105,6 +112,7 @@ export const useDirSizesStore = defineStore('dir-sizes', () => {
         if (progress && progress.size > 0) {
           sizes.value.set(path, {
             size: progress.size,
+            allocatedSize: progress.allocated_size,
             status: 'Loading',
             fileCount: progress.file_count,
             dirCount: progress.dir_count,
@@ -263,6 +271,10 @@ export const useDirSizesStore = defineStore('dir-sizes', () => {
       for (const result of results) {
         setSize(result.path, result);
       }
+
+      void refreshStaleSizes(
+        results.filter(result => result.is_stale).map(result => result.path),
+      );
     }
     catch (error) {
       for (const path of pathsToFetch) {
@@ -273,6 +285,48 @@ export const useDirSizesStore = defineStore('dir-sizes', () => {
     }
   }
 
+  /**
+   * Recomputes sizes that came from a stale cache entry, keeping the cached
+   * size on display until the new one is known.
+   */
+  async function refreshStaleSizes(paths: string[]): Promise<void> {
+    const pathsToRefresh = paths.filter(path => !staleRefreshPaths.has(path));
+
+    if (pathsToRefresh.length === 0) {
+      return;
+    }
+
+    for (const path of pathsToRefresh) {
+      staleRefreshPaths.add(path);
+    }
+
+    try {
+      const results = await invoke<DirSizeResult[]>('get_dir_sizes_batch', {
+        paths: pathsToRefresh,
+        timeoutMs: null,
+        useCache: false,
+      });
+
+      for (const result of results) {
+        if (
+          result.status === 'Complete'
+          && sizes.value.has(result.path)
+          && !pendingPaths.value.has(result.path)
+        ) {
+          setSize(result.path, result);
+        }
+      }
+    }
+    catch (error) {
+      console.error('Failed to refresh stale directory sizes:', error);
+    }
+    finally {
+      for (const path of pathsToRefresh) {
+        staleRefreshPaths.delete(path);
+      }
+    }
+  }
+
   async function refreshSizesAfterCopyMove(
     sources: CopyMoveSourceForSizeRefresh[],
     destinationDirectory: string,
@@ -333,13 +387,16 @@ export const useDirSizesStore = defineStore('dir-sizes', () => {
     }
   }
 
-  function invalidate(paths: string[]) {
+  function invalidate(paths: string[], includeAncestors = false) {
     for (const path of paths) {
       sizes.value.delete(path);
       pendingPaths.value.delete(path);
     }
 
-    invoke('invalidate_dir_size_cache', { paths }).catch((error) => {
+    invoke('invalidate_dir_size_cache', {
+      paths,
+      includeAncestors,
+    }).catch((error) => {
       console.error('Failed to invalidate cache:', error);
     });
   }
@@ -376,6 +433,7 @@ export const useDirSizesStore = defineStore('dir-sizes', () => {
         pendingPaths.value.add(calc.path);
         sizes.value.set(calc.path, {
           size: calc.size,
+          allocatedSize: calc.allocated_size,
           status: 'Loading',
           fileCount: calc.file_count,
           dirCount: calc.dir_count,
@@ -417,6 +475,7 @@ export const useDirSizesStore = defineStore('dir-sizes', () => {
           // Still running, update the size
           sizes.value.set(path, {
             size: progress.size,
+            allocatedSize: progress.allocated_size,
             status: 'Loading',
             fileCount: progress.file_count,
             dirCount: progress.dir_count,
@@ -4,8 +4,10 @@
 
 import { defineStore } from 'pinia';
 import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { computed, ref, watch } from 'vue';
 import type { DirEntry } from '@/types/dir-entry';
+import type { GlobalSearchMatchMode } from '@/types/user-settings';
 import { useUserSettingsStore } from '@/stores/storage/user-settings';
 import { useUserStatsStore } from '@/stores/storage/user-stats';
 import { useUserPathsStore } from '@/stores/storage/user-paths';
@@ -21,6 +23,53 @@ type GlobalSearchDriveScanError = {
 export type GlobalSearchScanReason = 'manual' | 'startup' | 'idle' | 'driveChange' | 'settingsChange';
 export type GlobalSearchScanPhase = 'idle' | 'scanning' | 'canceling' | 'committing';
 export type GlobalSearchScanOutcome = 'completed' | 'canceled' | 'failed';
+export type GlobalSearchLiveUpdateMode = 'native' | 'polling';
+export type GlobalSearchScanTarget = 'allDrives' | 'listedDrives' | 'addedDrives';
+
+export type GlobalSearchShardStatus = {
+  drive_root: string;
+  is_online: boolean;
+  indexed_item_count: number;
+  content_indexed_item_count: number;
+  index_size_bytes: number;
+  last_scan_time: number | null;
+};
+
+export type GlobalSearchSmartFolderOptions = {
+  limit: number;
+  include_files: boolean;
+  include_directories: boolean;
+  exact_match: boolean;
+  typo_tolerance: boolean;
+  min_score_threshold: number | null;
+  match_mode?: GlobalSearchMatchMode;
+  match_target?: 'auto' | 'name' | 'path';
+};
+
+export type GlobalSearchSmartFolder = {
+  id: string;
+  name: string;
+  query: string;
+  options: GlobalSearchSmartFolderOptions;
+  scope_path: string | null;
+  created_time: number;
+  modified_time: number;
+};
+
+export type GlobalSearchSmartFolderDraft = Omit<GlobalSearchSmartFolder, 'id' | 'created_time' | 'modified_time'> & {
+  id?: string;
+};
+
+export type GlobalSearchQueryError = {
+  kind: 'syntax' | 'search';
+  message: string;
+  token: string | null;
+  position: number | null;
+};
+
+function isGlobalSearchQueryError(error: unknown): error is GlobalSearchQueryError {
+  return typeof error === 'object' && error !== null && 'kind' in error && 'message' in error;
+}
 
 type GlobalSearchStatus = {
   is_scan_in_progress: boolean;
@@ -40,12 +89,26 @@ type GlobalSearchStatus = {
   scan_indexed_item_count: number;
   indexed_drive_roots: string[];
   index_size_bytes: number;
+  content_indexed_item_count: number;
+  live_update_mode: GlobalSearchLiveUpdateMode | null;
+  last_live_update_time: number | null;
   current_drive_root: string | null;
   current_scan_path: string | null;
   drive_scan_errors: GlobalSearchDriveScanError[];
   is_index_valid: boolean;
   scanned_drives_count: number;
   total_drives_count: number;
+  shards: GlobalSearchShardStatus[];
+};
+
+type GlobalSearchResultItem = DirEntry & { score?: number };
+
+type GlobalSearchScopeResultsEvent = {
+  request_id: number | null;
+  scope_path: string;
+  entries: GlobalSearchResultItem[];
+  scanned_count: number;
+  is_done: boolean;
 };
 
 type CancelScanOptions = {
@@ -85,8 +148,12 @@ export const useGlobalSearchStore = defineStore('globalSearch', () => {
   const isIndexValid = ref(false);
   const scannedDrivesCount = ref(0);
   const totalDrivesCount = ref(0);
+  const shards = ref<GlobalSearchShardStatus[]>([]);
+  const smartFolders = ref<GlobalSearchSmartFolder[]>([]);
   const isInitialized = ref(false);
   const lastError = ref<string | null>(null);
+  const queryError = ref<GlobalSearchQueryError | null>(null);
+  const scopePath = ref<string | null>(null);
 
   const statusPollTimerId = ref<ReturnType<typeof setTimeout> | null>(null);
   const debounceTimerId = ref<ReturnType<typeof setTimeout> | null>(null);
@@ -94,6 +161,8 @@ export const useGlobalSearchStore = defineStore('globalSearch', () => {
   const driveChangeDebounceTimerId = ref<ReturnType<typeof setTimeout> | null>(null);
   const lastKnownDriveCount = ref<number>(0);
   let searchRequestSequence = 0;
+  let scopeResultsUnlisten: UnlistenFn | null = null;
+  let streamedScopeResults: GlobalSearchResultItem[] = [];
 
   const userSettingsStore = useUserSettingsStore();
   const userStatsStore = useUserStatsStore();
@@ -191,6 +260,7 @@ export const useGlobalSearchStore = defineStore('globalSearch', () => {
     isIndexValid.value = status.is_index_valid ?? false;
     scannedDrivesCount.value = status.scanned_drives_count ?? 0;
     totalDrivesCount.value = status.total_drives_count ?? 0;
+    shards.value = Array.isArray(status.shards) ? status.shards : [];
   }
 
   async function refreshStatus() {
@@ -255,14 +325,20 @@ export const useGlobalSearchStore = defineStore('globalSearch', () => {
     statusPollTimerId.value = null;
   }
 
-  function createScanSettings(driveRoots: string[], scanReasonValue: GlobalSearchScanReason) {
+  function createScanSettings(
+    driveRoots: string[],
+    scanReasonValue: GlobalSearchScanReason,
+    scanTarget: GlobalSearchScanTarget = scanReasonValue === 'driveChange' ? 'addedDrives' : 'allDrives',
+  ) {
     const settings = userSettingsStore.userSettings.globalSearch;
     return {
       scan_depth: Math.max(1, Math.floor(settings.scanDepth)),
       ignored_paths: settings.ignoredPaths,
       drive_roots: driveRoots,
       parallel_scan: settings.parallelScan ?? false,
+      respect_ignore_files: settings.respectIgnoreFiles ?? false,
       scan_reason: scanReasonValue,
+      scan_target: scanTarget,
     };
   }
 
@@ -296,6 +372,75 @@ export const useGlobalSearchStore = defineStore('globalSearch', () => {
     }
   }
 
+  async function rescanDrive(driveRoot: string) {
+    if (isScanInProgress.value) return;
+
+    try {
+      startStatusPolling();
+
+      await invoke('global_search_start_scan', {
+        settings: createScanSettings([driveRoot], 'manual', 'listedDrives'),
+      });
+
+      await refreshStatus();
+      lastError.value = null;
+    }
+    catch (error) {
+      lastError.value = String(error);
+      isScanInProgress.value = false;
+    }
+  }
+
+  async function dropShard(driveRoot: string) {
+    try {
+      const status = await invoke<GlobalSearchStatus>('global_search_drop_shard', { driveRoot });
+      updateStatusFromResponse(status);
+      lastError.value = null;
+    }
+    catch (error) {
+      lastError.value = String(error);
+    }
+  }
+
+  async function loadSmartFolders() {
+    try {
+      smartFolders.value = await invoke<GlobalSearchSmartFolder[]>('list_smart_folders');
+    }
+    catch (error) {
+      lastError.value = String(error);
+    }
+  }
+
+  async function saveSmartFolder(folder: GlobalSearchSmartFolderDraft): Promise<GlobalSearchSmartFolder | null> {
+    try {
+      const savedFolder = await invoke<GlobalSearchSmartFolder>('save_smart_folder', {
+        folder: {
+          ...folder,
+          id: folder.id ?? '',
+        },
+      });
+      const isExistingFolder = smartFolders.value.some(existing => existing.id === savedFolder.id);
+      smartFolders.value = isExistingFolder
+        ? smartFolders.value.map(existing => (existing.id === savedFolder.id ? savedFolder : existing))
+        : [...smartFolders.value, savedFolder];
+      return savedFolder;
+    }
+    catch (error) {
+      lastError.value = String(error);
+      return null;
+    }
+  }
+
+  async function deleteSmartFolder(id: string) {
+    try {
+      await invoke('delete_smart_folder', { id });
+      smartFolders.value = smartFolders.value.filter(folder => folder.id !== id);
+    }
+    catch (error) {
+      lastError.value = String(error);
+    }
+  }
+
   async function cancelScan(options: CancelScanOptions = {}) {
     if (!isScanInProgress.value) return;
 
@@ -345,6 +490,49 @@ export const useGlobalSearchStore = defineStore('globalSearch', () => {
     }
 
     searchRequestSequence += 1;
+
+    if (scopePath.value) {
+      invoke('global_search_cancel_scope_walk').catch(() => {});
+    }
+  }
+
+  function toDirEntry(item: GlobalSearchResultItem): DirEntry {
+    return {
+      name: item.name,
+      ext: item.ext ?? null,
+      path: item.path,
+      size: item.size ?? 0,
+      item_count: item.item_count ?? null,
+      modified_time: item.modified_time ?? 0,
+      accessed_time: item.accessed_time ?? 0,
+      created_time: item.created_time ?? 0,
+      mime: item.mime ?? null,
+      is_file: Boolean(item.is_file),
+      is_dir: Boolean(item.is_dir),
+      is_symlink: Boolean(item.is_symlink),
+      is_hidden: Boolean(item.is_hidden),
+    };
+  }
+
+  async function ensureScopeResultsListener() {
+    if (scopeResultsUnlisten) return;
+
+    scopeResultsUnlisten = await listen<GlobalSearchScopeResultsEvent>('global-search-scope-results', (event) => {
+      const payload = event.payload;
+
+      if (payload.request_id !== searchRequestSequence || payload.entries.length === 0) {
+        return;
+      }
+
+      const limit = userSettingsStore.userSettings.globalSearch.resultLimit ?? SEARCH_CONSTANTS.DEFAULT_RESULT_LIMIT;
+      streamedScopeResults = mergeAndDeduplicateResults(streamedScopeResults, payload.entries).slice(0, limit);
+      results.value = streamedScopeResults.map(toDirEntry);
+    });
+  }
+
+  function setScopePath(path: string | null) {
+    scopePath.value = path;
+    search();
   }
 
   async function executeSearch(searchQuery: string) {
@@ -354,6 +542,7 @@ export const useGlobalSearchStore = defineStore('globalSearch', () => {
 
     if (!trimmedSearchQuery) {
       results.value = [];
+      queryError.value = null;
       isSearching.value = false;
       return;
     }
@@ -371,13 +560,21 @@ export const useGlobalSearchStore = defineStore('globalSearch', () => {
         exact_match: settings.exactMatch ?? false,
         typo_tolerance: settings.typoTolerance ?? true,
         min_score_threshold: null,
+        match_mode: settings.matchMode ?? 'fuzzy',
+        scope_path: scopePath.value,
+        request_id: requestSequence,
       };
 
-      const priorityPaths = getAllPriorityPaths();
+      if (scopePath.value) {
+        streamedScopeResults = [];
+        await ensureScopeResultsListener();
+      }
+
+      const priorityPaths = scopePath.value ? [] : getAllPriorityPaths();
 
       const searchPromises: Promise<Array<DirEntry & { score?: number }>>[] = [];
 
-      if (indexedItemCount.value > 0) {
+      if (indexedItemCount.value > 0 || scopePath.value) {
         searchPromises.push(
           invoke<Array<DirEntry & { score?: number }>>('global_search_query', {
             query: trimmedSearchQuery,
@@ -410,27 +607,15 @@ export const useGlobalSearchStore = defineStore('globalSearch', () => {
 
       const mergedResults = mergeAndDeduplicateResults(indexedResults, priorityResults);
 
-      results.value = mergedResults.map(item => ({
-        name: item.name,
-        ext: item.ext ?? null,
-        path: item.path,
-        size: item.size ?? 0,
-        item_count: item.item_count ?? null,
-        modified_time: item.modified_time ?? 0,
-        accessed_time: item.accessed_time ?? 0,
-        created_time: item.created_time ?? 0,
-        mime: item.mime ?? null,
-        is_file: Boolean(item.is_file),
-        is_dir: Boolean(item.is_dir),
-        is_symlink: Boolean(item.is_symlink),
-        is_hidden: Boolean(item.is_hidden),
-      }));
+      results.value = mergedResults.map(toDirEntry);
 
       lastError.value = null;
+      queryError.value = null;
     }
     catch (error) {
       if (!abortController.signal.aborted && requestSequence === searchRequestSequence) {
-        lastError.value = String(error);
+        queryError.value = isGlobalSearchQueryError(error) ? error : null;
+        lastError.value = isGlobalSearchQueryError(error) ? error.message : String(error);
         results.value = [];
       }
     }
@@ -523,6 +708,7 @@ export const useGlobalSearchStore = defineStore('globalSearch', () => {
     cancelPendingSearch();
     query.value = '';
     results.value = [];
+    queryError.value = null;
   }
 
   function checkIdleReindex() {
@@ -701,11 +887,16 @@ export const useGlobalSearchStore = defineStore('globalSearch', () => {
     isIndexValid,
     scannedDrivesCount,
     totalDrivesCount,
+    shards,
+    smartFolders,
     scanProgress,
     needsScan,
     getIsIndexStale,
     isInitialized,
     lastError,
+    queryError,
+    scopePath,
+    setScopePath,
     open,
     close,
     toggle,
@@ -717,6 +908,11 @@ export const useGlobalSearchStore = defineStore('globalSearch', () => {
     stopStatusPolling,
     startScan,
     cancelScan,
+    rescanDrive,
+    dropShard,
+    loadSmartFolders,
+    saveSmartFolder,
+    deleteSmartFolder,
     search,
   };
 });
@@ -149,11 +149,13 @@ export const useUserSettingsStore = defineStore('userSettings', () => {
       ignoredPaths: [...DEFAULT_GLOBAL_SEARCH_IGNORED_PATHS],
       selectedDriveRoots: [],
       parallelScan: false,
+      respectIgnoreFiles: false,
       resultLimit: SEARCH_CONSTANTS.DEFAULT_RESULT_LIMIT,
       includeFiles: true,
       includeDirectories: true,
       exactMatch: false,
       typoTolerance: true,
+      matchMode: 'fuzzy',
       lastManualCancelTime: null,
     },
     UIZoomLevel: 1.0,
@@ -17,6 +17,34 @@ export interface DirContents {
   dir_count: number;
   file_count: number;
   opened_directory_times: OpenedDirectoryTimes;
+  git?: GitRepositoryStatus;
+}
+
+export type GitFileStatus = 'ignored' | 'untracked' | 'staged' | 'modified' | 'conflicted';
+
+export type GitRepositoryStatus = {
+  root: string;
+  branch: string | null;
+  head: string | null;
+  upstream: string | null;
+  ahead: number | null;
+  behind: number | null;
+};
+
+export type DirEntrySortKey = 'name' | 'size' | 'modified' | 'accessed' | 'created' | 'extension' | 'type';
+
+export interface DirEntrySort {
+  by: DirEntrySortKey;
+  descending?: boolean;
+}
+
+export interface DirEntryFilter {
+  glob?: string | null;
+  minSize?: number | null;
+  maxSize?: number | null;
+  modifiedAfter?: number | null;
+  modifiedBefore?: number | null;
+  includeHidden?: boolean | null;
 }
 
 export interface ReadDirOptions {
@@ -24,6 +52,21 @@ export interface ReadDirOptions {
   includeHardLinkCounts: boolean;
   includeItemCounts?: boolean;
   includeHiddenItemCounts?: boolean;
+  recordVisit?: boolean;
+  includePosixMetadata?: boolean;
+  detectMimeFromContent?: boolean;
+  includeGitStatus?: boolean;
+  sort?: DirEntrySort;
+  filter?: DirEntryFilter;
+  offset?: number;
+  limit?: number;
+}
+
+export interface FlattenDirOptions {
+  maxDepth?: number | null;
+  includeGlobs?: string[];
+  excludeGlobs?: string[];
+  includeHidden?: boolean;
 }
 
 export type ExtendedVirtualEntry = {
@@ -51,6 +94,38 @@ export type DirEntry = {
   link_target?: string | null;
   link_status?: DirEntryLinkStatus | null;
   hard_link_count?: number | null;
+  posix?: DirEntryPosixMetadata;
+  git_status?: GitFileStatus;
+  relative_path?: string;
+};
+
+export type DirEntryPosixMetadata = {
+  mode: number;
+  permissions: string;
+  uid: number;
+  gid: number;
+  owner: string | null;
+  group: string | null;
+  inode: number;
+  device: number;
+  blocks: number;
+  block_size: number;
+  birth_time: number | null;
+};
+
+export type ItemProperties = {
+  entry: DirEntry;
+  file_system: string | null;
+  mount_point: string | null;
+};
+
+export type DirEntryName = {
+  name: string;
+  path: string;
+  is_file: boolean;
+  is_dir: boolean;
+  is_symlink: boolean;
+  relative_path?: string;
 };
 
 export type DirEntryLinkMetadata = {
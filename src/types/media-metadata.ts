@@ -0,0 +1,52 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+export type MediaMetadataRequest = {
+  path: string;
+  modified_time: number;
+  size: number;
+};
+
+export type MediaKind = 'image' | 'audio' | 'video';
+
+export type ExifMetadata = {
+  camera_make: string | null;
+  camera_model: string | null;
+  lens_model: string | null;
+  capture_time: string | null;
+  latitude: number | null;
+  longitude: number | null;
+  altitude: number | null;
+  orientation: number | null;
+  exposure_time: number | null;
+  f_number: number | null;
+  iso: number | null;
+  focal_length: number | null;
+};
+
+export type AudioTags = {
+  title: string | null;
+  artist: string | null;
+  album: string | null;
+  album_artist: string | null;
+  genre: string | null;
+  date: string | null;
+  track_number: number | null;
+  disc_number: number | null;
+};
+
+export type MediaMetadata = {
+  path: string;
+  kind: MediaKind | null;
+  width: number | null;
+  height: number | null;
+  duration: number | null;
+  video_codec: string | null;
+  audio_codec: string | null;
+  sample_rate: number | null;
+  channels: number | null;
+  exif: ExifMetadata | null;
+  tags: AudioTags | null;
+  error: string | null;
+};
@@ -207,6 +207,8 @@ export type UserSettings = {
   changelog: ChangelogSettings;
 };
 
+export type GlobalSearchMatchMode = 'fuzzy' | 'regex' | 'glob';
+
 export type UserSettingsGlobalSearch = {
   scanDepth: number;
   autoScanPeriodMinutes: number;
@@ -214,11 +216,13 @@ export type UserSettingsGlobalSearch = {
   ignoredPaths: string[];
   selectedDriveRoots: string[];
   parallelScan: boolean;
+  respectIgnoreFiles: boolean;
   resultLimit: number;
   includeFiles: boolean;
   includeDirectories: boolean;
   exactMatch: boolean;
   typoTolerance: boolean;
+  matchMode: GlobalSearchMatchMode;
   lastManualCancelTime: number | null;
 };
 
@@ -14,8 +14,11 @@ import {
   getLocationsDriveListDisplaySignature,
   getLocationsEntriesDisplaySignature,
   getNavigableParentPath,
+  getSmartFolderId,
+  getSmartFolderPath,
   getVirtualLocationDisplayName,
   isVirtualDirectoryPath,
+  isSmartFolderPath,
   isVirtualLocationPath,
   isWslHostVirtualPath,
   shouldPrependLocationsCrumb,
@@ -39,6 +42,16 @@ describe('virtual-locations', () => {
     expect(virtualLocationPathExists('//wsl.localhost/')).toBe(true);
   });
 
+  it('detects smart folder virtual paths', () => {
+    expect(getSmartFolderPath('18c2f')).toBe('sfm://smart-folder/18c2f');
+    expect(getSmartFolderId('sfm://smart-folder/18c2f')).toBe('18c2f');
+    expect(isSmartFolderPath('sfm://smart-folder/18c2f')).toBe(true);
+    expect(isVirtualDirectoryPath('sfm://smart-folder/18c2f')).toBe(true);
+    expect(isSmartFolderPath('sfm://smart-folder/')).toBe(false);
+    expect(isSmartFolderPath('sfm://locations')).toBe(false);
+    expect(getNavigableParentPath('sfm://smart-folder/18c2f', 'linux')).toBeNull();
+  });
+
   it('returns display names for virtual directories', () => {
     expect(getVirtualLocationDisplayName('sfm://locations', key => `t:${key}`)).toBe('t:locations');
     expect(getVirtualLocationDisplayName('//wsl.localhost', key => `t:${key}`)).toBe('wsl.localhost');
@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type {
+  DirEntry,
+  DirEntryName,
+  GitRepositoryStatus,
+  OpenedDirectoryTimes,
+  ReadDirOptions,
+} from '@/types/dir-entry';
+import uniqueId from '@/utils/unique-id';
+
+interface ReadDirStreamBatchPayload<T> {
+  streamId: string;
+  entries: T[];
+}
+
+export interface ReadDirStreamFinishedPayload {
+  streamId: string;
+  path: string;
+  success: boolean;
+  cancelled: boolean;
+  error: string | null;
+  totalCount: number;
+  dirCount: number;
+  fileCount: number;
+  openedDirectoryTimes: OpenedDirectoryTimes | null;
+  /** Sorted and paged listing, when the options ask for a sort, a page or git status. */
+  entries: DirEntry[] | null;
+  git: GitRepositoryStatus | null;
+}
+
+export interface ReadDirStreamHandlers {
+  onNames?: (names: DirEntryName[]) => void;
+  onEntries?: (entries: DirEntry[]) => void;
+}
+
+export interface ReadDirStream {
+  finished: Promise<ReadDirStreamFinishedPayload>;
+  cancel: () => Promise<boolean>;
+}
+
+/**
+ * Lists a directory in batches: names and entry types first, then entries
+ * with metadata. Cancel the stream when the user navigates away.
+ */
+export async function readDirStream(
+  path: string,
+  handlers: ReadDirStreamHandlers,
+  options?: ReadDirOptions,
+): Promise<ReadDirStream> {
+  const streamId = uniqueId();
+  const unlisteners: UnlistenFn[] = [];
+  const stopListening = () => {
+    for (const unlisten of unlisteners.splice(0)) {
+      unlisten();
+    }
+  };
+
+  let resolveFinished: (payload: ReadDirStreamFinishedPayload) => void = () => {};
+  const finished = new Promise<ReadDirStreamFinishedPayload>((resolve) => {
+    resolveFinished = resolve;
+  });
+
+  unlisteners.push(
+    await listen<ReadDirStreamBatchPayload<DirEntryName>>('read-dir-stream-names', (event) => {
+      if (event.payload.streamId === streamId) {
+        handlers.onNames?.(event.payload.entries);
+      }
+    }),
+    await listen<ReadDirStreamBatchPayload<DirEntry>>('read-dir-stream-entries', (event) => {
+      if (event.payload.streamId === streamId) {
+        handlers.onEntries?.(event.payload.entries);
+      }
+    }),
+    await listen<ReadDirStreamFinishedPayload>('read-dir-stream-finished', (event) => {
+      if (event.payload.streamId === streamId) {
+        stopListening();
+        resolveFinished(event.payload);
+      }
+    }),
+  );
+
+  try {
+    await invoke('start_read_dir_stream', {
+      path,
+      streamId,
+      options,
+    });
+  }
+  catch (error) {
+    stopListening();
+    throw error;
+  }
+
+  return {
+    finished,
+    cancel: () => invoke<boolean>('cancel_read_dir_stream', { streamId }),
+  };
+}
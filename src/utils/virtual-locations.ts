@@ -18,6 +18,8 @@ import {
   isWindowsLocationsScopePath,
 } from '@/utils/system-mount-roots';
 import {
+  getSmartFolderId,
+  isSmartFolderPath,
   isVirtualDirectoryPath,
   isVirtualLocationPath,
   isWslHostVirtualPath,
@@ -27,6 +29,9 @@ import {
 import { createDriveEntryMetadata } from '@/utils/drive-icon';
 
 export {
+  getSmartFolderId,
+  getSmartFolderPath,
+  isSmartFolderPath,
   isVirtualDirectoryPath,
   isVirtualLocationPath,
   isWslHostVirtualPath,
@@ -206,6 +211,14 @@ export async function resolveDirectoryContents(
     return readWslHostDirectory();
   }
 
+  const smartFolderId = getSmartFolderId(path);
+  if (smartFolderId !== null) {
+    return invoke<DirContents>('read_smart_folder', {
+      id: smartFolderId,
+      options,
+    });
+  }
+
   return invoke<DirContents>('read_dir', {
     path,
     options,
@@ -224,6 +237,10 @@ export async function resolveDirEntry(
     return createWslHostDirEntry();
   }
 
+  if (isSmartFolderPath(path)) {
+    return createVirtualDirectoryEntry(normalizePath(path), '');
+  }
+
   try {
     return await invoke<DirEntry>('get_dir_entry_with_timeout', {
       path,
@@ -243,7 +260,7 @@ export async function getLocationsDrivePaths(): Promise<string[]> {
 export function getNavigableParentPath(path: string, platform: string | null): string | null {
   const normalizedPath = normalizePath(path);
 
-  if (isVirtualLocationPath(normalizedPath)) {
+  if (isVirtualLocationPath(normalizedPath) || isSmartFolderPath(normalizedPath)) {
     return null;
   }
 
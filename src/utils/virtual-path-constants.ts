@@ -4,6 +4,7 @@
 
 export const LOCATIONS_VIRTUAL_PATH = 'sfm://locations';
 export const WSL_HOST_VIRTUAL_PATH = '//wsl.localhost';
+export const SMART_FOLDER_VIRTUAL_PATH_PREFIX = 'sfm://smart-folder/';
 
 export function isVirtualLocationPath(path: string): boolean {
   return path.replace(/\\/g, '/') === LOCATIONS_VIRTUAL_PATH;
@@ -15,6 +16,24 @@ export function isWslHostVirtualPath(path: string): boolean {
   return normalizedPath === WSL_HOST_VIRTUAL_PATH || normalizedPath === '//wsl$';
 }
 
+export function getSmartFolderPath(id: string): string {
+  return `${SMART_FOLDER_VIRTUAL_PATH_PREFIX}${id}`;
+}
+
+export function getSmartFolderId(path: string): string | null {
+  const normalizedPath = path.replace(/\\/g, '/');
+
+  if (!normalizedPath.startsWith(SMART_FOLDER_VIRTUAL_PATH_PREFIX)) {
+    return null;
+  }
+
+  return normalizedPath.slice(SMART_FOLDER_VIRTUAL_PATH_PREFIX.length) || null;
+}
+
+export function isSmartFolderPath(path: string): boolean {
+  return getSmartFolderId(path) !== null;
+}
+
 export function isVirtualDirectoryPath(path: string): boolean {
-  return isVirtualLocationPath(path) || isWslHostVirtualPath(path);
+  return isVirtualLocationPath(path) || isWslHostVirtualPath(path) || isSmartFolderPath(path);
 }